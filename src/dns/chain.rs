@@ -0,0 +1,1415 @@
+//! Walking the DNSSEC chain of trust from the root down to an answer, checking
+//! each link on the way.
+
+use std::{
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hickory_client::rr::rdata::DNSSECRData;
+use hickory_proto::{
+    op::{Message, Query, ResponseCode},
+    rr::{
+        dnssec::{
+            rdata::{DNSKEY, DS, RRSIG},
+            TrustAnchor, Verifier,
+        },
+        DNSClass, RData, Record, RecordType,
+    },
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Span, Value};
+
+use super::{client::DnsClient, config::Config, constants, denial, serde};
+
+/// The outcome of checking one link of the chain.
+struct Step {
+    zone: Name,
+    check: &'static str,
+    passed: bool,
+    detail: String,
+}
+
+impl Step {
+    fn into_value(self) -> Value {
+        Value::record(
+            nu_protocol::Record::from_iter(std::iter::zip(
+                constants::columns::CHAIN_STEP_COLS
+                    .iter()
+                    .map(|col| (*col).into()),
+                vec![
+                    Value::string(self.zone.to_utf8(), Span::unknown()),
+                    Value::string(self.check, Span::unknown()),
+                    Value::bool(self.passed, Span::unknown()),
+                    Value::string(self.detail, Span::unknown()),
+                ],
+            )),
+            Span::unknown(),
+        )
+    }
+}
+
+/// Explains why the answer to `query` is, or is not, secure: starting from the
+/// zone that signed it, each zone's keys are checked against its DS records,
+/// whose signatures are checked with the keys of the parent zone, up to the
+/// root keys, which are checked against the built-in trust anchors, or up to
+/// the zone of `--trust-anchor`. The steps are listed from the top down. The
+/// client must not validate by itself.
+pub async fn explain(
+    client: &DnsClient,
+    config: &Config,
+    query: Query,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let class = query.query_class();
+    let answer = fetch(
+        client,
+        config,
+        query.name().clone(),
+        query.query_type(),
+        span,
+    )
+    .await?;
+
+    // the name may be an alias, whose CNAME is what there is to check
+    let rtype = answer
+        .answers()
+        .iter()
+        .map(Record::record_type)
+        .find(|rtype| *rtype != RecordType::RRSIG)
+        .unwrap_or(query.query_type());
+    let (records, sigs) = rrset(&answer, query.name(), rtype);
+
+    let mut steps = Vec::new();
+
+    let Some(mut zone) = signer(&sigs) else {
+        steps.push(Step {
+            zone: query.name().clone(),
+            check: "answer signature",
+            passed: false,
+            detail: if records.is_empty() {
+                format!("there are no {} records to check", rtype)
+            } else {
+                "the answer is not signed".into()
+            },
+        });
+
+        return Ok(into_value(query, steps, config));
+    };
+
+    // the records that the keys of the zone at hand have to vouch for
+    let mut owner = query.name().clone();
+    let mut check = "answer signature";
+    let mut pending = (records, sigs);
+
+    loop {
+        let keys_msg = fetch(client, config, zone.clone(), RecordType::DNSKEY, span).await?;
+        let (key_records, key_sigs) = rrset(&keys_msg, &zone, RecordType::DNSKEY);
+        let keys = keys(&key_records);
+
+        steps.push(check_signature(
+            &owner, check, class, &pending.0, &pending.1, &keys,
+        ));
+
+        // only the keys that the trust anchor or DS records vouch for vouch
+        // for the others
+        if is_anchor(&zone, config) {
+            let (anchor, trusted) = check_trust_anchor(&zone, &keys, config);
+            steps.push(check_signature(
+                &zone,
+                "DNSKEY signature",
+                class,
+                &key_records,
+                &key_sigs,
+                &trusted,
+            ));
+            steps.push(anchor);
+            break;
+        }
+
+        let ds_msg = fetch(client, config, zone.clone(), RecordType::DS, span).await?;
+        let (ds_records, ds_sigs) = rrset(&ds_msg, &zone, RecordType::DS);
+        let ds: Vec<&DS> = ds_records
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::DNSSEC(DNSSECRData::DS(ds))) => Some(ds),
+                _ => None,
+            })
+            .collect();
+
+        let (digest, matched) = check_ds(&zone, &ds, &keys);
+        steps.push(check_signature(
+            &zone,
+            "DNSKEY signature",
+            class,
+            &key_records,
+            &key_sigs,
+            &matched,
+        ));
+        steps.push(digest);
+
+        // the DS records are signed by the parent zone, whose keys are next
+        let Some(parent) = signer(&ds_sigs) else {
+            steps.push(Step {
+                zone: zone.clone(),
+                check: "DS signature",
+                passed: false,
+                detail: if ds.is_empty() {
+                    "the parent zone has no DS records for this zone, so it is not signed as far as the parent is concerned".into()
+                } else {
+                    "the DS records are not signed".into()
+                },
+            });
+            break;
+        };
+
+        // going up has to end at the root
+        if parent == zone || !parent.zone_of(&zone) {
+            steps.push(Step {
+                zone: zone.clone(),
+                check: "DS signature",
+                passed: false,
+                detail: format!(
+                    "the DS records are signed by {}, which is not a parent zone",
+                    parent
+                ),
+            });
+            break;
+        }
+
+        owner = zone;
+        check = "DS signature";
+        pending = (ds_records, ds_sigs);
+        zone = parent;
+    }
+
+    steps.reverse();
+
+    Ok(into_value(query, steps, config))
+}
+
+fn into_value(query: Query, steps: Vec<Step>, config: &Config) -> Value {
+    let secure = !steps.is_empty() && steps.iter().all(|step| step.passed);
+
+    Value::record(
+        nu_protocol::Record::from_iter(std::iter::zip(
+            constants::columns::CHAIN_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            vec![
+                serde::Query(query).into_value(config),
+                Value::bool(secure, Span::unknown()),
+                Value::list(
+                    steps.into_iter().map(Step::into_value).collect(),
+                    Span::unknown(),
+                ),
+            ],
+        )),
+        Span::unknown(),
+    )
+}
+
+/// How far the chain of trust proves a zone or an answer (RFC 4035 section
+/// 4.3).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Security {
+    /// Every link from the trust anchor down holds.
+    Secure,
+    /// A zone on the way is proven to be unsigned, so there is nothing below it
+    /// to check.
+    Insecure,
+    /// A link that should hold does not.
+    Bogus,
+}
+
+impl Display for Security {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Security::Secure => "secure",
+            Security::Insecure => "insecure",
+            Security::Bogus => "bogus",
+        })
+    }
+}
+
+/// A zone between the answer and the root, with the records its link in the
+/// chain of trust rests on.
+struct Link {
+    zone: Name,
+    /// The DS records of the zone in its parent, and the signatures over them.
+    ds: (Vec<Record>, Vec<Record>),
+    /// Without DS records, the records by which the parent proves that there
+    /// are none, and the signatures over them.
+    denial: (Vec<Record>, Vec<Record>),
+    /// What the denial records prove about the DS records of the zone.
+    proof: Option<denial::Proof>,
+    /// The keys of the zone, and the signatures over them, which are only
+    /// looked up while the zones above are secure.
+    keys: (Vec<Record>, Vec<Record>),
+    security: Security,
+    steps: Vec<Step>,
+}
+
+impl Link {
+    fn new(zone: Name) -> Self {
+        Self {
+            zone,
+            ds: Default::default(),
+            denial: Default::default(),
+            proof: None,
+            keys: Default::default(),
+            security: Security::Bogus,
+            steps: Vec::new(),
+        }
+    }
+
+    fn into_value(self, config: &Config) -> Result<Value, LabeledError> {
+        let records = |records: Vec<Record>| -> Result<Value, LabeledError> {
+            Ok(Value::list(
+                records
+                    .into_iter()
+                    .map(|record| serde::Record(record).into_value(config))
+                    .collect::<Result<_, _>>()?,
+                Span::unknown(),
+            ))
+        };
+
+        let mut sigs = self.keys.1;
+        sigs.extend(self.ds.1);
+        sigs.extend(self.denial.1);
+
+        Ok(Value::record(
+            nu_protocol::Record::from_iter(std::iter::zip(
+                constants::columns::VALIDATION_ZONE_COLS
+                    .iter()
+                    .map(|col| (*col).into()),
+                vec![
+                    Value::string(self.zone.to_utf8(), Span::unknown()),
+                    Value::string(self.security.to_string(), Span::unknown()),
+                    records(self.keys.0)?,
+                    records(self.ds.0)?,
+                    records(self.denial.0)?,
+                    records(sigs)?,
+                    Value::list(
+                        self.steps.into_iter().map(Step::into_value).collect(),
+                        Span::unknown(),
+                    ),
+                ],
+            )),
+            Span::unknown(),
+        ))
+    }
+}
+
+/// Validates a response by building the chain of trust from the root, or the
+/// zone of `--trust-anchor`, down to the zone it came from: each zone is secure
+/// if its parent is, and the parent signed DS records that match a key of the
+/// zone, which signed its own keys. Below a zone whose parent proves that it
+/// has no DS records, everything is insecure. A negative response is only
+/// secure if its NSEC or NSEC3 records also prove it. The response must have
+/// been asked for with the DNSSEC OK bit, and the client must not validate by
+/// itself.
+pub async fn validate(
+    client: &DnsClient,
+    config: &Config,
+    response: &Message,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let Some(query) = response.queries().first() else {
+        return Ok(Value::nothing(Span::unknown()));
+    };
+    let class = query.query_class();
+
+    // a negative response is proven by the records of its authority section
+    let rrsets = if response.answers().is_empty() {
+        group_rrsets(response.name_servers())
+    } else {
+        group_rrsets(response.answers())
+    };
+
+    // the signer of an answer has to be a zone it is in
+    let signed_by = rrsets
+        .iter()
+        .filter_map(|(_, _, sigs)| signer(sigs))
+        .find(|zone| zone.zone_of(query.name()));
+    let mut zone = match signed_by.or_else(|| soa_owner(response)) {
+        Some(zone) => zone,
+        None => {
+            let soa = fetch(client, config, query.name().clone(), RecordType::SOA, span).await?;
+            soa_owner(&soa).unwrap_or_else(Name::root)
+        }
+    };
+
+    // going up from the zone of the answer, to find the zones in between
+    let mut links = Vec::new();
+    loop {
+        if is_anchor(&zone, config) {
+            links.push(Link::new(zone));
+            break;
+        }
+
+        let ds_msg = fetch(client, config, zone.clone(), RecordType::DS, span).await?;
+        let mut link = Link::new(zone.clone());
+        link.ds = rrset(&ds_msg, &zone, RecordType::DS);
+        if link.ds.0.is_empty() {
+            link.denial = denial(&ds_msg);
+            link.proof = denial::prove(&Query::query(zone.clone(), RecordType::DS), &ds_msg, span)?;
+        }
+
+        // the parent signed the DS records or their denial, or else its SOA
+        // record comes along with the denial
+        let parent = signer(&link.ds.1)
+            .or_else(|| signer(&link.denial.1))
+            .or_else(|| soa_owner(&ds_msg))
+            .filter(|parent| *parent != zone && parent.zone_of(&zone))
+            .unwrap_or_else(|| zone.base_name());
+
+        links.push(link);
+        zone = parent;
+    }
+
+    // and checking them from the root down, while they are secure
+    links.reverse();
+    for i in 0..links.len() {
+        let (above, below) = links.split_at_mut(i);
+        let link = &mut below[0];
+        let parent = above.last();
+        let parent_keys = parent.map_or_else(Vec::new, |parent| keys(&parent.keys.0));
+
+        link.security = match parent.map(|parent| parent.security) {
+            None => {
+                link.keys = fetch_keys(client, config, &link.zone, span).await?;
+                let keys = keys(&link.keys.0);
+                // only the anchored keys vouch for the others
+                let (anchor, trusted) = check_trust_anchor(&link.zone, &keys, config);
+                link.steps.push(anchor);
+                link.steps.push(check_signature(
+                    &link.zone,
+                    "DNSKEY signature",
+                    class,
+                    &link.keys.0,
+                    &link.keys.1,
+                    &trusted,
+                ));
+                secure_if_passed(&link.steps)
+            }
+            Some(Security::Secure) if !link.ds.0.is_empty() => {
+                link.steps.push(check_signature(
+                    &link.zone,
+                    "DS signature",
+                    class,
+                    &link.ds.0,
+                    &link.ds.1,
+                    &parent_keys,
+                ));
+                link.keys = fetch_keys(client, config, &link.zone, span).await?;
+                let keys = keys(&link.keys.0);
+                let ds: Vec<&DS> = link
+                    .ds
+                    .0
+                    .iter()
+                    .filter_map(|record| match record.data() {
+                        Some(RData::DNSSEC(DNSSECRData::DS(ds))) => Some(ds),
+                        _ => None,
+                    })
+                    .collect();
+                // only the keys that the parent vouches for vouch for the
+                // others
+                let (digest, matched) = check_ds(&link.zone, &ds, &keys);
+                link.steps.push(digest);
+                link.steps.push(check_signature(
+                    &link.zone,
+                    "DNSKEY signature",
+                    class,
+                    &link.keys.0,
+                    &link.keys.1,
+                    &matched,
+                ));
+                secure_if_passed(&link.steps)
+            }
+            // a secure parent has to prove that the zone is unsigned
+            Some(Security::Secure) => {
+                let denial = group_rrsets(link.denial.0.iter().chain(&link.denial.1));
+                link.steps.extend(check_rrsets(
+                    &link.zone,
+                    "DS denial signature",
+                    class,
+                    &denial,
+                    &parent_keys,
+                ));
+                link.steps
+                    .push(check_ds_denial(&link.zone, link.proof.as_ref()));
+
+                match secure_if_passed(&link.steps) {
+                    Security::Secure => Security::Insecure,
+                    security => security,
+                }
+            }
+            Some(security) => security,
+        };
+    }
+
+    let zone = links.last().expect("the root is always a link");
+    let (mut security, steps) = match zone.security {
+        Security::Secure => {
+            let steps = check_rrsets(
+                &zone.zone,
+                "answer signature",
+                class,
+                &rrsets,
+                &keys(&zone.keys.0),
+            );
+            (secure_if_passed(&steps), steps)
+        }
+        security => (security, Vec::new()),
+    };
+
+    // signed denial records have yet to prove what they deny
+    let proof = denial::prove(query, response, span)?;
+    if security == Security::Secure && proof.as_ref().is_some_and(|proof| !proof.proven()) {
+        security = Security::Bogus;
+    }
+
+    Ok(Value::record(
+        nu_protocol::Record::from_iter(std::iter::zip(
+            constants::columns::VALIDATION_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            vec![
+                Value::string(security.to_string(), Span::unknown()),
+                Value::list(
+                    links
+                        .into_iter()
+                        .map(|link| link.into_value(config))
+                        .collect::<Result<_, _>>()?,
+                    Span::unknown(),
+                ),
+                Value::list(
+                    steps.into_iter().map(Step::into_value).collect(),
+                    Span::unknown(),
+                ),
+                proof.map_or_else(
+                    || Value::nothing(Span::unknown()),
+                    denial::Proof::into_value,
+                ),
+            ],
+        )),
+        Span::unknown(),
+    ))
+}
+
+/// The RRsets among `records`, each with its owner, and the signatures over it.
+fn group_rrsets<'r>(
+    records: impl IntoIterator<Item = &'r Record>,
+) -> Vec<(Name, Vec<Record>, Vec<Record>)> {
+    let mut rrsets: Vec<(Name, RecordType, Vec<Record>, Vec<Record>)> = Vec::new();
+
+    for record in records {
+        let (rtype, is_sig) = match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::RRSIG(sig))) => (sig.type_covered(), true),
+            _ => (record.record_type(), false),
+        };
+
+        let index = rrsets
+            .iter()
+            .position(|(name, set_rtype, ..)| name == record.name() && *set_rtype == rtype)
+            .unwrap_or_else(|| {
+                rrsets.push((record.name().clone(), rtype, Vec::new(), Vec::new()));
+                rrsets.len() - 1
+            });
+
+        if is_sig {
+            rrsets[index].3.push(record.clone());
+        } else {
+            rrsets[index].2.push(record.clone());
+        }
+    }
+
+    rrsets
+        .into_iter()
+        .map(|(name, _, records, sigs)| (name, records, sigs))
+        .collect()
+}
+
+/// Checks the signatures over each of the RRsets in `zone`, of which there has
+/// to be at least one.
+fn check_rrsets(
+    zone: &Name,
+    check: &'static str,
+    class: DNSClass,
+    rrsets: &[(Name, Vec<Record>, Vec<Record>)],
+    keys: &[&DNSKEY],
+) -> Vec<Step> {
+    let steps: Vec<Step> = rrsets
+        .iter()
+        .filter(|(owner, ..)| zone.zone_of(owner))
+        .map(|(owner, records, sigs)| check_signature(owner, check, class, records, sigs, keys))
+        .collect();
+
+    if steps.is_empty() {
+        return vec![Step {
+            zone: zone.clone(),
+            check,
+            passed: false,
+            detail: "there are no records to check".into(),
+        }];
+    }
+
+    steps
+}
+
+fn secure_if_passed(steps: &[Step]) -> Security {
+    if !steps.is_empty() && steps.iter().all(|step| step.passed) {
+        Security::Secure
+    } else {
+        Security::Bogus
+    }
+}
+
+fn keys(records: &[Record]) -> Vec<&DNSKEY> {
+    records
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) => Some(key),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The NSEC or NSEC3 records by which a negative response proves that there
+/// are no records, and the signatures over them.
+fn denial(message: &Message) -> (Vec<Record>, Vec<Record>) {
+    let mut records = Vec::new();
+    let mut sigs = Vec::new();
+
+    for record in message.name_servers() {
+        match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::RRSIG(sig)))
+                if matches!(sig.type_covered(), RecordType::NSEC | RecordType::NSEC3) =>
+            {
+                sigs.push(record.clone())
+            }
+            _ if matches!(record.record_type(), RecordType::NSEC | RecordType::NSEC3) => {
+                records.push(record.clone())
+            }
+            _ => {}
+        }
+    }
+
+    (records, sigs)
+}
+
+/// The zone whose SOA record comes along with a response, which is the zone it
+/// is from.
+fn soa_owner(message: &Message) -> Option<Name> {
+    message
+        .answers()
+        .iter()
+        .chain(message.name_servers())
+        .find(|record| record.record_type() == RecordType::SOA)
+        .map(|record| record.name().clone())
+}
+
+async fn fetch_keys(
+    client: &DnsClient,
+    config: &Config,
+    zone: &Name,
+    span: Span,
+) -> Result<(Vec<Record>, Vec<Record>), LabeledError> {
+    let message = fetch(client, config, zone.clone(), RecordType::DNSKEY, span).await?;
+    Ok(rrset(&message, zone, RecordType::DNSKEY))
+}
+
+/// Sends a query with the DNSSEC OK bit set, so that the signatures come along.
+pub async fn fetch(
+    client: &DnsClient,
+    config: &Config,
+    name: Name,
+    rtype: RecordType,
+    span: Span,
+) -> Result<Message, LabeledError> {
+    client.ready().await;
+
+    let response = tokio::time::timeout(
+        config.timeout.item,
+        client.send_dnssec_ok_query(Query::query(name.clone(), rtype), config),
+    )
+    .await
+    .map_err(|_| {
+        LabeledError::new("timed out").with_label(
+            format!("request to {} timed out", config.server.item),
+            config.server.span,
+        )
+    })?
+    .map_err(|err| {
+        LabeledError::new("DNS error").with_label(
+            format!("looking up {} {} failed: {}", rtype, name, err.kind()),
+            span,
+        )
+    })?
+    .into_message();
+
+    match response.response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => Ok(response),
+        code => Err(LabeledError::new("DNS error").with_label(
+            format!("looking up {} {} failed: {}", rtype, name, code),
+            span,
+        )),
+    }
+}
+
+/// The records of an RRset in the answer, and the signatures over it.
+pub fn rrset(message: &Message, name: &Name, rtype: RecordType) -> (Vec<Record>, Vec<Record>) {
+    let mut records = Vec::new();
+    let mut sigs = Vec::new();
+
+    for record in message.answers() {
+        if record.name() != name {
+            continue;
+        }
+
+        match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::RRSIG(sig))) if sig.type_covered() == rtype => {
+                sigs.push(record.clone())
+            }
+            _ if record.record_type() == rtype => records.push(record.clone()),
+            _ => {}
+        }
+    }
+
+    (records, sigs)
+}
+
+fn rrsigs(records: &[Record]) -> impl Iterator<Item = &RRSIG> {
+    records.iter().filter_map(|record| match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::RRSIG(sig))) => Some(sig),
+        _ => None,
+    })
+}
+
+/// The zone that made the first of the signatures.
+fn signer(sigs: &[Record]) -> Option<Name> {
+    rrsigs(sigs).next().map(|sig| sig.signer_name().clone())
+}
+
+/// Whether any of the signatures over `records` is current and made with one
+/// of `keys`.
+fn check_signature(
+    owner: &Name,
+    check: &'static str,
+    class: DNSClass,
+    records: &[Record],
+    sigs: &[Record],
+    keys: &[&DNSKEY],
+) -> Step {
+    let step = |passed, detail| Step {
+        zone: owner.clone(),
+        check,
+        passed,
+        detail,
+    };
+
+    if records.is_empty() {
+        return step(false, "there are no records to check".into());
+    }
+
+    if sigs.is_empty() {
+        return step(false, "the records are not signed".into());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as u32);
+    let mut failure = String::new();
+
+    for sig in rrsigs(sigs) {
+        let key = keys.iter().find(|key| {
+            key.algorithm() == sig.algorithm()
+                && key.calculate_key_tag().ok() == Some(sig.key_tag())
+        });
+
+        let Some(key) = key else {
+            failure = format!(
+                "the signature is by {} key {} of {}, which is not among the keys trusted to check it",
+                sig.algorithm(),
+                sig.key_tag(),
+                sig.signer_name()
+            );
+            continue;
+        };
+
+        // timestamps are compared in serial number arithmetic (RFC 4034
+        // section 3.1.5), so that they keep working past 2106
+        if (now.wrapping_sub(sig.sig_inception()) as i32) < 0 {
+            failure = format!(
+                "the signature is not valid until {}",
+                timestamp(sig.sig_inception())
+            );
+            continue;
+        }
+
+        if (sig.sig_expiration().wrapping_sub(now) as i32) < 0 {
+            failure = format!(
+                "the signature expired on {}",
+                timestamp(sig.sig_expiration())
+            );
+            continue;
+        }
+
+        match key.verify_rrsig(owner, class, sig, records) {
+            Ok(()) => {
+                return step(
+                    true,
+                    format!(
+                        "signed by {} with {} key {}, valid until {}",
+                        sig.signer_name(),
+                        sig.algorithm(),
+                        sig.key_tag(),
+                        timestamp(sig.sig_expiration())
+                    ),
+                )
+            }
+            Err(err) => {
+                tracing::debug!(chain.owner = %owner, chain.key_tag = sig.key_tag(), chain.error = ?err);
+                failure = format!(
+                    "the signature by key {} does not match the records",
+                    sig.key_tag()
+                )
+            }
+        }
+    }
+
+    step(false, failure)
+}
+
+/// Whether any of the DS records in the parent zone matches a key of the zone,
+/// and the keys that they match, which are the ones that may sign the keys of
+/// the zone.
+fn check_ds<'k>(zone: &Name, ds: &[&DS], keys: &[&'k DNSKEY]) -> (Step, Vec<&'k DNSKEY>) {
+    let step = |passed, detail| Step {
+        zone: zone.clone(),
+        check: "DS digest",
+        passed,
+        detail,
+    };
+
+    if ds.is_empty() {
+        return (
+            step(
+                false,
+                "the parent zone has no DS records for this zone".into(),
+            ),
+            Vec::new(),
+        );
+    }
+
+    let matches: Vec<(&DS, &DNSKEY)> = ds
+        .iter()
+        .flat_map(|ds| keys.iter().map(move |key| (*ds, *key)))
+        .filter(|(ds, key)| ds.covers(zone, key).unwrap_or(false))
+        .collect();
+
+    let Some((ds, key)) = matches.first() else {
+        return (
+            step(
+                false,
+                format!(
+                    "none of the DS records ({}) match a key of the zone",
+                    ds.iter()
+                        .map(|ds| ds.key_tag().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ),
+            Vec::new(),
+        );
+    };
+
+    let step = step(
+        true,
+        format!(
+            "DS {} matches the {} digest of DNSKEY {}",
+            ds.key_tag(),
+            serde::digest_type_name(ds.digest_type()),
+            key.calculate_key_tag().unwrap_or_default()
+        ),
+    );
+
+    let mut matched = Vec::new();
+    for (_, key) in matches {
+        if !matched.contains(&key) {
+            matched.push(key);
+        }
+    }
+
+    (step, matched)
+}
+
+/// Whether the parent zone proves that the zone has no DS records, which makes
+/// it unsigned rather than bogus.
+fn check_ds_denial(zone: &Name, proof: Option<&denial::Proof>) -> Step {
+    let step = |passed, detail| Step {
+        zone: zone.clone(),
+        check: "DS denial",
+        passed,
+        detail,
+    };
+
+    match proof.map(denial::Proof::failure) {
+        Some(None) => step(
+            true,
+            "the parent zone proves that there are no DS records, so the zone is unsigned".into(),
+        ),
+        Some(Some(failure)) => step(false, failure.into()),
+        None => step(
+            false,
+            "the parent zone neither has nor denies DS records for this zone".into(),
+        ),
+    }
+}
+
+/// Whether the chain of trust ends at `zone`: the root, or the zone of the
+/// trust anchor given instead.
+fn is_anchor(zone: &Name, config: &Config) -> bool {
+    zone.is_root()
+        || config
+            .trust_anchor
+            .as_ref()
+            .is_some_and(|anchor| anchor.item.zone == *zone)
+}
+
+/// Whether any of the keys of the zone is a trust anchor: one of the root keys
+/// built into hickory, or else one given with `--trust-anchor`. The keys that
+/// are trust anchors are the ones that may sign the keys of the zone.
+fn check_trust_anchor<'k>(
+    zone: &Name,
+    keys: &[&'k DNSKEY],
+    config: &Config,
+) -> (Step, Vec<&'k DNSKEY>) {
+    let step = |passed, detail| Step {
+        zone: zone.clone(),
+        check: "trust anchor",
+        passed,
+        detail,
+    };
+
+    let trusted = match &config.trust_anchor {
+        Some(anchor) if anchor.item.zone != *zone => {
+            return (
+                step(
+                    false,
+                    format!(
+                        "the trust anchor is for {}, which the answer is not in",
+                        anchor.item.zone
+                    ),
+                ),
+                Vec::new(),
+            )
+        }
+        Some(anchor) => anchor.item.trusted(keys),
+        None => {
+            let anchors = TrustAnchor::default();
+            keys.iter()
+                .copied()
+                .filter(|key| anchors.contains_dnskey_bytes(key.public_key()))
+                .collect()
+        }
+    };
+
+    let step = match trusted.first() {
+        Some(key) => step(
+            true,
+            format!(
+                "DNSKEY {} is a trust anchor of {}",
+                key.calculate_key_tag().unwrap_or_default(),
+                zone
+            ),
+        ),
+        None => step(
+            false,
+            format!("none of the keys of {} is a trust anchor", zone),
+        ),
+    };
+
+    (step, trusted)
+}
+
+fn timestamp(secs: u32) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map_or_else(|| secs.to_string(), |time| time.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, net::SocketAddr};
+
+    use hickory_proto::{
+        op::MessageType,
+        rr::{
+            dnssec::{rdata::NSEC, tbs, Algorithm, DigestType, KeyFormat, KeyPair, Private},
+            rdata::{A, SOA},
+        },
+    };
+    use tokio::net::UdpSocket;
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_ascii(name).unwrap()
+    }
+
+    fn now() -> u32 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32
+    }
+
+    /// A new Ed25519 key of a zone.
+    struct Key(KeyPair<Private>);
+
+    impl Key {
+        fn new() -> Self {
+            let pkcs8 = KeyPair::<Private>::generate_pkcs8(Algorithm::ED25519).unwrap();
+            Self(
+                KeyFormat::Pkcs8
+                    .decode_key(&pkcs8, None, Algorithm::ED25519)
+                    .unwrap(),
+            )
+        }
+
+        fn dnskey(&self) -> DNSKEY {
+            self.0.to_dnskey(Algorithm::ED25519).unwrap()
+        }
+
+        fn record(&self, zone: &str) -> Record {
+            Record::from_rdata(
+                name(zone),
+                300,
+                RData::DNSSEC(DNSSECRData::DNSKEY(self.dnskey())),
+            )
+        }
+
+        fn ds(&self, zone: &str) -> Record {
+            let dnskey = self.dnskey();
+            let digest = dnskey.to_digest(&name(zone), DigestType::SHA256).unwrap();
+
+            Record::from_rdata(
+                name(zone),
+                300,
+                RData::DNSSEC(DNSSECRData::DS(DS::new(
+                    dnskey.calculate_key_tag().unwrap(),
+                    Algorithm::ED25519,
+                    DigestType::SHA256,
+                    digest.as_ref().to_vec(),
+                ))),
+            )
+        }
+
+        /// The signature of `zone` over an RRset.
+        fn sign(&self, zone: &str, records: &[Record]) -> Record {
+            let owner = records[0].name();
+            let rtype = records[0].record_type();
+            let key_tag = self.dnskey().calculate_key_tag().unwrap();
+            let (inception, expiration) = (now() - 3600, now() + 3600);
+
+            let tbs = tbs::rrset_tbs(
+                owner,
+                DNSClass::IN,
+                owner.num_labels(),
+                rtype,
+                Algorithm::ED25519,
+                300,
+                expiration,
+                inception,
+                key_tag,
+                &name(zone),
+                records,
+            )
+            .unwrap();
+
+            Record::from_rdata(
+                owner.clone(),
+                300,
+                RData::DNSSEC(DNSSECRData::RRSIG(RRSIG::new(
+                    rtype,
+                    Algorithm::ED25519,
+                    owner.num_labels(),
+                    300,
+                    expiration,
+                    inception,
+                    key_tag,
+                    name(zone),
+                    self.0.sign(Algorithm::ED25519, &tbs).unwrap(),
+                ))),
+            )
+        }
+    }
+
+    fn soa(zone: &str) -> Record {
+        Record::from_rdata(
+            name(zone),
+            300,
+            RData::SOA(SOA::new(
+                name(&format!("ns.{}", zone)),
+                name(&format!("hostmaster.{}", zone)),
+                1,
+                3600,
+                600,
+                86400,
+                300,
+            )),
+        )
+    }
+
+    fn nsec(owner: &str, next: &str, types: Vec<RecordType>) -> Record {
+        Record::from_rdata(
+            name(owner),
+            300,
+            RData::DNSSEC(DNSSECRData::NSEC(NSEC::new(name(next), types))),
+        )
+    }
+
+    fn a(owner: &str) -> Record {
+        Record::from_rdata(name(owner), 300, RData::A(A::new(192, 0, 2, 1)))
+    }
+
+    /// Records signed by `key` of `zone`, along with their signature.
+    fn signed(key: &Key, zone: &str, records: Vec<Record>) -> Vec<Record> {
+        let sig = key.sign(zone, &records);
+        records.into_iter().chain([sig]).collect()
+    }
+
+    /// The answer and authority sections to respond with, by name and type.
+    type Responses = HashMap<(Name, RecordType), (Vec<Record>, Vec<Record>)>;
+
+    /// A signed zone, `example.`, anchored by its key, with delegations to
+    /// each of the ways a child zone may be secure, insecure, or bogus.
+    struct Fixture {
+        parent: Key,
+        responses: Responses,
+        zones: Vec<&'static str>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let parent = Key::new();
+            let mut fixture = Self {
+                responses: HashMap::new(),
+                zones: vec!["example."],
+                parent,
+            };
+
+            let keys = signed(
+                &fixture.parent,
+                "example.",
+                vec![fixture.parent.record("example.")],
+            );
+            fixture.answer("example.", RecordType::DNSKEY, keys);
+
+            fixture
+        }
+
+        fn answer(&mut self, owner: &str, rtype: RecordType, records: Vec<Record>) {
+            self.responses
+                .insert((name(owner), rtype), (records, Vec::new()));
+        }
+
+        fn deny(&mut self, owner: &str, rtype: RecordType, records: Vec<Record>) {
+            self.responses
+                .insert((name(owner), rtype), (Vec::new(), records));
+        }
+
+        /// A child zone of `example.` with the keys of `keys`, whose DS
+        /// records match `ds`, and whose keys are signed by `signer`.
+        fn child(&mut self, zone: &'static str, ds: &Key, keys: &[&Key], signer: &Key) {
+            let ds = signed(&self.parent, "example.", vec![ds.ds(zone)]);
+            self.answer(zone, RecordType::DS, ds);
+
+            let records: Vec<Record> = keys.iter().map(|key| key.record(zone)).collect();
+            let keys = signed(signer, zone, records);
+            self.answer(zone, RecordType::DNSKEY, keys);
+            self.zones.push(zone);
+        }
+
+        /// A child zone of `example.` whose DS records the parent denies with
+        /// an NSEC record of the types of `types`.
+        fn unsigned_child(&mut self, zone: &'static str, types: Vec<RecordType>) {
+            let next = format!("z.{}", zone);
+            let mut denial = signed(&self.parent, "example.", vec![nsec(zone, &next, types)]);
+            denial.push(soa("example."));
+            self.deny(zone, RecordType::DS, denial);
+            self.zones.push(zone);
+        }
+
+        /// A nameserver that answers from the responses, and with the SOA
+        /// record of the zone that a name is in otherwise.
+        async fn serve(self) -> SocketAddr {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let addr = socket.local_addr().unwrap();
+
+            tokio::spawn(async move {
+                let mut buf = vec![0; u16::MAX as usize];
+
+                loop {
+                    let (len, client) = socket.recv_from(&mut buf).await.unwrap();
+                    let query = Message::from_vec(&buf[..len]).unwrap();
+                    let question = &query.queries()[0];
+
+                    let mut response = query.clone();
+                    response.set_message_type(MessageType::Response);
+
+                    match self
+                        .responses
+                        .get(&(question.name().clone(), question.query_type()))
+                    {
+                        Some((answers, authority)) => {
+                            response.add_answers(answers.clone());
+                            response.add_name_servers(authority.clone());
+                        }
+                        None => {
+                            let zone = self
+                                .zones
+                                .iter()
+                                .map(|zone| name(zone))
+                                .filter(|zone| zone.zone_of(question.name()))
+                                .max_by_key(Name::num_labels);
+                            if let Some(zone) = zone {
+                                response.add_name_server(soa(&zone.to_ascii()));
+                            }
+                        }
+                    }
+
+                    socket
+                        .send_to(&response.to_vec().unwrap(), client)
+                        .await
+                        .unwrap();
+                }
+            });
+
+            addr
+        }
+
+        /// Runs `f` with a client of the nameserver, and a config with the key
+        /// of `example.` as the trust anchor.
+        fn run<F, T>(self, owner: &str, f: impl FnOnce(DnsClient, Config) -> F) -> T
+        where
+            F: std::future::Future<Output = T>,
+        {
+            let anchor = std::env::temp_dir().join(format!(
+                "nu_plugin_dns-anchor-{}-{}",
+                std::process::id(),
+                owner
+            ));
+            std::fs::write(
+                &anchor,
+                format!(
+                    "example. IN DNSKEY 257 3 15 {}",
+                    data_encoding::BASE64.encode(self.parent.dnskey().public_key())
+                ),
+            )
+            .unwrap();
+
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let output = runtime.block_on(async {
+                let addr = self.serve().await;
+                let config = Config::from_values(|flag| match flag {
+                    constants::flags::SERVER => Some(Value::test_string(addr.to_string())),
+                    constants::flags::DNSSEC => Some(Value::test_string("validate")),
+                    constants::flags::TRUST_ANCHOR => {
+                        Some(Value::test_string(anchor.to_string_lossy()))
+                    }
+                    _ => None,
+                })
+                .unwrap();
+
+                let (client, _bg) = DnsClient::new(&config).await.unwrap();
+                f(client, config).await
+            });
+            std::fs::remove_file(anchor).unwrap();
+
+            output
+        }
+
+        /// Validates `answers` to an A query for `owner` against the
+        /// nameserver, with the key of `example.` as the trust anchor.
+        fn validate(self, owner: &str, answers: Vec<Record>) -> (Security, Value) {
+            let mut response = Message::new();
+            response
+                .set_message_type(MessageType::Response)
+                .add_query(Query::query(name(owner), RecordType::A))
+                .add_answers(answers);
+
+            let validation = self.run(owner, |client, config| async move {
+                validate(&client, &config, &response, Span::test_data())
+                    .await
+                    .unwrap()
+            });
+
+            let security = match validation.get_data_by_key("status").unwrap().as_str() {
+                Ok("secure") => Security::Secure,
+                Ok("insecure") => Security::Insecure,
+                _ => Security::Bogus,
+            };
+
+            (security, validation)
+        }
+    }
+
+    /// The detail of the failed step of a zone in a validation.
+    fn failure(validation: &Value, zone: &str) -> String {
+        let zones = validation.get_data_by_key("zones").unwrap();
+        let zone = zones
+            .as_list()
+            .unwrap()
+            .iter()
+            .find(|link| link.get_data_by_key("zone").unwrap().as_str().unwrap() == zone)
+            .unwrap();
+
+        zone.get_data_by_key("steps")
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .iter()
+            .find(|step| !step.get_data_by_key("passed").unwrap().as_bool().unwrap())
+            .map(|step| {
+                step.get_data_by_key("detail")
+                    .unwrap()
+                    .into_string()
+                    .unwrap()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn signed_zones_are_secure() {
+        let ksk = Key::new();
+        let mut fixture = Fixture::new();
+        fixture.child("signed.example.", &ksk, &[&ksk], &ksk);
+
+        let answers = signed(&ksk, "signed.example.", vec![a("www.signed.example.")]);
+        let (security, validation) = fixture.validate("www.signed.example.", answers);
+
+        assert_eq!(security, Security::Secure, "{:?}", validation);
+    }
+
+    #[test]
+    fn zones_with_a_proven_ds_denial_are_insecure() {
+        let mut fixture = Fixture::new();
+        fixture.unsigned_child(
+            "unsigned.example.",
+            vec![RecordType::NS, RecordType::RRSIG, RecordType::NSEC],
+        );
+
+        let (security, validation) =
+            fixture.validate("www.unsigned.example.", vec![a("www.unsigned.example.")]);
+
+        assert_eq!(security, Security::Insecure, "{:?}", validation);
+    }
+
+    #[test]
+    fn tampered_answers_are_bogus() {
+        let ksk = Key::new();
+        let mut fixture = Fixture::new();
+        fixture.child("signed.example.", &ksk, &[&ksk], &ksk);
+
+        let mut answers = signed(&ksk, "signed.example.", vec![a("www.signed.example.")]);
+        answers[0].set_data(Some(RData::A(A::new(192, 0, 2, 2))));
+        let (security, _) = fixture.validate("www.signed.example.", answers);
+        assert_eq!(security, Security::Bogus);
+
+        // nor may a signed zone answer without a signature
+        let mut fixture = Fixture::new();
+        fixture.child("signed.example.", &ksk, &[&ksk], &ksk);
+        let (security, _) = fixture.validate("www.signed.example.", vec![a("www.signed.example.")]);
+        assert_eq!(security, Security::Bogus);
+    }
+
+    #[test]
+    fn keys_must_be_signed_by_a_key_the_ds_records_match() {
+        let (ksk, forged) = (Key::new(), Key::new());
+        let mut fixture = Fixture::new();
+        // the DS record matches one key, while another one, which the parent
+        // knows nothing of, signs the keys
+        fixture.child("forged.example.", &ksk, &[&ksk, &forged], &forged);
+
+        let answers = signed(&forged, "forged.example.", vec![a("www.forged.example.")]);
+        let (security, validation) = fixture.validate("www.forged.example.", answers);
+
+        assert_eq!(security, Security::Bogus);
+        assert!(
+            failure(&validation, "forged.example.").contains("not among the keys trusted"),
+            "{:?}",
+            validation
+        );
+    }
+
+    #[test]
+    fn explained_keys_must_be_signed_by_a_key_the_ds_records_match() {
+        let (ksk, forged) = (Key::new(), Key::new());
+        let mut fixture = Fixture::new();
+        fixture.child("forged.example.", &ksk, &[&ksk, &forged], &forged);
+        let answers = signed(&forged, "forged.example.", vec![a("www.forged.example.")]);
+        fixture.answer("www.forged.example.", RecordType::A, answers);
+
+        let explained = fixture.run("explain", |client, config| async move {
+            let query = Query::query(name("www.forged.example."), RecordType::A);
+            explain(&client, &config, query, Span::test_data())
+                .await
+                .unwrap()
+        });
+
+        assert!(!explained
+            .get_data_by_key("secure")
+            .unwrap()
+            .as_bool()
+            .unwrap());
+        let steps = explained.get_data_by_key("steps").unwrap();
+        let failed: Vec<String> = steps
+            .as_list()
+            .unwrap()
+            .iter()
+            .filter(|step| !step.get_data_by_key("passed").unwrap().as_bool().unwrap())
+            .map(|step| {
+                step.get_data_by_key("check")
+                    .unwrap()
+                    .into_string()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(failed, ["DNSKEY signature"]);
+    }
+
+    #[test]
+    fn unsigned_zones_need_a_proven_ds_denial() {
+        let mut fixture = Fixture::new();
+        // NSEC records from the apex of the child itself
+        fixture.unsigned_child(
+            "apex.example.",
+            vec![
+                RecordType::SOA,
+                RecordType::NS,
+                RecordType::RRSIG,
+                RecordType::NSEC,
+            ],
+        );
+        let (security, validation) =
+            fixture.validate("www.apex.example.", vec![a("www.apex.example.")]);
+        assert_eq!(security, Security::Bogus);
+        assert!(failure(&validation, "apex.example.").contains("apex"));
+
+        let mut fixture = Fixture::new();
+        fixture.unsigned_child(
+            "delegated.example.",
+            vec![
+                RecordType::NS,
+                RecordType::DS,
+                RecordType::RRSIG,
+                RecordType::NSEC,
+            ],
+        );
+        let (security, _) =
+            fixture.validate("www.delegated.example.", vec![a("www.delegated.example.")]);
+        assert_eq!(security, Security::Bogus);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{pin::Pin, sync::Arc, time::Duration};
+use std::{net::SocketAddr, pin::Pin, sync::Arc, time::Duration};
 
 use futures_util::{future, Stream, StreamExt};
 use hickory_client::client::{AsyncClient, AsyncDnssecClient};
@@ -6,22 +6,81 @@ use hickory_proto::{
     error::{ProtoError, ProtoErrorKind},
     h2::HttpsClientStreamBuilder,
     iocompat::AsyncIoTokioAsStd,
-    op::NoopMessageFinalizer,
+    op::{Edns, Message, MessageType, OpCode, Query},
     quic::QuicClientStream,
+    rr::rdata::opt::{ClientSubnet, EdnsOption},
     tcp::TcpClientStream,
     udp::UdpClientStream,
-    xfer::DnsResponse,
+    xfer::{DnsRequest, DnsRequestOptions, DnsResponse, FirstAnswer, FirstAnswerFuture},
     DnsHandle, DnsMultiplexer,
 };
 use hickory_resolver::config::Protocol;
-use nu_protocol::{LabeledError, Span};
-use rustls::{OwnedTrustAnchor, RootCertStore};
-use tokio::{net::UdpSocket, task::JoinSet};
+use nu_protocol::{LabeledError, Span, Spanned};
+use rustls::{client::WebPkiVerifier, OwnedTrustAnchor, RootCertStore};
+use tokio::{net::UdpSocket, sync::Mutex, task::JoinSet};
+use tokio_util::sync::CancellationToken;
 
-use super::{config::Config, serde::DnssecMode};
+use super::{
+    config::Config,
+    constants,
+    cookie::{CookieStatus, Cookies},
+    dns_json,
+    dnscrypt::{DnsCryptClientStream, DnsCryptServer},
+    failure_limit::FailureLimit,
+    rate_limit::RateLimiter,
+    serde::{DnssecMode, TlsVersion},
+    stamp::PinnedCertVerifier,
+    tsig::{TsigKey, TsigSigner},
+    wire,
+};
 
 type DnsHandleResponse =
-    Pin<Box<(dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send + 'static)>>;
+    Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send + 'static>>;
+
+/// The tasks driving the connections of a client.
+type BackgroundTasks = JoinSet<Result<(), ProtoError>>;
+
+/// What the connections of a client were made with, which cannot be changed
+/// once they are: a config that differs in any of these needs a client of its
+/// own.
+#[derive(Debug, Clone, PartialEq)]
+struct Connection {
+    server: SocketAddr,
+    protocol: Protocol,
+    dns_name: Option<String>,
+    tsig: Option<TsigKey>,
+    gss_tsig: Option<String>,
+    min_tls_version: TlsVersion,
+    cert_hashes: Option<Vec<Vec<u8>>>,
+    dnscrypt: Option<DnsCryptServer>,
+    /// Whether the queries go to the JSON API of the server, rather than over
+    /// DNS over HTTPS.
+    dns_json: bool,
+    /// Which of the clients there are: a validating one would leave out the
+    /// bogus records that `--dnssec validate` is meant to report, and a
+    /// strict one would have no plain client at all.
+    dnssec_mode: DnssecMode,
+}
+
+impl Connection {
+    fn new(config: &Config, protocol: Protocol) -> Self {
+        Self {
+            server: config.server.item,
+            protocol,
+            dns_name: config.dns_name.as_ref().map(|name| name.item.clone()),
+            tsig: config.tsig.as_ref().map(|key| key.item.clone()),
+            gss_tsig: config.gss_tsig.as_ref().map(|host| host.item.clone()),
+            min_tls_version: config.min_tls_version.item,
+            cert_hashes: config
+                .cert_hashes
+                .as_ref()
+                .map(|hashes| hashes.item.clone()),
+            dnscrypt: config.dnscrypt.as_ref().map(|server| server.item.clone()),
+            dns_json: config.dns_json.is_some(),
+            dnssec_mode: config.dnssec_mode.item,
+        }
+    }
+}
 
 /// Client struct that wraps both a secure and non-secure client. This is a hack
 /// to allow falling back to unverified responses when the record is not signed.
@@ -34,6 +93,25 @@ type DnsHandleResponse =
 pub struct DnsClient {
     async_client: Option<AsyncClient>,
     dnssec_client: Option<AsyncDnssecClient>,
+    /// TLS config to ask the JSON API of the server with, instead of either
+    /// client.
+    json_api: Option<Arc<rustls::ClientConfig>>,
+    responder: SocketAddr,
+    connection: Connection,
+
+    /// Cancelled once any of the background tasks driving the connections
+    /// exits, after which no more responses can arrive.
+    closed: CancellationToken,
+
+    rate_limiter: Option<Arc<RateLimiter>>,
+    failure_limit: Option<Arc<FailureLimit>>,
+    response_window: Option<Duration>,
+    cookies: Option<Arc<Cookies>>,
+
+    /// Client for the same nameserver over TCP, to retry truncated UDP
+    /// responses with. It is only connected once it is needed, and shared
+    /// between the clones of this client.
+    tcp_fallback: Arc<Mutex<Option<(DnsClient, BackgroundTasks)>>>,
 }
 
 type TokioTcpConnect = AsyncIoTokioAsStd<tokio::net::TcpStream>;
@@ -42,6 +120,28 @@ impl DnsClient {
     pub async fn new(
         config: &Config,
     ) -> Result<(Self, JoinSet<Result<(), ProtoError>>), LabeledError> {
+        Self::connect(config, config.protocol.item).await
+    }
+
+    /// A new client, which gives up on connecting to the server after the
+    /// timeout of the config.
+    pub async fn connect_with_timeout(
+        config: &Config,
+    ) -> Result<(Self, BackgroundTasks), LabeledError> {
+        tokio::time::timeout(config.timeout.item, Self::new(config))
+            .await
+            .map_err(|_| {
+                LabeledError::new("timed out").with_label(
+                    format!("connecting to {} timed out", config.server.item),
+                    config.server.span,
+                )
+            })?
+    }
+
+    async fn connect(
+        config: &Config,
+        protocol: Protocol,
+    ) -> Result<(Self, BackgroundTasks), LabeledError> {
         let connect_err = |err| {
             LabeledError::new("connection error").with_label(
                 format!("Error creating client connection: {}", err),
@@ -50,23 +150,41 @@ impl DnsClient {
         };
 
         let mut join_set = JoinSet::new();
+        let closed = CancellationToken::new();
+
+        macro_rules! spawn_bg {
+            ($bg:expr) => {{
+                let bg = $bg;
+                let closed = closed.clone();
+
+                join_set.spawn(async move {
+                    let result = bg.await;
+                    tracing::debug!(task.background.phase = "exit", task.background.result = ?result);
+                    closed.cancel();
+                    result
+                });
+            }};
+        }
 
         macro_rules! make_clients {
             ($conn:expr) => {{
                 let async_client = if config.dnssec_mode.item != DnssecMode::Strict {
                     let (async_client, bg) =
                         AsyncClient::connect($conn).await.map_err(connect_err)?;
-                    join_set.spawn(bg);
+                    spawn_bg!(bg);
                     Some(async_client)
                 } else {
                     None
                 };
 
-                let dnssec_client = if config.dnssec_mode.item != DnssecMode::None {
+                let dnssec_client = if matches!(
+                    config.dnssec_mode.item,
+                    DnssecMode::Strict | DnssecMode::Opportunistic
+                ) {
                     let (dnssec_client, bg) = AsyncDnssecClient::connect($conn)
                         .await
                         .map_err(connect_err)?;
-                    join_set.spawn(bg);
+                    spawn_bg!(bg);
                     Some(dnssec_client)
                 } else {
                     None
@@ -75,22 +193,48 @@ impl DnsClient {
             }};
         }
 
-        let (async_client, dnssec_client) = match config.protocol.item {
-            Protocol::Udp => {
-                make_clients!(UdpClientStream::<UdpSocket>::with_timeout(
+        let signer = match (&config.tsig, &config.gss_tsig) {
+            (Some(key), _) => Some(Arc::new(key.item.signer())),
+            // each connection negotiates a key of its own
+            #[cfg(feature = "gss-tsig")]
+            (None, Some(host)) => Some(Arc::new(TsigSigner::from(
+                super::gss_tsig::negotiate(config, host).await?,
+            ))),
+            _ => None,
+        };
+        let mut json_api = None;
+
+        let (async_client, dnssec_client) = match protocol {
+            // the clients share the certificate of the resolver through the
+            // clones of the stream
+            Protocol::Udp | Protocol::Tcp if config.dnscrypt.is_some() => {
+                let stream = DnsCryptClientStream::new(
                     config.server.item,
-                    // can't set a timeout on HTTPS client, so work
-                    // around by setting the client internal timeout
-                    // very long for all the others so we can set
-                    // our own instead
-                    Duration::from_secs(60 * 60 * 24 * 365)
-                ))
+                    protocol,
+                    // safe to unwrap because of the match guard
+                    config.dnscrypt.as_ref().unwrap().item.clone(),
+                    signer.clone(),
+                );
+                make_clients!(future::ready(Ok::<_, ProtoError>(stream.clone())))
+            }
+            Protocol::Udp => {
+                make_clients!(
+                    UdpClientStream::<UdpSocket, TsigSigner>::with_timeout_and_signer(
+                        config.server.item,
+                        // can't set a timeout on HTTPS client, so work
+                        // around by setting the client internal timeout
+                        // very long for all the others so we can set
+                        // our own instead
+                        Duration::from_secs(60 * 60 * 24 * 365),
+                        signer.clone(),
+                    )
+                )
             }
             Protocol::Tcp => {
                 make_clients!({
                     let (stream, sender) =
                         TcpClientStream::<TokioTcpConnect>::new(config.server.item);
-                    DnsMultiplexer::<_, NoopMessageFinalizer>::with_timeout(
+                    DnsMultiplexer::<_, TsigSigner>::with_timeout(
                         stream,
                         sender,
                         // can't set a timeout on HTTPS client, so work
@@ -98,24 +242,21 @@ impl DnsClient {
                         // very long for all the others so we can set
                         // our own instead
                         Duration::from_secs(60 * 60 * 24 * 365),
-                        None,
+                        signer.clone(),
                     )
                 })
             }
-            proto @ (Protocol::Https | Protocol::Tls | Protocol::Quic) => {
-                let mut root_store = RootCertStore::empty();
-                root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
-                    OwnedTrustAnchor::from_subject_spki_name_constraints(
-                        ta.subject,
-                        ta.spki,
-                        ta.name_constraints,
-                    )
-                }));
+            // a JSON API is asked over a connection of each query's own, so
+            // there is nothing to connect yet
+            Protocol::Https if config.dns_json.is_some() => {
+                let mut client_config = tls_client_config(config)?;
+                client_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+                json_api = Some(Arc::new(client_config));
 
-                let client_config = rustls::ClientConfig::builder()
-                    .with_safe_defaults()
-                    .with_root_certificates(root_store)
-                    .with_no_client_auth();
+                (None, None)
+            }
+            proto @ (Protocol::Https | Protocol::Tls | Protocol::Quic) => {
+                let client_config = tls_client_config(config)?;
 
                 match proto {
                     Protocol::Tls => {
@@ -129,7 +270,7 @@ impl DnsClient {
                                     config.dns_name.as_ref().unwrap().clone().item,
                                     client_config.clone(),
                                 );
-                            DnsMultiplexer::<_, NoopMessageFinalizer>::with_timeout(
+                            DnsMultiplexer::<_, TsigSigner>::with_timeout(
                                 stream,
                                 sender,
                                 // can't set a timeout on HTTPS client, so work
@@ -137,7 +278,7 @@ impl DnsClient {
                                 // very long for all the others so we can set
                                 // our own instead
                                 Duration::from_secs(60 * 60 * 24 * 365),
-                                None,
+                                signer.clone(),
                             )
                         })
                     }
@@ -151,14 +292,23 @@ impl DnsClient {
                             )
                         })
                     }
-                    Protocol::Quic => make_clients!({
-                        let mut builder = QuicClientStream::builder();
-                        builder.crypto_config(client_config.clone());
-                        builder.build(
-                            config.server.item,
-                            config.dns_name.as_ref().unwrap().clone().item,
-                        )
-                    }),
+                    Protocol::Quic => {
+                        // the clones of the config share its session cache, so
+                        // connections after the first one can resume the
+                        // session and send their queries as 0-RTT data
+                        // (RFC 9250 section 4.5)
+                        let mut client_config = client_config;
+                        client_config.enable_early_data = true;
+
+                        make_clients!({
+                            let mut builder = QuicClientStream::builder();
+                            builder.crypto_config(client_config.clone());
+                            builder.build(
+                                config.server.item,
+                                config.dns_name.as_ref().unwrap().clone().item,
+                            )
+                        })
+                    }
                     _ => unreachable!(),
                 }
             }
@@ -172,10 +322,488 @@ impl DnsClient {
             Self {
                 async_client,
                 dnssec_client,
+                json_api,
+                responder: config.server.item,
+                connection: Connection::new(config, protocol),
+                closed,
+                rate_limiter: None,
+                failure_limit: None,
+                response_window: None,
+                cookies: None,
+                tcp_fallback: Arc::new(Mutex::new(None)),
             },
             join_set,
         ))
     }
+
+    /// The address responses are received from. The UDP stream discards any
+    /// datagram whose source does not match the nameserver, and all other
+    /// protocols are connection oriented, so this is always the nameserver
+    /// that was queried.
+    pub fn responder(&self) -> SocketAddr {
+        self.responder
+    }
+
+    /// Limits the rate of queries sent through this client and its clones.
+    pub fn with_rate_limit(mut self, per_second: f64) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(per_second)));
+        self
+    }
+
+    /// Waits until the rate limit, if any, allows sending the next query.
+    pub async fn ready(&self) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+    }
+
+    /// Gives up on sending queries through this client and its clones once
+    /// `limit` of them have failed.
+    pub fn with_failure_limit(mut self, limit: usize) -> Self {
+        self.failure_limit = Some(Arc::new(FailureLimit::new(limit)));
+        self
+    }
+
+    pub fn record_failure(&self) {
+        if let Some(failure_limit) = &self.failure_limit {
+            failure_limit.record_failure();
+        }
+    }
+
+    pub fn failure_limit_reached(&self) -> bool {
+        self.failure_limit
+            .as_ref()
+            .is_some_and(|failure_limit| failure_limit.reached())
+    }
+
+    /// Whether this is the first query skipped because the failure limit was
+    /// reached.
+    pub fn take_skip_report(&self) -> bool {
+        self.failure_limit
+            .as_ref()
+            .is_some_and(|failure_limit| failure_limit.take_skip_report())
+    }
+
+    /// Collects all responses that arrive within `window` of sending a query,
+    /// instead of only the first.
+    pub fn with_response_window(mut self, window: Duration) -> Self {
+        self.response_window = Some(window);
+        self
+    }
+
+    pub fn response_window(&self) -> Option<Duration> {
+        self.response_window
+    }
+
+    /// Sends DNS cookies with the queries of this client and its clones, with
+    /// a client cookie of their own.
+    pub fn with_cookies(mut self) -> Self {
+        self.cookies = Some(Arc::new(Cookies::new()));
+        self
+    }
+
+    /// Keeps the server cookie of a response, if cookies are sent, and tells
+    /// whether the response returned the client cookie.
+    pub fn receive_cookie(&self, response: &Message) -> Option<CookieStatus> {
+        self.cookies
+            .as_ref()
+            .map(|cookies| cookies.receive(response))
+    }
+
+    /// Whether this client was connected the way the config asks for, so that
+    /// it can be used in place of a new one.
+    pub fn is_for(&self, config: &Config) -> bool {
+        self.connection == Connection::new(config, config.protocol.item)
+    }
+
+    /// The client to retry a truncated response over TCP with, which is
+    /// connected on first use. It sends the same cookies.
+    pub async fn tcp_fallback(&self, config: &Config) -> Result<DnsClient, LabeledError> {
+        let mut fallback = self.tcp_fallback.lock().await;
+        let connection = Connection::new(config, Protocol::Tcp);
+
+        let mut client = match &mut *fallback {
+            Some((client, _)) if !client.is_closed() && client.connection == connection => {
+                client.clone()
+            }
+
+            // a closed connection is useless, and one made for another config
+            // would send the query elsewhere or sign it with the wrong key, so
+            // either is replaced
+            _ => {
+                let (client, bg) = Self::connect(config, Protocol::Tcp).await?;
+                if let Some((_, mut old_bg)) = fallback.replace((client.clone(), bg)) {
+                    // clones of the old client may still be in use, and its
+                    // connection ends by itself once they are all dropped
+                    old_bg.detach_all();
+                }
+                client
+            }
+        };
+
+        client.cookies = self.cookies.clone();
+        Ok(client)
+    }
+
+    /// A query built according to the config, with the cookies of the client,
+    /// and padded last, once its size is known.
+    fn query_message(&self, query: Query, config: &Config) -> Message {
+        let mut message = query_message(query, config);
+
+        if let (Some(cookies), Some(edns)) = (&self.cookies, message.extensions_mut()) {
+            edns.options_mut().insert(cookies.option());
+        }
+
+        if let Some(block_size) = &config.pad {
+            pad(&mut message, block_size.item);
+        }
+
+        message
+    }
+
+    /// Sends a query built according to the config and resolves to the first
+    /// response.
+    pub fn send_query(
+        &self,
+        query: Query,
+        config: &Config,
+    ) -> FirstAnswerFuture<DnsHandleResponse> {
+        self.send(DnsRequest::new(
+            self.query_message(query, config),
+            DnsRequestOptions::default(),
+        ))
+        .first_answer()
+    }
+
+    /// Sends a query with an opcode hickory does not know in a UDP datagram of
+    /// its own, writing the opcode into the encoded message, and resolves to
+    /// the response with the same ID.
+    pub async fn send_raw_query(
+        &self,
+        query: Query,
+        config: &Config,
+        opcode: u8,
+    ) -> Result<DnsResponse, ProtoError> {
+        let mut message = self.query_message(query, config);
+        message.set_id(rand::random());
+
+        let request = wire::with_op_code(message.to_vec()?, opcode);
+
+        let destination = config.server.item;
+        let bind_addr: SocketAddr = match destination {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+
+        let socket = UdpSocket::bind(bind_addr).await?;
+        socket.connect(destination).await?;
+        socket.send(&request).await?;
+
+        let mut buf = vec![0; u16::MAX as usize];
+        loop {
+            let len = socket.recv(&mut buf).await?;
+            let buffer = buf[..len].to_vec();
+
+            match Message::from_vec(&wire::readable(&buffer)) {
+                Ok(response) if response.id() == message.id() => {
+                    return Ok(DnsResponse::new(response, buffer));
+                }
+                Ok(response) => {
+                    tracing::debug!(
+                        response.id = response.id(),
+                        "skipping response to another query"
+                    )
+                }
+                Err(err) => {
+                    tracing::debug!(response.error = %err, "skipping malformed response")
+                }
+            }
+        }
+    }
+
+    /// Sends a query to the JSON API of the server (application/dns-json), and
+    /// maps its response into a message.
+    pub async fn send_json_query(
+        &self,
+        query: Query,
+        config: &Config,
+    ) -> Result<DnsResponse, ProtoError> {
+        let (Some(tls_config), Some(path)) = (&self.json_api, &config.dns_json) else {
+            return Err(ProtoError::from(
+                "the client is not connected to a JSON API",
+            ));
+        };
+
+        let message = dns_json::query(&query, config, &path.item, tls_config.clone()).await?;
+        DnsResponse::from_message(message)
+    }
+
+    /// Sends a message as it is, such as an UPDATE, and resolves to the first
+    /// response.
+    pub fn send_message(&self, message: Message) -> FirstAnswerFuture<DnsHandleResponse> {
+        self.send(DnsRequest::new(message, DnsRequestOptions::default()))
+            .first_answer()
+    }
+
+    /// Like [`DnsClient::send_query`], but asks the server not to recurse, so
+    /// that it answers from its own data or refers to the nameservers of a zone
+    /// closer to the name.
+    pub fn send_iterative_query(
+        &self,
+        query: Query,
+        config: &Config,
+    ) -> FirstAnswerFuture<DnsHandleResponse> {
+        let mut message = self.query_message(query, config);
+        message.set_recursion_desired(false);
+
+        self.send(DnsRequest::new(message, DnsRequestOptions::default()))
+            .first_answer()
+    }
+
+    /// Like [`DnsClient::send_query`], but sets the DNSSEC OK bit, so that
+    /// the signatures come along with the records. With a client that does
+    /// not validate, they are returned as they are.
+    pub fn send_dnssec_ok_query(
+        &self,
+        query: Query,
+        config: &Config,
+    ) -> FirstAnswerFuture<DnsHandleResponse> {
+        let mut message = self.query_message(query, config);
+        if let Some(edns) = message.extensions_mut() {
+            edns.set_dnssec_ok(true);
+        }
+
+        self.send(DnsRequest::new(message, DnsRequestOptions::default()))
+            .first_answer()
+    }
+
+    /// Sends a query built according to the config from a socket of its own,
+    /// and collects every response that arrives within the response window,
+    /// along with who sent it. This is meant for broadcast and multicast
+    /// addresses, which any number of hosts may respond to, so unlike with
+    /// [`DnsClient::send_query`], the responses are not validated.
+    pub async fn send_query_collect(
+        &self,
+        query: Query,
+        config: &Config,
+    ) -> Result<Vec<(Message, SocketAddr)>, ProtoError> {
+        let window = self.response_window.unwrap_or(config.timeout.item);
+        collect_responses(query_message(query, config), config.server.item, window).await
+    }
+
+    /// Sends a one-shot multicast DNS query (RFC 6762 section 5.1), and
+    /// collects the responses of every host that answers within the response
+    /// window. Names under `local.` are only ever resolved this way.
+    pub async fn send_mdns_query(
+        &self,
+        query: Query,
+        config: &Config,
+    ) -> Result<Vec<(Message, SocketAddr)>, ProtoError> {
+        let mut message = query_message(query, config);
+
+        // there is no recursion in mDNS (RFC 6762 section 18.2)
+        message.set_recursion_desired(false);
+
+        let window = self
+            .response_window
+            .unwrap_or(constants::config::default::MDNS_RESPONSE_WINDOW);
+        collect_responses(message, constants::config::default::MDNS_GROUP, window).await
+    }
+
+    /// Sends an LLMNR query (RFC 4795 section 2.4) to the link-local
+    /// multicast group, and collects the responses of every host that answers
+    /// within the response window.
+    pub async fn send_llmnr_query(
+        &self,
+        query: Query,
+        config: &Config,
+    ) -> Result<Vec<(Message, SocketAddr)>, ProtoError> {
+        let mut message = query_message(query, config);
+
+        // the bit is the T (tentative) bit in LLMNR, which is zero in queries
+        // (RFC 4795 section 2.1.1)
+        message.set_recursion_desired(false);
+
+        let window = self
+            .response_window
+            .unwrap_or(constants::config::default::LLMNR_RESPONSE_WINDOW);
+        collect_responses(message, constants::config::default::LLMNR_GROUP, window).await
+    }
+
+    /// Whether the connection to the nameserver has been closed, in which
+    /// case the client cannot be used anymore.
+    pub fn is_closed(&self) -> bool {
+        self.closed.is_cancelled()
+    }
+
+    /// Whether a query failed because the connection was closed while it was
+    /// in flight, rather than on its own.
+    pub async fn closed_during_query(&self) -> bool {
+        tokio::time::timeout(
+            constants::config::default::CLOSE_GRACE,
+            self.closed.cancelled(),
+        )
+        .await
+        .is_ok()
+    }
+}
+
+/// Sends a message from a socket of its own, and collects every response to it
+/// that arrives within the window, along with who sent it.
+async fn collect_responses(
+    mut message: Message,
+    destination: SocketAddr,
+    window: Duration,
+) -> Result<Vec<(Message, SocketAddr)>, ProtoError> {
+    let bind_addr: SocketAddr = match destination {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.set_broadcast(true)?;
+
+    message.set_id(rand::random());
+    socket.send_to(&message.to_vec()?, destination).await?;
+
+    let mut responses = Vec::new();
+    let mut buf = vec![0; u16::MAX as usize];
+    let deadline = tokio::time::Instant::now() + window;
+
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (len, from) = received?;
+
+        match Message::from_vec(&buf[..len]) {
+            Ok(response) if response.id() == message.id() => responses.push((response, from)),
+            Ok(response) => {
+                tracing::debug!(response.id = response.id(), response.from = %from, "skipping response to another query")
+            }
+            Err(err) => {
+                tracing::debug!(response.from = %from, response.error = %err, "skipping malformed response")
+            }
+        }
+    }
+
+    Ok(responses)
+}
+
+/// The TLS config to connect to the server with, trusting the web PKI.
+fn tls_client_config(config: &Config) -> Result<rustls::ClientConfig, LabeledError> {
+    let mut root_store = RootCertStore::empty();
+    root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    let mut client_config = rustls::ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(config.min_tls_version.item.allowed())
+        .map_err(|err| {
+            LabeledError::new("TLS error").with_label(
+                format!("Error configuring TLS: {}", err),
+                config.min_tls_version.span,
+            )
+        })?
+        .with_root_certificates(root_store.clone())
+        .with_no_client_auth();
+
+    if let Some(hashes) = &config.cert_hashes {
+        client_config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(PinnedCertVerifier::new(
+                WebPkiVerifier::new(root_store, None),
+                hashes.item.clone(),
+            )));
+    }
+
+    Ok(client_config)
+}
+
+/// A query built according to the config.
+fn query_message(query: Query, config: &Config) -> Message {
+    let mut message = Message::new();
+
+    // the ID is assigned by the transport when the request is sent
+    message
+        .add_query(query)
+        .set_message_type(MessageType::Query)
+        // an opcode hickory does not know is only written on the wire, by
+        // DnsClient::send_raw_query
+        .set_op_code(
+            config
+                .opcode
+                .as_ref()
+                .and_then(|opcode| OpCode::from_u8(opcode.item).ok())
+                .unwrap_or(OpCode::Query),
+        )
+        .set_recursion_desired(!config.no_recurse.item)
+        // asks the resolver to report whether it validated the answer
+        // (RFC 6840 section 5.7)
+        .set_authentic_data(config.trust_ad.item || config.authentic_data.item)
+        // validating by ourselves needs the records that a validating resolver
+        // would drop
+        .set_checking_disabled(
+            config.checking_disabled.item || config.dnssec_mode.item == DnssecMode::Validate,
+        );
+
+    if config.no_edns.item {
+        return message;
+    }
+
+    let edns = message
+        .extensions_mut()
+        .get_or_insert_with(Edns::new)
+        .set_max_payload(config.edns_payload.item)
+        .set_version(0);
+
+    for (code, data) in config.edns_options.item.iter() {
+        edns.options_mut()
+            .insert(EdnsOption::Unknown(*code, data.clone()));
+    }
+
+    if let Some(Spanned {
+        item: (network, prefix),
+        ..
+    }) = config.client_subnet
+    {
+        edns.options_mut()
+            .insert(EdnsOption::Subnet(ClientSubnet::new(network, prefix, 0)));
+    }
+
+    // the identifier is asked for with an empty option (RFC 5001 section 2.3)
+    if config.nsid.item {
+        edns.options_mut()
+            .insert(EdnsOption::Unknown(constants::edns::NSID, Vec::new()));
+    }
+
+    message
+}
+
+/// Pads a message with the padding option to a multiple of the block size
+/// (RFC 7830 section 3). The size is that of the message as encoded now, so a
+/// TSIG added when it is sent comes on top of it.
+fn pad(message: &mut Message, block_size: u16) {
+    let Some(edns) = message.extensions_mut() else {
+        return;
+    };
+    edns.options_mut()
+        .insert(EdnsOption::Unknown(constants::edns::PADDING, Vec::new()));
+
+    let Ok(len) = message.to_vec().map(|wire| wire.len()) else {
+        return;
+    };
+    let padding = (block_size as usize - len % block_size as usize) % block_size as usize;
+
+    if let Some(edns) = message.extensions_mut() {
+        edns.options_mut().insert(EdnsOption::Unknown(
+            constants::edns::PADDING,
+            vec![0; padding],
+        ));
+    }
 }
 
 impl DnsHandle for DnsClient {
@@ -189,7 +817,10 @@ impl DnsHandle for DnsClient {
         let request = request.into();
 
         match (&self.async_client, &self.dnssec_client) {
-            (None, None) => unreachable!(),
+            // a JSON API only takes queries, through DnsClient::send_json_query
+            (None, None) => Box::pin(futures_util::stream::once(future::err(ProtoError::from(
+                "only queries can be sent to a JSON API",
+            )))),
             (Some(async_client), None) => Box::pin(async_client.send(request)),
             (None, Some(dnssec_client)) => Box::pin(dnssec_client.send(request)),
             (Some(async_client), Some(dnssec_client)) => {
@@ -0,0 +1,311 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
+use hickory_proto::rr::Record;
+use hickory_resolver::{config::Protocol, Name};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    ByteStream, ByteStreamType, Example, LabeledError, PipelineData, ShellError, Signals,
+    Signature, Span, Spanned, SyntaxShape, Value,
+};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        commands::query::{response_stream, watch_sigterm, AnswerTransform},
+        config::Config,
+        constants, serde,
+        serde::DnssecMode,
+        xfr,
+    },
+    spanned, Dns,
+};
+
+/// How the transferred records are returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Table,
+    Zonefile,
+}
+
+impl TryFrom<Spanned<String>> for Output {
+    type Error = LabeledError;
+
+    fn try_from(value: Spanned<String>) -> Result<Self, Self::Error> {
+        match value.item.to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "zonefile" => Ok(Self::Zonefile),
+            _ => Err(LabeledError::new("invalid output").with_label(
+                format!(
+                    "output should be \"table\" or \"zonefile\", not \"{}\"",
+                    value.item
+                ),
+                value.span,
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DnsAxfr;
+
+impl DnsAxfr {
+    async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let zone: Spanned<String> = call.req(0)?;
+        let output = call
+            .get_flag::<Spanned<String>>(constants::flags::OUTPUT)?
+            .map(Output::try_from)
+            .transpose()?
+            .unwrap_or(Output::Table);
+
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // zone transfers do not fit in UDP responses (RFC 5936 section 4.2),
+        // and the DNSSEC client only ever waits for the first message
+        if config.protocol.item == Protocol::Udp {
+            config.protocol = spanned!(Protocol::Tcp, config.protocol.span);
+        }
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let zone_name = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+
+        let (client, bg) = DnsClient::connect_with_timeout(&config).await?;
+
+        let config = Arc::new(config);
+        let ctrlc = Signals::new(Arc::new(AtomicBool::new(false)));
+        let (resp_tx, resp_rx) = mpsc::channel(config.tasks.item);
+
+        plugin.spawn(watch_sigterm(ctrlc.clone(), plugin.cancel.clone()));
+
+        let cancel = plugin.cancel.clone();
+        let span = call.head;
+        plugin.spawn(async move {
+            // the connection is driven until the transfer is done
+            let _bg = bg;
+            stream_transfer(client, config, zone_name, output, resp_tx, cancel, span).await
+        });
+
+        Ok(match output {
+            Output::Table => response_stream(resp_rx, call.head, ctrlc, AnswerTransform::default()),
+            Output::Zonefile => zonefile_stream(resp_rx, call.head, ctrlc),
+        })
+    }
+}
+
+/// Transfers a zone, and sends its records on as soon as each message of the
+/// transfer arrives, so that a large zone is never held in memory at once.
+async fn stream_transfer(
+    client: DnsClient,
+    config: Arc<Config>,
+    zone: Name,
+    output: Output,
+    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
+    cancel: CancellationToken,
+    span: Span,
+) -> Result<(), LabeledError> {
+    // the transfer ends with the SOA it started with, so the last record is
+    // held back until it is known not to be the end
+    let mut held: Option<Record> = None;
+    let mut count = 0;
+
+    let result = xfr::transfer_each(&client, &config, zone, None, span, |records| {
+        count += records.len();
+
+        let ready: Vec<Record> = records
+            .into_iter()
+            .filter_map(|record| held.replace(record))
+            .collect();
+        let rows = output_rows(ready, output, &config);
+
+        let resp_tx = resp_tx.clone();
+        let cancel = cancel.clone();
+
+        async move {
+            for row in rows {
+                // the receiver is dropped along with the command's output
+                if cancel.is_cancelled() || resp_tx.send(row).await.is_err() {
+                    return false;
+                }
+            }
+
+            !cancel.is_cancelled()
+        }
+    })
+    .await;
+
+    let rows = match result {
+        // a lone record is all there is
+        Ok(()) if count == 1 => output_rows(held.into_iter().collect(), output, &config),
+        Ok(()) => Vec::new(),
+        Err(err) => vec![Err(err)],
+    };
+
+    for row in rows {
+        if resp_tx.send(row).await.is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Records as rows of a table, or as a chunk of zone file text.
+fn output_rows(
+    records: Vec<Record>,
+    output: Output,
+    config: &Config,
+) -> Vec<Result<Value, LabeledError>> {
+    match output {
+        Output::Table => records
+            .into_iter()
+            .map(|record| serde::Record(record).into_value(config))
+            .collect(),
+        Output::Zonefile if records.is_empty() => Vec::new(),
+        Output::Zonefile => vec![Ok(Value::string(
+            records
+                .iter()
+                .map(|record| format!("{}\n", record))
+                .collect::<String>(),
+            Span::unknown(),
+        ))],
+    }
+}
+
+/// The chunks of zone file text as a single string, which can be saved as it
+/// arrives.
+fn zonefile_stream(
+    mut resp_rx: mpsc::Receiver<Result<Value, LabeledError>>,
+    span: Span,
+    ctrlc: Signals,
+) -> PipelineData {
+    PipelineData::ByteStream(
+        ByteStream::from_result_iter(
+            std::iter::from_fn(move || {
+                tokio::task::block_in_place(|| resp_rx.blocking_recv()).map(|chunk| {
+                    chunk
+                        .map_err(ShellError::from)
+                        .and_then(Value::into_string)
+                        .map(String::into_bytes)
+                })
+            }),
+            span,
+            ctrlc,
+            ByteStreamType::String,
+        ),
+        None,
+    )
+}
+
+impl PluginCommand for DnsAxfr {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(plugin, engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::AXFR
+    }
+
+    fn description(&self) -> &str {
+        "Fetch all records of a zone with a zone transfer"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone to transfer",
+            )
+            .named(
+                constants::flags::OUTPUT,
+                SyntaxShape::String,
+                "How to return the records: table, zonefile. (default: table)",
+                Some('o'),
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to transfer the zone from (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: TCP, TLS. (default: TCP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TSIG_KEY,
+                SyntaxShape::String,
+                "TSIG key to sign the transfer with, as name:algorithm:secret with the secret in base64. The algorithm is one of: hmac-sha256, hmac-sha384, hmac-sha512 (for UDP, TCP, and TLS only)",
+                None,
+            )
+            .named(
+                constants::flags::TSIG_KEY_FILE,
+                SyntaxShape::Filepath,
+                "File with the TSIG key to sign the transfer with, in the format written by tsig-keygen",
+                None,
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait for each message of the transfer. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns axfr zonetransfer.me --server 81.4.108.41",
+                description: "list the records of a zone",
+                result: None,
+            },
+            Example {
+                example: "dns axfr zonetransfer.me --server 81.4.108.41 --output zonefile | save zonetransfer.me.zone",
+                description: "save a zone as a zone file",
+                result: None,
+            },
+            Example {
+                example: "dns axfr example.com --server ns1.example.com | where type == CNAME | first 10",
+                description: "find CNAME records in a large zone, which stops the transfer once enough are found",
+                result: None,
+            },
+            Example {
+                example: "dns axfr example.com --server ns1.example.com --tsig-key-file /etc/bind/transfer.key",
+                description: "transfer a zone that is only transferred to holders of a TSIG key",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "axfr", "zone", "transfer", "zonefile"]
+    }
+}
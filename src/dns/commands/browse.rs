@@ -0,0 +1,331 @@
+use hickory_proto::{
+    error::ProtoError,
+    rr::{
+        rdata::{SRV, TXT},
+        RData, Record, RecordType,
+    },
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, lookup, names},
+    Dns,
+};
+
+/// Name under which the service types on the network are enumerated (RFC 6763
+/// section 9).
+const SERVICE_TYPES: &str = "_services._dns-sd._udp.local.";
+
+#[derive(Debug)]
+pub struct DnsBrowse;
+
+impl DnsBrowse {
+    async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let service: Option<Spanned<String>> = call.opt(0)?;
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // the client only lends its settings to the multicast queries, which
+        // are sent from sockets of their own
+        let client = plugin.dns_client(&config).await?;
+        let client = match &config.response_window {
+            Some(window) => client.with_response_window(window.item),
+            None => client,
+        };
+
+        let services = match service {
+            Some(service) => vec![service_name(&service)?],
+            None => {
+                let owner = Name::from_ascii(SERVICE_TYPES).expect("valid name");
+                let records =
+                    lookup::mdns(&client, &config, owner.clone(), RecordType::PTR, call.head)
+                        .await?;
+                ptr_targets(&records, &owner)
+            }
+        };
+
+        let instances = futures_util::future::try_join_all(services.into_iter().map(|service| {
+            let client = &client;
+            let config = &config;
+
+            async move {
+                let records =
+                    lookup::mdns(client, config, service.clone(), RecordType::PTR, call.head)
+                        .await?;
+
+                futures_util::future::try_join_all(ptr_targets(&records, &service).into_iter().map(
+                    |instance| {
+                        resolve_instance(client, config, &service, instance, &records, call.head)
+                    },
+                ))
+                .await
+            }
+        }))
+        .await?;
+
+        let mut rows: Vec<_> = instances.into_iter().flatten().collect();
+        rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Ok(PipelineData::Value(
+            Value::list(rows.into_iter().map(|(_, row)| row).collect(), call.head),
+            None,
+        ))
+    }
+}
+
+/// The name a service type like `_ipp._tcp` is browsed under, which is in the
+/// `local.` domain unless it is given as a full name.
+fn service_name(service: &Spanned<String>) -> Result<Name, LabeledError> {
+    let invalid = |err: ProtoError| {
+        LabeledError::new("invalid name")
+            .with_label(format!("Error parsing name: {}", err), service.span)
+    };
+
+    let mut name = Name::from_utf8(&service.item).map_err(invalid)?;
+
+    if names::is_mdns(&name) {
+        name.set_fqdn(true);
+        return Ok(name);
+    }
+
+    name.append_domain(&Name::from_ascii("local.").expect("valid name"))
+        .map_err(invalid)
+}
+
+/// The distinct names the PTR records of `owner` point to.
+fn ptr_targets(records: &[Record], owner: &Name) -> Vec<Name> {
+    let mut targets: Vec<Name> = records
+        .iter()
+        .filter(|record| record.name() == owner)
+        .filter_map(|record| match record.data() {
+            Some(RData::PTR(ptr)) => Some(ptr.0.clone()),
+            _ => None,
+        })
+        .collect();
+
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+/// Looks up where a service instance is offered, along with its metadata, and
+/// returns it as a row, keyed for sorting. Records that responders already
+/// sent along are used instead of asking for them again.
+async fn resolve_instance(
+    client: &DnsClient,
+    config: &Config,
+    service: &Name,
+    instance: Name,
+    known: &[Record],
+    span: Span,
+) -> Result<((String, String), Value), LabeledError> {
+    let mut records: Vec<Record> = known
+        .iter()
+        .filter(|record| record.name() == &instance)
+        .cloned()
+        .collect();
+
+    for rtype in [RecordType::SRV, RecordType::TXT] {
+        if !records.iter().any(|record| record.record_type() == rtype) {
+            records.extend(lookup::mdns(client, config, instance.clone(), rtype, span).await?);
+        }
+    }
+
+    let srv: Option<SRV> = records.iter().find_map(|record| match record.data() {
+        Some(RData::SRV(srv)) if record.name() == &instance => Some(srv.clone()),
+        _ => None,
+    });
+    let txt: Option<TXT> = records.iter().find_map(|record| match record.data() {
+        Some(RData::TXT(txt)) if record.name() == &instance => Some(txt.clone()),
+        _ => None,
+    });
+
+    let (host, port, addresses) = match &srv {
+        Some(srv) => {
+            let target = srv.target();
+            let mut addresses = lookup::addresses_of(
+                known
+                    .iter()
+                    .chain(&records)
+                    .filter(|record| record.name() == target),
+            );
+
+            if addresses.is_empty() {
+                for rtype in [RecordType::A, RecordType::AAAA] {
+                    let found = lookup::mdns(client, config, target.clone(), rtype, span).await?;
+                    addresses.extend(lookup::addresses_of(
+                        found.iter().filter(|record| record.name() == target),
+                    ));
+                }
+            }
+
+            addresses.sort();
+            addresses.dedup();
+
+            (
+                // without the trailing dot, so that it can be passed straight
+                // to dns query
+                Value::string(target.to_utf8().trim_end_matches('.'), Span::unknown()),
+                Value::int(srv.port() as i64, Span::unknown()),
+                Value::list(
+                    addresses
+                        .into_iter()
+                        .map(|addr| Value::string(addr.to_string(), Span::unknown()))
+                        .collect(),
+                    Span::unknown(),
+                ),
+            )
+        }
+        // the instance went away between the responses
+        None => (
+            Value::nothing(Span::unknown()),
+            Value::nothing(Span::unknown()),
+            Value::list(Vec::new(), Span::unknown()),
+        ),
+    };
+
+    // the first label is the user-visible name of the instance, which may
+    // contain dots and spaces (RFC 6763 section 4.1.1)
+    let instance_label = instance
+        .iter()
+        .next()
+        .map(|label| String::from_utf8_lossy(label).into_owned())
+        .unwrap_or_default();
+    let service_type = service
+        .to_utf8()
+        .trim_end_matches('.')
+        .trim_end_matches(".local")
+        .to_string();
+
+    let values = vec![
+        Value::string(&instance_label, Span::unknown()),
+        Value::string(&service_type, Span::unknown()),
+        host,
+        port,
+        addresses,
+        txt.map_or_else(
+            || Value::nothing(Span::unknown()),
+            |txt| txt_attributes(&txt),
+        ),
+    ];
+
+    Ok((
+        (service_type, instance_label),
+        Value::record(
+            nu_protocol::Record::from_iter(std::iter::zip(
+                constants::columns::BROWSE_COLS
+                    .iter()
+                    .map(|col| (*col).into()),
+                values,
+            )),
+            Span::unknown(),
+        ),
+    ))
+}
+
+/// The `key=value` attributes of a TXT record (RFC 6763 section 6). A key
+/// without a value is a boolean attribute that is present, and only the first
+/// of repeated keys counts.
+fn txt_attributes(txt: &TXT) -> Value {
+    let mut attributes = nu_protocol::Record::new();
+
+    for entry in txt.iter() {
+        let entry = String::from_utf8_lossy(entry);
+        let (key, value) = match entry.split_once('=') {
+            Some((key, value)) => (key, Value::string(value, Span::unknown())),
+            None => (entry.as_ref(), Value::bool(true, Span::unknown())),
+        };
+
+        if key.is_empty()
+            || attributes
+                .columns()
+                .any(|col| col.eq_ignore_ascii_case(key))
+        {
+            continue;
+        }
+
+        attributes.push(key, value);
+    }
+
+    Value::record(attributes, Span::unknown())
+}
+
+impl PluginCommand for DnsBrowse {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(plugin, engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::BROWSE
+    }
+
+    fn description(&self) -> &str {
+        "Discover the services offered on the local network over DNS-SD (RFC 6763)"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::SERVICE,
+                SyntaxShape::String,
+                "Service type to browse, e.g. \"_ipp._tcp\". Every service type that is advertised is browsed if left out",
+            )
+            .named(
+                constants::flags::RESPONSE_WINDOW,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to collect responses to each query for. Default: {}sec",
+                    constants::config::default::MDNS_RESPONSE_WINDOW.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns browse",
+                description: "list every service advertised on the local network",
+                result: None,
+            },
+            Example {
+                example: "dns browse _ipp._tcp | select instance host port txt.ty?",
+                description: "find the printers on the local network, with their make and model",
+                result: None,
+            },
+            Example {
+                example: "dns browse _airplay._tcp --response-window 3sec",
+                description: "find AirPlay devices, waiting longer for slow responders",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "dns-sd",
+            "mdns",
+            "bonjour",
+            "zeroconf",
+            "service",
+            "discovery",
+        ]
+    }
+}
@@ -0,0 +1,388 @@
+use hickory_proto::{
+    op::{Edns, Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{
+        dnssec::{Algorithm, SupportedAlgorithms},
+        rdata::opt::EdnsOption,
+        DNSClass,
+    },
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, Span, Value};
+
+use crate::{
+    dns::{constants, serde, wire, zone},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsBuild;
+
+impl DnsBuild {
+    fn run_impl(
+        &self,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let value = match input.into_value(call.head)? {
+            Value::List { vals, .. } => Value::list(
+                vals.iter()
+                    .map(|val| {
+                        let (message, op_code) = build_message(val)?;
+                        encode(&message, op_code, val.span())
+                            .map(|wire| Value::binary(wire, call.head))
+                    })
+                    .collect::<Result<_, _>>()?,
+                call.head,
+            ),
+            val => {
+                let (message, op_code) = build_message(&val)?;
+                Value::binary(encode(&message, op_code, val.span())?, call.head)
+            }
+        };
+
+        Ok(PipelineData::Value(value, None))
+    }
+}
+
+/// The message in wire format, with the opcode hickory does not know, if any,
+/// patched in.
+pub(crate) fn encode(
+    message: &Message,
+    op_code: Option<u8>,
+    span: Span,
+) -> Result<Vec<u8>, LabeledError> {
+    let wire = message.to_vec().map_err(|err| {
+        LabeledError::new("could not encode message")
+            .with_label(format!("Error encoding message: {}", err.kind()), span)
+    })?;
+
+    Ok(match op_code {
+        Some(op_code) => wire::with_op_code(wire, op_code),
+        None => wire,
+    })
+}
+
+/// A message from a record of the shape `dns parse` and queries output. Every
+/// column is optional, and the header defaults to that of a recursive query
+/// with a random ID. The counts of the header are those of the sections given,
+/// and the status and size columns are ignored. An opcode hickory does not know
+/// is returned along with the message, to [`encode`] it with.
+pub(crate) fn build_message(value: &Value) -> Result<(Message, Option<u8>), LabeledError> {
+    let span = value.span();
+
+    if !matches!(value, Value::Record { .. }) {
+        return Err(LabeledError::new("invalid message").with_label(
+            format!(
+                "expected a record with the columns of a message, not a {}",
+                value.get_type()
+            ),
+            span,
+        ));
+    }
+
+    let column = |col: &str| value.get_data_by_key(col);
+
+    let mut message = Message::new();
+    message
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true);
+
+    let op_code = match column("header") {
+        Some(header) => set_header(&mut message, &header)?,
+        None => None,
+    };
+
+    for query in rows(column("question"))? {
+        let name = serde::Query::name_from_value(&field(&query, "name")?)?;
+        let serde::RType(rtype) = serde::RType::try_from(&code(&field(&query, "type")?))?;
+        let class = match query.get_data_by_key("class") {
+            Some(class) => serde::DNSClass::try_from(code(&class))?.0,
+            None => DNSClass::IN,
+        };
+
+        let mut query = Query::query(name, rtype);
+        query.set_query_class(class);
+        message.add_query(query);
+    }
+
+    let records = |col: &str| {
+        rows(column(col))?
+            .iter()
+            .map(|record| zone::record_from_value(record, None, DNSClass::IN))
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    message.add_answers(records("answer")?);
+    message.add_name_servers(records("authority")?);
+    message.add_additionals(records("additional")?);
+
+    if let Some(edns) = column("edns").filter(|edns| !matches!(edns, Value::Nothing { .. })) {
+        message.set_edns(build_edns(&edns)?);
+    }
+
+    Ok((message, op_code))
+}
+
+/// Sets the fields of the header given. Any opcode from 0 to 15 is taken, and
+/// one that hickory does not know is returned instead of set, for it to be
+/// patched into the wire format.
+pub(crate) fn set_header(
+    message: &mut Message,
+    header: &Value,
+) -> Result<Option<u8>, LabeledError> {
+    let flag = |col: &str| match header.get_data_by_key(col) {
+        Some(Value::Bool { val, .. }) => Ok(Some(val)),
+        None | Some(Value::Nothing { .. }) => Ok(None),
+        Some(other) => Err(LabeledError::new("invalid header")
+            .with_label(format!("{} must be a bool", col), other.span())),
+    };
+
+    if let Some(id) = header.get_data_by_key("id") {
+        message.set_id(number(&id, "id")?);
+    }
+
+    if let Some(message_type) = header.get_data_by_key("message_type").map(|val| code(&val)) {
+        message.set_message_type(match &message_type {
+            Value::String { val, .. } if val.eq_ignore_ascii_case("query") => MessageType::Query,
+            Value::String { val, .. } if val.eq_ignore_ascii_case("response") => {
+                MessageType::Response
+            }
+            Value::Int { val: 0, .. } => MessageType::Query,
+            Value::Int { val: 1, .. } => MessageType::Response,
+            other => {
+                return Err(LabeledError::new("invalid header").with_label(
+                    "message type must be QUERY or RESPONSE, or 0 or 1",
+                    other.span(),
+                ))
+            }
+        });
+    }
+
+    let mut raw_op_code = None;
+    if let Some(op_code) = header.get_data_by_key("op_code").map(|val| code(&val)) {
+        let serde::OpCode(op_code) = serde::OpCode::try_from(op_code)?;
+
+        match OpCode::from_u8(op_code) {
+            Ok(op_code) => {
+                message.set_op_code(op_code);
+            }
+            Err(_) => {
+                message.set_op_code(OpCode::Query);
+                raw_op_code = Some(op_code);
+            }
+        }
+    }
+
+    if let Some(authoritative) = flag("authoritative")? {
+        message.set_authoritative(authoritative);
+    }
+    if let Some(truncated) = flag("truncated")? {
+        message.set_truncated(truncated);
+    }
+    if let Some(recursion_desired) = flag("recursion_desired")? {
+        message.set_recursion_desired(recursion_desired);
+    }
+    if let Some(recursion_available) = flag("recursion_available")? {
+        message.set_recursion_available(recursion_available);
+    }
+    if let Some(authentic_data) = flag("authentic_data")? {
+        message.set_authentic_data(authentic_data);
+    }
+    if let Some(checking_disabled) = flag("checking_disabled")? {
+        message.set_checking_disabled(checking_disabled);
+    }
+
+    if let Some(response_code) = header
+        .get_data_by_key("response_code")
+        .map(|val| code(&val))
+    {
+        let response_code = match &response_code {
+            // by the names queries output, e.g. "Non-Existent Domain"
+            Value::String { val, .. } => (0..=4095)
+                .map(|code: u16| -> ResponseCode { code.into() })
+                .filter(|code| !matches!(code, ResponseCode::Unknown(_)))
+                .find(|code| code.to_str().eq_ignore_ascii_case(val)),
+            Value::Int { .. } => Some(number::<u16>(&response_code, "response code")?.into()),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            LabeledError::new("invalid header").with_label(
+                "response code must be the name of one, e.g. No Error, or its number",
+                response_code.span(),
+            )
+        })?;
+
+        message.set_response_code(response_code);
+    }
+
+    Ok(raw_op_code)
+}
+
+fn build_edns(value: &Value) -> Result<Edns, LabeledError> {
+    let mut edns = Edns::new();
+    edns.set_max_payload(constants::config::default::EDNS_PAYLOAD);
+
+    if let Some(version) = value.get_data_by_key("version") {
+        edns.set_version(number(&version, "EDNS version")?);
+    }
+
+    if let Some(Value::Bool { val, .. }) = value.get_data_by_key("dnssec_ok") {
+        edns.set_dnssec_ok(val);
+    }
+
+    if let Some(max_payload) = value.get_data_by_key("max_payload") {
+        let max_payload = match max_payload {
+            Value::Filesize { val, .. } => Value::int(val, max_payload.span()),
+            other => other,
+        };
+
+        edns.set_max_payload(number(&max_payload, "max payload")?);
+    }
+
+    if let Some(Value::Record { val, .. }) = value.get_data_by_key("opts") {
+        for (name, option) in val.iter() {
+            let algorithms = || -> Result<SupportedAlgorithms, LabeledError> {
+                let mut algorithms = SupportedAlgorithms::new();
+
+                for algorithm in option.as_list()? {
+                    let name = algorithm.as_str()?;
+                    let algorithm = (0..=u8::MAX)
+                        .map(Algorithm::from_u8)
+                        .filter(|algorithm| !matches!(algorithm, Algorithm::Unknown(_)))
+                        .find(|algorithm| algorithm.to_string().eq_ignore_ascii_case(name))
+                        .ok_or_else(|| {
+                            LabeledError::new("invalid EDNS option").with_label(
+                                format!("unknown DNSSEC algorithm: {}", name),
+                                algorithm.span(),
+                            )
+                        })?;
+
+                    algorithms.set(algorithm);
+                }
+
+                Ok(algorithms)
+            };
+
+            let option = match name.as_str() {
+                "DAU" => EdnsOption::DAU(algorithms()?),
+                "DHU" => EdnsOption::DHU(algorithms()?),
+                "N3U" => EdnsOption::N3U(algorithms()?),
+                _ => {
+                    let code = number(&field(option, "code")?, "EDNS option code")?;
+                    let data = match field(option, "data")? {
+                        Value::Binary { val, .. } => val,
+                        Value::String { val, .. } => val.into_bytes(),
+                        other => {
+                            return Err(LabeledError::new("invalid EDNS option")
+                                .with_label("data must be binary or a string", other.span()))
+                        }
+                    };
+
+                    EdnsOption::Unknown(code, data)
+                }
+            };
+
+            edns.options_mut().insert(option);
+        }
+    }
+
+    Ok(edns)
+}
+
+/// The rows of a section, given as a list of records or a single one.
+fn rows(value: Option<Value>) -> Result<Vec<Value>, LabeledError> {
+    match value {
+        None | Some(Value::Nothing { .. }) => Ok(Vec::new()),
+        Some(Value::List { vals, .. }) => Ok(vals),
+        Some(record @ Value::Record { .. }) if !record.as_record()?.is_empty() => Ok(vec![record]),
+        Some(Value::Record { .. }) => Ok(Vec::new()),
+        Some(other) => Err(LabeledError::new("invalid message")
+            .with_label("sections must be a list of records", other.span())),
+    }
+}
+
+fn field(value: &Value, col: &str) -> Result<Value, LabeledError> {
+    value.get_data_by_key(col).ok_or_else(|| {
+        LabeledError::new("invalid message")
+            .with_label(format!("must have a column named '{}'", col), value.span())
+    })
+}
+
+/// A code given as the record of its name and number, as output with
+/// `--code`, is taken by its number.
+fn code(value: &Value) -> Value {
+    match value.get_data_by_key("code") {
+        Some(code) if matches!(value, Value::Record { .. }) => code,
+        _ => value.clone(),
+    }
+}
+
+fn number<N: TryFrom<i64>>(value: &Value, what: &str) -> Result<N, LabeledError>
+where
+    N::Error: std::fmt::Display,
+{
+    let Value::Int { val, .. } = value else {
+        return Err(LabeledError::new("invalid message")
+            .with_label(format!("{} must be an int", what), value.span()));
+    };
+
+    N::try_from(*val).map_err(|err| {
+        LabeledError::new("invalid message")
+            .with_label(format!("{} is out of range: {}", what, err), value.span())
+    })
+}
+
+impl PluginCommand for DnsBuild {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(engine, call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::BUILD
+    }
+
+    fn description(&self) -> &str {
+        "Encode messages given as records, of the shape dns parse outputs, into wire format. The header defaults to that of a recursive query with a random ID"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "{question: {name: example.com, type: AAAA}} | dns build",
+                description: "encode a recursive query for the AAAA records of example.com",
+                result: None,
+            },
+            Example {
+                example: "{header: {id: 1, recursion_desired: false}, question: {name: example.com, type: A}, edns: {dnssec_ok: true}} | dns build | save query.bin",
+                description: "craft a query with a fixed ID, without recursion, asking for DNSSEC records",
+                result: None,
+            },
+            Example {
+                example: "open --raw response.bin | dns parse | update header.truncated true | dns build",
+                description: "change a captured response, and encode it again",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "build", "encode", "wire", "packet", "binary", "craft",
+        ]
+    }
+}
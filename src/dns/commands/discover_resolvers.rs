@@ -0,0 +1,238 @@
+use hickory_proto::rr::{
+    rdata::svcb::{IpHint, SvcParamKey, SvcParamValue, Unknown, SVCB},
+    RData, RecordType,
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, lookup},
+    Dns,
+};
+
+/// Name under which a resolver advertises its designated resolvers (RFC 9462
+/// section 4).
+const RESOLVER_ARPA: &str = "_dns.resolver.arpa.";
+
+/// Key of the URI template of a DoH endpoint (RFC 9461 section 5).
+const DOHPATH_KEY: u16 = 7;
+
+#[derive(Debug)]
+pub struct DnsDiscoverResolvers;
+
+impl DnsDiscoverResolvers {
+    async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        let client = tokio::time::timeout(config.timeout.item, plugin.dns_client(&config))
+            .await
+            .map_err(|_| {
+                LabeledError::new("timed out").with_label(
+                    format!("connecting to {} timed out", config.server.item),
+                    config.server.span,
+                )
+            })??;
+
+        let owner = Name::from_ascii(RESOLVER_ARPA).expect("valid name");
+        let response = lookup::lookup(&client, &config, owner, RecordType::SVCB, call.head).await?;
+
+        let mut endpoints: Vec<SVCB> = response
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::SVCB(svcb)) => Some(svcb.clone()),
+                _ => None,
+            })
+            // alias mode records do not describe an endpoint
+            .filter(|svcb| svcb.svc_priority() != 0)
+            .collect();
+
+        endpoints.sort_by_key(SVCB::svc_priority);
+
+        let rows = futures_util::future::try_join_all(endpoints.into_iter().map(|svcb| {
+            let client = client.clone();
+            let config = &config;
+            let additionals = response.additionals();
+
+            async move {
+                let mut alpn = Vec::new();
+                let mut port = Value::nothing(Span::unknown());
+                let mut dohpath = Value::nothing(Span::unknown());
+                let mut hints = Vec::new();
+
+                for (key, value) in svcb.svc_params() {
+                    match (key, value) {
+                        (_, SvcParamValue::Alpn(ids)) => alpn.clone_from(&ids.0),
+                        (_, SvcParamValue::Port(val)) => {
+                            port = Value::int(*val as i64, Span::unknown())
+                        }
+                        (_, SvcParamValue::Ipv4Hint(IpHint(addrs))) => {
+                            hints.extend(addrs.iter().map(|addr| addr.0.to_string()))
+                        }
+                        (_, SvcParamValue::Ipv6Hint(IpHint(addrs))) => {
+                            hints.extend(addrs.iter().map(|addr| addr.0.to_string()))
+                        }
+                        (SvcParamKey::Key(DOHPATH_KEY), SvcParamValue::Unknown(Unknown(bytes))) => {
+                            dohpath = Value::string(
+                                String::from_utf8_lossy(bytes).into_owned(),
+                                Span::unknown(),
+                            )
+                        }
+                        _ => {}
+                    }
+                }
+
+                let target = svcb.target_name();
+
+                // the hints are only hints, and are usually left out when the
+                // addresses are in the additional section. A target of "."
+                // stands for the owner name, which has no addresses of its own
+                let addresses = if hints.is_empty() && !target.is_root() {
+                    lookup::addresses(&client, config, target, additionals, call.head)
+                        .await?
+                        .into_iter()
+                        .map(|addr| addr.to_string())
+                        .collect()
+                } else {
+                    hints
+                };
+
+                let mut protocols: Vec<_> = alpn.iter().filter_map(|id| protocol_of(id)).collect();
+                protocols.dedup();
+
+                let values = vec![
+                    Value::int(svcb.svc_priority() as i64, Span::unknown()),
+                    // without the trailing dot, so that it can be passed
+                    // straight to --dns-name
+                    Value::string(target.to_utf8().trim_end_matches('.'), Span::unknown()),
+                    Value::list(
+                        protocols
+                            .into_iter()
+                            .map(|protocol| Value::string(protocol, Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                    Value::list(
+                        alpn.into_iter()
+                            .map(|id| Value::string(id, Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                    port,
+                    dohpath,
+                    Value::list(
+                        addresses
+                            .into_iter()
+                            .map(|addr| Value::string(addr, Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                ];
+
+                Result::<_, LabeledError>::Ok(Value::record(
+                    Record::from_iter(std::iter::zip(
+                        constants::columns::DESIGNATED_RESOLVER_COLS
+                            .iter()
+                            .map(|col| (*col).into()),
+                        values,
+                    )),
+                    Span::unknown(),
+                ))
+            }
+        }))
+        .await?;
+
+        Ok(PipelineData::Value(Value::list(rows, call.head), None))
+    }
+}
+
+/// The `--protocol` to reach an endpoint advertising an ALPN protocol ID with,
+/// if it is one of the supported encrypted transports (RFC 9461 section 4.1).
+fn protocol_of(alpn: &str) -> Option<&'static str> {
+    match alpn {
+        "dot" => Some("tls"),
+        "h2" => Some("https"),
+        "doq" => Some("quic"),
+        _ => None,
+    }
+}
+
+impl PluginCommand for DnsDiscoverResolvers {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(plugin, engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DISCOVER_RESOLVERS
+    }
+
+    fn description(&self) -> &str {
+        "Discover the encrypted endpoints a resolver advertises for itself (RFC 9462)"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Resolver to ask for its designated resolvers (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the resolver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the resolver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long a request can take before timing out. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns discover-resolvers --server 1.1.1.1",
+                description: "list the encrypted endpoints of a resolver, most preferred first",
+                result: None,
+            },
+            Example {
+                example: "let dot = (dns discover-resolvers | where 'tls' in protocols | first); dns query --protocol tls --server $dot.addresses.0 --dns-name $dot.target example.com",
+                description: "upgrade to DNS over TLS with the endpoint the resolver advertises",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "ddr", "svcb", "discovery", "encrypted", "resolver"]
+    }
+}
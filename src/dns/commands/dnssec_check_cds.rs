@@ -0,0 +1,402 @@
+use hickory_client::rr::rdata::DNSSECRData;
+use hickory_proto::rr::{
+    dnssec::{
+        rdata::{DNSKEY, DS},
+        Algorithm,
+    },
+    RData, Record as DnsRecord, RecordType,
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Record, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{chain, client::DnsClient, config::Config, constants, serde, serde::DnssecMode},
+    spanned, Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsDnssecCheckCds;
+
+impl DnsDnssecCheckCds {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let zone: Spanned<String> = call.req(0)?;
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // the records are what there is to compare, rather than to validate
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let mut zone = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+        zone.set_fqdn(true);
+
+        let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+        Ok(PipelineData::Value(
+            check(&client, &config, &zone, call.head).await?,
+            None,
+        ))
+    }
+}
+
+/// Compares the CDS and CDNSKEY records of `zone` with the DS records of its
+/// parent, to tell whether the parent has yet to pick up an update of them
+/// (RFC 7344, RFC 8078), and whether the update is one it can accept.
+async fn check(
+    client: &DnsClient,
+    config: &Config,
+    zone: &Name,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let (ds_records, _) = rrset(client, config, zone, RecordType::DS, span).await?;
+    let (dnskey_records, _) = rrset(client, config, zone, RecordType::DNSKEY, span).await?;
+
+    let mut issues = Vec::new();
+
+    // hickory can not read CDS records of digest type 0, which are the CDS
+    // form of a request to delete the DS records (RFC 8078 section 4)
+    let (cds_records, cds_sigs) = match rrset(client, config, zone, RecordType::CDS, span).await {
+        Ok(rrset) => rrset,
+        Err(err) => {
+            issues.push(format!(
+                "the CDS records could not be read: {}",
+                err.labels
+                    .first()
+                    .map_or(err.msg.clone(), |label| label.text.clone())
+            ));
+            Default::default()
+        }
+    };
+    let (cdnskey_records, cdnskey_sigs) =
+        rrset(client, config, zone, RecordType::CDNSKEY, span).await?;
+
+    let ds: Vec<&DS> = ds_records
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::DS(ds))) => Some(ds),
+            _ => None,
+        })
+        .collect();
+    let dnskeys: Vec<&DNSKEY> = dnskey_records
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) => Some(key),
+            _ => None,
+        })
+        .collect();
+    let cds: Vec<&DS> = cds_records
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::CDS(cds))) => Some(&**cds),
+            _ => None,
+        })
+        .collect();
+    let cdnskeys: Vec<&DNSKEY> = cdnskey_records
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::CDNSKEY(key))) => Some(&**key),
+            _ => None,
+        })
+        .collect();
+
+    let mut add = Vec::new();
+    let mut remove = Vec::new();
+
+    let status = if cds.is_empty() && cdnskeys.is_empty() && issues.is_empty() {
+        "none"
+    } else {
+        let covers = |ds: &DS, key: &DNSKEY| ds.covers(zone, key).unwrap_or(false);
+
+        // the parent is asked to remove the DS records with algorithm 0
+        // (RFC 8078 section 4), which can not come with any other records
+        let deletes = cds.iter().any(|ds| ds.algorithm() == Algorithm::Unknown(0))
+            || cdnskeys
+                .iter()
+                .any(|key| key.algorithm() == Algorithm::Unknown(0));
+        if deletes
+            && (cds.iter().any(|ds| ds.algorithm() != Algorithm::Unknown(0))
+                || cdnskeys
+                    .iter()
+                    .any(|key| key.algorithm() != Algorithm::Unknown(0)))
+        {
+            issues.push(
+                "the request to delete the DS records comes with other CDS or CDNSKEY records"
+                    .to_string(),
+            );
+        }
+
+        if !deletes {
+            // both sets have to describe the same keys (RFC 7344 section 4.1),
+            // and those keys have to be in the zone
+            for cds in &cds {
+                if !cdnskeys.is_empty() && !cdnskeys.iter().any(|key| covers(cds, key)) {
+                    issues.push(format!(
+                        "CDS {} matches none of the CDNSKEY records",
+                        cds.key_tag()
+                    ));
+                }
+                if !dnskeys.iter().any(|key| covers(cds, key)) {
+                    issues.push(format!(
+                        "CDS {} matches none of the DNSKEY records of {}",
+                        cds.key_tag(),
+                        zone
+                    ));
+                }
+            }
+
+            for cdnskey in &cdnskeys {
+                let key_tag = cdnskey.calculate_key_tag().unwrap_or_default();
+                if !cds.is_empty() && !cds.iter().any(|cds| covers(cds, cdnskey)) {
+                    issues.push(format!("CDNSKEY {} has no matching CDS record", key_tag));
+                }
+                if !dnskeys.iter().any(|key| {
+                    key.algorithm() == cdnskey.algorithm()
+                        && key.public_key() == cdnskey.public_key()
+                }) {
+                    issues.push(format!(
+                        "CDNSKEY {} is none of the DNSKEY records of {}",
+                        key_tag, zone
+                    ));
+                }
+            }
+        }
+
+        // once the zone is secure, the parent only accepts records signed
+        // with a key that the DS records vouch for (RFC 7344 section 4.1)
+        for (rtype, records, sigs) in [
+            (RecordType::CDS, &cds_records, &cds_sigs),
+            (RecordType::CDNSKEY, &cdnskey_records, &cdnskey_sigs),
+        ] {
+            if records.is_empty() {
+                continue;
+            }
+
+            let key_tags: Vec<u16> = sigs
+                .iter()
+                .filter_map(|sig| match sig.data() {
+                    Some(RData::DNSSEC(DNSSECRData::RRSIG(sig))) => Some(sig.key_tag()),
+                    _ => None,
+                })
+                .collect();
+            if key_tags.is_empty() {
+                issues.push(format!("the {} records are not signed", rtype));
+            } else if !ds.is_empty()
+                && !key_tags
+                    .iter()
+                    .any(|tag| ds.iter().any(|ds| ds.key_tag() == *tag))
+            {
+                issues.push(format!(
+                    "the {} records are not signed with any of the keys of the DS records",
+                    rtype
+                ));
+            }
+        }
+
+        // what the parent would publish in place of its DS records: the CDS
+        // records, or else the SHA-256 digests of the CDNSKEY records
+        let ttl = ds_records
+            .first()
+            .or(cds_records.first())
+            .or(cdnskey_records.first())
+            .map_or(0, DnsRecord::ttl);
+        let ds_record = |ds: DS| {
+            serde::Record(DnsRecord::from_rdata(
+                zone.clone(),
+                ttl,
+                RData::DNSSEC(DNSSECRData::DS(ds)),
+            ))
+            .into_value(config)
+        };
+
+        if deletes {
+            for ds in &ds {
+                remove.push(ds_record((*ds).clone())?);
+            }
+        } else if !cds.is_empty() {
+            for cds in &cds {
+                if !ds.contains(cds) {
+                    add.push(ds_record((*cds).clone())?);
+                }
+            }
+            for ds in &ds {
+                if !cds.contains(ds) {
+                    remove.push(ds_record((*ds).clone())?);
+                }
+            }
+        } else {
+            for cdnskey in &cdnskeys {
+                if ds.iter().any(|ds| covers(ds, cdnskey)) {
+                    continue;
+                }
+
+                let digest_type = constants::config::default::DS_DIGEST_TYPE;
+                let digest = cdnskey.to_digest(zone, digest_type).map_err(|err| {
+                    LabeledError::new("invalid CDNSKEY")
+                        .with_label(format!("Error calculating digest: {}", err), span)
+                })?;
+                add.push(ds_record(DS::new(
+                    cdnskey.calculate_key_tag().unwrap_or_default(),
+                    cdnskey.algorithm(),
+                    digest_type,
+                    digest.as_ref().to_vec(),
+                ))?);
+            }
+            for ds in &ds {
+                if !cdnskeys.iter().any(|key| covers(ds, key)) {
+                    remove.push(ds_record((*ds).clone())?);
+                }
+            }
+        }
+
+        if !issues.is_empty() {
+            "mismatched"
+        } else if add.is_empty() && remove.is_empty() {
+            "in sync"
+        } else {
+            "pending"
+        }
+    };
+
+    let records = |records: Vec<DnsRecord>| -> Result<Value, LabeledError> {
+        Ok(Value::list(
+            records
+                .into_iter()
+                .map(|record| serde::Record(record).into_value(config))
+                .collect::<Result<_, _>>()?,
+            Span::unknown(),
+        ))
+    };
+
+    Ok(Value::record(
+        Record::from_iter(std::iter::zip(
+            constants::columns::CDS_CHECK_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            vec![
+                Value::string(zone.to_utf8(), Span::unknown()),
+                Value::string(status, Span::unknown()),
+                Value::list(
+                    issues
+                        .into_iter()
+                        .map(|issue| Value::string(issue, Span::unknown()))
+                        .collect(),
+                    Span::unknown(),
+                ),
+                Value::list(add, Span::unknown()),
+                Value::list(remove, Span::unknown()),
+                records(ds_records)?,
+                records(cds_records)?,
+                records(cdnskey_records)?,
+            ],
+        )),
+        span,
+    ))
+}
+
+/// The records of an RRset at the apex of `zone`, and the signatures over it.
+async fn rrset(
+    client: &DnsClient,
+    config: &Config,
+    zone: &Name,
+    rtype: RecordType,
+    span: Span,
+) -> Result<(Vec<DnsRecord>, Vec<DnsRecord>), LabeledError> {
+    let message = chain::fetch(client, config, zone.clone(), rtype, span).await?;
+    Ok(chain::rrset(&message, zone, rtype))
+}
+
+impl PluginCommand for DnsDnssecCheckCds {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DNSSEC_CHECK_CDS
+    }
+
+    fn description(&self) -> &str {
+        "Compare the CDS and CDNSKEY records of a zone with the DS records at its parent, to find automated DS updates that are pending or that the parent can not accept"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(constants::flags::ZONE, SyntaxShape::String, "Zone to check")
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to query (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait for each response. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns dnssec check-cds example.com | get status",
+                description:
+                    "tell whether the parent of a zone has picked up its CDS and CDNSKEY records",
+                result: None,
+            },
+            Example {
+                example: "dns dnssec check-cds example.com | select add remove",
+                description: "list the DS records that the parent has yet to add and remove",
+                result: None,
+            },
+            Example {
+                example: "dns dnssec check-cds example.com | get issues",
+                description: "find out why the parent would not accept the CDS and CDNSKEY records",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "dnssec",
+            "cds",
+            "cdnskey",
+            "ds",
+            "parent",
+            "rollover",
+            "automation",
+        ]
+    }
+}
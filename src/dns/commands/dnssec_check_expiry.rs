@@ -0,0 +1,266 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hickory_client::rr::rdata::DNSSECRData;
+use hickory_proto::rr::{RData, RecordType};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Record, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        chain,
+        client::DnsClient,
+        config::Config,
+        constants,
+        serde::{util::sec_to_date, DnssecMode},
+    },
+    spanned, Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsDnssecCheckExpiry;
+
+impl DnsDnssecCheckExpiry {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let zone: Spanned<String> = call.req(0)?;
+        let warn = match call.get_flag_value(constants::flags::WARN) {
+            Some(val) => {
+                let nanos = val.as_duration()?;
+                if nanos < 0 {
+                    return Err(LabeledError::new("invalid input")
+                        .with_label("should be positive duration", val.span()));
+                }
+
+                nanos / 1_000_000_000
+            }
+            None => constants::config::default::EXPIRY_WARNING.as_secs() as i64,
+        };
+
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // the signatures are what there is to see, rather than to validate
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let rtypes: Vec<RecordType> = match call.get_flag_value(constants::flags::TYPE) {
+            Some(_) => config.qtypes.item.iter().map(|rtype| rtype.item).collect(),
+            None => constants::config::default::EXPIRY_TYPES.to_vec(),
+        };
+
+        let mut zone = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+        zone.set_fqdn(true);
+
+        let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+        Ok(PipelineData::Value(
+            Value::list(
+                check(&client, &config, &zone, rtypes, warn, call.head).await?,
+                call.head,
+            ),
+            None,
+        ))
+    }
+}
+
+/// The signatures over the RRsets of `rtypes` at the apex of `zone`, with the
+/// status of each: expired or not yet valid, or else expiring within `warn`
+/// seconds or not.
+async fn check(
+    client: &DnsClient,
+    config: &Config,
+    zone: &Name,
+    rtypes: Vec<RecordType>,
+    warn: i64,
+    span: Span,
+) -> Result<Vec<Value>, LabeledError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as u32);
+
+    let mut rows = Vec::new();
+    for rtype in rtypes {
+        let message = chain::fetch(client, config, zone.clone(), rtype, span).await?;
+        let (records, sigs) = chain::rrset(&message, zone, rtype);
+
+        if sigs.is_empty() {
+            let status = if records.is_empty() {
+                "missing"
+            } else {
+                "unsigned"
+            };
+            rows.push(row(zone, rtype, status, None)?);
+            continue;
+        }
+
+        for sig in &sigs {
+            let Some(RData::DNSSEC(DNSSECRData::RRSIG(sig))) = sig.data() else {
+                continue;
+            };
+
+            // timestamps are compared in serial number arithmetic (RFC 4034
+            // section 3.1.5), so that they keep working past 2106
+            let remaining = sig.sig_expiration().wrapping_sub(now) as i32 as i64;
+            let status = if (now.wrapping_sub(sig.sig_inception()) as i32) < 0 {
+                "not yet valid"
+            } else if remaining < 0 {
+                "expired"
+            } else if remaining < warn {
+                "warning"
+            } else {
+                "ok"
+            };
+
+            rows.push(row(zone, rtype, status, Some((sig, remaining)))?);
+        }
+    }
+
+    Ok(rows)
+}
+
+/// The status of a signature over an RRset, and how long it has left, or of
+/// an RRset without any.
+fn row(
+    zone: &Name,
+    rtype: RecordType,
+    status: &str,
+    sig: Option<(&hickory_proto::rr::dnssec::rdata::RRSIG, i64)>,
+) -> Result<Value, LabeledError> {
+    let nothing = || Value::nothing(Span::unknown());
+
+    let sig_cols = match sig {
+        Some((sig, remaining)) => vec![
+            Value::duration(remaining * 1_000_000_000, Span::unknown()),
+            sec_to_date(sig.sig_inception(), Span::unknown())?,
+            sec_to_date(sig.sig_expiration(), Span::unknown())?,
+            Value::int(sig.key_tag() as i64, Span::unknown()),
+            Value::string(sig.algorithm().to_string(), Span::unknown()),
+            Value::string(sig.signer_name().to_utf8(), Span::unknown()),
+        ],
+        None => vec![nothing(); 6],
+    };
+
+    Ok(Value::record(
+        Record::from_iter(std::iter::zip(
+            constants::columns::EXPIRY_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            [
+                Value::string(zone.to_utf8(), Span::unknown()),
+                Value::string(rtype.to_string(), Span::unknown()),
+                Value::string(status, Span::unknown()),
+            ]
+            .into_iter()
+            .chain(sig_cols),
+        )),
+        Span::unknown(),
+    ))
+}
+
+impl PluginCommand for DnsDnssecCheckExpiry {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DNSSEC_CHECK_EXPIRY
+    }
+
+    fn description(&self) -> &str {
+        "Check how long the signatures over the key RRsets of a zone have left before they expire"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(constants::flags::ZONE, SyntaxShape::String, "Zone to check")
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Type(s) of the RRsets at the apex to check. Default: SOA, NS, DNSKEY",
+                Some('t'),
+            )
+            .named(
+                constants::flags::WARN,
+                SyntaxShape::Duration,
+                format!(
+                    "Signatures expiring within this long have the status warning. Default: {}day",
+                    constants::config::default::EXPIRY_WARNING.as_secs() / (24 * 60 * 60)
+                ),
+                Some('w'),
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to query (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait for each response. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns dnssec check-expiry example.com",
+                description: "check how long the signatures over the SOA, NS, and DNSKEY records of a zone have left",
+                result: None,
+            },
+            Example {
+                example: "dns dnssec check-expiry --warn 3day example.com | where status != ok",
+                description: "find the signatures that expire within 3 days, or are not valid at all, e.g. from cron",
+                result: None,
+            },
+            Example {
+                example: "dns dnssec check-expiry --type [DNSKEY CDS] example.com | sort-by remaining | first",
+                description: "find the signature that expires first",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "dnssec",
+            "rrsig",
+            "signature",
+            "expiry",
+            "expiration",
+            "monitoring",
+        ]
+    }
+}
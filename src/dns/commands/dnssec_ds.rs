@@ -0,0 +1,175 @@
+use hickory_client::rr::rdata::DNSSECRData;
+use hickory_proto::rr::{
+    dnssec::{rdata::DS, DigestType},
+    DNSClass, RData, Record, RecordType,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, SyntaxShape, Value};
+
+use crate::{
+    dns::{config::Config, constants, dig, serde, zone},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsDnssecDs;
+
+impl DnsDnssecDs {
+    fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let digest_types = match call.get_flag_value(constants::flags::DIGEST) {
+            Some(Value::List { vals, .. }) => vals
+                .into_iter()
+                .map(|val| serde::DigestType::try_from(val).map(|digest_type| digest_type.0))
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(val) => vec![serde::DigestType::try_from(val)?.0],
+            None => vec![constants::config::default::DS_DIGEST_TYPE],
+        };
+        let all_keys = call.has_flag(constants::flags::ALL_KEYS)?;
+
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+        let input = input.into_value(call.head)?;
+
+        Ok(PipelineData::Value(
+            Value::list(
+                ds_records(&input, &digest_types, all_keys, &config)?,
+                call.head,
+            ),
+            None,
+        ))
+    }
+}
+
+/// The DS records of the DNSKEY records among `input`, which are either
+/// records or messages, one for each digest type.
+fn ds_records(
+    input: &Value,
+    digest_types: &[DigestType],
+    all_keys: bool,
+    config: &Config,
+) -> Result<Vec<Value>, LabeledError> {
+    let mut ds = Vec::new();
+    for value in dig::messages(input).into_iter().flat_map(records) {
+        // the signatures and any other records that come along are skipped
+        let (_, rtype, ..) = zone::record_columns(value, None, DNSClass::IN)?;
+        if rtype != RecordType::DNSKEY {
+            continue;
+        }
+
+        let record = zone::record_from_value(value, None, DNSClass::IN)?;
+        let Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) = record.data() else {
+            continue;
+        };
+
+        // the parent only vouches for the key signing keys (RFC 4034
+        // section 2.1.1), and not for those that are revoked (RFC 5011)
+        if !all_keys && (!key.secure_entry_point() || key.revoke()) {
+            continue;
+        }
+
+        let key_tag = key.calculate_key_tag().map_err(|err| {
+            LabeledError::new("invalid DNSKEY")
+                .with_label(format!("Error calculating key tag: {}", err), value.span())
+        })?;
+
+        for digest_type in digest_types {
+            let digest = key.to_digest(record.name(), *digest_type).map_err(|err| {
+                LabeledError::new("invalid DNSKEY")
+                    .with_label(format!("Error calculating digest: {}", err), value.span())
+            })?;
+
+            let mut ds_record = Record::from_rdata(
+                record.name().clone(),
+                record.ttl(),
+                RData::DNSSEC(DNSSECRData::DS(DS::new(
+                    key_tag,
+                    key.algorithm(),
+                    *digest_type,
+                    digest.as_ref().to_vec(),
+                ))),
+            );
+            ds_record.set_dns_class(record.dns_class());
+
+            ds.push(serde::Record(ds_record).into_value(config)?);
+        }
+    }
+
+    Ok(ds)
+}
+
+/// The records of a message, which are those of its answer, or a record as it
+/// is.
+fn records(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Record { val, .. } if val.contains("answer") => match val.get("answer") {
+            Some(Value::List { vals, .. }) => vals.iter().collect(),
+            _ => Vec::new(),
+        },
+        value => vec![value],
+    }
+}
+
+impl PluginCommand for DnsDnssecDs {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(engine, call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DNSSEC_DS
+    }
+
+    fn description(&self) -> &str {
+        "Generate the DS records for the parent zone from the DNSKEY records of a zone"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .named(
+                constants::flags::DIGEST,
+                SyntaxShape::Any,
+                "Digest type(s) to generate DS records with: SHA-1, SHA-256, SHA-384, by name or number. Default: SHA-256",
+                Some('d'),
+            )
+            .switch(
+                constants::flags::ALL_KEYS,
+                "Generate DS records for every key, rather than only for the key signing keys that are not revoked",
+                Some('a'),
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns query --type DNSKEY example.com | dns dnssec ds",
+                description: "generate the DS record to submit to the registrar of a zone",
+                result: None,
+            },
+            Example {
+                example: "dns query --type DNSKEY example.com | dns dnssec ds --digest [SHA-256 SHA-384] | dns zone export",
+                description: "generate DS records with several digest types, in zone file format",
+                result: None,
+            },
+            Example {
+                example: "open keys.nuon | dns dnssec ds --all-keys --digest 4",
+                description: "generate SHA-384 DS records of saved DNSKEY records, key signing keys or not",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "dnssec", "ds", "dnskey", "digest", "registrar"]
+    }
+}
@@ -0,0 +1,149 @@
+use hickory_resolver::{config::Protocol, Name};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, serde::DnssecMode, xfr},
+    spanned, Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsIxfr;
+
+impl DnsIxfr {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let zone: Spanned<String> = call.req(0)?;
+        let serial: Spanned<i64> = call.get_flag(constants::flags::SERIAL)?.ok_or_else(|| {
+            LabeledError::new("missing serial").with_label(
+                "need the serial of the version of the zone to fetch the changes since",
+                call.head,
+            )
+        })?;
+        let serial = u32::try_from(serial.item).map_err(|err| {
+            LabeledError::new("invalid serial").with_label(
+                format!("should be a 32 bit unsigned int: {err}"),
+                serial.span,
+            )
+        })?;
+
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // incremental transfers are sent over TCP like full ones (RFC 1995
+        // section 2), and the DNSSEC client only ever waits for the first
+        // message
+        if config.protocol.item == Protocol::Udp {
+            config.protocol = spanned!(Protocol::Tcp, config.protocol.span);
+        }
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let zone_name = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+
+        let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+        let value = xfr::steps_since(&client, &config, zone_name, serial, call.head).await?;
+
+        Ok(PipelineData::Value(value, None))
+    }
+}
+
+impl PluginCommand for DnsIxfr {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::IXFR
+    }
+
+    fn description(&self) -> &str {
+        "Fetch the changes to a zone since a serial with an incremental zone transfer"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone to transfer",
+            )
+            .required_named(
+                constants::flags::SERIAL,
+                SyntaxShape::Int,
+                "Serial of the version of the zone to fetch the changes since. If the server does not keep the history of the zone, the whole zone is returned as a single step that adds every record",
+                None,
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to transfer the zone from (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: TCP, TLS. (default: TCP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TSIG_KEY,
+                SyntaxShape::String,
+                "TSIG key to sign the transfer with, as name:algorithm:secret with the secret in base64. The algorithm is one of: hmac-sha256, hmac-sha384, hmac-sha512 (for UDP, TCP, and TLS only)",
+                None,
+            )
+            .named(
+                constants::flags::TSIG_KEY_FILE,
+                SyntaxShape::Filepath,
+                "File with the TSIG key to sign the transfer with, in the format written by tsig-keygen",
+                None,
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait for each message of the transfer. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns ixfr example.com --serial 2024010100 --server ns1.example.com",
+                description: "fetch the records each version of a zone removed and added since a serial",
+                result: None,
+            },
+            Example {
+                example: "dns ixfr example.com --serial 2024010100 --server ns1.example.com | get steps | each { |step| $step.added | where type == A }",
+                description: "list the address records added in each version",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "ixfr", "zone", "transfer", "incremental", "serial"]
+    }
+}
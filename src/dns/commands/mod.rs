@@ -1 +1,65 @@
+use nu_plugin::{Plugin, PluginCommand};
+
+use crate::Dns;
+
+pub mod axfr;
+pub mod browse;
+pub mod build;
+pub mod discover_resolvers;
+pub mod dnssec_check_cds;
+pub mod dnssec_check_expiry;
+pub mod dnssec_ds;
+pub mod ixfr;
+pub mod notify;
+pub mod nsec3_hash;
+pub mod owner_name;
+pub mod parse;
+pub mod ping;
 pub mod query;
+pub mod reverse;
+pub mod serve;
+pub mod server_id;
+pub mod srv;
+pub mod to_json;
+pub mod trace;
+pub mod update;
+pub mod walk;
+pub mod zone_check;
+pub mod zone_export;
+pub mod zone_parse;
+
+impl Plugin for Dns {
+    fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
+        vec![
+            Box::new(query::DnsQuery),
+            Box::new(owner_name::DnsOwnerName),
+            Box::new(srv::DnsSrv),
+            Box::new(ping::DnsPing),
+            Box::new(axfr::DnsAxfr),
+            Box::new(ixfr::DnsIxfr),
+            Box::new(trace::DnsTrace),
+            Box::new(discover_resolvers::DnsDiscoverResolvers),
+            Box::new(browse::DnsBrowse),
+            Box::new(reverse::DnsReverse),
+            Box::new(update::DnsUpdate),
+            Box::new(notify::DnsNotify),
+            Box::new(serve::DnsServe),
+            Box::new(zone_parse::DnsZoneParse),
+            Box::new(zone_export::DnsZoneExport),
+            Box::new(zone_check::DnsZoneCheck),
+            Box::new(parse::DnsParse),
+            Box::new(build::DnsBuild),
+            Box::new(to_json::DnsToJson),
+            Box::new(server_id::DnsServerId),
+            Box::new(walk::DnsWalk),
+            Box::new(nsec3_hash::DnsNsec3Hash),
+            Box::new(dnssec_ds::DnsDnssecDs),
+            Box::new(dnssec_check_expiry::DnsDnssecCheckExpiry),
+            Box::new(dnssec_check_cds::DnsDnssecCheckCds),
+        ]
+    }
+
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").into()
+    }
+}
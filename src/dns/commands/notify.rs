@@ -0,0 +1,192 @@
+use hickory_proto::{
+    op::{Message, MessageType, OpCode, Query},
+    rr::{DNSClass, RecordType},
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape};
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        config::Config,
+        constants,
+        serde::{self, DnssecMode},
+    },
+    spanned, Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsNotify;
+
+impl DnsNotify {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let zone: Spanned<String> = call.req(0)?;
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // the response to a notify has nothing to validate
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let zone = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+
+        let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+        let message = notify_message(zone.clone(), config.class.item);
+
+        let response = tokio::time::timeout(config.timeout.item, client.send_message(message))
+            .await
+            .map_err(|_| {
+                LabeledError::new("timed out").with_label(
+                    format!("notify of {} to {} timed out", zone, config.server.item),
+                    config.server.span,
+                )
+            })?
+            .map_err(|err| {
+                LabeledError::new("notify failed").with_label(
+                    format!(
+                        "notify of {} to {} failed: {}",
+                        zone,
+                        config.server.item,
+                        err.kind()
+                    ),
+                    call.head,
+                )
+            })?
+            .into_message();
+
+        // a secondary that is not configured for the zone answers with an
+        // error, which is reported rather than failed on, so that a script
+        // notifying several of them can check each
+        Ok(PipelineData::Value(
+            serde::Message::new(response)
+                .with_responder(client.responder())
+                .into_value(&config)?,
+            None,
+        ))
+    }
+}
+
+/// A NOTIFY message (RFC 1996 section 3) that tells secondaries the zone
+/// changed, so they check its SOA and transfer it without waiting for the
+/// refresh interval.
+fn notify_message(zone: Name, class: DNSClass) -> Message {
+    let mut query = Query::query(zone, RecordType::SOA);
+    query.set_query_class(class);
+
+    let mut message = Message::new();
+    message
+        .add_query(query)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Notify)
+        // the sender is the authority for the zone (RFC 1996 section 3.7)
+        .set_authoritative(true);
+
+    message
+}
+
+impl PluginCommand for DnsNotify {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::NOTIFY
+    }
+
+    fn description(&self) -> &str {
+        "Tell a secondary nameserver that a zone changed with a NOTIFY message (RFC 1996)"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone that changed",
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Secondary nameserver to notify (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP, TLS. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Class of the zone. Default: IN",
+                Some('c'),
+            )
+            .named(
+                constants::flags::TSIG_KEY,
+                SyntaxShape::String,
+                "TSIG key to sign the notify with, as name:algorithm:secret with the secret in base64. The algorithm is one of: hmac-sha256, hmac-sha384, hmac-sha512 (for UDP, TCP, and TLS only)",
+                None,
+            )
+            .named(
+                constants::flags::TSIG_KEY_FILE,
+                SyntaxShape::Filepath,
+                "File with the TSIG key to sign the notify with, in the format written by tsig-keygen",
+                None,
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long a request can take before timing out. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns notify example.com --server 203.0.113.5",
+                description: "tell a secondary to check for a new version of a zone",
+                result: None,
+            },
+            Example {
+                example: "[203.0.113.5 198.51.100.7] | each { |server| dns notify example.com --server $server | select responder status }",
+                description: "notify every secondary of a zone, and check which of them accepted it",
+                result: None,
+            },
+            Example {
+                example: "dns notify example.com --server 203.0.113.5 --tsig-key-file notify.key",
+                description: "sign the notify with a TSIG key, for secondaries that require it",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "notify", "secondary", "slave", "zone", "rfc1996"]
+    }
+}
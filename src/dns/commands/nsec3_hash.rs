@@ -0,0 +1,127 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, Span, SyntaxShape, Value};
+
+use crate::{
+    dns::{
+        commands::owner_name::{nsec3_params, parse_name},
+        constants, names,
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsNsec3Hash;
+
+impl DnsNsec3Hash {
+    fn run_impl(
+        &self,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let (salt, iterations) = nsec3_params(call)?;
+        let arg_input = call.nth(0).unwrap_or(Value::nothing(call.head));
+
+        let input = match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => arg_input,
+            PipelineData::Value(val, _) => {
+                if !arg_input.is_nothing() {
+                    return Err(LabeledError::new("ambiguous input").with_label(
+                        "Input should either be positional args or piped, but not both",
+                        val.span(),
+                    ));
+                }
+
+                val
+            }
+            data => {
+                return Err(LabeledError::new("invalid input").with_label(
+                    "Only values can be passed as input",
+                    data.span().unwrap_or(Span::unknown()),
+                ))
+            }
+        };
+
+        let hash = |val: &Value| -> Result<Value, LabeledError> {
+            let span = val.span();
+            let name = parse_name(val.as_str()?, span)?;
+
+            Ok(Value::string(
+                names::nsec3_hash(&name, &salt, iterations, span)?,
+                span,
+            ))
+        };
+
+        let output = match input {
+            Value::List { ref vals, .. } => Value::list(
+                vals.iter().map(hash).collect::<Result<_, _>>()?,
+                input.span(),
+            ),
+            Value::Nothing { .. } => {
+                return Err(
+                    LabeledError::new("missing input").with_label("Need a name to hash", call.head)
+                )
+            }
+            val => hash(&val)?,
+        };
+
+        Ok(PipelineData::Value(output, None))
+    }
+}
+
+impl PluginCommand for DnsNsec3Hash {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::NSEC3_HASH
+    }
+
+    fn description(&self) -> &str {
+        "Hash a name the way NSEC3 records do, to match it against their owner names and next hashed owner names"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(constants::flags::NAME, SyntaxShape::Any, "Name to hash")
+            .named(
+                constants::flags::SALT,
+                SyntaxShape::Any,
+                "NSEC3 salt, either as hex string or binary. Default: empty",
+                None,
+            )
+            .named(
+                constants::flags::ITERATIONS,
+                SyntaxShape::Int,
+                "Number of additional NSEC3 hash iterations. Default: 0",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns nsec3-hash --salt aabbccdd --iterations 12 a.example",
+                description: "hash a name with the NSEC3 parameters of its zone",
+                result: None,
+            },
+            Example {
+                example: "let param = (dns query --type NSEC3PARAM example.com).0.answer.0.rdata; [example.com www.example.com] | dns nsec3-hash --salt $param.salt --iterations $param.iterations",
+                description: "hash names with the NSEC3 parameters that a zone publishes",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "dnssec", "nsec3", "hash", "base32hex"]
+    }
+}
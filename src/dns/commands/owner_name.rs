@@ -0,0 +1,263 @@
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{constants, names},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsOwnerName;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scheme {
+    Nsec3,
+    Tlsa,
+    OpenPgpKey,
+}
+
+impl TryFrom<&Spanned<String>> for Scheme {
+    type Error = LabeledError;
+
+    fn try_from(value: &Spanned<String>) -> Result<Self, Self::Error> {
+        match value.item.to_lowercase().as_str() {
+            "nsec3" => Ok(Scheme::Nsec3),
+            "tlsa" => Ok(Scheme::Tlsa),
+            "openpgpkey" => Ok(Scheme::OpenPgpKey),
+            _ => Err(LabeledError::new("invalid scheme").with_label(
+                "Invalid scheme. Must be one of: nsec3, tlsa, openpgpkey",
+                value.span,
+            )),
+        }
+    }
+}
+
+impl DnsOwnerName {
+    fn run_impl(
+        &self,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let scheme: Spanned<String> = call.req(0)?;
+        let scheme = Scheme::try_from(&scheme)?;
+        let arg_input = call.nth(1).unwrap_or(Value::nothing(call.head));
+
+        let input = match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => arg_input,
+            PipelineData::Value(val, _) => {
+                if !arg_input.is_nothing() {
+                    return Err(LabeledError::new("ambiguous input").with_label(
+                        "Input should either be positional args or piped, but not both",
+                        val.span(),
+                    ));
+                }
+
+                val
+            }
+            data => {
+                return Err(LabeledError::new("invalid input").with_label(
+                    "Only values can be passed as input",
+                    data.span().unwrap_or(Span::unknown()),
+                ))
+            }
+        };
+
+        let derive = |val: &Value| -> Result<Value, LabeledError> {
+            let span = val.span();
+            let input = val.as_str()?;
+
+            let name = match scheme {
+                Scheme::Nsec3 => {
+                    let zone: Spanned<String> =
+                        call.get_flag(constants::flags::ZONE)?.ok_or_else(|| {
+                            LabeledError::new("need zone").with_label(
+                                "NSEC3 owner names need to be accompanied by --zone",
+                                call.head,
+                            )
+                        })?;
+
+                    let (salt, iterations) = nsec3_params(call)?;
+
+                    names::nsec3(
+                        &parse_name(input, span)?,
+                        &parse_name(&zone.item, zone.span)?,
+                        &salt,
+                        iterations,
+                        span,
+                    )?
+                }
+                Scheme::Tlsa => {
+                    let port = match call.get_flag::<Spanned<i64>>(constants::flags::PORT)? {
+                        Some(port) => u16::try_from(port.item).map_err(|err| {
+                            LabeledError::new("invalid port")
+                                .with_label(format!("should be a valid port: {err}"), port.span)
+                        })?,
+                        None => constants::config::default::TLSA_PORT,
+                    };
+
+                    let transport = call
+                        .get_flag::<String>(constants::flags::TRANSPORT)?
+                        .unwrap_or(constants::config::default::TLSA_TRANSPORT.into());
+
+                    names::tlsa(&parse_name(input, span)?, port, &transport, span)?
+                }
+                Scheme::OpenPgpKey => names::openpgpkey(input, span)?,
+            };
+
+            Ok(Value::string(name.to_utf8(), span))
+        };
+
+        let output = match input {
+            Value::List { ref vals, .. } => Value::list(
+                vals.iter().map(derive).collect::<Result<_, _>>()?,
+                input.span(),
+            ),
+            Value::Nothing { .. } => {
+                return Err(LabeledError::new("missing input")
+                    .with_label("Need a name to derive an owner name from", call.head))
+            }
+            val => derive(&val)?,
+        };
+
+        Ok(PipelineData::Value(output, None))
+    }
+}
+
+/// The NSEC3 salt and number of additional iterations of `--salt` and
+/// `--iterations`, which default to none.
+pub fn nsec3_params(call: &EvaluatedCall) -> Result<(Vec<u8>, u16), LabeledError> {
+    let salt = match call.get_flag_value(constants::flags::SALT) {
+        Some(Value::Binary { val, .. }) => val,
+        Some(val @ Value::String { .. }) => {
+            let salt = val.as_str()?;
+
+            // "-" denotes an empty salt in presentation format
+            if salt == "-" {
+                vec![]
+            } else {
+                data_encoding::HEXLOWER_PERMISSIVE
+                    .decode(salt.as_bytes())
+                    .map_err(|err| {
+                        LabeledError::new("invalid salt")
+                            .with_label(format!("Salt must be hex encoded: {}", err), val.span())
+                    })?
+            }
+        }
+        Some(val) => {
+            return Err(LabeledError::new("invalid salt")
+                .with_label("Salt must be either a hex string or binary", val.span()))
+        }
+        None => vec![],
+    };
+
+    let iterations = match call.get_flag::<Spanned<i64>>(constants::flags::ITERATIONS)? {
+        Some(iterations) => u16::try_from(iterations.item).map_err(|err| {
+            LabeledError::new("invalid iterations").with_label(
+                format!("should be a 16 bit unsigned int: {err}"),
+                iterations.span,
+            )
+        })?,
+        None => 0,
+    };
+
+    Ok((salt, iterations))
+}
+
+pub fn parse_name(name: &str, span: Span) -> Result<Name, LabeledError> {
+    Name::from_utf8(name).map_err(|err| {
+        LabeledError::new("invalid name").with_label(format!("Error parsing name: {}", err), span)
+    })
+}
+
+impl PluginCommand for DnsOwnerName {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::OWNER_NAME
+    }
+
+    fn description(&self) -> &str {
+        "Derive the owner name that a record type is published under"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(
+                constants::flags::SCHEME,
+                SyntaxShape::String,
+                "How to derive the owner name: \"nsec3\" (hashed name), \"tlsa\" (_port._transport prefix), \"openpgpkey\" (hashed local part of an email address)",
+            )
+            .optional(
+                constants::flags::NAME,
+                SyntaxShape::Any,
+                "DNS record name, or email address for \"openpgpkey\"",
+            )
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone the name belongs to (for nsec3 only)",
+                Some('z'),
+            )
+            .named(
+                constants::flags::SALT,
+                SyntaxShape::Any,
+                "NSEC3 salt, either as hex string or binary. Default: empty",
+                None,
+            )
+            .named(
+                constants::flags::ITERATIONS,
+                SyntaxShape::Int,
+                "Number of additional NSEC3 hash iterations. Default: 0",
+                None,
+            )
+            .named(
+                constants::flags::PORT,
+                SyntaxShape::Int,
+                format!("Port of the TLS service (for tlsa only). Default: {}", constants::config::default::TLSA_PORT),
+                None,
+            )
+            .named(
+                constants::flags::TRANSPORT,
+                SyntaxShape::String,
+                format!("Transport protocol of the TLS service (for tlsa only). Default: {}", constants::config::default::TLSA_TRANSPORT),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns owner-name tlsa --port 25 mail.example.com",
+                description: "owner name of the TLSA records of an SMTP server",
+                result: None,
+            },
+            Example {
+                example: "dns owner-name nsec3 --zone example.com --salt aabbccdd --iterations 12 www.example.com",
+                description: "hashed NSEC3 owner name of a name in a zone",
+                result: None,
+            },
+            Example {
+                example: "dns owner-name openpgpkey hugh@example.com | dns query --type OPENPGPKEY",
+                description: "look up the OpenPGP key of an email address",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "nsec3", "tlsa", "openpgpkey", "hash"]
+    }
+}
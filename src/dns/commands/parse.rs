@@ -0,0 +1,315 @@
+use hickory_resolver::config::Protocol;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, pcap, serde, wire},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsParse;
+
+impl DnsParse {
+    fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        if call.has_flag(constants::flags::PCAP)? {
+            let port = match call.get_flag::<Spanned<i64>>(constants::flags::PORT)? {
+                Some(port) => u16::try_from(port.item).map_err(|err| {
+                    LabeledError::new("invalid port")
+                        .with_label(format!("should be a valid port: {err}"), port.span)
+                })?,
+                None => constants::config::default_port(Protocol::Udp),
+            };
+
+            let rows = parse_capture(&input.into_value(call.head)?, port, &config)?;
+            return Ok(PipelineData::Value(Value::list(rows, call.head), None));
+        }
+
+        let value = match input.into_value(call.head)? {
+            Value::List { vals, .. } => Value::list(
+                vals.iter()
+                    .map(|val| parse_message(val, &config))
+                    .collect::<Result<_, _>>()?,
+                call.head,
+            ),
+            val => parse_message(&val, &config)?,
+        };
+
+        Ok(PipelineData::Value(value, None))
+    }
+}
+
+/// Decodes a message in wire format (RFC 1035 section 4) into the same record
+/// a query outputs for its response.
+fn parse_message(value: &Value, config: &Config) -> Result<Value, LabeledError> {
+    let Value::Binary { val: wire, .. } = value else {
+        return Err(LabeledError::new("invalid input").with_label(
+            format!(
+                "expected a message in wire format as binary, not a {}",
+                value.get_type()
+            ),
+            value.span(),
+        ));
+    };
+
+    let message = hickory_proto::op::Message::from_vec(&wire::readable(wire)).map_err(|err| {
+        LabeledError::new("invalid message").with_label(
+            format!("Error parsing message: {}", err.kind()),
+            value.span(),
+        )
+    })?;
+
+    let message = serde::Message::new(message).with_wire(wire.clone());
+
+    message.into_value(config)
+}
+
+/// The messages sent to or from `port` in a packet capture, each with when it
+/// was captured and the addresses it was sent from and to. Packets on the port
+/// that are not DNS messages are left out.
+fn parse_capture(value: &Value, port: u16, config: &Config) -> Result<Vec<Value>, LabeledError> {
+    let Value::Binary { val: capture, .. } = value else {
+        return Err(LabeledError::new("invalid input").with_label(
+            format!(
+                "expected a capture in the pcap or pcapng format as binary, not a {}",
+                value.get_type()
+            ),
+            value.span(),
+        ));
+    };
+
+    let mut rows = Vec::new();
+
+    for captured in pcap::messages(capture, port, value.span())? {
+        let message = match hickory_proto::op::Message::from_vec(&captured.message) {
+            Ok(message) => message,
+            Err(err) => {
+                tracing::debug!(
+                    pcap.source = %captured.source,
+                    pcap.error = %err.kind(),
+                    "skipping packet that is not a DNS message"
+                );
+                continue;
+            }
+        };
+
+        let message = serde::Message::new(message)
+            .with_transport(captured.protocol)
+            .with_wire(captured.message);
+
+        let mut row = message.into_value(config)?;
+
+        if let Value::Record { val, .. } = &mut row {
+            let row = val.to_mut();
+            row.push(
+                constants::columns::TIME,
+                Value::date(captured.timestamp.fixed_offset(), Span::unknown()),
+            );
+            row.push(
+                constants::columns::SOURCE,
+                Value::string(captured.source.to_string(), Span::unknown()),
+            );
+            row.push(
+                constants::columns::DESTINATION,
+                Value::string(captured.destination.to_string(), Span::unknown()),
+            );
+        }
+
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+impl PluginCommand for DnsParse {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(engine, call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::PARSE
+    }
+
+    fn description(&self) -> &str {
+        "Decode DNS messages in wire format, e.g. from packet captures, into the same records as the responses of queries"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .switch(
+                constants::flags::PCAP,
+                "Decode the DNS messages over UDP and TCP in a packet capture in the pcap or pcapng format, with when each was captured and its source and destination",
+                None,
+            )
+            .named(
+                constants::flags::PORT,
+                SyntaxShape::Int,
+                "Port DNS messages are on in the capture, with --pcap. Default: 53",
+                None,
+            )
+            .switch(
+                constants::flags::TYPE_AS_NUMBER,
+                "Return the type of questions and records as its numeric code only, e.g. 28 instead of AAAA. Takes precedence over --code",
+                None,
+            )
+            .switch(
+                constants::flags::CANONICAL_ORDER,
+                "Sort the records of each RRset into DNSSEC canonical order (RFC 4034 section 6.3)",
+                None,
+            )
+            .switch(
+                constants::flags::RECORD_SPANS,
+                "Add the byte offset and length of each record and each of its fields in the message, for debugging wire format parsers",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "open --raw response.bin | dns parse",
+                description: "decode a message saved from a packet capture",
+                result: None,
+            },
+            Example {
+                example: "0x[abcd 0100 0001 0000 0000 0000 0765 7861 6d70 6c65 0363 6f6d 0000 0100 01] | dns parse | get question",
+                description: "decode the question of a query for the A records of example.com",
+                result: None,
+            },
+            Example {
+                example: "open --raw capture.pcap | dns parse --pcap | where header.response_code != 'No Error' | select time source question",
+                description: "find the failed responses in a packet capture",
+                result: None,
+            },
+            Example {
+                example: "ls *.bin | each { open --raw $in.name } | dns parse --record-spans",
+                description: "decode several messages, with where each record is in them",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "parse", "decode", "wire", "packet", "binary", "pcap"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{record, Span};
+
+    use super::*;
+    use crate::dns::commands::build;
+
+    /// Builds a message with the records as its answers, and parses it back.
+    fn round_trip(records: Vec<Value>) -> Vec<Value> {
+        let message = build::build_message(&Value::test_record(record! {
+            "answer" => Value::test_list(records),
+        }))
+        .unwrap();
+        let wire = build::encode(&message.0, message.1, Span::unknown()).unwrap();
+
+        let config = Config::from_values(|_| None).unwrap();
+        parse_message(&Value::test_binary(wire), &config)
+            .unwrap()
+            .get_data_by_key("answer")
+            .unwrap()
+            .into_list()
+            .unwrap()
+    }
+
+    fn answer(rtype: &str, rdata: Value) -> Value {
+        Value::test_record(record! {
+            "name" => Value::test_string("example.com."),
+            "type" => Value::test_string(rtype),
+            "class" => Value::test_string("IN"),
+            "ttl" => Value::test_int(300),
+            "rdata" => rdata,
+        })
+    }
+
+    #[test]
+    fn avc_and_doa_survive_build_and_parse() {
+        let avc = Value::test_list(vec![
+            Value::test_string("app-name:WOW|app-class:games|"),
+            Value::test_string("business=Blizzard"),
+        ]);
+        let doa = Value::test_record(record! {
+            "enterprise" => Value::test_int(0),
+            "type" => Value::test_int(1),
+            "location" => Value::test_int(2),
+            "media_type" => Value::test_string("image/jpeg"),
+            "data" => Value::test_binary(vec![0xff, 0xd8, 0xff]),
+        });
+
+        let answers = round_trip(vec![answer("AVC", avc.clone()), answer("DOA", doa.clone())]);
+
+        let rdata: Vec<_> = answers
+            .iter()
+            .map(|answer| answer.get_data_by_key("rdata").unwrap())
+            .collect();
+        assert_eq!(rdata, [avc, doa]);
+    }
+
+    #[test]
+    fn op_codes_hickory_does_not_know_survive_build_and_parse() {
+        for (op_code, name) in [(1, "IQUERY"), (3, "RESERVED3"), (6, "DSO")] {
+            let (message, raw_op_code) = build::build_message(&Value::test_record(record! {
+                "header" => Value::test_record(record! {
+                    "op_code" => Value::test_string(name),
+                }),
+            }))
+            .unwrap();
+            assert_eq!(raw_op_code, Some(op_code));
+
+            let wire = build::encode(&message, raw_op_code, Span::unknown()).unwrap();
+            assert_eq!(wire::op_code(&wire), Some(op_code));
+
+            let config = Config::from_values(|_| None).unwrap();
+            let parsed = parse_message(&Value::test_binary(wire), &config).unwrap();
+            assert_eq!(
+                parsed
+                    .get_data_by_key("header")
+                    .and_then(|header| header.get_data_by_key("op_code")),
+                Some(Value::test_string(name))
+            );
+        }
+    }
+
+    #[test]
+    fn hip_survives_build_and_parse() {
+        // the example of RFC 8005 section 5
+        let hip = Value::test_record(record! {
+            "pk_algorithm" => Value::test_int(2),
+            "hit" => Value::test_string("200100107B1A74DF365639CC39F1D578"),
+            "public_key" => Value::test_string(
+                "AwEAAbdxyhNuSutc5EMzxTs9LBPCIkOFH8cIvM4p9+LrV4e19WzK00+CI6zBCQTdtWsuxKbWIy87UOoJTwkUs7lBu+Upr1gsNrut79ryra+bSRGQb1slImA8YVJyuIDsj7kwzG7jnERNqnWxZ48AWkskmdHaVDP4BcelrTI3rMXdXF5D",
+            ),
+            "rendezvous_servers" => Value::test_list(vec![
+                Value::test_string("rvs1.example.com."),
+                Value::test_string("rvs2.example.com."),
+            ]),
+        });
+
+        let answers = round_trip(vec![answer("HIP", hip.clone())]);
+
+        assert_eq!(answers[0].get_data_by_key("rdata"), Some(hip));
+    }
+}
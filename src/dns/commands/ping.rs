@@ -0,0 +1,180 @@
+use std::time::Instant;
+
+use hickory_proto::{op::Query, rr::RecordType};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Record, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, serde::DnssecMode},
+    spanned, Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsPing;
+
+impl DnsPing {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // validating would send more queries, which would skew the round trip
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let name = match call.opt::<Spanned<String>>(0)? {
+            Some(name) => Name::from_utf8(&name.item).map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), name.span)
+            })?,
+            None => Name::root(),
+        };
+
+        let start = Instant::now();
+        let result = tokio::time::timeout(config.timeout.item, async {
+            let (client, _bg) = DnsClient::new(&config).await?;
+
+            // setting up the client does not count towards the round trip
+            let connected = Instant::now();
+
+            let response = client
+                .send_query(Query::query(name, RecordType::SOA), &config)
+                .await
+                .map_err(|err| {
+                    LabeledError::new("DNS error").with_label(err.kind().to_string(), call.head)
+                })?;
+
+            Ok::<_, LabeledError>((response.into_message(), connected.elapsed()))
+        })
+        .await;
+        let elapsed = start.elapsed();
+
+        // an unreachable server is a result, not an error, so that scripts can
+        // branch on it
+        let (reachable, rtt, response_code, error) = match result {
+            Ok(Ok((message, rtt))) => (
+                true,
+                Value::duration(rtt.as_nanos() as i64, Span::unknown()),
+                Value::string(message.response_code().to_string(), Span::unknown()),
+                Value::nothing(Span::unknown()),
+            ),
+            Ok(Err(err)) => (
+                false,
+                Value::nothing(Span::unknown()),
+                Value::nothing(Span::unknown()),
+                Value::string(
+                    err.labels
+                        .first()
+                        .map_or_else(|| err.msg.clone(), |label| label.text.clone()),
+                    Span::unknown(),
+                ),
+            ),
+            Err(_) => (
+                false,
+                Value::nothing(Span::unknown()),
+                Value::nothing(Span::unknown()),
+                Value::string(format!("timed out after {:?}", elapsed), Span::unknown()),
+            ),
+        };
+
+        let values = vec![
+            Value::string(config.server.item.to_string(), Span::unknown()),
+            Value::string(config.protocol.item.to_string(), Span::unknown()),
+            Value::bool(reachable, Span::unknown()),
+            rtt,
+            response_code,
+            error,
+        ];
+
+        Ok(PipelineData::Value(
+            Value::record(
+                Record::from_iter(std::iter::zip(
+                    constants::columns::PING_COLS
+                        .iter()
+                        .map(|col| (*col).into()),
+                    values,
+                )),
+                call.head,
+            ),
+            None,
+        ))
+    }
+}
+
+impl PluginCommand for DnsPing {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::PING
+    }
+
+    fn description(&self) -> &str {
+        "Check whether a nameserver is reachable, and how long it takes to respond"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Name whose SOA record to query. Default: the root zone",
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to query (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!("How long to wait for a response before considering the nameserver unreachable. Default: {}sec", constants::config::default::TIMEOUT.as_secs()),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns ping --server 1.1.1.1",
+                description: "check whether a nameserver responds",
+                result: None,
+            },
+            Example {
+                example: "if (dns ping --server 9.9.9.9).reachable { dns query --server 9.9.9.9 example.com }",
+                description: "only query a nameserver that is up",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "ping", "health", "reachable", "latency"]
+    }
+}
@@ -1,17 +1,20 @@
 use std::{
+    collections::HashMap,
     sync::{atomic::AtomicBool, Arc},
     time::Duration,
 };
 
-use futures_util::{
-    select,
-    stream::{FuturesOrdered, FuturesUnordered},
-    FutureExt, StreamExt,
+use futures_util::{select, stream::FuturesOrdered, FutureExt, StreamExt};
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{DNSClass, RecordType},
+    xfer::DnsResponse,
 };
-use hickory_client::client::ClientHandle;
-use nu_plugin::{EngineInterface, EvaluatedCall, Plugin, PluginCommand};
+use hickory_resolver::{config::Protocol, Name};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
 use nu_protocol::{
-    Example, LabeledError, ListStream, PipelineData, Signals, Signature, Span, SyntaxShape, Value,
+    engine::Closure, record, Example, LabeledError, ListStream, PipelineData, Record, Signals,
+    Signature, Span, Spanned, SyntaxShape, Value,
 };
 use tokio::{sync::mpsc, task::JoinSet};
 use tokio_util::sync::CancellationToken;
@@ -19,15 +22,20 @@ use tracing_subscriber::prelude::*;
 
 use crate::{
     dns::{
+        chain,
         client::DnsClient,
         config::Config,
         constants,
-        serde::{self, Query},
+        cookie::CookieStatus,
+        dig, lookup, msgpack, names, path_mtu,
+        presentation::{self, Names},
+        serde::{self, DnssecMode, OutputFormat, Query},
+        watch, xfr, zone,
     },
-    Dns,
+    spanned, Dns,
 };
 
-pub type DnsQueryResult = FuturesUnordered<Result<Value, LabeledError>>;
+pub type DnsQueryResult = Vec<Result<Value, LabeledError>>;
 pub type DnsQueryPluginClient = Arc<
     tokio::sync::RwLock<
         Option<(
@@ -53,7 +61,55 @@ impl DnsQuery {
             .with(tracing_subscriber::EnvFilter::from_default_env())
             .try_init();
 
-        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+        let plugin_config = engine.get_plugin_config()?;
+        let config = Config::from_nu(plugin_config.clone(), call)?;
+        let output_format = config.output_format.item;
+        let short = config.short.item;
+        let flatten = config.flatten.item;
+        let answers_only = config.answers_only.item;
+
+        let data = Self::dispatch(plugin, engine, plugin_config, config, call, input).await?;
+        let data = if short {
+            let val = data.into_value(call.head)?;
+            PipelineData::Value(Value::list(dig::short(&val)?, call.head), None)
+        } else if flatten {
+            let val = data.into_value(call.head)?;
+            PipelineData::Value(Value::list(flat_records(&val)?, call.head), None)
+        } else if answers_only {
+            let val = data.into_value(call.head)?;
+            PipelineData::Value(Value::list(answer_records(&val), call.head), None)
+        } else {
+            data
+        };
+
+        match output_format {
+            OutputFormat::Table => Ok(data),
+            OutputFormat::Msgpack => {
+                let val = data.into_value(call.head)?;
+                Ok(PipelineData::Value(
+                    Value::binary(msgpack::encode(&val)?, call.head),
+                    None,
+                ))
+            }
+            OutputFormat::Dig => {
+                let val = data.into_value(call.head)?;
+                Ok(PipelineData::Value(
+                    Value::string(dig::render(&val)?, call.head),
+                    None,
+                ))
+            }
+        }
+    }
+
+    /// Runs the queries in whichever mode the config asks for.
+    async fn dispatch(
+        plugin: &Dns,
+        engine: &EngineInterface,
+        plugin_config: Option<Value>,
+        config: Config,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
         let arg_inputs: Value = call.nth(0).unwrap_or(Value::nothing(call.head));
 
         let input: PipelineData = match input {
@@ -72,6 +128,108 @@ impl DnsQuery {
             }
         };
 
+        if config.dry_run.item {
+            let input = input.into_value(call.head)?;
+            let plan = match &config.servers {
+                Some(servers) => servers
+                    .item
+                    .iter()
+                    .map(|server| {
+                        let config =
+                            Config::from_nu_with_overrides(plugin_config.clone(), call, server)?;
+                        query_plan(&config, &input)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+                None => query_plan(&config, &input)?,
+            };
+
+            return Ok(PipelineData::Value(
+                Value::list(plan, Span::unknown()),
+                None,
+            ));
+        }
+
+        if let Some(serial) = &config.since_serial {
+            let input = input.into_value(call.head)?;
+            let zone = Query::name_from_value(&input)?;
+
+            // the shared client may be validating DNSSEC, which would cut the
+            // transfer short after the first message
+            let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+            let val = xfr::changes_since(&client, &config, zone, serial.item, input.span()).await?;
+            return Ok(PipelineData::Value(val, None));
+        }
+
+        if config.measure_path_mtu.item {
+            let input = input.into_value(call.head)?;
+            let query = match input {
+                // the root NS query is answered by any recursive resolver
+                Value::Nothing { .. } => {
+                    hickory_proto::op::Query::query(Name::root(), RecordType::NS)
+                }
+                input => Query::try_from_value(&input, &config)?
+                    .into_iter()
+                    .next()
+                    .map(|query| query.0)
+                    .ok_or_else(|| {
+                        LabeledError::new("missing input")
+                            .with_label("Need a name to probe with", input.span())
+                    })?,
+            };
+
+            let val = path_mtu::measure(&config, query).await?;
+            return Ok(PipelineData::Value(val, None));
+        }
+
+        if config.explain_dnssec.item {
+            let input = input.into_value(call.head)?;
+            let queries = Query::try_from_value(&input, &config)?;
+
+            // a validating client would leave out the records that fail, which
+            // are just the ones to explain
+            let mut config = config;
+            config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+            let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+            let mut explained = Vec::with_capacity(queries.len());
+            for query in queries {
+                explained.push(chain::explain(&client, &config, query.0, input.span()).await?);
+            }
+
+            return Ok(PipelineData::Value(Value::list(explained, call.head), None));
+        }
+
+        let transform = AnswerTransform::new(engine, &config);
+
+        if config.pipeline.item {
+            let input = input.into_value(call.head)?;
+            return Self::query_pipeline(config, input, transform)
+                .await
+                .map(|steps| PipelineData::Value(Value::list(steps, call.head), None));
+        }
+
+        if let Some(servers) = &config.servers {
+            let input = input.into_value(call.head)?;
+            let servers = servers.item.clone();
+
+            return Self::query_servers(plugin_config, call, servers, input, transform)
+                .await
+                .map(|results| {
+                    let results = if config.prefer_server_order.item {
+                        merge_by_server_order(results)
+                    } else {
+                        results
+                    };
+
+                    PipelineData::Value(Value::list(results, Span::unknown()), None)
+                });
+        }
+
         let client = tokio::time::timeout(config.timeout.item, plugin.dns_client(&config))
             .await
             .map_err(|_| {
@@ -81,8 +239,35 @@ impl DnsQuery {
                 )
             })??;
 
+        let client = with_limits(client, &config);
+
         let config = Arc::new(config);
 
+        if let Some(interval) = &config.watch_diff {
+            let interval = interval.item;
+            let input = input.into_value(call.head)?;
+            let ctrlc = Signals::new(Arc::new(AtomicBool::new(false)));
+            let (resp_tx, resp_rx) = mpsc::channel(config.tasks.item);
+
+            plugin.spawn(watch_sigterm(ctrlc.clone(), plugin.cancel.clone()));
+            plugin.spawn(watch::watch_diff(
+                config,
+                input,
+                client,
+                interval,
+                transform,
+                resp_tx,
+                plugin.cancel.clone(),
+            ));
+
+            return Ok(response_stream(
+                resp_rx,
+                call.head,
+                ctrlc,
+                AnswerTransform(None),
+            ));
+        }
+
         match input {
             PipelineData::Value(val, _) => {
                 if tracing::enabled!(tracing::Level::TRACE) {
@@ -91,15 +276,13 @@ impl DnsQuery {
                     tracing::debug!(phase = "input", data.kind = "value");
                 }
 
-                let values = Self::query(config, val, client.clone()).await;
+                let values = Self::query(config.clone(), val, client.clone()).await;
+                let values = collect_responses(
+                    values.into_iter().map(|resp| transform.apply(resp)),
+                    &config,
+                )?;
 
-                let val = PipelineData::Value(
-                    Value::list(
-                        values.into_iter().collect::<Result<Vec<_>, _>>()?,
-                        Span::unknown(),
-                    ),
-                    None,
-                );
+                let val = PipelineData::Value(Value::list(values, Span::unknown()), None);
 
                 tracing::trace!(phase = "return", ?val);
 
@@ -111,12 +294,12 @@ impl DnsQuery {
                 let span = stream.span();
                 let ctrlc = Signals::new(Arc::new(AtomicBool::new(false)));
                 let (request_tx, request_rx) = mpsc::channel(config.tasks.item);
-                let (resp_tx, mut resp_rx) = mpsc::channel(config.tasks.item);
+                let (resp_tx, resp_rx) = mpsc::channel(config.tasks.item);
 
                 plugin.spawn(watch_sigterm(ctrlc.clone(), plugin.cancel.clone()));
 
                 plugin.spawn(coordinate_queries(
-                    config,
+                    config.clone(),
                     client,
                     request_rx,
                     resp_tx,
@@ -126,27 +309,11 @@ impl DnsQuery {
                 plugin
                     .spawn_blocking({
                         let cancel = plugin.cancel.clone();
-                        move || stream_requests(stream, cancel, request_tx)
+                        move || stream_requests(stream, config, cancel, request_tx)
                     })
                     .await;
 
-                Ok(PipelineData::ListStream(
-                    ListStream::new(
-                        std::iter::from_fn(move || {
-                            tokio::task::block_in_place(|| {
-                                resp_rx.blocking_recv().map(|resp| {
-                                    resp.unwrap_or_else(|err| {
-                                        Value::error(err.into(), Span::unknown())
-                                    })
-                                })
-                            })
-                        })
-                        .inspect(|val| log_response_val(val, "return")),
-                        span,
-                        ctrlc,
-                    ),
-                    None,
-                ))
+                Ok(response_stream(resp_rx, span, ctrlc, transform))
             }
             data => Err(LabeledError::new("invalid input").with_label(
                 "Only values can be passed as input",
@@ -155,13 +322,117 @@ impl DnsQuery {
         }
     }
 
+    /// Runs the same queries against each of the servers, with a fresh client
+    /// per server. Each server's messages are grouped under its record.
+    async fn query_servers(
+        plugin_config: Option<Value>,
+        call: &EvaluatedCall,
+        servers: Vec<Record>,
+        input: Value,
+        transform: AnswerTransform,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let results = futures_util::future::join_all(servers.into_iter().map(|server| {
+            let plugin_config = plugin_config.clone();
+            let input = input.clone();
+            let transform = &transform;
+
+            async move {
+                let config = Arc::new(Config::from_nu_with_overrides(
+                    plugin_config,
+                    call,
+                    &server,
+                )?);
+
+                // the background tasks are aborted when the join set is
+                // dropped, so it must be kept until all queries are done
+                let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+                tracing::info!(client.addr = ?config.server, client.protocol = ?config.protocol);
+
+                let client = with_limits(client, &config);
+
+                let values = Self::query(config.clone(), input, client).await;
+                let messages = collect_responses(
+                    values.into_iter().map(|resp| transform.apply(resp)),
+                    &config,
+                )
+                .map(|values| Value::list(values, Span::unknown()))
+                .or_else(|err| {
+                    if config.keep_errors.item {
+                        Ok(Value::error(err.into(), Span::unknown()))
+                    } else {
+                        Err(err)
+                    }
+                })?;
+
+                let mut output = server;
+                output.push(constants::columns::MESSAGES, messages);
+
+                Result::<_, LabeledError>::Ok(Value::record(output, Span::unknown()))
+            }
+        }))
+        .await;
+
+        let mut results = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        if results.len() > 1 {
+            attach_serial_deltas(&mut results);
+        }
+
+        tracing::trace!(phase = "return", ?results);
+
+        Ok(results)
+    }
+
+    /// Sends the queries of each input in order over a client of its own, so
+    /// that no other queries are interleaved with them, and each step only
+    /// starts once the previous one is done. Each step's messages are grouped
+    /// under its record.
+    async fn query_pipeline(
+        config: Config,
+        input: Value,
+        transform: AnswerTransform,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let steps = match input {
+            Value::List { vals, .. } => vals,
+            input => vec![input],
+        };
+
+        let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+        let client = with_limits(client, &config);
+        let config = Arc::new(config);
+        let mut results = Vec::with_capacity(steps.len());
+
+        for (step, input) in steps.into_iter().enumerate() {
+            let messages = Self::query(config.clone(), input, client.clone()).await;
+            let messages = collect_responses(
+                messages.into_iter().map(|resp| transform.apply(resp)),
+                &config,
+            )?;
+
+            let mut record = Record::new();
+            record.push(
+                constants::columns::STEP,
+                Value::int(step as i64, Span::unknown()),
+            );
+            record.push(
+                constants::columns::MESSAGES,
+                Value::list(messages, Span::unknown()),
+            );
+            results.push(Value::record(record, Span::unknown()));
+        }
+
+        Ok(results)
+    }
+
     pub(crate) async fn query(
         config: Arc<Config>,
         input: Value,
         client: DnsClient,
     ) -> DnsQueryResult {
         let in_span = input.span();
-        let queries = match Query::try_from_value(&input, &config) {
+        let mut queries = match Query::try_from_value(&input, &config) {
             Ok(queries) => queries,
             Err(err) => {
                 return vec![Ok(Value::error(err.into(), in_span))]
@@ -170,208 +441,937 @@ impl DnsQuery {
             }
         };
 
+        let positions = if config.coalesce_identical_queries.item {
+            coalesce(&mut queries)
+        } else {
+            (0..queries.len()).collect()
+        };
+
         tracing::debug!(request.queries = ?queries);
 
-        futures_util::stream::iter(queries)
+        let results: Vec<Vec<_>> = futures_util::stream::iter(queries)
             .then(|query| {
-                let mut client = client.clone();
+                let client = client.clone();
                 let config = config.clone();
 
                 async move {
-                    let parts = query.0.into_parts();
+                    // once too many queries failed, the rest are skipped, and
+                    // only the first of them reports it
+                    if client.failure_limit_reached() {
+                        return Vec::from_iter(client.take_skip_report().then(|| {
+                            Err(LabeledError::new("failing fast").with_label(
+                                "too many queries failed, so the remaining queries were not sent",
+                                config
+                                    .fail_fast_after
+                                    .as_ref()
+                                    .map_or(Span::unknown(), |limit| limit.span),
+                            ))
+                        }));
+                    }
 
-                    if tracing::enabled!(tracing::Level::TRACE) {
-                        tracing::trace!(query.phase = "start", query.parts = ?parts);
+                    let results = if client.response_window().is_some()
+                        || config.llmnr.item
+                        || names::is_mdns(query.0.name())
+                    {
+                        Self::query_collect(&client, &config, query, in_span).await
                     } else {
-                        tracing::debug!(query.phase = "start");
+                        vec![Self::query_one(&client, &config, query, in_span).await]
+                    };
+
+                    if results.iter().any(Result::is_err) {
+                        client.record_failure();
                     }
 
-                    let request = tokio::time::timeout(
-                        config.timeout.item,
-                        client.query(parts.name, parts.query_class, parts.query_type),
-                    );
-
-                    request
-                        .await
-                        .map_err(|_| {
-                            LabeledError::new("timed out").with_label(
-                                format!("request to {} timed out", config.server.item),
-                                config.server.span,
-                            )
-                        })?
-                        .map_err(|err| {
-                            LabeledError::new("DNS error")
-                                .with_label(format!("Error in DNS response: {:?}", err), in_span)
-                        })
-                        .and_then(|resp: hickory_proto::xfer::DnsResponse| {
-                            let msg = serde::Message::new(resp.into_message());
-                            msg.into_value(&config)
-                        })
-                        .inspect_err(
-                            |err| tracing::debug!(query.phase = "finish", query.error = ?err),
-                        )
-                        .inspect(|resp| {
-                            log_response_val(resp, "finish");
-                        })
+                    results
                 }
             })
-            .collect::<FuturesUnordered<_>>()
-            .await
-    }
-}
+            .collect()
+            .await;
 
-async fn watch_sigterm(ctrlc: Signals, cancel: CancellationToken) -> Result<(), LabeledError> {
-    while !ctrlc.interrupted() {
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        positions
+            .into_iter()
+            .flat_map(|idx| results[idx].clone())
+            .collect()
     }
 
-    cancel.cancel();
-    Ok(())
-}
+    /// Like [`DnsQuery::query_one`], but returns every response that arrived
+    /// within the response window as a message of its own. Names under
+    /// `local.` are asked over multicast DNS instead of the nameserver, and so
+    /// are all names over LLMNR.
+    async fn query_collect(
+        client: &DnsClient,
+        config: &Config,
+        query: Query,
+        in_span: Span,
+    ) -> Vec<Result<Value, LabeledError>> {
+        tracing::debug!(query.phase = "start", query.collect = true);
 
-fn stream_requests(
-    stream: ListStream,
-    cancel: CancellationToken,
-    request_tx: mpsc::Sender<Value>,
-) -> Result<(), LabeledError> {
-    tracing::trace!(task.sender.phase = "start");
+        client.ready().await;
 
-    let result = stream.into_iter().try_for_each(|val| {
-        tracing::trace!(query = ?val, query.phase = "send");
+        let context = query_context(&query, config);
+        let responses = if names::is_mdns(query.0.name()) {
+            client.send_mdns_query(query.0, config).await
+        } else if config.llmnr.item {
+            client.send_llmnr_query(query.0, config).await
+        } else {
+            client.send_query_collect(query.0, config).await
+        };
 
-        if cancel.is_cancelled() {
-            return Err(LabeledError::new("canceled"));
+        let responses = match responses {
+            Ok(responses) => responses,
+            Err(err) => {
+                return vec![Err(LabeledError::new("DNS error")
+                    .with_label(format!("{} failed: {}", context, err.kind()), in_span))]
+            }
+        };
+
+        if responses.is_empty() {
+            return vec![Err(LabeledError::new("timed out").with_label(
+                format!(
+                    "{}: no responses arrived within the response window",
+                    context
+                ),
+                config
+                    .response_window
+                    .as_ref()
+                    .map_or(config.server.span, |window| window.span),
+            ))];
         }
 
-        request_tx.blocking_send(val).map_err(|send_err| {
-            LabeledError::new("internal error").with_label(
-                format!("failed to send dns query result: {}", send_err),
-                Span::unknown(),
-            )
-        })
-    });
+        responses
+            .into_iter()
+            .map(|(msg, responder)| {
+                check_min_answers(&msg, &context, config)?;
+                serde::Message::new(msg)
+                    .with_responder(responder)
+                    .into_value(config)
+                    .inspect(|resp| log_response_val(resp, "finish"))
+            })
+            .collect()
+    }
 
-    tracing::trace!(task.sender.phase = "exit", task.sender.result = ?result);
+    /// Sends a query and waits for its response.
+    async fn send_one(
+        client: &DnsClient,
+        config: &Config,
+        query: hickory_proto::op::Query,
+        context: &str,
+        in_span: Span,
+    ) -> Result<DnsResponse, LabeledError> {
+        let raw_opcode = config
+            .opcode
+            .as_ref()
+            .map(|opcode| opcode.item)
+            .filter(|opcode| hickory_proto::op::OpCode::from_u8(*opcode).is_err());
 
-    result
-}
+        let request = if config.dns_json.is_some() {
+            tokio::time::timeout(config.timeout.item, client.send_json_query(query, config)).await
+        } else if config.dnssec_mode.item == DnssecMode::Validate {
+            tokio::time::timeout(
+                config.timeout.item,
+                client.send_dnssec_ok_query(query, config),
+            )
+            .await
+        } else if let Some(opcode) = raw_opcode {
+            tokio::time::timeout(
+                config.timeout.item,
+                client.send_raw_query(query, config, opcode),
+            )
+            .await
+        } else {
+            tokio::time::timeout(config.timeout.item, client.send_query(query, config)).await
+        };
 
-async fn coordinate_queries(
-    config: Arc<Config>,
-    client: DnsClient,
-    mut request_rx: mpsc::Receiver<Value>,
-    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
-    cancel: CancellationToken,
-) -> Result<(), LabeledError> {
-    tracing::trace!(task.query_coordinator.phase = "start");
-    let mut buf = Vec::with_capacity(config.tasks.item);
-    let mut result_queue = FuturesOrdered::new();
+        let response = request.map_err(|_| {
+            LabeledError::new("timed out").with_label(
+                format!("{} timed out after {:?}", context, config.timeout.item),
+                config.server.span,
+            )
+        })?;
 
-    while request_rx.recv_many(&mut buf, config.tasks.item).await > 0 {
-        tracing::trace!(query.phase = "batch received", query.batchsize = buf.len());
+        match response {
+            Ok(response) => Ok(response),
+            Err(err) if client.closed_during_query().await => {
+                Err(LabeledError::new("connection closed").with_label(
+                    format!(
+                        "{}: the connection was closed before a response arrived: {}",
+                        context,
+                        err.kind()
+                    ),
+                    in_span,
+                ))
+            }
+            Err(err) => Err(LabeledError::new("DNS error")
+                .with_label(format!("{} failed: {}", context, err.kind()), in_span)),
+        }
+    }
 
-        for val in buf.drain(..) {
-            tracing::trace!(query = ?val, query.phase = "received");
+    async fn query_one(
+        client: &DnsClient,
+        config: &Config,
+        query: Query,
+        in_span: Span,
+    ) -> Result<Value, LabeledError> {
+        if tracing::enabled!(tracing::Level::TRACE) {
+            tracing::trace!(query.phase = "start", query.query = ?query.0);
+        } else {
+            tracing::debug!(query.phase = "start");
+        }
 
-            let config = config.clone();
-            let client = client.clone();
-            let cancel = cancel.clone();
+        // waiting for the rate limit must not count towards the timeout
+        client.ready().await;
 
-            let handle = tokio::spawn(async move {
-                tracing::trace!(task.query_exec.phase = "start");
+        let context = query_context(&query, config);
+        let question = query.0.clone();
+        let response = Self::send_one(client, config, query.0.clone(), &context, in_span).await;
 
-                let mut query = Box::pin(DnsQuery::query(config, val, client).fuse());
-                let mut cancelled = Box::pin(cancel.cancelled().fuse());
+        // a UDP response that did not fit is sent again over TCP, which has
+        // room for any message
+        let (response, transport) = match response {
+            Ok(response) if response.truncated() && config.protocol.item == Protocol::Udp => {
+                tracing::debug!(query.phase = "truncated", "retrying over TCP");
 
-                let result = select! {
-                    resp = query => resp,
-                    _ = cancelled => vec![Err(LabeledError::new("canceled"))].into_iter().collect(),
+                let response = match client.tcp_fallback(config).await {
+                    Ok(tcp_client) => {
+                        Self::send_one(&tcp_client, config, query.0, &context, in_span).await
+                    }
+                    Err(err) => Err(err),
                 };
 
-                tracing::trace!(
-                    task.query_exec.phase = "end",
-                    task.query_exec.result = ?result
+                (response, Protocol::Tcp)
+            }
+            response => (response, config.protocol.item),
+        };
+
+        // a server that requires cookies answers a query without its server
+        // cookie with BADCOOKIE and the cookie, to send again with (RFC 7873
+        // section 5.3)
+        let response = match response {
+            Ok(response)
+                if response.response_code() == ResponseCode::BADCOOKIE
+                    && client.receive_cookie(&response) == Some(CookieStatus::Valid) =>
+            {
+                tracing::debug!(
+                    query.phase = "bad cookie",
+                    "retrying with the server cookie"
                 );
+                Self::send_one(client, config, question.clone(), &context, in_span).await
+            }
+            response => response,
+        };
 
-                result
-            });
+        let cookie = response
+            .as_ref()
+            .ok()
+            .and_then(|response| client.receive_cookie(response));
 
-            result_queue.push_back(handle);
-        }
+        let response = response.and_then(|response| {
+            check_min_answers(&response, &context, config)?;
+            Ok(response.into_parts())
+        });
 
-        while let Some(query_result) = result_queue.next().await {
-            let query_result = query_result.map_err(|err| {
-                LabeledError::new("internal error")
-                    .with_label(format!("task panicked: {}", err), Span::unknown())
-            })?;
+        let message = async {
+            let (message, wire) = response?;
 
-            for resp in query_result.into_iter() {
-                resp_tx.send(resp).await.map_err(|send_err| {
-                    LabeledError::new("internal error").with_label(
-                        format!("failed to send dns query result: {}", send_err),
-                        Span::unknown(),
-                    )
-                })?;
-            }
+            let dnssec = if config.dnssec_mode.item == DnssecMode::Validate {
+                Some(chain::validate(client, config, &message, in_span).await?)
+            } else {
+                None
+            };
+
+            let ns_addresses = if config.resolve_ns_addresses.item {
+                lookup::ns_addresses(client, config, &message, in_span).await?
+            } else {
+                HashMap::new()
+            };
+
+            let message = serde::Message::new(message)
+                .with_responder(client.responder())
+                .with_transport(transport)
+                .with_question(question)
+                .with_ns_addresses(ns_addresses);
+            let message = match cookie {
+                Some(cookie) => message.with_cookie(cookie),
+                None => message,
+            };
+            let message = match dnssec {
+                Some(dnssec) => message.with_dnssec(dnssec),
+                None => message,
+            };
+
+            Ok(message.with_wire(wire))
         }
+        .await;
+
+        message
+            .and_then(|msg| msg.into_value(config))
+            .inspect_err(|err| tracing::debug!(query.phase = "finish", query.error = ?err))
+            .inspect(|resp| {
+                log_response_val(resp, "finish");
+            })
     }
+}
 
-    tracing::trace!(task.query_coordinator.phase = "exit");
+/// Fails a response with fewer answer records of the queried type than
+/// `--min-answers`, so that e.g. a shrunken pool of addresses is noticed. The
+/// CNAMEs leading to the records do not count.
+fn check_min_answers(
+    message: &hickory_proto::op::Message,
+    context: &str,
+    config: &Config,
+) -> Result<(), LabeledError> {
+    let Some(min) = &config.min_answers else {
+        return Ok(());
+    };
+
+    let rtype = message.queries().first().map(|query| query.query_type());
+    let count = message
+        .answers()
+        .iter()
+        .filter(|record| match rtype {
+            Some(RecordType::ANY) | None => true,
+            Some(rtype) => record.record_type() == rtype,
+        })
+        .count();
+
+    if count < min.item {
+        return Err(LabeledError::new("too few answers").with_label(
+            format!(
+                "{}: expected at least {} answer records, got {}",
+                context, min.item, count
+            ),
+            min.span,
+        ));
+    }
 
     Ok(())
 }
 
-pub(crate) fn log_response_val(resp: &Value, phase: &str) {
-    if tracing::enabled!(tracing::Level::TRACE) {
-        tracing::trace!(query.phase = phase, query.response = ?resp)
-    } else {
-        let question = resp.get_data_by_key("question");
-        let answer = resp.get_data_by_key("answer");
-        tracing::debug!(
-            query.phase = phase,
-            query.response.question = ?question,
-            query.response.answer = ?answer
-        );
-    }
+/// The answer records of the messages, as one list, for `--answers-only`. Errors
+/// kept in the list are kept as they are.
+fn answer_records(value: &Value) -> Vec<Value> {
+    dig::messages(value)
+        .into_iter()
+        .flat_map(|message| match message {
+            Value::Error { .. } => vec![message.clone()],
+            message => match message.get_data_by_key("answer") {
+                Some(Value::List { vals, .. }) => vals,
+                _ => Vec::new(),
+            },
+        })
+        .collect()
 }
 
-impl Plugin for Dns {
-    fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
-        vec![Box::new(DnsQuery)]
-    }
+/// One row per record of the answer, authority, and additional sections of the
+/// messages, with the name of the question and the section it is from, for
+/// `--flatten`. The record data is in presentation format, for the rows to be
+/// flat. Errors kept in the list are kept as they are.
+fn flat_records(value: &Value) -> Result<Vec<Value>, LabeledError> {
+    let mut rows = Vec::new();
+
+    for message in dig::messages(value) {
+        if let Value::Error { .. } = message {
+            rows.push(message.clone());
+            continue;
+        }
+
+        let question = message
+            .get_data_by_key(constants::columns::QUESTION)
+            .and_then(|question| question.get_data_by_key(constants::columns::NAME))
+            .unwrap_or(Value::nothing(Span::unknown()));
+
+        for section in ["answer", "authority", "additional"] {
+            let Some(Value::List { vals, .. }) = message.get_data_by_key(section) else {
+                continue;
+            };
 
-    fn version(&self) -> String {
-        env!("CARGO_PKG_VERSION").into()
+            for record in vals {
+                let (_, rtype, _, _, rdata) = zone::record_columns(&record, None, DNSClass::IN)?;
+                let rdata = presentation::rdata(rtype, &rdata, &Names::default())?.line();
+                let column = |col: &str| {
+                    record
+                        .get_data_by_key(col)
+                        .unwrap_or(Value::nothing(Span::unknown()))
+                };
+
+                rows.push(Value::record(
+                    Record::from_iter(std::iter::zip(
+                        constants::columns::FLAT_RECORD_COLS
+                            .iter()
+                            .map(|col| (*col).into()),
+                        vec![
+                            question.clone(),
+                            Value::string(section, Span::unknown()),
+                            column(constants::columns::NAME),
+                            column(constants::columns::TYPE),
+                            column(constants::columns::CLASS),
+                            column(constants::columns::TTL),
+                            Value::string(rdata, Span::unknown()),
+                        ],
+                    )),
+                    Span::unknown(),
+                ));
+            }
+        }
     }
+
+    Ok(rows)
 }
 
-impl PluginCommand for DnsQuery {
-    type Plugin = Dns;
+/// Flattens the messages grouped by server into one list, in the order the
+/// servers were listed, and tags each message with the server it came from.
+fn merge_by_server_order(servers: Vec<Value>) -> Vec<Value> {
+    servers
+        .into_iter()
+        .flat_map(|server| {
+            let mut server = match server {
+                Value::Record { val, .. } => val.into_owned(),
+                val => return vec![val],
+            };
 
-    fn run(
-        &self,
-        plugin: &Self::Plugin,
-        engine: &EngineInterface,
-        call: &EvaluatedCall,
-        input: PipelineData,
-    ) -> Result<PipelineData, LabeledError> {
-        plugin
-            .runtime
-            .block_on(self.run_impl(plugin, engine, call, input))
-    }
+            let messages = match server.remove(constants::columns::MESSAGES) {
+                Some(Value::List { vals, .. }) => vals,
+                // a server whose queries failed as a whole
+                Some(val) => vec![val],
+                None => vec![],
+            };
 
-    fn name(&self) -> &str {
-        constants::commands::QUERY
-    }
+            let source = Value::record(server, Span::unknown());
 
-    fn description(&self) -> &str {
-        "Perform a DNS query"
-    }
+            messages
+                .into_iter()
+                .map(|mut message| {
+                    if let Value::Record { val, .. } = &mut message {
+                        val.to_mut()
+                            .push(constants::columns::SERVER, source.clone());
+                    }
 
-    fn signature(&self) -> nu_protocol::Signature {
-        Signature::build(self.name())
-            .rest(
-                constants::flags::NAME,
+                    message
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Annotates each message with an SOA answer with the difference of its serial
+/// to the highest serial seen for the same zone across all servers, so that
+/// secondaries which are behind show up with negative deltas. Serials are
+/// compared with serial number arithmetic (RFC 1982), so they may wrap around.
+fn attach_serial_deltas(servers: &mut [Value]) {
+    fn messages_mut(server: &mut Value) -> impl Iterator<Item = &mut Value> {
+        let messages = match server {
+            Value::Record { val, .. } => match val.to_mut().get_mut(constants::columns::MESSAGES) {
+                Some(Value::List { vals, .. }) => Some(vals.iter_mut()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        messages.into_iter().flatten()
+    }
+
+    fn soa_serial(message: &Value) -> Option<(String, u32)> {
+        let answer = message.get_data_by_key("answer")?;
+
+        answer.as_list().ok()?.iter().find_map(|record| {
+            let rtype = record.get_data_by_key(constants::columns::TYPE)?;
+            let is_soa = match rtype {
+                Value::Record { .. } => {
+                    rtype
+                        .get_data_by_key(constants::columns::NAME)?
+                        .as_str()
+                        .ok()?
+                        == "SOA"
+                }
+                Value::Int { val, .. } => val == u16::from(RecordType::SOA) as i64,
+                rtype => rtype.as_str().ok()? == "SOA",
+            };
+
+            if !is_soa {
+                return None;
+            }
+
+            let zone = record
+                .get_data_by_key(constants::columns::NAME)?
+                .as_str()
+                .ok()?
+                .to_lowercase();
+            let serial = record.get_data_by_key("rdata")?.get_data_by_key("serial")?;
+
+            Some((zone, serial.as_int().ok()? as u32))
+        })
+    }
+
+    let mut max_serials: HashMap<String, u32> = HashMap::new();
+
+    for server in servers.iter_mut() {
+        for (zone, serial) in messages_mut(server).filter_map(|msg| soa_serial(msg)) {
+            max_serials
+                .entry(zone)
+                .and_modify(|max| {
+                    if (serial.wrapping_sub(*max) as i32) > 0 {
+                        *max = serial;
+                    }
+                })
+                .or_insert(serial);
+        }
+    }
+
+    for server in servers.iter_mut() {
+        for message in messages_mut(server) {
+            let Some((zone, serial)) = soa_serial(message) else {
+                continue;
+            };
+
+            let delta = serial.wrapping_sub(max_serials[&zone]) as i32;
+
+            if let Value::Record { val, .. } = message {
+                val.to_mut().push(
+                    constants::columns::SERIAL_DELTA,
+                    Value::int(delta as i64, Span::unknown()),
+                );
+            }
+        }
+    }
+}
+
+/// Describes a query for error messages, e.g. `query for example.com. AAAA IN
+/// to 8.8.8.8:53 over udp`, so that the failed query of a batch can be told
+/// apart.
+fn query_context(query: &Query, config: &Config) -> String {
+    let (server, protocol) = destination(query, config);
+    format!(
+        "query for {} {} {} to {} over {}",
+        query.0.name(),
+        query.0.query_type(),
+        query.0.query_class(),
+        server,
+        protocol
+    )
+}
+
+/// Where a query is sent, and over which protocol. Names under `local.` go to
+/// the multicast DNS group rather than the nameserver, and LLMNR queries to
+/// the LLMNR group.
+fn destination(query: &Query, config: &Config) -> (String, String) {
+    if names::is_mdns(query.0.name()) {
+        (
+            constants::config::default::MDNS_GROUP.to_string(),
+            "mdns".into(),
+        )
+    } else if config.llmnr.item {
+        (
+            constants::config::default::LLMNR_GROUP.to_string(),
+            "llmnr".into(),
+        )
+    } else if config.dns_json.is_some() {
+        (config.server.item.to_string(), "dns-json".into())
+    } else {
+        (
+            config.server.item.to_string(),
+            config.protocol.item.to_string(),
+        )
+    }
+}
+
+/// The queries that would be sent for the input, one row per query, with the
+/// nameserver they would be sent to.
+fn query_plan(config: &Config, input: &Value) -> Result<Vec<Value>, LabeledError> {
+    let mut queries = Query::try_from_value(input, config)?;
+
+    if config.coalesce_identical_queries.item {
+        coalesce(&mut queries);
+    }
+
+    Ok(queries
+        .into_iter()
+        .map(|query| {
+            let (server, protocol) = destination(&query, config);
+            let mut row = match query.into_value(config) {
+                Value::Record { val, .. } => val.into_owned(),
+                _ => Record::new(),
+            };
+
+            for (col, val) in std::iter::zip(constants::columns::PLAN_COLS, [server, protocol]) {
+                row.push(*col, Value::string(val, Span::unknown()));
+            }
+
+            Value::record(row, Span::unknown())
+        })
+        .collect())
+}
+
+/// Removes the queries that repeat an earlier one, and returns for each of the
+/// original queries the index of the query left to answer it. Names are
+/// compared case-insensitively, as nameservers do.
+fn coalesce(queries: &mut Vec<Query>) -> Vec<usize> {
+    let mut unique: Vec<Query> = Vec::with_capacity(queries.len());
+    let mut positions = Vec::with_capacity(queries.len());
+    let key = |query: &Query| {
+        (
+            query.0.name().to_lowercase(),
+            query.0.query_type(),
+            query.0.query_class(),
+        )
+    };
+
+    for query in queries.drain(..) {
+        match unique.iter().position(|seen| key(seen) == key(&query)) {
+            Some(idx) => positions.push(idx),
+            None => {
+                positions.push(unique.len());
+                unique.push(query);
+            }
+        }
+    }
+
+    *queries = unique;
+    positions
+}
+
+/// Applies the limits of a single invocation to the client.
+fn with_limits(client: DnsClient, config: &Config) -> DnsClient {
+    let client = match &config.qps {
+        Some(qps) => client.with_rate_limit(qps.item),
+        None => client,
+    };
+
+    let client = match &config.response_window {
+        Some(window) => client.with_response_window(window.item),
+        None => client,
+    };
+
+    let client = match &config.fail_fast_after {
+        Some(limit) => client.with_failure_limit(limit.item),
+        None => client,
+    };
+
+    if config.cookie.item {
+        client.with_cookies()
+    } else {
+        client
+    }
+}
+
+/// By default, one failed query fails the whole batch, but when keeping errors,
+/// only the affected queries are marked.
+fn collect_responses(
+    values: impl IntoIterator<Item = Result<Value, LabeledError>>,
+    config: &Config,
+) -> Result<Vec<Value>, LabeledError> {
+    if config.keep_errors.item {
+        Ok(values
+            .into_iter()
+            .map(|resp| resp.unwrap_or_else(|err| Value::error(err.into(), Span::unknown())))
+            .collect())
+    } else {
+        values.into_iter().collect()
+    }
+}
+
+/// The `--transform` closure, which is applied to every answer record of the
+/// messages. Records it maps to nothing are dropped.
+#[derive(Clone, Default)]
+pub(crate) struct AnswerTransform(Option<(EngineInterface, Spanned<Closure>)>);
+
+impl AnswerTransform {
+    fn new(engine: &EngineInterface, config: &Config) -> Self {
+        Self(
+            config
+                .transform
+                .clone()
+                .map(|closure| (engine.clone(), closure)),
+        )
+    }
+
+    pub(crate) fn apply(
+        &self,
+        message: Result<Value, LabeledError>,
+    ) -> Result<Value, LabeledError> {
+        let Some((engine, closure)) = &self.0 else {
+            return message;
+        };
+
+        let mut message = message?;
+
+        if let Value::Record { val, .. } = &mut message {
+            if let Some(Value::List { vals, .. }) = val.to_mut().get_mut("answer") {
+                let answer = std::mem::take(vals);
+
+                for record in answer {
+                    // evaluating the closure blocks until the engine answers
+                    let transformed = tokio::task::block_in_place(|| {
+                        engine.eval_closure(closure, vec![record.clone()], Some(record.clone()))
+                    })
+                    .map_err(|err| {
+                        LabeledError::new("transform failed").with_label(
+                            format!(
+                                "transforming the answer record {} failed: {}",
+                                describe_record(&record),
+                                err
+                            ),
+                            closure.span,
+                        )
+                    })?;
+
+                    if !transformed.is_nothing() {
+                        vals.push(transformed);
+                    }
+                }
+            }
+        }
+
+        Ok(message)
+    }
+}
+
+/// A short description of a record for error messages, e.g.
+/// `www.example.com. A 192.0.2.1`.
+fn describe_record(record: &Value) -> String {
+    let config = nu_protocol::Config::default();
+    let column = |col: &str| {
+        record.get_data_by_key(col).map(|val| match val {
+            // with --code, types are records of their name and code
+            Value::Record { .. } => val
+                .get_data_by_key(constants::columns::NAME)
+                .map(|name| name.to_abbreviated_string(&config))
+                .unwrap_or_default(),
+            val => val.to_abbreviated_string(&config),
+        })
+    };
+
+    [constants::columns::NAME, constants::columns::TYPE, "rdata"]
+        .into_iter()
+        .filter_map(column)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Turns the responses sent by a background task into the command's output
+/// stream, which ends once the sender is dropped.
+pub(crate) fn response_stream(
+    mut resp_rx: mpsc::Receiver<Result<Value, LabeledError>>,
+    span: Span,
+    ctrlc: Signals,
+    transform: AnswerTransform,
+) -> PipelineData {
+    PipelineData::ListStream(
+        ListStream::new(
+            std::iter::from_fn(move || {
+                tokio::task::block_in_place(|| {
+                    resp_rx.blocking_recv().map(|resp| {
+                        transform
+                            .apply(resp)
+                            .unwrap_or_else(|err| Value::error(err.into(), Span::unknown()))
+                    })
+                })
+            })
+            .inspect(|val| log_response_val(val, "return")),
+            span,
+            ctrlc,
+        ),
+        None,
+    )
+}
+
+pub(crate) async fn watch_sigterm(
+    ctrlc: Signals,
+    cancel: CancellationToken,
+) -> Result<(), LabeledError> {
+    while !ctrlc.interrupted() {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    cancel.cancel();
+    Ok(())
+}
+
+fn stream_requests(
+    stream: ListStream,
+    config: Arc<Config>,
+    cancel: CancellationToken,
+    request_tx: mpsc::Sender<Value>,
+) -> Result<(), LabeledError> {
+    tracing::trace!(task.sender.phase = "start");
+
+    let send = |val| {
+        request_tx.blocking_send(val).map_err(|send_err| {
+            LabeledError::new("internal error").with_label(
+                format!("failed to send dns query result: {}", send_err),
+                Span::unknown(),
+            )
+        })
+    };
+
+    let mut count = 0;
+
+    let result = stream.into_iter().try_for_each(|val| {
+        tracing::trace!(query = ?val, query.phase = "send");
+
+        if cancel.is_cancelled() {
+            return Err(LabeledError::new("canceled"));
+        }
+
+        let val = if config.zip.item {
+            zip_query(val, count, &config)
+        } else {
+            val
+        };
+
+        count += 1;
+        send(val)
+    });
+
+    // a stream's length is not known up front, so a mismatch with the number
+    // of types can only be reported once it has been exhausted
+    let result = match result {
+        Ok(()) if config.zip.item && count < config.qtypes.item.len() => send(Value::error(
+            zip_mismatch_err(count, &config).into(),
+            Span::unknown(),
+        )),
+        result => result,
+    };
+
+    tracing::trace!(task.sender.phase = "exit", task.sender.result = ?result);
+
+    result
+}
+
+/// Pairs a streamed name with the type at the same position in the list of
+/// types as a query record.
+fn zip_query(name: Value, idx: usize, config: &Config) -> Value {
+    let span = name.span();
+
+    match config.qtypes.item.get(idx) {
+        Some(qtype) => Value::record(
+            record! {
+                constants::columns::NAME  => name,
+                constants::columns::TYPE  => Value::int(u16::from(qtype.item) as i64, span),
+                constants::columns::CLASS => Value::int(u16::from(config.class.item) as i64, span),
+            },
+            span,
+        ),
+        None => Value::error(zip_mismatch_err(idx + 1, config).into(), span),
+    }
+}
+
+fn zip_mismatch_err(names: usize, config: &Config) -> LabeledError {
+    LabeledError::new("mismatched lengths").with_label(
+        format!(
+            "{} types given, but --zip needs as many as names, and at least {} names were given",
+            config.qtypes.item.len(),
+            names
+        ),
+        config.qtypes.span,
+    )
+}
+
+async fn coordinate_queries(
+    config: Arc<Config>,
+    client: DnsClient,
+    mut request_rx: mpsc::Receiver<Value>,
+    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
+    cancel: CancellationToken,
+) -> Result<(), LabeledError> {
+    tracing::trace!(task.query_coordinator.phase = "start");
+    let mut buf = Vec::with_capacity(config.tasks.item);
+    let mut result_queue = FuturesOrdered::new();
+
+    while request_rx.recv_many(&mut buf, config.tasks.item).await > 0 {
+        tracing::trace!(query.phase = "batch received", query.batchsize = buf.len());
+
+        for val in buf.drain(..) {
+            tracing::trace!(query = ?val, query.phase = "received");
+
+            let config = config.clone();
+            let client = client.clone();
+            let cancel = cancel.clone();
+
+            let handle = tokio::spawn(async move {
+                tracing::trace!(task.query_exec.phase = "start");
+
+                let mut query = Box::pin(DnsQuery::query(config, val, client).fuse());
+                let mut cancelled = Box::pin(cancel.cancelled().fuse());
+
+                let result = select! {
+                    resp = query => resp,
+                    _ = cancelled => vec![Err(LabeledError::new("canceled"))].into_iter().collect(),
+                };
+
+                tracing::trace!(
+                    task.query_exec.phase = "end",
+                    task.query_exec.result = ?result
+                );
+
+                result
+            });
+
+            result_queue.push_back(handle);
+        }
+
+        while let Some(query_result) = result_queue.next().await {
+            let query_result = query_result.map_err(|err| {
+                LabeledError::new("internal error")
+                    .with_label(format!("task panicked: {}", err), Span::unknown())
+            })?;
+
+            for resp in query_result.into_iter() {
+                resp_tx.send(resp).await.map_err(|send_err| {
+                    LabeledError::new("internal error").with_label(
+                        format!("failed to send dns query result: {}", send_err),
+                        Span::unknown(),
+                    )
+                })?;
+            }
+        }
+    }
+
+    tracing::trace!(task.query_coordinator.phase = "exit");
+
+    Ok(())
+}
+
+pub(crate) fn log_response_val(resp: &Value, phase: &str) {
+    if tracing::enabled!(tracing::Level::TRACE) {
+        tracing::trace!(query.phase = phase, query.response = ?resp)
+    } else {
+        let question = resp.get_data_by_key("question");
+        let answer = resp.get_data_by_key("answer");
+        tracing::debug!(
+            query.phase = phase,
+            query.response.question = ?question,
+            query.response.answer = ?answer
+        );
+    }
+}
+
+impl PluginCommand for DnsQuery {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::QUERY
+    }
+
+    fn description(&self) -> &str {
+        "Perform a DNS query"
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        Signature::build(self.name())
+            .rest(
+                constants::flags::NAME,
 
                 // [NOTE] this does not work
                 // SyntaxShape::OneOf(vec![
@@ -388,35 +1388,206 @@ impl PluginCommand for DnsQuery {
                 "DNS record name",
             )
             .named(
-                constants::flags::SERVER,
-                SyntaxShape::String,
-                "Nameserver to query (defaults to system config or 8.8.8.8)",
-                Some('s'),
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to query: an address, a hostname, or a URL with the scheme of its protocol, https://, tls://, or quic://, or a DNS stamp (sdns://). A hostname is also the default --dns-name (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::SERVERS,
+                SyntaxShape::Any,
+                "Run the queries against each of these nameservers, grouping the messages by server. Takes a list of addresses, or a table with a \"server\" column; its other columns are carried into the output, and columns named like flags (e.g. \"protocol\") override them for that server",
+                None,
+            )
+            .switch(
+                constants::flags::PREFER_SERVER_ORDER,
+                "With --servers, merge the messages of all servers into one list in the order the servers were listed, tagging each message with the server it came from",
+                None,
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP, DNS-JSON to ask the JSON API of a resolver over HTTPS, or LLMNR to ask the hosts on the local link for single-label names instead. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Query type, or ANY (also *). Also accepts the groups \"mail\" (MX, TXT, and TXT at _dmarc), \"web\" (A, AAAA, CNAME, HTTPS), \"dnssec\" (DNSKEY, DS, RRSIG, NSEC), and \"all\" (see --all-types)",
+                Some('t'),
+            )
+            .switch(
+                constants::flags::ALL_TYPES,
+                "Query the common types one by one: A, AAAA, CNAME, MX, NS, SOA, TXT, SRV, CAA, and HTTPS. For servers that answer ANY queries with a single record (RFC 8482)",
+                None,
+            )
+            .named(constants::flags::CLASS, SyntaxShape::Any, "Query class", None)
+            .switch(
+                constants::flags::ZIP,
+                "Pair names and types by position instead of querying every type for every name",
+                None,
+            )
+            .switch(
+                constants::flags::COALESCE_IDENTICAL_QUERIES,
+                "Send repeated queries for the same name, type, and class only once, and output the result at every position it was asked at. Streamed input is coalesced per item",
+                None,
+            )
+            .switch(
+                constants::flags::CODE,
+                "Return code fields with both string and numeric representations",
+                Some('c'),
+            )
+            .switch(
+                constants::flags::TYPE_AS_NUMBER,
+                "Return the type of questions and records as its numeric code only, e.g. 28 instead of AAAA. Takes precedence over --code",
+                None,
+            )
+            .switch(
+                constants::flags::KEEP_ERRORS,
+                "Return failed queries as errors in the output instead of failing the whole batch",
+                Some('k'),
+            )
+            .switch(
+                constants::flags::CANONICAL_ORDER,
+                "Sort the records of each RRset into DNSSEC canonical order (RFC 4034 section 6.3)",
+                None,
+            )
+            .switch(
+                constants::flags::SHORT,
+                "Output only the record data of each answer, as a list of strings like dig +short. Can not be used with --watch-diff",
+                None,
+            )
+            .switch(
+                constants::flags::FLATTEN,
+                "Output one flat row per record of every message, with the question and section it is from, instead of nested messages. Can not be used with --watch-diff",
+                None,
+            )
+            .switch(
+                constants::flags::ANSWERS_ONLY,
+                "Output only the answer records of each message, as one list, leaving out the header and the other sections. Can not be used with --watch-diff",
+                None,
+            )
+            .switch(
+                constants::flags::TTL_AS_INT,
+                "Output the TTLs of records as ints of seconds rather than durations",
+                None,
+            )
+            .switch(
+                constants::flags::ANSWER_CHECKSUM,
+                "Add a SHA-256 hash of the answer records in canonical form and order to each message, leaving out TTLs. Equal hashes mean the answers did not change",
+                None,
+            )
+            .named(
+                constants::flags::DNSSEC,
+                SyntaxShape::String,
+                "Perform DNSSEC validation on records. Choices are: \"none\", \"strict\" (error if record has no RRSIG or does not validate), \"opportunistic\" (validate if RRSIGs present, otherwise no validation; default), \"validate\" (build the chain of trust from the root, and return whether each zone is secure, insecure, or bogus, and the records it rests on)",
+                Some('d'),
+            )
+            .switch(
+                constants::flags::TRUST_AD,
+                "Trust the AD bit of the resolver instead of validating DNSSEC locally, and report it as a \"validated\" column. Needs an encrypted protocol",
+                None,
+            )
+            .named(
+                constants::flags::EDNS_OPTION,
+                SyntaxShape::Any,
+                "Raw EDNS option(s) to add to the query, given as CODE=HEX, e.g. 65001=beef. Takes a string or a list of strings",
+                None,
+            )
+            .named(
+                constants::flags::CLIENT_SUBNET,
+                SyntaxShape::String,
+                "Client subnet (RFC 7871) to send with queries, e.g. 203.0.113.0/24, for resolvers to answer as they would for clients in it. The scope of the answer is in edns.opts.subnet",
+                None,
+            )
+            .switch(
+                constants::flags::COOKIE,
+                "Send DNS cookies (RFC 7873) with queries, keeping the server cookie for the next ones, and report in a \"cookie\" column whether the response returned the client cookie",
+                None,
+            )
+            .switch(
+                constants::flags::NSID,
+                "Ask for the name server identifier (RFC 5001), to tell which instance of an anycast server answered. It is in edns.opts.NSID",
+                None,
+            )
+            .switch(
+                constants::flags::PAD,
+                "Pad queries to a multiple of 128 bytes (RFC 8467), so that their size does not give away the name asked for. Needs an encrypted protocol",
+                None,
+            )
+            .named(
+                constants::flags::PAD_BLOCK_SIZE,
+                SyntaxShape::Int,
+                "Pad queries to a multiple of this many bytes instead of 128. Implies --pad",
+                None,
+            )
+            .named(
+                constants::flags::BUFSIZE,
+                SyntaxShape::Int,
+                "UDP payload size to advertise with EDNS, instead of 1232 bytes",
+                None,
+            )
+            .switch(
+                constants::flags::NO_EDNS,
+                "Send queries without EDNS, as before it. Implies --dnssec none",
+                None,
+            )
+            .switch(
+                constants::flags::NO_RECURSE,
+                "Clear the RD bit of queries, for authoritative servers to answer from their own data or refer to the nameservers of a zone closer to the name",
+                None,
+            )
+            .switch(
+                constants::flags::CHECKING_DISABLED,
+                "Set the CD bit of queries, for resolvers to return records even if they fail DNSSEC validation. Implies --dnssec none unless given",
+                None,
+            )
+            .switch(
+                constants::flags::AUTHENTIC_DATA,
+                "Set the AD bit of queries, for resolvers to report in the AD bit of responses whether they validated the answer",
+                None,
+            )
+            .named(
+                constants::flags::OPCODE,
+                SyntaxShape::Any,
+                "Opcode of the queries, for testing how servers handle unusual messages: QUERY, IQUERY, STATUS, NOTIFY, UPDATE, DSO, or a number up to 15. Those other than QUERY, STATUS, NOTIFY, and UPDATE are only sent over UDP. Default: QUERY",
+                None,
+            )
+            .named(
+                constants::flags::TRANSFORM,
+                SyntaxShape::Closure(Some(vec![SyntaxShape::Record(vec![])])),
+                "Closure to reshape each answer record with before it is returned. Records it returns nothing for are dropped",
+                None,
             )
             .named(
-                constants::flags::PROTOCOL,
+                constants::flags::DNS_NAME,
                 SyntaxShape::String,
-                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
-                Some('p'),
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
             )
-            .named(constants::flags::TYPE, SyntaxShape::Any, "Query type", Some('t'))
-            .named(constants::flags::CLASS, SyntaxShape::Any, "Query class", None)
-            .switch(
-                constants::flags::CODE,
-                "Return code fields with both string and numeric representations",
-                Some('c'),
+            .named(
+                constants::flags::MIN_TLS_VERSION,
+                SyntaxShape::String,
+                "Oldest TLS version to accept from the nameserver: 1.2, 1.3. The connection fails if the nameserver does not support it (for TLS, HTTPS, and QUIC only). Default: 1.2",
+                None,
             )
             .named(
-                constants::flags::DNSSEC,
+                constants::flags::TSIG_KEY,
                 SyntaxShape::String,
-                "Perform DNSSEC validation on records. Choices are: \"none\", \"strict\" (error if record has no RRSIG or does not validate), \"opportunistic\" (validate if RRSIGs present, otherwise no validation; default)",
-                Some('d'),
+                "TSIG key to sign queries with, as name:algorithm:secret with the secret in base64. The algorithm is one of: hmac-sha256, hmac-sha384, hmac-sha512 (for UDP, TCP, and TLS only)",
+                None,
             )
             .named(
-                constants::flags::DNS_NAME,
+                constants::flags::TSIG_KEY_FILE,
+                SyntaxShape::Filepath,
+                "File with the TSIG key to sign queries with, in the format written by tsig-keygen",
+                None,
+            )
+            .named(
+                constants::flags::OUTPUT_FORMAT,
                 SyntaxShape::String,
-                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
-                Some('n'),
+                "How to return the results: table, msgpack for the same structure serialized as MessagePack bytes, or dig for the messages as text the way dig prints them. Can not be used with --watch-diff. Default: table",
+                None,
             )
             .named(
                 constants::flags::TASKS,
@@ -424,6 +1595,89 @@ impl PluginCommand for DnsQuery {
                 format!("Number of concurrent tasks to execute queries. Please be mindful not to overwhelm your nameserver! Default: {}", constants::config::default::TASKS),
                 Some('j'),
             )
+            .named(
+                constants::flags::QPS,
+                SyntaxShape::Number,
+                "Maximum number of queries per second to send, independent of the number of concurrent tasks. Default: unlimited",
+                None,
+            )
+            .named(
+                constants::flags::FAIL_FAST_AFTER,
+                SyntaxShape::Int,
+                "Stop sending queries once this many have failed, and return the results so far. Useful with --keep-errors or piped input",
+                None,
+            )
+            .named(
+                constants::flags::WATCH_DIFF,
+                SyntaxShape::Duration,
+                "Re-run the queries at this interval and output the records added to or removed from each answer since the previous poll, until interrupted. TTLs are ignored when comparing",
+                None,
+            )
+            .named(
+                constants::flags::JITTER,
+                SyntaxShape::Duration,
+                format!("Delay each poll of --watch-diff by a random amount up to this long, to spread out the load of many watches. Default: 1/{} of the interval", constants::config::default::JITTER_INTERVAL_DIVISOR),
+                None,
+            )
+            .named(
+                constants::flags::RESPONSE_WINDOW,
+                SyntaxShape::Duration,
+                "Collect every response that arrives within this window instead of only the first, e.g. when querying a broadcast or multicast address. Each response is returned as a message of its own, and they are not DNSSEC validated. UDP only. Names under local. are always asked over multicast DNS, collecting responses for this long (default: 1sec)",
+                None,
+            )
+            .named(
+                constants::flags::SINCE_SERIAL,
+                SyntaxShape::Int,
+                "Return the changes to the zone given as name since this serial, using an incremental zone transfer (IXFR) over TCP. If the server sends the whole zone instead, all of its records are returned as added",
+                None,
+            )
+            .named(
+                constants::flags::MIN_ANSWERS,
+                SyntaxShape::Int,
+                "Fail a query whose answer has fewer records of the queried type than this, e.g. to check that a pool of addresses is complete. With --keep-errors, the failure is returned as an error in the output",
+                None,
+            )
+            .switch(
+                constants::flags::EXPLAIN_DNSSEC,
+                "Walk the DNSSEC chain of trust from the root down to the answer, and return whether each link holds: the trust anchor, the signatures over each zone's keys and DS records, the DS digests, and the signature over the answer",
+                None,
+            )
+            .named(
+                constants::flags::TRUST_ANCHOR,
+                SyntaxShape::Filepath,
+                "File of DS or DNSKEY records in presentation format, to root --dnssec validate and --explain-dnssec at instead of the root keys, e.g. those of an internal signed zone",
+                None,
+            )
+            .switch(
+                constants::flags::PIPELINE,
+                "Send the queries of each input in order, over a connection of their own, and only once the previous ones got their responses. Returns a record per input with its messages",
+                None,
+            )
+            .switch(
+                constants::flags::NORMALIZE_RDATA_CASE,
+                "Lowercase the names in RDATA, e.g. CNAME targets and MX exchanges, so that outputs of servers that differ only in case compare equal",
+                None,
+            )
+            .switch(
+                constants::flags::RECORD_SPANS,
+                "Add the byte offset and length of each record and each of its fields in the message as received, for debugging wire format parsers",
+                None,
+            )
+            .switch(
+                constants::flags::RESOLVE_NS_ADDRESSES,
+                "Attach the addresses of the nameservers to the NS records of the answer, taken from the additional section when the server included them, and looked up otherwise",
+                None,
+            )
+            .switch(
+                constants::flags::DRY_RUN,
+                "Return the queries that would be sent, with the nameservers they would be sent to, without sending anything",
+                None,
+            )
+            .switch(
+                constants::flags::MEASURE_PATH_MTU,
+                "Estimate the path MTU towards the nameserver by sending UDP queries of increasing size with the Don't Fragment bit set, and report the largest that got a response. Probes with the first query given, or the root NS query. Linux only",
+                None,
+            )
             .named(
                 constants::flags::TIMEOUT,
                 SyntaxShape::Duration,
@@ -432,7 +1686,7 @@ impl PluginCommand for DnsQuery {
             )
     }
 
-    fn examples(&self) -> Vec<nu_protocol::Example> {
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
         vec![
             Example {
                 example: "dns query google.com",
@@ -454,6 +1708,226 @@ impl PluginCommand for DnsQuery {
                 description: "specify query types by numeric ID, and get numeric IDs in output",
                 result: None,
             },
+            Example {
+                example: "dns query --type [web, mail] google.com",
+                description: "query groups of related record types",
+                result: None,
+            },
+            Example {
+                example: "dns query --zip --type [A, MX] [www.google.com, google.com]",
+                description: "query each name for the type at the same position",
+                result: None,
+            },
+            Example {
+                example: "dns query --servers [{server: 8.8.8.8, tag: google}, {server: 1.1.1.1, tag: cloudflare}] google.com",
+                description: "compare the answers of several nameservers",
+                result: None,
+            },
+            Example {
+                example: "dns query --watch-diff 10sec --type [A, AAAA] www.example.com",
+                description: "watch the addresses of a name change during a deploy",
+                result: None,
+            },
+            Example {
+                example: "(dns query --answer-checksum example.com).0.answer_checksum == $last_checksum",
+                description: "check whether an answer changed since a previous run",
+                result: None,
+            },
+            Example {
+                example: "dns query --watch-diff 1min --jitter 15sec example.com",
+                description: "poll once a minute, at a random point within 15 seconds of each tick",
+                result: None,
+            },
+            Example {
+                example: "dns query --type MX --transform {|rec| $rec.rdata } google.com",
+                description: "reshape each answer record, here to only keep the data",
+                result: None,
+            },
+            Example {
+                example: "dns query --measure-path-mtu --server 9.9.9.9",
+                description: "find out how large DNS messages to a nameserver can get before they are dropped",
+                result: None,
+            },
+            Example {
+                example: "dns query --dry-run --type [web, mail] [example.com, example.org]",
+                description: "check which queries a batch would send before sending it",
+                result: None,
+            },
+            Example {
+                example: "[www.example.com WWW.example.com www.example.com] | dns query --coalesce-identical-queries",
+                description: "send a single query for names repeated in the input, and still get a result for each",
+                result: None,
+            },
+            Example {
+                example: "dns query --min-answers 3 --keep-errors [www.example.com api.example.com]",
+                description: "check that names resolve to at least 3 addresses each",
+                result: None,
+            },
+            Example {
+                example: "dns query --server https://cloudflare-dns.com/dns-query example.com",
+                description: "query over DNS over HTTPS, with the URL of the resolver",
+                result: None,
+            },
+            Example {
+                example: "dns query --protocol dns-json --server https://dns.google/resolve example.com",
+                description: "query the JSON API of a resolver, at the path it serves it on",
+                result: None,
+            },
+            Example {
+                example: "dns query --server tls://dns.quad9.net example.com",
+                description: "query over DNS over TLS on port 853, checking the certificate against the hostname",
+                result: None,
+            },
+            Example {
+                example: "dns query --server quic://dns.adguard-dns.com [example.com example.org]",
+                description: "query over DNS over QUIC, with all queries sharing one connection",
+                result: None,
+            },
+            Example {
+                example: "dns query --server sdns://AgcAAAAAAAAABzEuMC4wLjEAEmRucy5jbG91ZGZsYXJlLmNvbQovZG5zLXF1ZXJ5 example.com",
+                description: "query the resolver described by a DNS stamp, here Cloudflare over DNS over HTTPS",
+                result: None,
+            },
+            Example {
+                example: "dns query --protocol tls --server 1.1.1.1:853 --dns-name cloudflare-dns.com --min-tls-version 1.3 example.com",
+                description: "only accept a TLS 1.3 connection to the nameserver",
+                result: None,
+            },
+            Example {
+                example: "dns query --output-format msgpack example.com | save answers.msgpack",
+                description: "save the results as MessagePack for another program to read",
+                result: None,
+            },
+            Example {
+                example: "dns query --short --type MX example.com",
+                description: "list just the mail exchangers of a name, as strings",
+                result: None,
+            },
+            Example {
+                example: "dns query --flatten --type [A MX] example.com | to csv",
+                description: "export every record of the responses as CSV, one row each",
+                result: None,
+            },
+            Example {
+                example: "dns query --answers-only --type [A AAAA] example.com | get rdata",
+                description: "get the addresses of a name, without the rest of the responses",
+                result: None,
+            },
+            Example {
+                example: "dns query example.com | get 0.answer | where ttl < 5min",
+                description: "find the answers that expire within five minutes",
+                result: None,
+            },
+            Example {
+                example: "dns query --ttl-as-int example.com | get 0.answer.ttl",
+                description: "get the TTLs of the answers as seconds",
+                result: None,
+            },
+            Example {
+                example: "dns query --code does-not-exist.example.com | get 0.header.response_code",
+                description: "tell a name that does not exist from one without records, by the name and number of the response code",
+                result: None,
+            },
+            Example {
+                example: "dns query --client-subnet 203.0.113.0/24 --server 8.8.8.8 example.com | get 0.edns.opts.subnet",
+                description: "see the answer of a resolver for clients in a subnet, and the scope it applies to",
+                result: None,
+            },
+            Example {
+                example: "dns query --cookie --type [A AAAA] example.com | select question.type cookie",
+                description: "send DNS cookies, and check that the server returned them",
+                result: None,
+            },
+            Example {
+                example: "dns query --nsid --server 1.1.1.1 example.com | get 0.edns.opts.NSID.data",
+                description: "find out which instance of an anycast server answered",
+                result: None,
+            },
+            Example {
+                example: "dns query --pad --protocol tls --server 1.1.1.1 --dns-name cloudflare-dns.com example.com",
+                description: "pad the query over TLS, so its size does not give away the name asked for",
+                result: None,
+            },
+            Example {
+                example: "dns query --bufsize 4096 --type TXT example.com | get 0.transport",
+                description: "advertise a larger UDP payload size, and see whether the response still had to be retried over TCP",
+                result: None,
+            },
+            Example {
+                example: "dns query --no-edns example.com",
+                description: "query like a client from before EDNS, to diagnose middleboxes that drop EDNS",
+                result: None,
+            },
+            Example {
+                example: "dns query --no-recurse --server a.gtld-servers.net example.com | get 0.authority",
+                description: "see the referral of a TLD server to the nameservers of a domain, rather than its resolved records",
+                result: None,
+            },
+            Example {
+                example: "dns query --checking-disabled --server 1.1.1.1 dnssec-failed.org | get 0.answer",
+                description: "get the records of a name whose signatures are broken, to look at them",
+                result: None,
+            },
+            Example {
+                example: "dns query --authentic-data --server 1.1.1.1 example.com | get 0.header.authentic_data",
+                description: "ask a resolver whether it validated the answer",
+                result: None,
+            },
+            Example {
+                example: "dns query --all-types --flatten example.com",
+                description: "list the records of the common types of a name, from a server that answers ANY queries minimally",
+                result: None,
+            },
+            Example {
+                example: "dns query --dnssec validate example.com | get 0.dnssec.zones | select zone status",
+                description: "validate an answer from the root down, and see which zones are secure, insecure, or bogus",
+                result: None,
+            },
+            Example {
+                example: "dns query --dnssec validate --trust-anchor corp.example.key --server 10.0.0.53 www.corp.example | get 0.dnssec.status",
+                description: "validate an internal signed zone from its own key, rather than from the root",
+                result: None,
+            },
+            Example {
+                example: "dns query --dnssec validate nonexistent.example.com | get 0.dnssec.denial.steps",
+                description: "see how the NSEC or NSEC3 records of a negative answer prove that there is no such name",
+                result: None,
+            },
+            Example {
+                example: "dns query --opcode 3 --server 9.9.9.9 example.com | get 0.header.response_code",
+                description: "check that a server answers an unassigned opcode with NOTIMP",
+                result: None,
+            },
+            Example {
+                example: "dns query --output-format dig example.com",
+                description: "print the response the way dig does, to paste into a ticket or compare against dig",
+                result: None,
+            },
+            Example {
+                example: "dns query --explain-dnssec --type A example.com | get 0.steps",
+                description: "check each link of the DNSSEC chain of trust of an answer",
+                result: None,
+            },
+            Example {
+                example: "[{name: example.com, type: SOA} {name: www.example.com, type: A}] | dns query --pipeline --protocol tcp",
+                description: "send queries strictly in order over a single connection",
+                result: None,
+            },
+            Example {
+                example: "dns query --record-spans example.com | get answer.0.spans.rdata",
+                description: "where the RDATA of the first answer record is in the message",
+                result: None,
+            },
+            Example {
+                example: "dns query --type NS --resolve-ns-addresses example.com",
+                description: "find the nameservers of a domain along with their addresses",
+                result: None,
+            },
+            Example {
+                example: "dns query --server ns1.example.com --since-serial 2024010101 example.com",
+                description: "fetch the changes to a zone since a known serial",
+                result: None,
+            },
             Example {
                 example: "'google.com' | dns query",
                 description: "pipe name to command",
@@ -474,6 +1948,21 @@ impl PluginCommand for DnsQuery {
                 description: "pipe table of queries to command (ignores --type flag)",
                 result: None,
             },
+            Example {
+                example: "dns query --protocol llmnr fileserver",
+                description: "resolve a single-label name with LLMNR, as Windows hosts on the local link answer it",
+                result: None,
+            },
+            Example {
+                example: "dns query --type SOA example.com --server ns1.example.com --tsig-key 'mykey:hmac-sha256:c2VjcmV0'",
+                description: "sign a query with a TSIG key, and check the MAC of the response",
+                result: None,
+            },
+            Example {
+                example: "dns query printer.local",
+                description: "resolve a name on the local network over multicast DNS, with a message for each host that answers",
+                result: None,
+            },
         ]
     }
 
@@ -481,3 +1970,175 @@ impl PluginCommand for DnsQuery {
         vec!["dns", "network", "dig"]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use hickory_proto::{
+        op::{Message, MessageType},
+        rr::{rdata::A, RData},
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    async fn read_message(stream: &mut TcpStream) -> Message {
+        let len = stream.read_u16().await.unwrap();
+        let mut buf = vec![0; len as usize];
+        stream.read_exact(&mut buf).await.unwrap();
+        Message::from_vec(&buf).unwrap()
+    }
+
+    /// A nameserver that answers the first query of its one connection, and
+    /// then closes it once the next query arrives, without answering it.
+    async fn answer_once_then_close() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let query = read_message(&mut stream).await;
+            let mut response = query.clone();
+            response.set_message_type(MessageType::Response);
+            response.add_answer(hickory_proto::rr::Record::from_rdata(
+                query.queries()[0].name().clone(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ));
+            let wire = response.to_vec().unwrap();
+            stream.write_u16(wire.len() as u16).await.unwrap();
+            stream.write_all(&wire).await.unwrap();
+
+            read_message(&mut stream).await;
+        });
+
+        addr
+    }
+
+    #[test]
+    fn only_unanswered_queries_fail_when_the_connection_closes() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        runtime.block_on(async {
+            let addr = answer_once_then_close().await;
+            let config = Config::from_values(|flag| match flag {
+                constants::flags::SERVER => Some(Value::test_string(addr.to_string())),
+                constants::flags::PROTOCOL => Some(Value::test_string("tcp")),
+                constants::flags::KEEP_ERRORS => Some(Value::test_bool(true)),
+                constants::flags::TYPE => Some(Value::test_string("A")),
+                // a single client, over a single connection
+                constants::flags::DNSSEC => Some(Value::test_string("none")),
+                _ => None,
+            })
+            .unwrap();
+
+            let (client, _bg) = DnsClient::new(&config).await.unwrap();
+            let input = Value::test_list(vec![
+                Value::test_string("answered.example."),
+                Value::test_string("in-flight.example."),
+            ]);
+
+            let mut answered = Vec::new();
+            let mut failed = Vec::new();
+            for result in DnsQuery::query(Arc::new(config), input, client).await {
+                match result {
+                    Ok(message) => answered.push(
+                        message
+                            .get_data_by_key("question")
+                            .and_then(|question| question.get_data_by_key(constants::columns::NAME))
+                            .unwrap(),
+                    ),
+                    Err(err) => failed.push(err),
+                }
+            }
+
+            assert_eq!(answered, [Value::test_string("answered.example.")]);
+
+            assert_eq!(failed.len(), 1);
+            assert_eq!(failed[0].msg, "connection closed");
+            assert!(failed[0].labels[0].text.contains("in-flight.example."));
+        });
+    }
+
+    /// A nameserver that answers every query over UDP.
+    async fn answer_all() -> SocketAddr {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = vec![0; u16::MAX as usize];
+
+            loop {
+                let (len, client) = socket.recv_from(&mut buf).await.unwrap();
+                let query = Message::from_vec(&buf[..len]).unwrap();
+                let mut response = query.clone();
+                response.set_message_type(MessageType::Response);
+                response.add_answer(hickory_proto::rr::Record::from_rdata(
+                    query.queries()[0].name().clone(),
+                    300,
+                    RData::A(A::new(192, 0, 2, 1)),
+                ));
+                socket
+                    .send_to(&response.to_vec().unwrap(), client)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn coalesced_queries_are_answered_in_the_order_of_the_input() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        runtime.block_on(async {
+            let addr = answer_all().await;
+            let config = Config::from_values(|flag| match flag {
+                constants::flags::SERVER => Some(Value::test_string(addr.to_string())),
+                constants::flags::PROTOCOL => Some(Value::test_string("udp")),
+                constants::flags::TYPE => Some(Value::test_string("A")),
+                constants::flags::DNSSEC => Some(Value::test_string("none")),
+                constants::flags::COALESCE_IDENTICAL_QUERIES => Some(Value::test_bool(true)),
+                _ => None,
+            })
+            .unwrap();
+
+            let (client, _bg) = DnsClient::new(&config).await.unwrap();
+            let names = [
+                "b.example.",
+                "a.example.",
+                "B.example.",
+                "c.example.",
+                "a.example.",
+            ];
+            let input =
+                Value::test_list(names.iter().map(|name| Value::test_string(*name)).collect());
+
+            let answered: Vec<String> = DnsQuery::query(Arc::new(config), input, client)
+                .await
+                .into_iter()
+                .map(|result| {
+                    result
+                        .unwrap()
+                        .get_data_by_key("question")
+                        .and_then(|question| question.get_data_by_key(constants::columns::NAME))
+                        .unwrap()
+                        .into_string()
+                        .unwrap()
+                })
+                .collect();
+
+            assert_eq!(
+                answered,
+                names.map(str::to_lowercase),
+                "the answers of repeated queries are where the repeats are"
+            );
+        });
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    net::IpAddr,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use futures_util::StreamExt;
+use hickory_proto::rr::{RData, RecordType};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Record, Signals, Signature, Span, Spanned, SyntaxShape,
+    Value,
+};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        commands::query::{response_stream, watch_sigterm, AnswerTransform},
+        config::Config,
+        constants, lookup,
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsReverse;
+
+impl DnsReverse {
+    async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let arg_input = call.nth(0).unwrap_or(Value::nothing(call.head));
+
+        let input = match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => arg_input,
+            PipelineData::Value(val, _) => {
+                if !arg_input.is_nothing() {
+                    return Err(LabeledError::new("ambiguous input").with_label(
+                        "Input should either be positional args or piped, but not both",
+                        val.span(),
+                    ));
+                }
+
+                val
+            }
+            data => {
+                return Err(LabeledError::new("invalid input").with_label(
+                    "Only values can be passed as input",
+                    data.span().unwrap_or(Span::unknown()),
+                ))
+            }
+        };
+
+        let addresses = match input {
+            Value::List { ref vals, .. } => {
+                let mut addresses = Vec::new();
+                for val in vals {
+                    addresses.extend(parse_addresses(val)?);
+                }
+                addresses
+            }
+            Value::Nothing { .. } => {
+                return Err(LabeledError::new("missing input")
+                    .with_label("Need an address to look up the name of", call.head))
+            }
+            ref val => parse_addresses(val)?,
+        };
+
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        let client = tokio::time::timeout(config.timeout.item, plugin.dns_client(&config))
+            .await
+            .map_err(|_| {
+                LabeledError::new("timed out").with_label(
+                    format!("connecting to {} timed out", config.server.item),
+                    config.server.span,
+                )
+            })??;
+
+        let config = Arc::new(config);
+        let ctrlc = Signals::new(Arc::new(AtomicBool::new(false)));
+        let (resp_tx, resp_rx) = mpsc::channel(config.tasks.item);
+
+        plugin.spawn(watch_sigterm(ctrlc.clone(), plugin.cancel.clone()));
+        plugin.spawn(sweep(
+            client,
+            config,
+            addresses,
+            resp_tx,
+            plugin.cancel.clone(),
+            call.head,
+        ));
+
+        Ok(response_stream(
+            resp_rx,
+            call.head,
+            ctrlc,
+            AnswerTransform::default(),
+        ))
+    }
+}
+
+/// An address, or every address of a network in CIDR notation, e.g.
+/// `192.0.2.0/24`.
+fn parse_addresses(val: &Value) -> Result<Vec<Spanned<IpAddr>>, LabeledError> {
+    let span = val.span();
+    let input = val.as_str()?;
+    let invalid = |msg: String| LabeledError::new("invalid address").with_label(msg, span);
+
+    let (addr, prefix) = match input.split_once('/') {
+        Some((addr, prefix)) => (
+            addr,
+            Some(
+                prefix
+                    .parse::<u32>()
+                    .map_err(|err| invalid(format!("Error parsing prefix length: {}", err)))?,
+            ),
+        ),
+        None => (input, None),
+    };
+
+    // also accept addresses with their brackets, as they are written in URLs
+    let addr: IpAddr = addr
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|err| invalid(format!("Error parsing IP address: {}", err)))?;
+
+    let Some(prefix) = prefix else {
+        return Ok(vec![Spanned { item: addr, span }]);
+    };
+
+    let bits = match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+
+    if prefix > bits {
+        return Err(invalid(format!(
+            "prefix length should be at most {} for this address",
+            bits
+        )));
+    }
+
+    let count = 1u128.checked_shl(bits - prefix).unwrap_or(u128::MAX);
+    if count > constants::config::default::REVERSE_SWEEP_MAX_ADDRESSES {
+        return Err(invalid(format!(
+            "a /{} has more addresses than the {} that can be swept at once",
+            prefix,
+            constants::config::default::REVERSE_SWEEP_MAX_ADDRESSES
+        )));
+    }
+
+    // the host bits of the given address are ignored, so that 192.0.2.7/24
+    // sweeps 192.0.2.0/24
+    Ok(match addr {
+        IpAddr::V4(addr) => {
+            let network = u32::from(addr) & u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            (0..count as u32)
+                .map(|host| Spanned {
+                    item: IpAddr::from((network + host).to_be_bytes()),
+                    span,
+                })
+                .collect()
+        }
+        IpAddr::V6(addr) => {
+            let network = u128::from(addr) & u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            (0..count)
+                .map(|host| Spanned {
+                    item: IpAddr::from((network + host).to_be_bytes()),
+                    span,
+                })
+                .collect()
+        }
+    })
+}
+
+/// Looks up the addresses with up to `--tasks` lookups at a time, and sends
+/// each row as soon as it is done, so a sweep returns rows out of order.
+async fn sweep(
+    client: DnsClient,
+    config: Arc<Config>,
+    addresses: Vec<Spanned<IpAddr>>,
+    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
+    cancel: CancellationToken,
+    span: Span,
+) -> Result<(), LabeledError> {
+    let mut rows = futures_util::stream::iter(addresses)
+        .map(|addr| {
+            let client = client.clone();
+            let config = config.clone();
+
+            async move { reverse(&client, &config, addr, span).await }
+        })
+        .buffer_unordered(config.tasks.item)
+        .take_until(Box::pin(cancel.cancelled()));
+
+    while let Some(row) = rows.next().await {
+        // the receiver is dropped along with the command's output
+        if resp_tx.send(row).await.is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Looks up the PTR records of the reverse name of `addr` (RFC 1035 section
+/// 3.5, RFC 3596 section 2.5), and returns them as a row.
+async fn reverse(
+    client: &DnsClient,
+    config: &Config,
+    addr: Spanned<IpAddr>,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let name = Name::from(addr.item);
+    let response = lookup::lookup(client, config, name.clone(), RecordType::PTR, span).await?;
+
+    let hostnames = response
+        .answers()
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::PTR(ptr)) => Some(Value::string(
+                // without the trailing dot, so that it can be passed straight
+                // to dns query
+                ptr.0.to_utf8().trim_end_matches('.'),
+                Span::unknown(),
+            )),
+            _ => None,
+        })
+        .collect();
+
+    let values = vec![
+        Value::string(addr.item.to_string(), addr.span),
+        Value::string(name.to_utf8(), Span::unknown()),
+        Value::list(hostnames, Span::unknown()),
+    ];
+
+    Ok(Value::record(
+        Record::from_iter(std::iter::zip(
+            constants::columns::REVERSE_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            values,
+        )),
+        Span::unknown(),
+    ))
+}
+
+impl PluginCommand for DnsReverse {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::REVERSE
+    }
+
+    fn description(&self) -> &str {
+        "Look up the hostnames of IP addresses from their PTR records"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::ADDRESS,
+                SyntaxShape::Any,
+                "IPv4 or IPv6 address, or a network in CIDR notation to sweep, or a list of them",
+            )
+            .named(
+                constants::flags::TASKS,
+                SyntaxShape::Int,
+                format!("Number of concurrent lookups. Please be mindful not to overwhelm your nameserver! Default: {}", constants::config::default::TASKS),
+                Some('j'),
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to query (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long a request can take before timing out. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns reverse 8.8.8.8",
+                description: "look up the hostname of an address",
+                result: None,
+            },
+            Example {
+                example: "[2001:4860:4860::8888 1.1.1.1] | dns reverse",
+                description: "look up the hostnames of a list of addresses",
+                result: None,
+            },
+            Example {
+                example: "dns query --type A example.com | get answer.rdata | dns reverse",
+                description: "check the reverse names of the addresses a name resolves to",
+                result: None,
+            },
+            Example {
+                example: "dns reverse 192.0.2.0/24 --tasks 32 | where hostnames != []",
+                description:
+                    "sweep a network for the addresses that have a hostname, 32 lookups at a time",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "ptr", "reverse", "rdns", "in-addr.arpa", "ip6.arpa"]
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use hickory_proto::op::{Message, ResponseCode};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signals, Signature, Span, Spanned, SyntaxShape, Value,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::mpsc,
+};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+use crate::{
+    dns::{
+        commands::query::{response_stream, AnswerTransform},
+        config::Config,
+        constants, serde,
+        zone::{self, Zone},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsServe;
+
+impl DnsServe {
+    async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let zone_file: Option<Spanned<String>> = call.opt(0)?;
+        let origin = call
+            .get_flag::<Spanned<String>>(constants::flags::ZONE)?
+            .map(|zone| zone::parse_origin(&zone))
+            .transpose()?;
+        let listen = match call.get_flag::<Spanned<String>>(constants::flags::LISTEN)? {
+            Some(listen) => listen.item.parse::<SocketAddr>().map_err(|err| {
+                LabeledError::new("invalid address").with_label(
+                    format!("should be an address and port, e.g. 127.0.0.1:1053: {err}"),
+                    listen.span,
+                )
+            })?,
+            None => constants::config::default::SERVE_ADDRESS,
+        };
+
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        let (file_origin, mut records) = match &zone_file {
+            Some(path) => zone::parse_file(path, origin.clone())?,
+            None => (None, Vec::new()),
+        };
+
+        let record_origin = origin.clone().or(file_origin.clone());
+
+        for value in input {
+            if matches!(value, Value::Nothing { .. }) {
+                continue;
+            }
+
+            records.push(zone::record_from_value(
+                &value,
+                record_origin.as_ref(),
+                config.class.item,
+            )?);
+        }
+
+        if records.is_empty() {
+            return Err(LabeledError::new("no records").with_label(
+                "pass a zone file, or pipe in a table of records to serve",
+                call.head,
+            ));
+        }
+
+        let zone = Arc::new(Zone::from_records(
+            records,
+            origin.or(file_origin),
+            call.head,
+        )?);
+
+        let udp = UdpSocket::bind(listen).await.map_err(|err| {
+            LabeledError::new("could not listen")
+                .with_label(format!("binding UDP {} failed: {}", listen, err), call.head)
+        })?;
+
+        // with port 0, TCP listens on the same port the system picked for UDP
+        let listen = udp.local_addr().unwrap_or(listen);
+        let tcp = TcpListener::bind(listen).await.map_err(|err| {
+            LabeledError::new("could not listen")
+                .with_label(format!("binding TCP {} failed: {}", listen, err), call.head)
+        })?;
+
+        tracing::info!(
+            serve.zone = %zone.origin,
+            serve.records = zone.len(),
+            serve.addr = %listen,
+        );
+
+        let config = Arc::new(config);
+        let (resp_tx, resp_rx) = mpsc::channel(config.tasks.item);
+
+        // the server runs until interrupted, so it has to listen for the
+        // interrupt itself, rather than only stop when the stream is dropped.
+        // A token of its own keeps the interrupt from canceling other commands
+        let signals = engine.signals().clone();
+        let cancel = plugin.cancel.child_token();
+
+        plugin.spawn(watch_interrupt(
+            signals.clone(),
+            cancel.clone(),
+            resp_tx.clone(),
+        ));
+        plugin.spawn(serve_udp(
+            udp,
+            zone.clone(),
+            config.clone(),
+            resp_tx.clone(),
+            cancel.clone(),
+        ));
+        plugin.spawn(serve_tcp(
+            tcp,
+            zone,
+            config,
+            resp_tx,
+            cancel,
+            plugin.tasks.clone(),
+        ));
+
+        Ok(response_stream(
+            resp_rx,
+            call.head,
+            signals,
+            AnswerTransform::default(),
+        ))
+    }
+}
+
+/// Stops the server when the command is interrupted, or when the output is no
+/// longer read, e.g. after `first 3`.
+async fn watch_interrupt(
+    signals: Signals,
+    cancel: CancellationToken,
+    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
+) -> Result<(), LabeledError> {
+    while cancel
+        .run_until_cancelled(tokio::time::sleep(Duration::from_millis(500)))
+        .await
+        .is_some()
+    {
+        if signals.interrupted() || resp_tx.is_closed() {
+            cancel.cancel();
+        }
+    }
+
+    Ok(())
+}
+
+async fn serve_udp(
+    socket: UdpSocket,
+    zone: Arc<Zone>,
+    config: Arc<Config>,
+    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
+    cancel: CancellationToken,
+) -> Result<(), LabeledError> {
+    let mut buf = vec![0; u16::MAX as usize];
+
+    while let Some(received) = cancel.run_until_cancelled(socket.recv_from(&mut buf)).await {
+        let (len, client) = match received {
+            Ok(received) => received,
+            Err(err) => {
+                tracing::debug!(serve.error = %err, "receiving UDP request failed");
+                continue;
+            }
+        };
+
+        let start = Instant::now();
+        let Some((request, mut response)) = answer(&zone, &buf[..len]) else {
+            continue;
+        };
+
+        let Ok(mut wire) = response.to_vec() else {
+            continue;
+        };
+
+        // without EDNS, UDP responses are limited to 512 bytes, and a larger
+        // one is truncated for the client to retry over TCP
+        if wire.len() > usize::from(request.max_payload().max(512)) {
+            response.take_answers();
+            response.take_name_servers();
+            response.take_additionals();
+            response.set_truncated(true);
+            wire = match response.to_vec() {
+                Ok(wire) => wire,
+                Err(_) => continue,
+            };
+        }
+
+        if let Err(err) = socket.send_to(&wire, client).await {
+            tracing::debug!(serve.error = %err, "sending UDP response failed");
+            continue;
+        }
+
+        let row = served_row(client, "udp", &response, start.elapsed(), &config);
+
+        if resp_tx.send(Ok(row)).await.is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn serve_tcp(
+    listener: TcpListener,
+    zone: Arc<Zone>,
+    config: Arc<Config>,
+    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
+    cancel: CancellationToken,
+    tasks: TaskTracker,
+) -> Result<(), LabeledError> {
+    while let Some(accepted) = cancel.run_until_cancelled(listener.accept()).await {
+        let (stream, client) = match accepted {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                tracing::debug!(serve.error = %err, "accepting TCP connection failed");
+                continue;
+            }
+        };
+
+        let (zone, config, resp_tx, cancel) = (
+            zone.clone(),
+            config.clone(),
+            resp_tx.clone(),
+            cancel.clone(),
+        );
+
+        // tracked like the plugin's other tasks, so that connections still
+        // being served are waited for on shutdown
+        tasks.spawn(async move {
+            cancel
+                .run_until_cancelled(serve_connection(stream, client, zone, config, resp_tx))
+                .await
+        });
+    }
+
+    Ok(())
+}
+
+/// Answers the requests of a TCP connection, each prefixed with its length
+/// (RFC 1035 section 4.2.2), until the client closes it.
+async fn serve_connection(
+    mut stream: TcpStream,
+    client: SocketAddr,
+    zone: Arc<Zone>,
+    config: Arc<Config>,
+    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
+) -> std::io::Result<()> {
+    loop {
+        let len = stream.read_u16().await?;
+        let mut buf = vec![0; usize::from(len)];
+        stream.read_exact(&mut buf).await?;
+
+        let start = Instant::now();
+        let Some((_, mut response)) = answer(&zone, &buf) else {
+            return Ok(());
+        };
+
+        let Ok(mut wire) = response.to_vec() else {
+            return Ok(());
+        };
+
+        // a response larger than its two byte length prefix can tell would be
+        // cut short, so the client is told the server failed instead
+        if wire.len() > usize::from(u16::MAX) {
+            response.take_answers();
+            response.take_name_servers();
+            response.take_additionals();
+            response.set_response_code(ResponseCode::ServFail);
+            wire = match response.to_vec() {
+                Ok(wire) => wire,
+                Err(_) => return Ok(()),
+            };
+        }
+
+        stream.write_u16(wire.len() as u16).await?;
+        stream.write_all(&wire).await?;
+
+        let row = served_row(client, "tcp", &response, start.elapsed(), &config);
+
+        if resp_tx.send(Ok(row)).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// The request in `wire` and the response of the zone to it. Messages that can
+/// not be parsed are dropped, since there is no ID to answer them with.
+fn answer(zone: &Zone, wire: &[u8]) -> Option<(Message, Message)> {
+    let request = match Message::from_vec(wire) {
+        Ok(request) => request,
+        Err(err) => {
+            tracing::debug!(serve.error = %err.kind(), "dropping request that does not parse");
+            return None;
+        }
+    };
+
+    let response = zone.respond(&request);
+    Some((request, response))
+}
+
+fn served_row(
+    client: SocketAddr,
+    protocol: &str,
+    response: &Message,
+    elapsed: Duration,
+    config: &Config,
+) -> Value {
+    let question = response
+        .queries()
+        .first()
+        .map(|query| serde::Query(query.clone()).into_value(config))
+        .unwrap_or(Value::nothing(Span::unknown()));
+
+    Value::record(
+        nu_protocol::Record::from_iter(std::iter::zip(
+            Vec::from_iter(constants::columns::SERVE_COLS.iter().map(|s| (*s).into())),
+            vec![
+                Value::string(client.to_string(), Span::unknown()),
+                Value::string(protocol, Span::unknown()),
+                question,
+                Value::string(response.response_code().to_string(), Span::unknown()),
+                Value::int(response.answers().len() as i64, Span::unknown()),
+                Value::duration(elapsed.as_nanos() as i64, Span::unknown()),
+            ],
+        )),
+        Span::unknown(),
+    )
+}
+
+impl PluginCommand for DnsServe {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SERVE
+    }
+
+    fn description(&self) -> &str {
+        "Serve a zone from a zone file or a table of records on a local port, as a mock authoritative nameserver for testing"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::ZONE_FILE,
+                SyntaxShape::Filepath,
+                "Zone file to serve, with $ORIGIN, $TTL, and $INCLUDE directives",
+            )
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone to serve, which relative names are in. Default: the $ORIGIN of the zone file, or the owner of the SOA record",
+                None,
+            )
+            .named(
+                constants::flags::LISTEN,
+                SyntaxShape::String,
+                format!(
+                    "Address and port to listen on over UDP and TCP. Default: {}",
+                    constants::config::default::SERVE_ADDRESS
+                ),
+                Some('l'),
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Class of records that do not have one. Default: IN",
+                Some('c'),
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns serve example.com.zone",
+                description: "serve a zone file on 127.0.0.1:1053 until interrupted",
+                result: None,
+            },
+            Example {
+                example: "[[name type rdata]; [www A 192.0.2.1] [mail A 192.0.2.2] [@ MX '10 mail']] | dns serve --zone example.com --listen 127.0.0.1:5300",
+                description: "serve a table of records, with names relative to the zone",
+                result: None,
+            },
+            Example {
+                example: "dns axfr example.com --server ns1.example.com | dns serve",
+                description: "serve a copy of a zone transferred from its nameserver",
+                result: None,
+            },
+            Example {
+                example: "dns serve example.com.zone | first 2",
+                description: "stop serving after answering two queries",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "serve",
+            "server",
+            "mock",
+            "zone",
+            "authoritative",
+            "test",
+        ]
+    }
+}
@@ -0,0 +1,184 @@
+use hickory_proto::{
+    op::{Query, ResponseCode},
+    rr::{DNSClass, RData, RecordType},
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, serde::DnssecMode},
+    spanned, Dns,
+};
+
+/// The names in class CH that identify a nameserver: its software version and
+/// hostname as BIND reports them, and its instance (RFC 4892).
+const NAMES: &[&str] = &["version.bind.", "hostname.bind.", "id.server."];
+
+#[derive(Debug)]
+pub struct DnsServerId;
+
+impl DnsServerId {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // there is nothing to validate in class CH
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let ids = tokio::time::timeout(config.timeout.item, async {
+            let (client, _bg) = DnsClient::new(&config).await?;
+
+            let mut ids = Vec::with_capacity(NAMES.len());
+            for name in NAMES {
+                let mut query =
+                    Query::query(Name::from_ascii(name).expect("valid name"), RecordType::TXT);
+                query.set_query_class(DNSClass::CH);
+
+                // servers commonly refuse or ignore some of these names, which
+                // leaves only that one empty
+                let id = client
+                    .send_query(query, &config)
+                    .await
+                    .ok()
+                    .map(|response| response.into_message())
+                    .filter(|message| message.response_code() == ResponseCode::NoError)
+                    .and_then(|message| {
+                        message
+                            .answers()
+                            .iter()
+                            .find_map(|record| match record.data() {
+                                Some(RData::TXT(txt)) => Some(
+                                    txt.txt_data()
+                                        .iter()
+                                        .map(|data| String::from_utf8_lossy(data))
+                                        .collect::<String>(),
+                                ),
+                                _ => None,
+                            })
+                    });
+
+                ids.push(id);
+            }
+
+            Ok::<_, LabeledError>(ids)
+        })
+        .await
+        .map_err(|_| {
+            LabeledError::new("timed out").with_label(
+                format!("no response within {:?}", config.timeout.item),
+                call.head,
+            )
+        })??;
+
+        let values = [
+            Value::string(config.server.item.to_string(), Span::unknown()),
+            Value::string(config.protocol.item.to_string(), Span::unknown()),
+        ]
+        .into_iter()
+        .chain(ids.into_iter().map(|id| {
+            id.map_or_else(
+                || Value::nothing(Span::unknown()),
+                |id| Value::string(id, Span::unknown()),
+            )
+        }));
+
+        Ok(PipelineData::Value(
+            Value::record(
+                Record::from_iter(std::iter::zip(
+                    constants::columns::SERVER_ID_COLS
+                        .iter()
+                        .map(|col| (*col).into()),
+                    values,
+                )),
+                call.head,
+            ),
+            None,
+        ))
+    }
+}
+
+impl PluginCommand for DnsServerId {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SERVER_ID
+    }
+
+    fn description(&self) -> &str {
+        "Identify the software and instance of a nameserver, from version.bind, hostname.bind, and id.server in class CH"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to query (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait for the responses. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns server-id --server 9.9.9.9",
+                description: "identify the software and instance of a nameserver",
+                result: None,
+            },
+            Example {
+                example:
+                    "[1.1.1.1 8.8.8.8 9.9.9.9] | each { |server| dns server-id --server $server }",
+                description: "compare the instances of several resolvers",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "chaos",
+            "version.bind",
+            "hostname.bind",
+            "id.server",
+            "nsid",
+        ]
+    }
+}
@@ -0,0 +1,192 @@
+use hickory_proto::rr::{rdata::SRV, RData, RecordType};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Record, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, lookup, names},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsSrv;
+
+impl DnsSrv {
+    async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let service: Spanned<String> = call.req(0)?;
+        let proto: Spanned<String> = call.req(1)?;
+        let domain: Spanned<String> = call.req(2)?;
+
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        let domain_name = Name::from_utf8(&domain.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), domain.span)
+        })?;
+        let owner = names::srv(&service.item, &proto.item, &domain_name, service.span)?;
+
+        let client = tokio::time::timeout(config.timeout.item, plugin.dns_client(&config))
+            .await
+            .map_err(|_| {
+                LabeledError::new("timed out").with_label(
+                    format!("connecting to {} timed out", config.server.item),
+                    config.server.span,
+                )
+            })??;
+
+        let response = lookup::lookup(&client, &config, owner, RecordType::SRV, call.head).await?;
+
+        let mut targets: Vec<SRV> = response
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::SRV(srv)) => Some(srv.clone()),
+                _ => None,
+            })
+            // a single target of "." means the service is decidedly not
+            // available at this domain (RFC 2782)
+            .filter(|srv| !srv.target().is_root())
+            .collect();
+
+        // lower priorities are tried first, and within the same priority,
+        // heavier targets are picked more often
+        targets.sort_by(|a, b| {
+            a.priority()
+                .cmp(&b.priority())
+                .then(b.weight().cmp(&a.weight()))
+        });
+
+        let rows = futures_util::future::try_join_all(targets.into_iter().map(|srv| {
+            let client = client.clone();
+            let config = &config;
+            let additionals = response.additionals();
+
+            async move {
+                let addresses =
+                    lookup::addresses(&client, config, srv.target(), additionals, call.head)
+                        .await?;
+
+                let values = vec![
+                    Value::int(srv.priority() as i64, Span::unknown()),
+                    Value::int(srv.weight() as i64, Span::unknown()),
+                    Value::int(srv.port() as i64, Span::unknown()),
+                    Value::string(srv.target().to_utf8(), Span::unknown()),
+                    Value::list(
+                        addresses
+                            .into_iter()
+                            .map(|addr| Value::string(addr.to_string(), Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                ];
+
+                Result::<_, LabeledError>::Ok(Value::record(
+                    Record::from_iter(std::iter::zip(
+                        constants::columns::SRV_TARGET_COLS
+                            .iter()
+                            .map(|col| (*col).into()),
+                        values,
+                    )),
+                    Span::unknown(),
+                ))
+            }
+        }))
+        .await?;
+
+        Ok(PipelineData::Value(Value::list(rows, call.head), None))
+    }
+}
+
+impl PluginCommand for DnsSrv {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(plugin, engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SRV
+    }
+
+    fn description(&self) -> &str {
+        "Discover the targets of a service from its SRV records"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(
+                constants::flags::SERVICE,
+                SyntaxShape::String,
+                "Symbolic name of the service, e.g. \"ldap\"",
+            )
+            .required(
+                constants::flags::PROTO,
+                SyntaxShape::String,
+                "Transport protocol of the service, e.g. \"tcp\" or \"udp\"",
+            )
+            .required(
+                constants::flags::DOMAIN,
+                SyntaxShape::String,
+                "Domain the service is offered in",
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to query (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long a request can take before timing out. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns srv ldap tcp example.com",
+                description: "find the LDAP servers of a domain, in the order they should be tried",
+                result: None,
+            },
+            Example {
+                example: "dns srv xmpp-client tcp example.com | first | get addresses",
+                description: "addresses of the preferred XMPP server",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "srv", "service", "discovery"]
+    }
+}
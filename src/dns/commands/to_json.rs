@@ -0,0 +1,308 @@
+use hickory_proto::{
+    op::{Message, MessageType},
+    rr::{DNSClass, Record, RecordType},
+    serialize::binary::BinEncodable,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, Span, Value};
+
+use crate::{
+    dns::{
+        commands::build,
+        config::Config,
+        constants,
+        presentation::{self, Names},
+        serde, wire, zone,
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsToJson;
+
+impl DnsToJson {
+    fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        let value = match input.into_value(call.head)? {
+            Value::List { vals, .. } => Value::list(
+                vals.iter()
+                    .map(|val| to_json(val, &config).map(|json| Value::string(json, call.head)))
+                    .collect::<Result<_, _>>()?,
+                call.head,
+            ),
+            val => Value::string(to_json(&val, &config)?, call.head),
+        };
+
+        Ok(PipelineData::Value(value, None))
+    }
+}
+
+/// A message, given in wire format or as a record of the shape queries output,
+/// as an object of the members of RFC 8427 section 2.
+fn to_json(value: &Value, config: &Config) -> Result<String, LabeledError> {
+    let span = value.span();
+
+    // a message given as a record is encoded and decoded again, for the counts
+    // of its header to be those of its sections, with the OPT record
+    let wire = match value {
+        Value::Binary { val, .. } => val.clone(),
+        _ => {
+            let (message, op_code) = build::build_message(value)?;
+            build::encode(&message, op_code, span)?
+        }
+    };
+
+    let message = Message::from_vec(&wire::readable(&wire)).map_err(|err| {
+        LabeledError::new("invalid message")
+            .with_label(format!("Error parsing message: {}", err.kind()), span)
+    })?;
+
+    let header = message.header();
+    let flag = |set: bool| Value::int(set as i64, Span::unknown());
+    let int = |int: i64| Value::int(int, Span::unknown());
+
+    let mut object = nu_protocol::Record::new();
+    object.push("ID", int(header.id().into()));
+    object.push("QR", flag(header.message_type() == MessageType::Response));
+    object.push(
+        "Opcode",
+        int(wire::op_code(&wire)
+            .unwrap_or_else(|| header.op_code().into())
+            .into()),
+    );
+    object.push("AA", flag(header.authoritative()));
+    object.push("TC", flag(header.truncated()));
+    object.push("RD", flag(header.recursion_desired()));
+    object.push("RA", flag(header.recursion_available()));
+    object.push("AD", flag(header.authentic_data()));
+    object.push("CD", flag(header.checking_disabled()));
+    // the header holds the low 4 bits, and the OPT record the high 8
+    object.push(
+        "RCODE",
+        int((u16::from(header.response_code()) & 0x0f).into()),
+    );
+    object.push("QDCOUNT", int(header.query_count().into()));
+    object.push("ANCOUNT", int(header.answer_count().into()));
+    object.push("NSCOUNT", int(header.name_server_count().into()));
+    object.push("ARCOUNT", int(header.additional_count().into()));
+
+    // a single question is given by the members of its fields (section 2.2),
+    // and more as an array of objects of them
+    match message.queries() {
+        [query] => {
+            object.push("QNAME", string(query.name().to_string()));
+            object.push("QTYPE", int(u16::from(query.query_type()).into()));
+            object.push("QTYPEname", type_name(query.query_type()));
+            object.push("QCLASS", int(u16::from(query.query_class()).into()));
+            object.push("QCLASSname", class_name(query.query_class()));
+        }
+        [] => {}
+        queries => {
+            let queries = queries
+                .iter()
+                .map(|query| {
+                    let mut object = nu_protocol::Record::new();
+                    object.push("NAME", string(query.name().to_string()));
+                    object.push("TYPE", int(u16::from(query.query_type()).into()));
+                    object.push("TYPEname", type_name(query.query_type()));
+                    object.push("CLASS", int(u16::from(query.query_class()).into()));
+                    object.push("CLASSname", class_name(query.query_class()));
+                    Value::record(object, Span::unknown())
+                })
+                .collect();
+
+            object.push("questionRRs", Value::list(queries, Span::unknown()));
+        }
+    }
+
+    let mut additionals = message.additionals().to_vec();
+    if let Some(edns) = message.extensions() {
+        additionals.push(Record::from(edns));
+    }
+    additionals.extend(message.sig0().iter().cloned());
+
+    for (member, records) in [
+        ("answerRRs", message.answers()),
+        ("authorityRRs", message.name_servers()),
+        ("additionalRRs", additionals.as_slice()),
+    ] {
+        if records.is_empty() {
+            continue;
+        }
+
+        let records = records
+            .iter()
+            .map(|record| resource_record(record, config, span))
+            .collect::<Result<_, _>>()?;
+
+        object.push(member, Value::list(records, Span::unknown()));
+    }
+
+    let mut json = String::new();
+    write_json(&mut json, &Value::record(object, Span::unknown()));
+
+    Ok(json)
+}
+
+/// The members of a resource record (RFC 8427 section 2.3), with its data both
+/// in presentation format and as hex. The OPT record has no presentation
+/// format, and only its data as hex.
+fn resource_record(record: &Record, config: &Config, span: Span) -> Result<Value, LabeledError> {
+    let rtype = record.record_type();
+    let int = |int: i64| Value::int(int, Span::unknown());
+
+    let rdata = record
+        .data()
+        .map(|rdata| rdata.to_bytes())
+        .transpose()
+        .map_err(|err| {
+            LabeledError::new("invalid message").with_label(
+                format!("Error encoding {} record data: {}", rtype, err.kind()),
+                span,
+            )
+        })?
+        .unwrap_or_default();
+
+    let mut object = nu_protocol::Record::new();
+    object.push("NAME", string(record.name().to_string()));
+    object.push("TYPE", int(u16::from(rtype).into()));
+    object.push("TYPEname", type_name(rtype));
+    object.push("CLASS", int(u16::from(record.dns_class()).into()));
+
+    if rtype != RecordType::OPT {
+        object.push("CLASSname", class_name(record.dns_class()));
+    }
+
+    object.push("TTL", int(record.ttl().into()));
+    object.push("RDLENGTH", int(rdata.len() as i64));
+
+    if rtype != RecordType::OPT && !rdata.is_empty() {
+        let value = serde::Record(record.clone()).into_value(config)?;
+        let (_, _, _, _, data) = zone::record_columns(&value, None, DNSClass::IN)?;
+        let text = presentation::rdata(rtype, &data, &Names::default())?.line();
+
+        object.push(format!("rdata{}", serde::RTypeName(rtype)), string(text));
+    }
+
+    object.push("RDATAHEX", string(data_encoding::HEXUPPER.encode(&rdata)));
+
+    Ok(Value::record(object, Span::unknown()))
+}
+
+fn string(text: String) -> Value {
+    Value::string(text, Span::unknown())
+}
+
+fn type_name(rtype: RecordType) -> Value {
+    string(serde::RTypeName(rtype).to_string())
+}
+
+/// The name of a class, or `CLASSnnn` for one without a name (RFC 3597 section
+/// 5).
+fn class_name(class: DNSClass) -> Value {
+    match class {
+        DNSClass::Unknown(code) => string(format!("CLASS{}", code)),
+        class => string(class.to_string()),
+    }
+}
+
+/// Writes the objects, arrays, strings, and integers the members are made of as
+/// JSON, keeping the order of the members.
+fn write_json(json: &mut String, value: &Value) {
+    match value {
+        Value::Record { val, .. } => {
+            json.push('{');
+            for (i, (member, value)) in val.iter().enumerate() {
+                if i > 0 {
+                    json.push_str(", ");
+                }
+                write_string(json, member);
+                json.push_str(": ");
+                write_json(json, value);
+            }
+            json.push('}');
+        }
+        Value::List { vals, .. } => {
+            json.push('[');
+            for (i, value) in vals.iter().enumerate() {
+                if i > 0 {
+                    json.push_str(", ");
+                }
+                write_json(json, value);
+            }
+            json.push(']');
+        }
+        Value::Int { val, .. } => json.push_str(&val.to_string()),
+        Value::String { val, .. } => write_string(json, val),
+        _ => json.push_str("null"),
+    }
+}
+
+fn write_string(json: &mut String, text: &str) {
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+impl PluginCommand for DnsToJson {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(engine, call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::TO_JSON
+    }
+
+    fn description(&self) -> &str {
+        "Convert messages, in wire format or as records of the shape queries output, into JSON as specified by RFC 8427, for other DNS tools to read"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns query example.com | dns to-json",
+                description: "convert the response of a query into RFC 8427 JSON",
+                result: None,
+            },
+            Example {
+                example: "open --raw response.bin | dns to-json | save response.json",
+                description: "convert a message in wire format, and save it",
+                result: None,
+            },
+            Example {
+                example: "dns query example.com | dns to-json | from json | get answerRRs.rdataA",
+                description: "read the addresses back from the JSON",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "json", "rfc8427", "export", "convert", "interop"]
+    }
+}
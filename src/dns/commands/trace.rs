@@ -0,0 +1,152 @@
+use hickory_proto::{op::Query, rr::RecordType};
+use hickory_resolver::{config::Protocol, Name};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, serde::DnssecMode, trace},
+    spanned, Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsTrace;
+
+impl DnsTrace {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let name: Spanned<String> = call.req(0)?;
+        let max_depth = match call.get_flag::<Spanned<i64>>(constants::flags::MAX_DEPTH)? {
+            Some(depth) => usize::try_from(depth.item).map_err(|err| {
+                LabeledError::new("invalid input")
+                    .with_label(format!("should be positive int: {err}"), depth.span)
+            })?,
+            None => constants::config::default::TRACE_MAX_DEPTH,
+        };
+
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // the servers on the way are only reachable over plain DNS
+        if !matches!(config.protocol.item, Protocol::Udp | Protocol::Tcp) {
+            return Err(LabeledError::new("unsupported protocol")
+                .with_label("tracing needs UDP or TCP", config.protocol.span));
+        }
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let name = Name::from_utf8(&name.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), name.span)
+        })?;
+
+        // unlike for queries, tracing both address types by default would
+        // just walk the same delegations twice
+        let qtypes = if call.has_flag(constants::flags::TYPE)? {
+            config.qtypes.item.iter().map(|qtype| qtype.item).collect()
+        } else {
+            vec![RecordType::A]
+        };
+
+        let mut traces = Vec::new();
+        for qtype in qtypes {
+            let mut query = Query::query(name.clone(), qtype);
+            query.set_query_class(config.class.item);
+
+            traces.push(trace::trace(&mut config, query, max_depth, call.head).await?);
+        }
+
+        Ok(PipelineData::Value(Value::list(traces, call.head), None))
+    }
+}
+
+impl PluginCommand for DnsTrace {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::TRACE
+    }
+
+    fn description(&self) -> &str {
+        "Resolve a name iteratively from the root servers, showing every delegation on the way"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(constants::flags::NAME, SyntaxShape::String, "Name to trace")
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Record type(s) to trace. Default: A",
+                Some('t'),
+            )
+            .named(
+                constants::flags::MAX_DEPTH,
+                SyntaxShape::Int,
+                format!(
+                    "How many referrals to follow before giving up and returning the trace so far. Default: {}",
+                    constants::config::default::TRACE_MAX_DEPTH
+                ),
+                None,
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to look up the addresses of nameservers without glue with (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameservers: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait for each nameserver before trying the next. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns trace www.example.com",
+                description:
+                    "follow the delegations from the root to the servers of www.example.com",
+                result: None,
+            },
+            Example {
+                example: "dns trace --max-depth 3 deeply.nested.example.com",
+                description: "give up after following 3 referrals",
+                result: None,
+            },
+            Example {
+                example: "dns trace example.com | get 0.steps | where ($it.queried | length) > 1",
+                description:
+                    "find the delegations where the first nameserver asked did not respond",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "trace", "iterative", "delegation", "referral"]
+    }
+}
@@ -0,0 +1,340 @@
+use hickory_proto::{
+    op::UpdateMessage,
+    rr::{RData, Record},
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        config::Config,
+        constants,
+        serde::{self, DnssecMode, RType},
+        update::{self, parse_rdata, Change},
+    },
+    spanned, Dns,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Add,
+    Delete,
+}
+
+impl TryFrom<&Spanned<String>> for Action {
+    type Error = LabeledError;
+
+    fn try_from(value: &Spanned<String>) -> Result<Self, Self::Error> {
+        match value.item.to_lowercase().as_str() {
+            "add" => Ok(Action::Add),
+            "delete" => Ok(Action::Delete),
+            _ => Err(LabeledError::new("invalid action")
+                .with_label("Invalid action. Must be one of: add, delete", value.span)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DnsUpdate;
+
+impl DnsUpdate {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let action: Spanned<String> = call.req(0)?;
+        let action = Action::try_from(&action)?;
+        let name: Spanned<String> = call.req(1)?;
+        let rtype = call.nth(2).map(|val| RType::try_from(&val)).transpose()?;
+        let rdata: Option<Spanned<String>> = call.opt(3)?;
+
+        let ttl = match call.get_flag::<Spanned<i64>>(constants::flags::TTL)? {
+            Some(ttl) => u32::try_from(ttl.item).map_err(|err| {
+                LabeledError::new("invalid TTL")
+                    .with_label(format!("should be a 32 bit unsigned int: {err}"), ttl.span)
+            })?,
+            None => constants::config::default::UPDATE_TTL,
+        };
+
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // the response to an update has nothing to validate
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let name = parse_name(&name)?;
+        let class = config.class.item;
+
+        let change = match (action, rtype, rdata) {
+            (Action::Add, Some(RType(rtype)), Some(rdata)) => {
+                Change::Add(record(name, class, ttl, parse_rdata(rtype, &rdata)?))
+            }
+            (Action::Add, _, _) => {
+                return Err(LabeledError::new("missing record").with_label(
+                    "adding a record needs its type and data, e.g. A 192.0.2.1",
+                    call.head,
+                ))
+            }
+            (Action::Delete, Some(RType(rtype)), Some(rdata)) => {
+                Change::Delete(record(name, class, 0, parse_rdata(rtype, &rdata)?))
+            }
+            (Action::Delete, Some(RType(rtype)), None) => Change::DeleteRrset(name, rtype),
+            (Action::Delete, None, _) => Change::DeleteName(name),
+        };
+
+        let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+
+        let zone = match call.get_flag::<Spanned<String>>(constants::flags::ZONE)? {
+            Some(zone) => parse_name(&zone)?,
+            None => update::find_zone(&client, &config, change.name(), call.head).await?,
+        };
+
+        if !zone.zone_of(change.name()) {
+            return Err(LabeledError::new("name not in zone").with_label(
+                format!("{} is not in the zone {}", change.name(), zone),
+                call.head,
+            ));
+        }
+
+        let mut message = change.into_message(zone, class);
+
+        for (flag, exists) in [
+            (constants::flags::PREREQ_EXISTS, true),
+            (constants::flags::PREREQ_NOT_EXISTS, false),
+        ] {
+            for spec in prerequisite_specs(call, flag)? {
+                message.add_pre_requisite(update::prerequisite(&spec, exists, class)?);
+            }
+        }
+
+        let response = update::send(&client, &config, message, call.head).await?;
+
+        Ok(PipelineData::Value(
+            serde::Message::new(response).into_value(&config)?,
+            None,
+        ))
+    }
+}
+
+fn parse_name(name: &Spanned<String>) -> Result<Name, LabeledError> {
+    Name::from_utf8(&name.item).map_err(|err| {
+        LabeledError::new("invalid name")
+            .with_label(format!("Error parsing name: {}", err), name.span)
+    })
+}
+
+/// The prerequisites given with a flag, as a string or a list of them.
+fn prerequisite_specs(
+    call: &EvaluatedCall,
+    flag: &str,
+) -> Result<Vec<Spanned<String>>, LabeledError> {
+    let spec = |val: &Value| -> Result<Spanned<String>, LabeledError> {
+        Ok(Spanned {
+            item: val.as_str()?.to_string(),
+            span: val.span(),
+        })
+    };
+
+    match call.get_flag_value(flag) {
+        None => Ok(Vec::new()),
+        Some(Value::List { vals, .. }) => vals.iter().map(spec).collect(),
+        Some(val) => Ok(vec![spec(&val)?]),
+    }
+}
+
+fn record(name: Name, class: hickory_proto::rr::DNSClass, ttl: u32, rdata: RData) -> Record {
+    let mut record = Record::from_rdata(name, ttl, rdata);
+    record.set_dns_class(class);
+    record
+}
+
+impl PluginCommand for DnsUpdate {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::UPDATE
+    }
+
+    fn description(&self) -> &str {
+        "Add or delete records in a zone with a dynamic update (RFC 2136)"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(
+                constants::flags::ACTION,
+                SyntaxShape::String,
+                "What to do: \"add\" a record, or \"delete\" records",
+            )
+            .required(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Name of the records",
+            )
+            .optional(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Type of the records. Deleting without a type deletes every record of the name",
+            )
+            .optional(
+                constants::flags::RDATA,
+                SyntaxShape::String,
+                "Data of the record in zone file format, e.g. \"10 mail.example.com.\" for MX. Deleting without data deletes every record of the type",
+            )
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone to update. Default: the zone the server says the name belongs to",
+                Some('z'),
+            )
+            .named(
+                constants::flags::PREREQ_EXISTS,
+                SyntaxShape::Any,
+                "Only update if these exist: \"NAME\" for a name in use, \"NAME TYPE\" for an RRset, or \"NAME TYPE RDATA\" for an RRset of exactly these records. Takes a string or a list of them",
+                None,
+            )
+            .named(
+                constants::flags::PREREQ_NOT_EXISTS,
+                SyntaxShape::Any,
+                "Only update if these do not exist: \"NAME\" for a name that is not in use, or \"NAME TYPE\" for an RRset. Takes a string or a list of them",
+                None,
+            )
+            .named(
+                constants::flags::TTL,
+                SyntaxShape::Int,
+                format!(
+                    "TTL of added records, in seconds. Default: {}",
+                    constants::config::default::UPDATE_TTL
+                ),
+                None,
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Class of the records. Default: IN",
+                Some('c'),
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Primary nameserver of the zone (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::TSIG_KEY,
+                SyntaxShape::String,
+                "TSIG key to sign the update with, as name:algorithm:secret with the secret in base64. The algorithm is one of: hmac-sha256, hmac-sha384, hmac-sha512 (for UDP, TCP, and TLS only)",
+                None,
+            )
+            .named(
+                constants::flags::TSIG_KEY_FILE,
+                SyntaxShape::Filepath,
+                "File with the TSIG key to sign the update with, in the format written by tsig-keygen",
+                None,
+            )
+            .switch(
+                constants::flags::GSS_TSIG,
+                "Sign the update with GSS-TSIG, negotiating a key with the nameserver from the Kerberos credentials of the user, as Active Directory requires. The server must be given by its hostname (needs the gss-tsig feature)",
+                None,
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long a request can take before timing out. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns update add www.example.com A 192.0.2.1 --ttl 300 --server ns1.example.com",
+                description: "add an address record",
+                result: None,
+            },
+            Example {
+                example: "dns update add example.com MX '10 mail.example.com.' --zone example.com --server 192.0.2.53",
+                description: "add a mail exchanger, naming the zone instead of looking it up",
+                result: None,
+            },
+            Example {
+                example: "dns update delete www.example.com A 192.0.2.1 --server ns1.example.com",
+                description: "delete a single record",
+                result: None,
+            },
+            Example {
+                example: "dns update delete www.example.com AAAA --server ns1.example.com",
+                description: "delete all AAAA records of a name",
+                result: None,
+            },
+            Example {
+                example: "dns update add www.example.com A 192.0.2.1 --prereq-not-exists www.example.com --server ns1.example.com",
+                description: "add a record only if the name is not in use yet",
+                result: None,
+            },
+            Example {
+                example: "dns update add www.example.com A 192.0.2.2 --prereq-exists 'www.example.com A 192.0.2.1' --server ns1.example.com",
+                description: "add a record only if the address records are still what they were",
+                result: None,
+            },
+            Example {
+                example: "dns update add www.example.com A 192.0.2.1 --server ns1.example.com --tsig-key-file ddns.key",
+                description: "add a record to a zone that only accepts updates signed with a TSIG key",
+                result: None,
+            },
+            Example {
+                example: "dns update add host1.ad.example.com A 192.0.2.1 --server dc1.ad.example.com --gss-tsig",
+                description: "add a record to an Active Directory integrated zone, after kinit",
+                result: None,
+            },
+            Example {
+                example: "dns update delete old.example.com --server ns1.example.com",
+                description: "delete every record of a name",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "update",
+            "nsupdate",
+            "ddns",
+            "dynamic",
+            "rfc2136",
+            "gss-tsig",
+            "kerberos",
+            "active directory",
+        ]
+    }
+}
@@ -0,0 +1,375 @@
+use hickory_client::rr::rdata::DNSSECRData;
+use hickory_proto::rr::{RData, Record as DnsRecord, RecordType};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Record, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{chain, client::DnsClient, config::Config, constants, serde::DnssecMode},
+    spanned, Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsWalk;
+
+impl DnsWalk {
+    async fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let zone: Spanned<String> = call.req(0)?;
+        let max_names = match call.get_flag::<Spanned<i64>>(constants::flags::MAX_NAMES)? {
+            Some(max) => usize::try_from(max.item).map_err(|err| {
+                LabeledError::new("invalid input")
+                    .with_label(format!("should be positive int: {err}"), max.span)
+            })?,
+            None => constants::config::default::WALK_MAX_NAMES,
+        };
+
+        let mut config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        // the NSEC records are what there is to see, rather than to validate
+        config.dnssec_mode = spanned!(DnssecMode::None, Span::unknown());
+
+        let mut zone = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+        zone.set_fqdn(true);
+
+        let (client, _bg) = DnsClient::connect_with_timeout(&config).await?;
+        let client = match &config.qps {
+            Some(qps) => client.with_rate_limit(qps.item),
+            None => client,
+        };
+
+        Ok(PipelineData::Value(
+            walk(&client, &config, &zone, max_names, call.head).await?,
+            None,
+        ))
+    }
+}
+
+/// Follows the NSEC chain of `zone` from its apex, where each record names the
+/// next name of the zone, until it wraps around to the apex again, or after
+/// `max_names` names. A zone that denies with NSEC3 only has hashes of its
+/// names to follow, which is noted instead.
+async fn walk(
+    client: &DnsClient,
+    config: &Config,
+    zone: &Name,
+    max_names: usize,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let apex = chain::fetch(client, config, zone.clone(), RecordType::NSEC, span).await?;
+    let mut nsec = own_nsec(apex.answers(), zone, zone);
+
+    let (denial, mut note) = match nsec {
+        Some(_) => ("NSEC", None),
+        None => match nsec3_params(client, config, zone, apex.name_servers(), span).await? {
+            Some(params) => (
+                "NSEC3",
+                Some(format!(
+                    "{} denies with NSEC3 ({}), whose owner names are hashes of the names, so they cannot be walked",
+                    zone, params
+                )),
+            ),
+            None => (
+                "none",
+                Some(format!(
+                    "{} has no NSEC or NSEC3 records, so it is not signed",
+                    zone
+                )),
+            ),
+        },
+    };
+
+    let mut names = Vec::new();
+    let mut complete = false;
+    let mut name = zone.clone();
+
+    while let Some((types, next)) = nsec.take() {
+        names.push(Value::record(
+            Record::from_iter(std::iter::zip(
+                constants::columns::WALK_NAME_COLS
+                    .iter()
+                    .map(|col| (*col).into()),
+                vec![
+                    Value::string(name.to_utf8(), Span::unknown()),
+                    Value::list(
+                        types
+                            .iter()
+                            .map(|rtype| Value::string(rtype.to_string(), Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                    Value::string(next.to_utf8(), Span::unknown()),
+                ],
+            )),
+            Span::unknown(),
+        ));
+
+        // the last record of the chain points back to the apex
+        if next == *zone {
+            complete = true;
+            break;
+        }
+
+        // names come in canonical order, so going back would go around in
+        // circles
+        if !zone.zone_of(&next) || next <= name {
+            note = Some(format!(
+                "the NSEC record of {} points to {}, which does not come after it in {}",
+                name, next, zone
+            ));
+            break;
+        }
+
+        if names.len() >= max_names {
+            note = Some(format!("stopped after {} names", max_names));
+            break;
+        }
+
+        name = next;
+        match lookup(client, config, zone, &name, span).await {
+            Ok(Some(found)) => nsec = Some(found),
+            Ok(None) => note = Some(format!("there is no NSEC record of {}", name)),
+            Err(err) => {
+                note = Some(
+                    err.labels
+                        .first()
+                        .map_or(err.msg.clone(), |label| label.text.clone()),
+                )
+            }
+        }
+    }
+
+    Ok(Value::record(
+        Record::from_iter(std::iter::zip(
+            constants::columns::WALK_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            vec![
+                Value::string(zone.to_utf8(), Span::unknown()),
+                Value::string(denial, Span::unknown()),
+                Value::bool(complete, Span::unknown()),
+                note.map_or_else(
+                    || Value::nothing(Span::unknown()),
+                    |note| Value::string(note, Span::unknown()),
+                ),
+                Value::list(names, Span::unknown()),
+            ],
+        )),
+        span,
+    ))
+}
+
+/// The NSEC record of a name in the zone, asking for it first, and then for
+/// the name right after it, in case it is a delegation: a resolver asks the
+/// child zone for records at a delegation, but the parent for the names after
+/// it, of which the NSEC record of the delegation is the one that covers them.
+async fn lookup(
+    client: &DnsClient,
+    config: &Config,
+    zone: &Name,
+    name: &Name,
+    span: Span,
+) -> Result<Option<(Vec<RecordType>, Name)>, LabeledError> {
+    let message = chain::fetch(client, config, name.clone(), RecordType::NSEC, span).await?;
+    if let Some(found) = own_nsec(message.answers(), name, zone) {
+        return Ok(Some(found));
+    }
+
+    let Some(after) = successor(name) else {
+        return Ok(None);
+    };
+    let message = chain::fetch(client, config, after, RecordType::NSEC, span).await?;
+
+    Ok(own_nsec(message.name_servers(), name, zone))
+}
+
+/// The types and next name of the NSEC record of `name` among `records`, if
+/// `zone` rather than a zone below it signed it.
+fn own_nsec(records: &[DnsRecord], name: &Name, zone: &Name) -> Option<(Vec<RecordType>, Name)> {
+    let signed_elsewhere = records.iter().any(|record| match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::RRSIG(sig))) => {
+            record.name() == name
+                && sig.type_covered() == RecordType::NSEC
+                && sig.signer_name() != zone
+        }
+        _ => false,
+    });
+    if signed_elsewhere {
+        return None;
+    }
+
+    records.iter().find_map(|record| match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::NSEC(nsec))) if record.name() == name => Some((
+            nsec.type_bit_maps().to_vec(),
+            nsec.next_domain_name().clone(),
+        )),
+        _ => None,
+    })
+}
+
+/// The name that comes right after `name` in canonical order, but not below
+/// it: its first label with a zero octet appended (RFC 4034 section 6.1).
+fn successor(name: &Name) -> Option<Name> {
+    let mut labels = name.iter();
+    let mut first = labels.next()?.to_vec();
+    first.push(0);
+
+    let mut successor = Name::from_labels(std::iter::once(first.as_slice()).chain(labels)).ok()?;
+    successor.set_fqdn(true);
+
+    Some(successor)
+}
+
+/// The NSEC3 parameters of a zone, from the NSEC3 records that came along
+/// with the denial of its NSEC record, or else its NSEC3PARAM record.
+async fn nsec3_params(
+    client: &DnsClient,
+    config: &Config,
+    zone: &Name,
+    authority: &[DnsRecord],
+    span: Span,
+) -> Result<Option<String>, LabeledError> {
+    let describe = |iterations: u16, salt: &[u8], opt_out: bool| {
+        format!(
+            "{} iterations, salt {}{}",
+            iterations,
+            if salt.is_empty() {
+                "-".to_string()
+            } else {
+                data_encoding::HEXUPPER.encode(salt)
+            },
+            if opt_out { ", opt-out" } else { "" }
+        )
+    };
+
+    let params = authority.iter().find_map(|record| match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::NSEC3(nsec3))) => {
+            Some(describe(nsec3.iterations(), nsec3.salt(), nsec3.opt_out()))
+        }
+        _ => None,
+    });
+    if params.is_some() {
+        return Ok(params);
+    }
+
+    let message = chain::fetch(client, config, zone.clone(), RecordType::NSEC3PARAM, span).await?;
+
+    Ok(message
+        .answers()
+        .iter()
+        .find_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::NSEC3PARAM(param))) => {
+                Some(describe(param.iterations(), param.salt(), param.opt_out()))
+            }
+            _ => None,
+        }))
+}
+
+impl PluginCommand for DnsWalk {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.runtime.block_on(self.run_impl(engine, call))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::WALK
+    }
+
+    fn description(&self) -> &str {
+        "List the names of a signed zone by following its chain of NSEC records, or tell that it denies with NSEC3, which cannot be walked"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .required(constants::flags::ZONE, SyntaxShape::String, "Zone to walk")
+            .named(
+                constants::flags::MAX_NAMES,
+                SyntaxShape::Int,
+                format!(
+                    "How many names to follow before stopping with the names so far. Default: {}",
+                    constants::config::default::WALK_MAX_NAMES
+                ),
+                None,
+            )
+            .named(
+                constants::flags::SERVER,
+                SyntaxShape::String,
+                "Nameserver to query (defaults to system config or 8.8.8.8)",
+                Some('s'),
+            )
+            .named(
+                constants::flags::PROTOCOL,
+                SyntaxShape::String,
+                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+                Some('p'),
+            )
+            .named(
+                constants::flags::DNS_NAME,
+                SyntaxShape::String,
+                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+                Some('n'),
+            )
+            .named(
+                constants::flags::QPS,
+                SyntaxShape::Number,
+                "Maximum number of queries per second to send. Default: unlimited",
+                None,
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait for each response. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns walk example.com | get names.name",
+                description: "list the names of a zone signed with NSEC",
+                result: None,
+            },
+            Example {
+                example: "dns walk example.com | get names | where { |row| 'NS' in $row.types } | get name",
+                description: "find the delegations of a zone, and the zone itself",
+                result: None,
+            },
+            Example {
+                example: "dns walk --qps 5 --max-names 500 example.com",
+                description: "walk slowly, and stop after 500 names",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "dnssec",
+            "nsec",
+            "nsec3",
+            "walk",
+            "enumerate",
+            "zone",
+        ]
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use hickory_proto::rr::{DNSClass, LowerName, RecordType};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        presentation::{self, Names},
+        serde, zone,
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsZoneCheck;
+
+impl DnsZoneCheck {
+    fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let zone_file: Option<Spanned<String>> = call.opt(0)?;
+        let origin = call
+            .get_flag::<Spanned<String>>(constants::flags::ZONE)?
+            .map(|zone| zone::parse_origin(&zone))
+            .transpose()?;
+
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        let (origin, values) = match &zone_file {
+            Some(path) => {
+                let (file_origin, records) = zone::parse_file(path, origin.clone())?;
+                let values = records
+                    .into_iter()
+                    .map(|record| serde::Record(record).into_value(&config))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                (origin.or(file_origin), values)
+            }
+            None => (origin, input.into_iter().collect()),
+        };
+
+        let mut records = Vec::new();
+        for value in values {
+            if matches!(value, Value::Nothing { .. }) {
+                continue;
+            }
+
+            let (name, rtype, class, ttl, rdata) =
+                zone::record_columns(&value, origin.as_ref(), config.class.item)?;
+
+            records.push(Entry {
+                name,
+                rtype,
+                class,
+                ttl,
+                rdata,
+            });
+        }
+
+        // a transfer ends with the SOA record it starts with (RFC 5936
+        // section 2.2), which is not a duplicate in the zone
+        if records.len() > 1
+            && records[0].rtype == RecordType::SOA
+            && records.first() == records.last()
+        {
+            records.pop();
+        }
+
+        let origin = origin
+            .or_else(|| {
+                records
+                    .iter()
+                    .find(|record| record.rtype == RecordType::SOA)
+                    .map(|soa| soa.name.clone())
+            })
+            .ok_or_else(|| {
+                LabeledError::new("unknown zone").with_label(
+                    "the records have no SOA record to tell which zone they are, pass it with --zone",
+                    call.head,
+                )
+            })?;
+
+        let findings = check(&origin, &records)?;
+
+        Ok(PipelineData::Value(
+            Value::list(
+                findings.into_iter().map(Finding::into_value).collect(),
+                call.head,
+            ),
+            None,
+        ))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+    name: Name,
+    rtype: RecordType,
+    class: DNSClass,
+    ttl: u32,
+    rdata: Value,
+}
+
+struct Finding {
+    severity: &'static str,
+    check: &'static str,
+    name: Name,
+    rtype: Option<RecordType>,
+    message: String,
+}
+
+impl Finding {
+    fn error(check: &'static str, name: &Name, rtype: Option<RecordType>, message: String) -> Self {
+        Self {
+            severity: "error",
+            check,
+            name: name.clone(),
+            rtype,
+            message,
+        }
+    }
+
+    fn warning(
+        check: &'static str,
+        name: &Name,
+        rtype: Option<RecordType>,
+        message: String,
+    ) -> Self {
+        Self {
+            severity: "warning",
+            ..Self::error(check, name, rtype, message)
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::record(
+            nu_protocol::Record::from_iter(std::iter::zip(
+                Vec::from_iter(constants::columns::CHECK_COLS.iter().map(|s| (*s).into())),
+                vec![
+                    Value::string(self.severity, Span::unknown()),
+                    Value::string(self.check, Span::unknown()),
+                    Value::string(self.name.to_utf8(), Span::unknown()),
+                    self.rtype.map_or(Value::nothing(Span::unknown()), |rtype| {
+                        Value::string(serde::RTypeName(rtype).to_string(), Span::unknown())
+                    }),
+                    Value::string(self.message, Span::unknown()),
+                ],
+            )),
+            Span::unknown(),
+        )
+    }
+}
+
+/// The problems found in the records of the zone at `origin`, ordered by name.
+fn check(origin: &Name, records: &[Entry]) -> Result<Vec<Finding>, LabeledError> {
+    let mut findings = Vec::new();
+
+    let owners: BTreeSet<LowerName> = records
+        .iter()
+        .map(|record| LowerName::new(&record.name))
+        .collect();
+    let at_apex = |rtype: RecordType| {
+        records
+            .iter()
+            .any(|record| record.rtype == rtype && record.name.eq(origin))
+    };
+
+    if !at_apex(RecordType::SOA) {
+        findings.push(Finding::error(
+            "missing-soa",
+            origin,
+            Some(RecordType::SOA),
+            format!("the zone {} has no SOA record at its apex", origin),
+        ));
+    }
+
+    if !at_apex(RecordType::NS) {
+        findings.push(Finding::error(
+            "missing-ns",
+            origin,
+            Some(RecordType::NS),
+            format!("the zone {} has no NS records at its apex", origin),
+        ));
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut rrsets: BTreeMap<(LowerName, u16, u16), BTreeSet<u32>> = BTreeMap::new();
+
+    for record in records {
+        let name = &record.name;
+
+        if !origin.zone_of(name) {
+            let message = match record.rtype {
+                RecordType::A | RecordType::AAAA => {
+                    format!("glue for {} is outside the zone {}", name, origin)
+                }
+                rtype => format!(
+                    "{} record of {} is outside the zone {}",
+                    rtype, name, origin
+                ),
+            };
+
+            findings.push(Finding::warning(
+                "out-of-zone",
+                name,
+                Some(record.rtype),
+                message,
+            ));
+        }
+
+        if record.rtype == RecordType::CNAME {
+            if name.eq(origin) {
+                findings.push(Finding::error(
+                    "cname-at-apex",
+                    name,
+                    Some(record.rtype),
+                    "a CNAME can not be at the apex of a zone, which has SOA and NS records (RFC 1912 section 2.4)".into(),
+                ));
+            }
+
+            let target = match serde::Query::name_from_value(&record.rdata)? {
+                target if target.is_fqdn() => target,
+                target => target.clone().append_domain(origin).unwrap_or(target),
+            };
+
+            if origin.zone_of(&target) && !exists(&owners, origin, &target) {
+                findings.push(Finding::warning(
+                    "dangling-cname",
+                    name,
+                    Some(record.rtype),
+                    format!("the target {} has no records in the zone", target),
+                ));
+            }
+        }
+
+        let rdata = presentation::rdata(record.rtype, &record.rdata, &Names::default())?.line();
+        let key = (
+            LowerName::new(name),
+            u16::from(record.rtype),
+            u16::from(record.class),
+        );
+
+        if !seen.insert((key.clone(), rdata.clone())) {
+            findings.push(Finding::warning(
+                "duplicate",
+                name,
+                Some(record.rtype),
+                format!("{} {} is in the zone more than once", record.rtype, rdata),
+            ));
+        }
+
+        // the signatures of an RRset have TTLs of their own (RFC 4034 section
+        // 3), which are not one RRset by type
+        if record.rtype != RecordType::RRSIG {
+            rrsets.entry(key).or_default().insert(record.ttl);
+        }
+    }
+
+    for ((name, rtype, _), ttls) in rrsets {
+        if ttls.len() > 1 {
+            let ttls = ttls.iter().map(u32::to_string).collect::<Vec<_>>();
+
+            findings.push(Finding::warning(
+                "ttl-mismatch",
+                &Name::from(name),
+                Some(RecordType::from(rtype)),
+                format!(
+                    "the records of the RRset have different TTLs: {} (RFC 2181 section 5.2)",
+                    ttls.join(", ")
+                ),
+            ));
+        }
+    }
+
+    findings.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(findings)
+}
+
+/// Whether `name` has records, directly or from a wildcard of one of its
+/// ancestors in the zone.
+fn exists(owners: &BTreeSet<LowerName>, origin: &Name, name: &Name) -> bool {
+    if owners.contains(&LowerName::new(name)) {
+        return true;
+    }
+
+    // a wildcard replaces the first label of the name, or of an ancestor of
+    // it below the apex
+    let mut child = name.clone();
+
+    while origin.zone_of(&child) && !child.eq(origin) {
+        if owners.contains(&LowerName::new(&child).into_wildcard()) {
+            return true;
+        }
+
+        child = child.base_name();
+    }
+
+    false
+}
+
+impl PluginCommand for DnsZoneCheck {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(engine, call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::ZONE_CHECK
+    }
+
+    fn description(&self) -> &str {
+        "Check the records of a zone for common mistakes, such as dangling CNAMEs, a missing SOA or NS, duplicate records, and RRsets with different TTLs"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::ZONE_FILE,
+                SyntaxShape::Filepath,
+                "Zone file to check. Default: the table of records piped in",
+            )
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone to check, which relative names are in. Default: the $ORIGIN of the zone file, or the owner of the SOA record",
+                None,
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Class of records that do not have one. Default: IN",
+                Some('c'),
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns zone check example.com.zone",
+                description: "check a zone file before loading it",
+                result: None,
+            },
+            Example {
+                example: "dns axfr example.com --server ns1.example.com | dns zone check | where severity == error",
+                description: "find the errors in a zone as served by its nameserver",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "zone", "check", "lint", "validate", "cname", "ttl"]
+    }
+}
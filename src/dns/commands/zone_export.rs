@@ -0,0 +1,94 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, Spanned, SyntaxShape, Value};
+
+use crate::{
+    dns::{config::Config, constants, zone},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsZoneExport;
+
+impl DnsZoneExport {
+    fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let origin = call
+            .get_flag::<Spanned<String>>(constants::flags::ZONE)?
+            .map(|zone| zone::parse_origin(&zone))
+            .transpose()?;
+
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        let text = zone::export(input, origin.as_ref(), config.class.item)?;
+
+        Ok(PipelineData::Value(Value::string(text, call.head), None))
+    }
+}
+
+impl PluginCommand for DnsZoneExport {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(engine, call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::ZONE_EXPORT
+    }
+
+    fn description(&self) -> &str {
+        "Write a table of records as the text of a zone file (RFC 1035 section 5)"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone to write an $ORIGIN for, with names in it relative to it. Default: absolute names",
+                None,
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Class of records that do not have one. Default: IN",
+                Some('c'),
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns axfr example.com --server ns1.example.com | dns zone export --zone example.com | save example.com.zone",
+                description: "save a copy of a zone transferred from its nameserver as a zone file",
+                result: None,
+            },
+            Example {
+                example: "dns zone parse example.com.zone | where type != TXT | dns zone export",
+                description: "write a zone file without its TXT records, with absolute names",
+                result: None,
+            },
+            Example {
+                example: "[[name type rdata]; [www A 192.0.2.1] [@ MX '10 mail']] | dns zone export --zone example.com",
+                description: "write a zone file from a table of records, with names relative to the zone",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "zone", "export", "zonefile", "master", "bind", "rfc1035",
+        ]
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, Spanned, SyntaxShape, Value};
+
+use crate::{
+    dns::{config::Config, constants, serde, zone},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsZoneParse;
+
+impl DnsZoneParse {
+    fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let path: Option<Spanned<String>> = call.opt(0)?;
+        let origin = call
+            .get_flag::<Spanned<String>>(constants::flags::ZONE)?
+            .map(|zone| zone::parse_origin(&zone))
+            .transpose()?;
+
+        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
+
+        let (_, records) = match path {
+            Some(path) => zone::parse_file(&path, origin)?,
+            None => {
+                let input = input.into_value(call.head)?;
+                let span = input.span();
+
+                let text = match input {
+                    Value::String { val, .. } => val,
+                    Value::Binary { val, .. } => String::from_utf8(val).map_err(|err| {
+                        LabeledError::new("invalid zone file")
+                            .with_label(format!("zone file is not UTF-8: {}", err), span)
+                    })?,
+                    _ => {
+                        return Err(LabeledError::new("missing zone file").with_label(
+                            "pass the path of a zone file, or pipe in its text",
+                            call.head,
+                        ))
+                    }
+                };
+
+                // relative $INCLUDE paths are resolved against the directory
+                // of the file, which piped text is taken to be in the current
+                // one of
+                let path = PathBuf::from(engine.get_current_dir()?).join("-");
+
+                zone::parse(text, path, origin, span)?
+            }
+        };
+
+        let rows = records
+            .into_iter()
+            .map(|record| serde::Record(record).into_value(&config))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PipelineData::Value(Value::list(rows, call.head), None))
+    }
+}
+
+impl PluginCommand for DnsZoneParse {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(engine, call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::ZONE_PARSE
+    }
+
+    fn description(&self) -> &str {
+        "Parse a zone file (RFC 1035 section 5) into a table of its records"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::ZONE_FILE,
+                SyntaxShape::Filepath,
+                "Zone file to parse, with $ORIGIN, $TTL, and $INCLUDE directives. Default: the text piped in",
+            )
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone that relative names are in, until an $ORIGIN directive",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns zone parse example.com.zone",
+                description: "list the records of a zone file",
+                result: None,
+            },
+            Example {
+                example:
+                    "open --raw db.example | dns zone parse --zone example.com | where type == MX",
+                description:
+                    "parse the text of a zone file without an $ORIGIN, and find its mail servers",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "zone", "parse", "zonefile", "master", "bind", "rfc1035",
+        ]
+    }
+}
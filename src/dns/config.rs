@@ -1,35 +1,124 @@
 use std::{
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
     str::FromStr,
     time::Duration,
 };
 
-use hickory_proto::rr::{DNSClass, RecordType};
+use hickory_proto::{
+    op::OpCode,
+    rr::{DNSClass, RecordType},
+};
 use hickory_resolver::config::{Protocol, ResolverConfig};
 use nu_plugin::EvaluatedCall;
-use nu_protocol::{record, LabeledError, Span, Spanned, Value};
+use nu_protocol::{engine::Closure, record, LabeledError, Record, Span, Spanned, Value};
 
 use crate::spanned;
 
 use super::{
     constants::{self, flags},
+    dnscrypt::DnsCryptServer,
     serde::{self, DnssecMode, RType},
+    stamp::Stamp,
+    trust_anchor::TrustAnchor,
+    tsig::TsigKey,
 };
 
 #[derive(Debug)]
 pub struct Config {
     pub protocol: Spanned<Protocol>,
+    /// Queries are sent to the LLMNR multicast group instead of the server.
+    pub llmnr: Spanned<bool>,
+    /// Queries are sent to the JSON API of the server at this path, rather
+    /// than as messages in wire format over HTTPS.
+    pub dns_json: Option<Spanned<String>>,
     pub server: Spanned<SocketAddr>,
+    pub servers: Option<Spanned<Vec<Record>>>,
+    pub prefer_server_order: Spanned<bool>,
 
     pub qtypes: Spanned<Vec<Spanned<RecordType>>>,
+    /// The DMARC policy of each name is queried too, as part of the `mail`
+    /// type group.
+    pub dmarc: Spanned<bool>,
     pub class: Spanned<DNSClass>,
+    pub zip: Spanned<bool>,
 
     pub code: Spanned<bool>,
+    pub type_as_number: Spanned<bool>,
+    pub canonical_order: Spanned<bool>,
+    pub coalesce_identical_queries: Spanned<bool>,
+    pub answer_checksum: Spanned<bool>,
+    pub keep_errors: Spanned<bool>,
     pub dnssec_mode: Spanned<DnssecMode>,
+    pub trust_ad: Spanned<bool>,
+    pub edns_options: Spanned<Vec<(u16, Vec<u8>)>>,
+    /// The network and prefix length of the client subnet (RFC 7871) to send,
+    /// with the bits past the prefix cleared.
+    pub client_subnet: Option<Spanned<(IpAddr, u8)>>,
+    /// DNS cookies (RFC 7873) are sent with queries.
+    pub cookie: Spanned<bool>,
+    /// The name server identifier (RFC 5001) is asked for with queries.
+    pub nsid: Spanned<bool>,
+    /// Queries are padded to a multiple of this block size (RFC 7830).
+    pub pad: Option<Spanned<u16>>,
+    /// The UDP payload size advertised in the OPT record of queries.
+    pub edns_payload: Spanned<u16>,
+    /// Queries are sent without an OPT record, as before EDNS.
+    pub no_edns: Spanned<bool>,
+    /// Queries are sent with the RD bit cleared, for the server to answer from
+    /// its own data or refer to the nameservers of a zone closer to the name.
+    pub no_recurse: Spanned<bool>,
+    /// Queries are sent with the CD bit set, for resolvers to return records
+    /// even if they fail to validate.
+    pub checking_disabled: Spanned<bool>,
+    /// Queries are sent with the AD bit set, for resolvers to report whether
+    /// they validated the answer.
+    pub authentic_data: Spanned<bool>,
+    /// The opcode of queries, if not QUERY. Those hickory does not know are
+    /// sent as raw UDP datagrams.
+    pub opcode: Option<Spanned<u8>>,
     pub dns_name: Option<Spanned<String>>,
+    pub min_tls_version: Spanned<serde::TlsVersion>,
+    /// SHA-256 digests of certificates pinned by the stamp of the server, one
+    /// of which its certificate chain must have.
+    pub cert_hashes: Option<Spanned<Vec<Vec<u8>>>>,
+    /// The server is a DNSCrypt resolver, whose certificates are signed by
+    /// this provider.
+    pub dnscrypt: Option<Spanned<DnsCryptServer>>,
+    /// Key to sign requests with, and to check the MAC of responses with.
+    pub tsig: Option<Spanned<TsigKey>>,
+    /// The hostname of the server, with whose DNS service a GSS-TSIG key is
+    /// negotiated to sign requests with instead.
+    pub gss_tsig: Option<Spanned<String>>,
+    pub transform: Option<Spanned<Closure>>,
 
     pub tasks: Spanned<usize>,
     pub timeout: Spanned<Duration>,
+    pub qps: Option<Spanned<f64>>,
+    pub fail_fast_after: Option<Spanned<usize>>,
+    pub watch_diff: Option<Spanned<Duration>>,
+    pub jitter: Option<Spanned<Duration>>,
+    pub measure_path_mtu: Spanned<bool>,
+    pub response_window: Option<Spanned<Duration>>,
+    pub dry_run: Spanned<bool>,
+    pub since_serial: Option<Spanned<u32>>,
+    pub resolve_ns_addresses: Spanned<bool>,
+    pub min_answers: Option<Spanned<usize>>,
+    pub record_spans: Spanned<bool>,
+    pub normalize_rdata_case: Spanned<bool>,
+    pub pipeline: Spanned<bool>,
+    pub explain_dnssec: Spanned<bool>,
+    /// The keys to root `--dnssec validate` and `--explain-dnssec` at, in
+    /// place of the root keys.
+    pub trust_anchor: Option<Spanned<TrustAnchor>>,
+    pub output_format: Spanned<serde::OutputFormat>,
+    /// Only the record data of the answers is output, like `dig +short`.
+    pub short: Spanned<bool>,
+    /// The records of all sections are output as one flat table.
+    pub flatten: Spanned<bool>,
+    /// Only the answer records of the messages are output.
+    pub answers_only: Spanned<bool>,
+    /// TTLs of records are output as ints of seconds rather than durations.
+    pub ttl_as_int: Spanned<bool>,
 }
 
 impl TryFrom<Value> for Config {
@@ -54,7 +143,17 @@ impl Config {
         plugin_config: Option<Value>,
         call: &EvaluatedCall,
     ) -> Result<Self, LabeledError> {
-        tracing::debug!(?plugin_config, ?call);
+        Config::from_nu_with_overrides(plugin_config, call, &Record::new())
+    }
+
+    /// Like [`Config::from_nu`], but the columns of `overrides` take
+    /// precedence over both the CLI flags and the plugin config.
+    pub fn from_nu_with_overrides(
+        plugin_config: Option<Value>,
+        call: &EvaluatedCall,
+        overrides: &Record,
+    ) -> Result<Self, LabeledError> {
+        tracing::debug!(?plugin_config, ?call, ?overrides);
 
         let plugin_config = match plugin_config {
             None => Value::record(record!(), Span::unknown()),
@@ -62,6 +161,10 @@ impl Config {
         };
 
         Config::from_values(|name| {
+            if let Some(val) = overrides.get(name) {
+                return Some(val.clone());
+            }
+
             let cfg_val = plugin_config.get_data_by_key(name);
             let call_val = match (call.has_flag(name), call.get_flag_value(name)) {
                 (Ok(true), None) => Some(Value::bool(true, Span::unknown())),
@@ -83,8 +186,32 @@ impl Config {
     where
         F: FnMut(&str) -> Option<Value>,
     {
+        let mut llmnr = spanned!(false, Span::unknown());
+        let mut dns_json = None;
         let protocol = match get_value(flags::PROTOCOL) {
             None => None,
+            // LLMNR is plain DNS over UDP, only sent to a multicast group
+            Some(val @ Value::String { .. })
+                if val
+                    .as_str()
+                    .is_ok_and(|proto| proto.eq_ignore_ascii_case("llmnr")) =>
+            {
+                llmnr = spanned!(true, val.span());
+                Some(spanned!(Protocol::Udp, val.span()))
+            }
+            // the JSON API of a resolver (application/dns-json) is served over
+            // HTTPS, at a path of its own
+            Some(val @ Value::String { .. })
+                if val
+                    .as_str()
+                    .is_ok_and(|proto| proto.eq_ignore_ascii_case("dns-json")) =>
+            {
+                dns_json = Some(spanned!(
+                    constants::config::default::DNS_JSON_PATH.to_string(),
+                    val.span()
+                ));
+                Some(spanned!(Protocol::Https, val.span()))
+            }
             Some(val) => {
                 let span = val.span();
                 Some(
@@ -94,48 +221,180 @@ impl Config {
             }
         };
 
-        let needs_dns_name = matches!(
-            protocol,
-            Some(Spanned {
-                item: Protocol::Tls | Protocol::Https | Protocol::Quic,
-                ..
-            })
-        );
-
-        let dns_name = match get_value(constants::flags::DNS_NAME) {
-            None => None,
-            Some(val) => {
+        let since_serial = match get_value(constants::flags::SINCE_SERIAL) {
+            Some(val @ Value::Int { .. }) => {
                 let span = val.span();
+                let serial = u32::try_from(val.as_int()?).map_err(|err| {
+                    LabeledError::new("invalid serial")
+                        .with_label(format!("should be a 32 bit unsigned int: {err}"), span)
+                })?;
 
-                if !needs_dns_name {
-                    return Err(LabeledError::new("invalid config combination").with_label(
-                        "DNS name only makes sense for TLS, HTTPS, or QUIC",
-                        val.span(),
-                    ));
-                }
+                Some(spanned!(serial, span))
+            }
+            None => None,
 
-                Some(spanned!(val.into_string()?, span))
+            Some(val) => {
+                return Err(LabeledError::new("should be int")
+                    .with_label("serial should be an int", val.span()))
             }
         };
 
-        let (addr, protocol) = match get_value(flags::SERVER) {
+        let requested_protocol = protocol;
+        let mut cert_hashes = None;
+        let mut dnscrypt = None;
+
+        let (addr, protocol, server_name) = match get_value(flags::SERVER) {
             Some(ref value @ Value::String { .. }) => {
-                let protocol = protocol.unwrap_or(spanned!(Protocol::Udp, Span::unknown()));
+                let span = value.span();
+                let server = value.as_str().unwrap();
 
-                let addr = SocketAddr::from_str(value.as_str().unwrap())
-                    .or_else(|_| {
-                        IpAddr::from_str(value.as_str().unwrap()).map(|ip| {
-                            SocketAddr::new(ip, constants::config::default_port(protocol.item))
-                        })
-                    })
-                    .map_err(|err| {
-                        LabeledError::new("invalid server")
-                            .with_label(err.to_string(), value.clone().span())
-                    })?;
+                let imply = |implied: Protocol, scheme: &str| match protocol {
+                    Some(protocol) if protocol.item != implied => {
+                        Err(LabeledError::new("conflicting flags").with_label(
+                            format!(
+                                "a {}:// server can not be queried over {}",
+                                scheme, protocol.item
+                            ),
+                            protocol.span,
+                        ))
+                    }
+                    _ => Ok(spanned!(implied, span)),
+                };
+
+                // the scheme of a URL implies the protocol
+                let (server, protocol, cert_name) = match server.split_once("://") {
+                    Some(("sdns", _)) => {
+                        let mut stamp = Stamp::parse(server, span)?;
+                        let protocol = match (&stamp.dnscrypt, protocol) {
+                            // DNSCrypt is spoken over TCP as well as UDP
+                            (Some(_), Some(protocol)) if protocol.item == Protocol::Tcp => protocol,
+                            _ => imply(stamp.protocol, "sdns")?,
+                        };
+
+                        if let Some(server) = stamp.dnscrypt.take() {
+                            dnscrypt = Some(spanned!(server, span));
+                        }
+
+                        if !stamp.hashes.is_empty() {
+                            cert_hashes = Some(spanned!(std::mem::take(&mut stamp.hashes), span));
+                        }
+
+                        if !matches!(stamp.path.as_deref(), None | Some("/dns-query")) {
+                            return Err(LabeledError::new("invalid server").with_label(
+                                "only the standard /dns-query path of HTTPS is supported",
+                                span,
+                            ));
+                        }
+
+                        // the hostname may come with a port, which is only
+                        // used when there is no address
+                        let cert_name = stamp.hostname.as_ref().map(|hostname| {
+                            let name = hostname
+                                .rsplit_once(':')
+                                .map_or(hostname.as_str(), |(name, _)| name);
+                            spanned!(name.to_string(), span)
+                        });
+
+                        let server = stamp.address.or(stamp.hostname).ok_or_else(|| {
+                            LabeledError::new("invalid stamp")
+                                .with_label("stamp has neither an address nor a hostname", span)
+                        })?;
+
+                        (server, protocol, cert_name)
+                    }
+                    Some((scheme, rest)) => {
+                        let implied = match scheme.to_lowercase().as_str() {
+                            "https" => Protocol::Https,
+                            "tls" => Protocol::Tls,
+                            "quic" => Protocol::Quic,
+                            _ => {
+                                return Err(LabeledError::new("invalid server").with_label(
+                                    format!(
+                                    "unsupported scheme {}. Must be one of: https, tls, quic, sdns",
+                                    scheme
+                                ),
+                                    span,
+                                ))
+                            }
+                        };
+                        let protocol = imply(implied, scheme)?;
+
+                        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+
+                        // the path is fixed by the HTTPS client, and the other
+                        // protocols have none. JSON APIs are served at paths
+                        // of their own, e.g. /resolve
+                        let valid_path = match implied {
+                            Protocol::Https => match &mut dns_json {
+                                Some(json_path) if !path.is_empty() => {
+                                    json_path.item = format!("/{}", path);
+                                    true
+                                }
+                                Some(_) => true,
+                                None => matches!(path, "" | "dns-query"),
+                            },
+                            _ => path.is_empty(),
+                        };
+
+                        if !valid_path {
+                            return Err(LabeledError::new("invalid server").with_label(
+                                "only the standard /dns-query path of HTTPS is supported",
+                                span,
+                            ));
+                        }
+
+                        (authority.to_string(), protocol, None)
+                    }
+                    None => (
+                        server.to_string(),
+                        protocol.unwrap_or(spanned!(Protocol::Udp, Span::unknown())),
+                        None,
+                    ),
+                };
+                let server = server.as_str();
+
+                let default_port = constants::config::default_port(protocol.item);
+
+                let (addr, server_name) = match SocketAddr::from_str(server).or_else(|_| {
+                    // IPv6 addresses may be bracketed even without a port
+                    IpAddr::from_str(server.trim_start_matches('[').trim_end_matches(']'))
+                        .map(|ip| SocketAddr::new(ip, default_port))
+                }) {
+                    Ok(addr) => (addr, None),
 
-                let addr = spanned!(addr, value.span());
+                    // a hostname, which is resolved with the system resolver,
+                    // and doubles as the name of the certificate to expect
+                    Err(_) => {
+                        let (host, port) = match server.rsplit_once(':') {
+                            Some((host, port)) => (
+                                host,
+                                port.parse::<u16>().map_err(|err| {
+                                    LabeledError::new("invalid server")
+                                        .with_label(format!("invalid port: {}", err), span)
+                                })?,
+                            ),
+                            None => (server, default_port),
+                        };
+
+                        let addr = (host, port)
+                            .to_socket_addrs()
+                            .map_err(|err| {
+                                LabeledError::new("invalid server").with_label(
+                                    format!("could not resolve {}: {}", host, err),
+                                    span,
+                                )
+                            })?
+                            .next()
+                            .ok_or_else(|| {
+                                LabeledError::new("invalid server")
+                                    .with_label(format!("{} has no addresses", host), span)
+                            })?;
+
+                        (addr, Some(spanned!(host.to_string(), span)))
+                    }
+                };
 
-                (addr, protocol)
+                (spanned!(addr, span), protocol, cert_name.or(server_name))
             }
             None => {
                 let (config, _) =
@@ -145,6 +404,7 @@ impl Config {
                     [ns, ..] => (
                         spanned!(ns.socket_addr, Span::unknown()),
                         spanned!(ns.protocol, Span::unknown()),
+                        None,
                     ),
                     [] => {
                         let config = ResolverConfig::default();
@@ -155,6 +415,7 @@ impl Config {
                         (
                             spanned!(ns.socket_addr, Span::unknown()),
                             protocol.unwrap_or(spanned!(ns.protocol, Span::unknown())),
+                            None,
                         )
                     }
                 }
@@ -165,6 +426,77 @@ impl Config {
             }
         };
 
+        let needs_dns_name = [Some(protocol), requested_protocol].iter().any(|protocol| {
+            matches!(
+                protocol,
+                Some(Spanned {
+                    item: Protocol::Tls | Protocol::Https | Protocol::Quic,
+                    ..
+                })
+            )
+        });
+
+        // the name of the Kerberos principal of the server, for GSS-TSIG
+        let server_host = server_name.clone();
+
+        let dns_name = match get_value(constants::flags::DNS_NAME) {
+            None if needs_dns_name => server_name,
+            None => None,
+            Some(val) => {
+                let span = val.span();
+
+                if !needs_dns_name {
+                    return Err(LabeledError::new("invalid config combination").with_label(
+                        "DNS name only makes sense for TLS, HTTPS, or QUIC",
+                        val.span(),
+                    ));
+                }
+
+                Some(spanned!(val.into_string()?, span))
+            }
+        };
+
+        // a zone transfer takes several messages, which needs a connection
+        let protocol = match &since_serial {
+            Some(_) if protocol.item == Protocol::Udp => spanned!(Protocol::Tcp, protocol.span),
+            _ => protocol,
+        };
+
+        // each server is normalized to a record, whose columns override the
+        // config for the queries to that server
+        let servers = match get_value(flags::SERVERS) {
+            Some(list @ Value::List { .. }) => {
+                let span = list.span();
+
+                let servers = list
+                    .as_list()?
+                    .iter()
+                    .map(|val| match val {
+                        Value::String { .. } => Ok(record! {
+                            flags::SERVER => val.clone(),
+                        }),
+                        Value::Record { val: rec, .. } if rec.contains(flags::SERVER) => {
+                            Ok(rec.clone().into_owned())
+                        }
+                        val => Err(LabeledError::new("invalid server").with_label(
+                            format!(
+                                "server should be a string or a record with a '{}' column",
+                                flags::SERVER
+                            ),
+                            val.span(),
+                        )),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Some(spanned!(servers, span))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("invalid servers")
+                    .with_label("servers should be a list or a table", val.span()));
+            }
+        };
+
         if needs_dns_name && dns_name.is_none() {
             return Err(LabeledError::new("need DNS name").with_label(
                 "protocol needs to be accompanied by --dns-name",
@@ -172,34 +504,75 @@ impl Config {
             ));
         }
 
+        // a type can be given as a group name, which expands to several
+        // record types
+        let parse_qtype = |val: &Value| -> Result<Vec<Spanned<RecordType>>, LabeledError> {
+            let span = val.span();
+
+            if let Some(group) = val.as_str().ok().and_then(constants::type_groups::get) {
+                return Ok(group.iter().map(|rtype| spanned!(*rtype, span)).collect());
+            }
+
+            Ok(vec![spanned!(RType::try_from(val)?.0, span)])
+        };
+
+        let all_types = match get_value(constants::flags::ALL_TYPES) {
+            Some(val @ Value::Bool { .. }) if val.as_bool()? => Some(val.span()),
+            _ => None,
+        };
+
         let qtypes: Spanned<Vec<Spanned<RecordType>>> = match get_value(constants::flags::TYPE) {
+            Some(val) if all_types.is_some() => {
+                return Err(LabeledError::new("conflicting flags")
+                    .with_label("--all-types can not be combined with --type", val.span()))
+            }
             Some(list @ Value::List { .. }) => {
                 let span = list.span();
                 let vals = list.as_list()?;
 
                 spanned!(
                     vals.iter()
-                        .map(|val| {
-                            let span = val.span();
-                            Result::<_, LabeledError>::Ok(spanned!(RType::try_from(val)?.0, span))
-                        })
+                        .map(parse_qtype)
                         .collect::<Result<Vec<_>, _>>()?
                         .into_iter()
+                        .flatten()
                         .collect(),
                     span
                 )
             }
-            Some(ref val) => spanned!(
-                vec![spanned!(RType::try_from(val)?.0, val.span())],
-                val.span()
-            ),
-            None => spanned!(
-                vec![
-                    spanned!(RecordType::AAAA, Span::unknown()),
-                    spanned!(RecordType::A, Span::unknown()),
-                ],
-                Span::unknown()
-            ),
+            Some(ref val) => spanned!(parse_qtype(val)?, val.span()),
+            None => match all_types {
+                Some(span) => spanned!(
+                    constants::type_groups::ALL
+                        .iter()
+                        .map(|rtype| spanned!(*rtype, span))
+                        .collect(),
+                    span
+                ),
+                None => spanned!(
+                    vec![
+                        spanned!(RecordType::AAAA, Span::unknown()),
+                        spanned!(RecordType::A, Span::unknown()),
+                    ],
+                    Span::unknown()
+                ),
+            },
+        };
+
+        let is_mail = |val: &Value| {
+            val.as_str()
+                .is_ok_and(|group| group.eq_ignore_ascii_case("mail"))
+        };
+        let dmarc = match get_value(constants::flags::TYPE) {
+            Some(ref list @ Value::List { .. }) => list
+                .as_list()?
+                .iter()
+                .find(|val| is_mail(val))
+                .map_or(spanned!(false, Span::unknown()), |val| {
+                    spanned!(true, val.span())
+                }),
+            Some(ref val) if is_mail(val) => spanned!(true, val.span()),
+            _ => spanned!(false, Span::unknown()),
         };
 
         let class = match get_value(constants::flags::CLASS) {
@@ -210,6 +583,20 @@ impl Config {
             None => spanned!(hickory_proto::rr::DNSClass::IN, Span::unknown()),
         };
 
+        let zip = match get_value(constants::flags::ZIP) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let type_as_number = match get_value(constants::flags::TYPE_AS_NUMBER) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
         let code = match get_value(constants::flags::CODE) {
             Some(val @ Value::Bool { .. }) => {
                 spanned!(val.as_bool().unwrap(), val.span())
@@ -217,14 +604,397 @@ impl Config {
             _ => spanned!(false, Span::unknown()),
         };
 
+        let canonical_order = match get_value(constants::flags::CANONICAL_ORDER) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let coalesce_identical_queries =
+            match get_value(constants::flags::COALESCE_IDENTICAL_QUERIES) {
+                Some(val @ Value::Bool { .. }) => {
+                    spanned!(val.as_bool().unwrap(), val.span())
+                }
+                _ => spanned!(false, Span::unknown()),
+            };
+
+        let answer_checksum = match get_value(constants::flags::ANSWER_CHECKSUM) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let keep_errors = match get_value(constants::flags::KEEP_ERRORS) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let trust_ad = match get_value(constants::flags::TRUST_AD) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let min_tls_version = match get_value(constants::flags::MIN_TLS_VERSION) {
+            Some(val) => {
+                let span = val.span();
+
+                if !needs_dns_name {
+                    return Err(LabeledError::new("invalid config combination").with_label(
+                        "a minimum TLS version only makes sense for TLS, HTTPS, or QUIC",
+                        span,
+                    ));
+                }
+
+                spanned!(serde::TlsVersion::try_from(val)?, span)
+            }
+            None => spanned!(serde::TlsVersion::default(), Span::unknown()),
+        };
+
+        if trust_ad.item && !needs_dns_name {
+            return Err(LabeledError::new("invalid config combination").with_label(
+                "trusting the AD bit only makes sense over an encrypted protocol: TLS, HTTPS, or QUIC",
+                trust_ad.span,
+            ));
+        }
+
+        let tsig = match (
+            get_value(constants::flags::TSIG_KEY),
+            get_value(constants::flags::TSIG_KEY_FILE),
+        ) {
+            (Some(key), Some(file)) => {
+                return Err(LabeledError::new("conflicting flags")
+                    .with_label(
+                        "--tsig-key can not be combined with --tsig-key-file",
+                        key.span(),
+                    )
+                    .with_label("key file given here", file.span()))
+            }
+            (Some(key), None) => {
+                let span = key.span();
+                let key = spanned!(key.into_string()?, span);
+                Some(spanned!(TsigKey::parse(&key)?, span))
+            }
+            (None, Some(file)) => {
+                let span = file.span();
+                let file = spanned!(file.into_string()?, span);
+                Some(spanned!(TsigKey::from_file(&file)?, span))
+            }
+            (None, None) => None,
+        };
+
+        let gss_tsig = match get_value(constants::flags::GSS_TSIG) {
+            Some(val @ Value::Bool { .. }) if val.as_bool()? => {
+                let span = val.span();
+
+                if let Some(tsig) = &tsig {
+                    return Err(LabeledError::new("conflicting flags")
+                        .with_label("--gss-tsig can not be combined with a TSIG key", span)
+                        .with_label("key given here", tsig.span));
+                }
+
+                if !cfg!(feature = "gss-tsig") {
+                    return Err(LabeledError::new("unsupported flag").with_label(
+                        "GSS-TSIG needs nu_plugin_dns built with the gss-tsig feature",
+                        span,
+                    ));
+                }
+
+                let host = server_host.ok_or_else(|| {
+                    LabeledError::new("invalid config combination").with_label(
+                        "GSS-TSIG needs the server given by its hostname, which names its Kerberos principal",
+                        span,
+                    )
+                })?;
+
+                Some(spanned!(host.item, span))
+            }
+            _ => None,
+        };
+
+        let signed = tsig
+            .as_ref()
+            .map(|key| key.span)
+            .or(gss_tsig.as_ref().map(|host| host.span));
+        if let Some(span) = signed {
+            // hickory has no way to sign the requests of these clients
+            if matches!(protocol.item, Protocol::Https | Protocol::Quic) || llmnr.item {
+                return Err(LabeledError::new("invalid config combination")
+                    .with_label("TSIG is only supported over UDP, TCP, or TLS", span));
+            }
+        }
+
+        let no_edns = match get_value(constants::flags::NO_EDNS) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let edns_payload = match get_value(constants::flags::BUFSIZE) {
+            Some(val @ Value::Int { .. }) => {
+                let span = val.span();
+
+                if no_edns.item {
+                    return Err(LabeledError::new("conflicting flags").with_label(
+                        "the payload size is advertised with EDNS, which --no-edns leaves out",
+                        span,
+                    ));
+                }
+
+                spanned!(
+                    u16::try_from(val.as_int()?).map_err(|err| {
+                        LabeledError::new("invalid payload size")
+                            .with_label(format!("should be from 0 to 65535: {err}"), span)
+                    })?,
+                    span
+                )
+            }
+            Some(val) => {
+                return Err(LabeledError::new("should be int")
+                    .with_label("payload size should be an int", val.span()))
+            }
+            None => spanned!(constants::config::default::EDNS_PAYLOAD, Span::unknown()),
+        };
+
+        let checking_disabled = match get_value(constants::flags::CHECKING_DISABLED) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let authentic_data = match get_value(constants::flags::AUTHENTIC_DATA) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        // the JSON APIs take the CD bit as a parameter, but not the AD bit
+        if authentic_data.item && dns_json.is_some() {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--authentic-data can not be combined with --protocol dns-json",
+                authentic_data.span,
+            ));
+        }
+
         let dnssec_mode = match get_value(constants::flags::DNSSEC) {
             Some(val) => {
                 let span = val.span();
-                spanned!(serde::DnssecMode::try_from(val)?, span)
+                let mode = serde::DnssecMode::try_from(val)?;
+
+                if since_serial.is_some() && mode != serde::DnssecMode::None {
+                    return Err(LabeledError::new("invalid config combination")
+                        .with_label("zone transfers are not DNSSEC validated", span));
+                }
+
+                if dns_json.is_some() && mode != serde::DnssecMode::None {
+                    return Err(LabeledError::new("invalid config combination").with_label(
+                        "JSON APIs return records without the signatures to validate them with",
+                        span,
+                    ));
+                }
+
+                if trust_ad.item && mode != serde::DnssecMode::None {
+                    return Err(LabeledError::new("invalid config combination").with_label(
+                        "DNSSEC validation is left to the resolver when trusting the AD bit",
+                        span,
+                    ));
+                }
+
+                if no_edns.item && mode != serde::DnssecMode::None {
+                    return Err(LabeledError::new("invalid config combination").with_label(
+                        "signatures are asked for with the DO bit of EDNS, which --no-edns leaves out",
+                        span,
+                    ));
+                }
+
+                spanned!(mode, span)
+            }
+
+            // the resolver does the validation
+            // with checking disabled, bogus records are returned to be looked
+            // at, rather than to fail validation
+            None if trust_ad.item
+                || since_serial.is_some()
+                || dns_json.is_some()
+                || no_edns.item
+                || checking_disabled.item =>
+            {
+                spanned!(serde::DnssecMode::None, Span::unknown())
             }
             None => spanned!(serde::DnssecMode::Opportunistic, Span::unknown()),
         };
 
+        let edns_options = match get_value(constants::flags::EDNS_OPTION) {
+            Some(list @ Value::List { .. }) => {
+                let span = list.span();
+
+                spanned!(
+                    list.as_list()?
+                        .iter()
+                        .map(|val| serde::RawEdnsOption::try_from(val).map(|opt| (opt.0, opt.1)))
+                        .collect::<Result<_, _>>()?,
+                    span
+                )
+            }
+            Some(val) => {
+                let opt = serde::RawEdnsOption::try_from(&val)?;
+                spanned!(vec![(opt.0, opt.1)], val.span())
+            }
+            None => spanned!(vec![], Span::unknown()),
+        };
+
+        let client_subnet = match get_value(constants::flags::CLIENT_SUBNET) {
+            Some(val @ Value::String { .. }) => {
+                let span = val.span();
+                let invalid =
+                    |msg: String| LabeledError::new("invalid client subnet").with_label(msg, span);
+
+                let (address, prefix) = match val.as_str()?.split_once('/') {
+                    Some((address, prefix)) => (address, Some(prefix)),
+                    None => (val.as_str()?, None),
+                };
+                let address = IpAddr::from_str(address)
+                    .map_err(|err| invalid(format!("should be an IP address: {err}")))?;
+                let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+                let prefix = match prefix {
+                    Some(prefix) => prefix
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|prefix| *prefix <= max_prefix)
+                        .ok_or_else(|| {
+                            invalid(format!("prefix length should be from 0 to {max_prefix}"))
+                        })?,
+                    None => max_prefix,
+                };
+
+                // the address must not have bits past the prefix set (RFC 7871
+                // section 6)
+                let network = match address {
+                    IpAddr::V4(addr) => IpAddr::from(std::net::Ipv4Addr::from(
+                        u32::from(addr) & u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0),
+                    )),
+                    IpAddr::V6(addr) => IpAddr::from(std::net::Ipv6Addr::from(
+                        u128::from(addr) & u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0),
+                    )),
+                };
+
+                Some(spanned!((network, prefix), span))
+            }
+            None => None,
+
+            Some(val) => {
+                return Err(LabeledError::new("invalid client subnet").with_label(
+                    "client subnet should be a string such as 203.0.113.0/24",
+                    val.span(),
+                ))
+            }
+        };
+
+        let cookie = match get_value(constants::flags::COOKIE) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        // the JSON APIs take no EDNS options, and answer over their own
+        // connections anyway
+        if cookie.item && dns_json.is_some() {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--cookie can not be combined with --protocol dns-json",
+                cookie.span,
+            ));
+        }
+
+        let nsid = match get_value(constants::flags::NSID) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if nsid.item && dns_json.is_some() {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--nsid can not be combined with --protocol dns-json",
+                nsid.span,
+            ));
+        }
+
+        let pad = match (
+            get_value(constants::flags::PAD),
+            get_value(constants::flags::PAD_BLOCK_SIZE),
+        ) {
+            (_, Some(val @ Value::Int { .. })) => {
+                let span = val.span();
+                let block_size = u16::try_from(val.as_int()?)
+                    .ok()
+                    .filter(|block_size| *block_size > 0)
+                    .ok_or_else(|| {
+                        LabeledError::new("invalid block size")
+                            .with_label("block size should be from 1 to 65535", span)
+                    })?;
+
+                Some(spanned!(block_size, span))
+            }
+            (_, Some(val)) => {
+                return Err(LabeledError::new("should be int")
+                    .with_label("block size should be an int", val.span()))
+            }
+            (Some(val @ Value::Bool { .. }), None) if val.as_bool()? => Some(spanned!(
+                constants::config::default::PAD_BLOCK_SIZE,
+                val.span()
+            )),
+            _ => None,
+        };
+
+        // padding hides the size of queries only from those who can not read
+        // them anyway (RFC 7830 section 6)
+        if let Some(pad) = &pad {
+            if !needs_dns_name || dns_json.is_some() {
+                return Err(LabeledError::new("invalid config combination").with_label(
+                    "padding only makes sense over an encrypted protocol with EDNS: TLS, HTTPS, or QUIC",
+                    pad.span,
+                ));
+            }
+        }
+
+        if no_edns.item
+            && (!edns_options.item.is_empty()
+                || client_subnet.is_some()
+                || cookie.item
+                || nsid.item
+                || pad.is_some())
+        {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "EDNS options, client subnets, cookies, NSID, and padding can not be sent with --no-edns",
+                no_edns.span,
+            ));
+        }
+
+        let no_recurse = match get_value(constants::flags::NO_RECURSE) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        // the JSON APIs always resolve the name
+        if no_recurse.item && dns_json.is_some() {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--no-recurse can not be combined with --protocol dns-json",
+                no_recurse.span,
+            ));
+        }
+
+        let transform = match get_value(constants::flags::TRANSFORM) {
+            Some(val @ Value::Closure { .. }) => {
+                let span = val.span();
+                Some(spanned!(val.into_closure()?, span))
+            }
+            None => None,
+
+            Some(val) => {
+                return Err(LabeledError::new("should be closure")
+                    .with_label("transform should be a closure", val.span()))
+            }
+        };
+
         let tasks = match get_value(constants::flags::TASKS) {
             Some(val @ Value::Int { .. }) => {
                 let span = val.span();
@@ -263,16 +1033,445 @@ impl Config {
             }
         };
 
+        let qps = match get_value(constants::flags::QPS) {
+            Some(val @ (Value::Int { .. } | Value::Float { .. })) => {
+                let span = val.span();
+                let qps = val.coerce_float()?;
+
+                if qps <= 0.0 || !qps.is_finite() {
+                    return Err(LabeledError::new("invalid input")
+                        .with_label("queries per second should be a positive number", span));
+                }
+
+                Some(spanned!(qps, span))
+            }
+            None => None,
+
+            Some(val) => {
+                return Err(LabeledError::new("should be number")
+                    .with_label("queries per second should be a number", val.span()))
+            }
+        };
+
+        let fail_fast_after = match get_value(constants::flags::FAIL_FAST_AFTER) {
+            Some(val @ Value::Int { .. }) => {
+                let span = val.span();
+                let limit: usize = val.as_int()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid input")
+                        .with_label(format!("should be positive int: {err}"), span)
+                })?;
+
+                if limit == 0 {
+                    return Err(LabeledError::new("invalid input")
+                        .with_label("should be positive int", span));
+                }
+
+                Some(spanned!(limit, span))
+            }
+            None => None,
+
+            Some(val) => {
+                return Err(LabeledError::new("should be int")
+                    .with_label("number of failures should be an int", val.span()))
+            }
+        };
+
+        let watch_diff = match get_value(constants::flags::WATCH_DIFF) {
+            Some(val @ Value::Duration { .. }) => {
+                let span = val.span();
+                let nanos: u64 = val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), span)
+                })?;
+
+                if nanos == 0 {
+                    return Err(LabeledError::new("invalid duration")
+                        .with_label("poll interval should be a positive duration", span));
+                }
+
+                if servers.is_some() {
+                    return Err(LabeledError::new("conflicting flags")
+                        .with_label("--watch-diff can not be combined with --servers", span));
+                }
+
+                Some(spanned!(Duration::from_nanos(nanos), span))
+            }
+            None => None,
+
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("poll interval should be a positive duration", val.span()))
+            }
+        };
+
+        let jitter = match (get_value(constants::flags::JITTER), &watch_diff) {
+            (Some(val @ Value::Duration { .. }), Some(interval)) => {
+                let span = val.span();
+                let nanos: u64 = val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), span)
+                })?;
+                let jitter = Duration::from_nanos(nanos);
+
+                if jitter > interval.item {
+                    return Err(LabeledError::new("invalid duration")
+                        .with_label("jitter should not be longer than the poll interval", span));
+                }
+
+                Some(spanned!(jitter, span))
+            }
+            (Some(val @ Value::Duration { .. }), None) => {
+                return Err(LabeledError::new("invalid config combination").with_label(
+                    "jitter only makes sense when polling with --watch-diff",
+                    val.span(),
+                ));
+            }
+            (None, Some(interval)) => Some(spanned!(
+                interval.item / constants::config::default::JITTER_INTERVAL_DIVISOR,
+                Span::unknown()
+            )),
+            (None, None) => None,
+            (Some(val), _) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("jitter should be a duration", val.span()))
+            }
+        };
+
+        let measure_path_mtu = match get_value(constants::flags::MEASURE_PATH_MTU) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if measure_path_mtu.item && (servers.is_some() || watch_diff.is_some()) {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--measure-path-mtu can not be combined with --servers or --watch-diff",
+                measure_path_mtu.span,
+            ));
+        }
+
+        let response_window = match get_value(constants::flags::RESPONSE_WINDOW) {
+            Some(val @ Value::Duration { .. }) => {
+                let span = val.span();
+                let nanos: u64 = val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), span)
+                })?;
+
+                // any number of hosts may respond to a datagram, but only to
+                // one on a connection
+                if protocol.item != Protocol::Udp {
+                    return Err(LabeledError::new("unsupported protocol")
+                        .with_label("collecting several responses needs UDP", span));
+                }
+
+                Some(spanned!(Duration::from_nanos(nanos), span))
+            }
+            None => None,
+
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("response window should be a positive duration", val.span()))
+            }
+        };
+
+        let opcode = match get_value(constants::flags::OPCODE) {
+            Some(val) => {
+                let span = val.span();
+                Some(spanned!(serde::OpCode::try_from(val)?.0, span))
+            }
+            None => None,
+        };
+
+        if let Some(opcode) = &opcode {
+            // the JSON APIs only take queries
+            if dns_json.is_some() {
+                return Err(LabeledError::new("conflicting flags").with_label(
+                    "--opcode can not be combined with --protocol dns-json",
+                    opcode.span,
+                ));
+            }
+
+            if OpCode::from_u8(opcode.item).is_err() {
+                // it is written into the encoded query, which is sent as a
+                // datagram of its own
+                let unsupported = if protocol.item != Protocol::Udp {
+                    Some("is only sent over UDP")
+                } else if tsig.is_some() {
+                    Some("can not be signed with TSIG")
+                } else if response_window.is_some() {
+                    Some("can not be combined with --response-window")
+                } else {
+                    None
+                };
+
+                if let Some(unsupported) = unsupported {
+                    return Err(LabeledError::new("invalid config combination").with_label(
+                        format!("opcode {} {}", opcode.item, unsupported),
+                        opcode.span,
+                    ));
+                }
+            }
+        }
+
+        if let Some(dnscrypt) = &dnscrypt {
+            // these are sent as they are, from sockets of their own, or
+            // expect several responses to a query, where DNSCrypt answers
+            // each query with one
+            let unsupported = if response_window.is_some() {
+                Some("--response-window")
+            } else if measure_path_mtu.item {
+                Some("--measure-path-mtu")
+            } else if since_serial.is_some() {
+                Some("--since-serial")
+            } else if opcode
+                .as_ref()
+                .is_some_and(|opcode| OpCode::from_u8(opcode.item).is_err())
+            {
+                Some("--opcode with an opcode hickory does not know")
+            } else {
+                None
+            };
+
+            if let Some(unsupported) = unsupported {
+                return Err(LabeledError::new("invalid config combination").with_label(
+                    format!("{} can not be combined with a DNSCrypt server", unsupported),
+                    dnscrypt.span,
+                ));
+            }
+        }
+
+        let dry_run = match get_value(constants::flags::DRY_RUN) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let prefer_server_order = match get_value(constants::flags::PREFER_SERVER_ORDER) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if prefer_server_order.item && servers.is_none() {
+            return Err(LabeledError::new("invalid config combination").with_label(
+                "merging the messages of several servers needs --servers",
+                prefer_server_order.span,
+            ));
+        }
+
+        let resolve_ns_addresses = match get_value(constants::flags::RESOLVE_NS_ADDRESSES) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if resolve_ns_addresses.item && response_window.is_some() {
+            return Err(LabeledError::new("invalid config combination").with_label(
+                "resolving nameserver addresses needs a single response per query, which --response-window does not guarantee",
+                resolve_ns_addresses.span,
+            ));
+        }
+
+        let min_answers = match get_value(constants::flags::MIN_ANSWERS) {
+            Some(val @ Value::Int { .. }) => {
+                let span = val.span();
+                let min: usize = val.as_int()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid input")
+                        .with_label(format!("should be positive int: {err}"), span)
+                })?;
+
+                Some(spanned!(min, span))
+            }
+            None => None,
+
+            Some(val) => {
+                return Err(LabeledError::new("should be int")
+                    .with_label("number of answers should be an int", val.span()))
+            }
+        };
+
+        let record_spans = match get_value(constants::flags::RECORD_SPANS) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if record_spans.item && response_window.is_some() {
+            return Err(LabeledError::new("invalid config combination").with_label(
+                "the messages collected in a response window are not kept as received, so their record spans are unknown",
+                record_spans.span,
+            ));
+        }
+
+        let normalize_rdata_case = match get_value(constants::flags::NORMALIZE_RDATA_CASE) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let pipeline = match get_value(constants::flags::PIPELINE) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if pipeline.item && (servers.is_some() || watch_diff.is_some()) {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--pipeline can not be combined with --servers or --watch-diff",
+                pipeline.span,
+            ));
+        }
+
+        let explain_dnssec = match get_value(constants::flags::EXPLAIN_DNSSEC) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if explain_dnssec.item && (servers.is_some() || watch_diff.is_some()) {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--explain-dnssec can not be combined with --servers or --watch-diff",
+                explain_dnssec.span,
+            ));
+        }
+
+        let trust_anchor = match get_value(constants::flags::TRUST_ANCHOR) {
+            Some(val) => {
+                let span = val.span();
+                let path = spanned!(val.into_string()?, span);
+                Some(spanned!(TrustAnchor::from_file(&path)?, span))
+            }
+            None => None,
+        };
+
+        // hickory only validates from the root keys built into it
+        if let Some(trust_anchor) = &trust_anchor {
+            if dnssec_mode.item != DnssecMode::Validate && !explain_dnssec.item {
+                return Err(LabeledError::new("invalid config combination").with_label(
+                    "a trust anchor is only used by --dnssec validate and --explain-dnssec",
+                    trust_anchor.span,
+                ));
+            }
+        }
+
+        let output_format = match get_value(constants::flags::OUTPUT_FORMAT) {
+            Some(val) => {
+                let span = val.span();
+                spanned!(serde::OutputFormat::try_from(val)?, span)
+            }
+            None => spanned!(serde::OutputFormat::default(), Span::unknown()),
+        };
+
+        // a watch never ends, so there would never be a whole document to encode
+        if output_format.item != serde::OutputFormat::Table && watch_diff.is_some() {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--output-format can not be combined with --watch-diff",
+                output_format.span,
+            ));
+        }
+
+        let short = match get_value(constants::flags::SHORT) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if short.item && (watch_diff.is_some() || output_format.item == serde::OutputFormat::Dig) {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--short can not be combined with --watch-diff or --output-format dig",
+                short.span,
+            ));
+        }
+
+        let flatten = match get_value(constants::flags::FLATTEN) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if flatten.item
+            && (short.item
+                || watch_diff.is_some()
+                || output_format.item == serde::OutputFormat::Dig)
+        {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--flatten can not be combined with --short, --watch-diff, or --output-format dig",
+                flatten.span,
+            ));
+        }
+
+        let answers_only = match get_value(constants::flags::ANSWERS_ONLY) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if answers_only.item
+            && (short.item
+                || flatten.item
+                || watch_diff.is_some()
+                || output_format.item == serde::OutputFormat::Dig)
+        {
+            return Err(LabeledError::new("conflicting flags").with_label(
+                "--answers-only can not be combined with --short, --flatten, --watch-diff, or --output-format dig",
+                answers_only.span,
+            ));
+        }
+
+        let ttl_as_int = match get_value(constants::flags::TTL_AS_INT) {
+            Some(val @ Value::Bool { .. }) => spanned!(val.as_bool()?, val.span()),
+            _ => spanned!(false, Span::unknown()),
+        };
+
         Ok(Self {
             protocol,
+            llmnr,
+            dns_json,
             server: addr,
+            servers,
+            prefer_server_order,
             qtypes,
+            dmarc,
+            zip,
             code,
+            type_as_number,
+            canonical_order,
+            coalesce_identical_queries,
+            answer_checksum,
+            keep_errors,
             class,
             dnssec_mode,
+            trust_ad,
+            edns_options,
+            client_subnet,
+            cookie,
+            nsid,
+            pad,
+            edns_payload,
+            no_edns,
+            no_recurse,
+            checking_disabled,
+            authentic_data,
+            opcode,
             dns_name,
+            min_tls_version,
+            cert_hashes,
+            dnscrypt,
+            tsig,
+            gss_tsig,
+            transform,
             tasks,
             timeout,
+            qps,
+            fail_fast_after,
+            watch_diff,
+            jitter,
+            measure_path_mtu,
+            response_window,
+            dry_run,
+            since_serial,
+            resolve_ns_addresses,
+            min_answers,
+            record_spans,
+            normalize_rdata_case,
+            pipeline,
+            explain_dnssec,
+            trust_anchor,
+            output_format,
+            short,
+            flatten,
+            answers_only,
+            ttl_as_int,
         })
     }
 }
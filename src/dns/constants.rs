@@ -1,28 +1,338 @@
 pub mod commands {
     pub const QUERY: &str = "dns query";
+    pub const OWNER_NAME: &str = "dns owner-name";
+    pub const NSEC3_HASH: &str = "dns nsec3-hash";
+    pub const DNSSEC_DS: &str = "dns dnssec ds";
+    pub const DNSSEC_CHECK_EXPIRY: &str = "dns dnssec check-expiry";
+    pub const DNSSEC_CHECK_CDS: &str = "dns dnssec check-cds";
+    pub const SRV: &str = "dns srv";
+    pub const PING: &str = "dns ping";
+    pub const SERVER_ID: &str = "dns server-id";
+    pub const AXFR: &str = "dns axfr";
+    pub const IXFR: &str = "dns ixfr";
+    pub const TRACE: &str = "dns trace";
+    pub const WALK: &str = "dns walk";
+    pub const DISCOVER_RESOLVERS: &str = "dns discover-resolvers";
+    pub const BROWSE: &str = "dns browse";
+    pub const REVERSE: &str = "dns reverse";
+    pub const UPDATE: &str = "dns update";
+    pub const NOTIFY: &str = "dns notify";
+    pub const SERVE: &str = "dns serve";
+    pub const PARSE: &str = "dns parse";
+    pub const BUILD: &str = "dns build";
+    pub const TO_JSON: &str = "dns to-json";
+    pub const ZONE_PARSE: &str = "dns zone parse";
+    pub const ZONE_CHECK: &str = "dns zone check";
+    pub const ZONE_EXPORT: &str = "dns zone export";
 }
 
 pub mod flags {
     pub const DNS_NAME: &str = "dns-name";
     pub const NAME: &str = "name";
     pub const SERVER: &str = "server";
+    pub const SERVERS: &str = "servers";
+    pub const PREFER_SERVER_ORDER: &str = "prefer-server-order";
     pub const PROTOCOL: &str = "protocol";
     pub const TYPE: &str = "type";
     pub const CLASS: &str = "class";
     pub const DNSSEC: &str = "dnssec";
+    pub const TRUST_AD: &str = "trust-ad";
     pub const CODE: &str = "code";
+    pub const TYPE_AS_NUMBER: &str = "output-record-type-as-number";
+    pub const CANONICAL_ORDER: &str = "canonical-order";
+    pub const ZIP: &str = "zip";
+    pub const KEEP_ERRORS: &str = "keep-errors";
+    pub const EDNS_OPTION: &str = "edns-option";
+    pub const CLIENT_SUBNET: &str = "client-subnet";
+    pub const COOKIE: &str = "cookie";
+    pub const NSID: &str = "nsid";
+    pub const PAD: &str = "pad";
+    pub const PAD_BLOCK_SIZE: &str = "pad-block-size";
+    pub const BUFSIZE: &str = "bufsize";
+    pub const NO_EDNS: &str = "no-edns";
+    pub const NO_RECURSE: &str = "no-recurse";
+    pub const CHECKING_DISABLED: &str = "checking-disabled";
+    pub const AUTHENTIC_DATA: &str = "authentic-data";
+    pub const OPCODE: &str = "opcode";
+    pub const ALL_TYPES: &str = "all-types";
     pub const TASKS: &str = "tasks";
     pub const TIMEOUT: &str = "timeout";
+    pub const QPS: &str = "qps";
+    pub const FAIL_FAST_AFTER: &str = "fail-fast-after";
+    pub const WATCH_DIFF: &str = "watch-diff";
+    pub const TRANSFORM: &str = "transform";
+    pub const MEASURE_PATH_MTU: &str = "measure-path-mtu";
+    pub const RESPONSE_WINDOW: &str = "response-window";
+    pub const DRY_RUN: &str = "dry-run";
+    pub const SINCE_SERIAL: &str = "since-serial";
+    pub const SCHEME: &str = "scheme";
+    pub const ZONE: &str = "zone";
+    pub const SALT: &str = "salt";
+    pub const ITERATIONS: &str = "iterations";
+    pub const PORT: &str = "port";
+    pub const TRANSPORT: &str = "transport";
+    pub const SERVICE: &str = "service";
+    pub const PROTO: &str = "proto";
+    pub const DOMAIN: &str = "domain";
+    pub const OUTPUT: &str = "output";
+    pub const RESOLVE_NS_ADDRESSES: &str = "resolve-ns-addresses";
+    pub const MIN_ANSWERS: &str = "min-answers";
+    pub const MAX_DEPTH: &str = "max-depth";
+    pub const MAX_NAMES: &str = "max-names";
+    pub const DIGEST: &str = "digest";
+    pub const ALL_KEYS: &str = "all-keys";
+    pub const WARN: &str = "warn";
+    pub const RECORD_SPANS: &str = "record-spans";
+    pub const NORMALIZE_RDATA_CASE: &str = "normalize-rdata-case";
+    pub const PIPELINE: &str = "pipeline";
+    pub const EXPLAIN_DNSSEC: &str = "explain-dnssec";
+    pub const TRUST_ANCHOR: &str = "trust-anchor";
+    pub const MIN_TLS_VERSION: &str = "min-tls-version";
+    pub const OUTPUT_FORMAT: &str = "output-format";
+    pub const SHORT: &str = "short";
+    pub const FLATTEN: &str = "flatten";
+    pub const ANSWERS_ONLY: &str = "answers-only";
+    pub const TTL_AS_INT: &str = "ttl-as-int";
+    pub const JITTER: &str = "jitter";
+    pub const ANSWER_CHECKSUM: &str = "answer-checksum";
+    pub const COALESCE_IDENTICAL_QUERIES: &str = "coalesce-identical-queries";
+    pub const ADDRESS: &str = "address";
+    pub const ACTION: &str = "action";
+    pub const RDATA: &str = "rdata";
+    pub const TTL: &str = "ttl";
+    pub const PREREQ_EXISTS: &str = "prereq-exists";
+    pub const PREREQ_NOT_EXISTS: &str = "prereq-not-exists";
+    pub const TSIG_KEY: &str = "tsig-key";
+    pub const TSIG_KEY_FILE: &str = "tsig-key-file";
+    pub const GSS_TSIG: &str = "gss-tsig";
+    pub const SERIAL: &str = "serial";
+    pub const ZONE_FILE: &str = "zone-file";
+    pub const LISTEN: &str = "listen";
+    pub const PCAP: &str = "pcap";
+}
+
+/// Codes of record types that hickory does not know by name.
+pub mod rtypes {
+    /// Application Visibility and Control.
+    pub const AVC: u16 = 258;
+    /// Digital Object Architecture.
+    pub const DOA: u16 = 259;
+    /// Host Identity Protocol.
+    pub const HIP: u16 = 55;
+
+    pub const NAMED: &[(&str, u16)] = &[("AVC", AVC), ("DOA", DOA), ("HIP", HIP)];
+
+    pub fn name(code: u16) -> Option<&'static str> {
+        NAMED
+            .iter()
+            .find_map(|(name, named_code)| (*named_code == code).then_some(*name))
+    }
+
+    pub fn code(name: &str) -> Option<u16> {
+        NAMED
+            .iter()
+            .find_map(|(named, code)| named.eq_ignore_ascii_case(name).then_some(*code))
+    }
+}
+
+/// Extended DNS Errors (RFC 8914).
+pub mod ede {
+    /// EDNS option carrying an extended error.
+    pub const OPTION_CODE: u16 = 15;
+
+    /// Purposes of the registered info codes.
+    pub const PURPOSES: &[&str] = &[
+        "Other Error",
+        "Unsupported DNSKEY Algorithm",
+        "Unsupported DS Digest Type",
+        "Stale Answer",
+        "Forged Answer",
+        "DNSSEC Indeterminate",
+        "DNSSEC Bogus",
+        "Signature Expired",
+        "Signature Not Yet Valid",
+        "DNSKEY Missing",
+        "RRSIGs Missing",
+        "No Zone Key Bit Set",
+        "NSEC Missing",
+        "Cached Error",
+        "Not Ready",
+        "Blocked",
+        "Censored",
+        "Filtered",
+        "Prohibited",
+        "Stale NXDomain Answer",
+        "Not Authoritative",
+        "Not Supported",
+        "No Reachable Authority",
+        "Network Error",
+        "Invalid Data",
+        "Signature Expired before Valid",
+        "Too Early",
+        "Unsupported NSEC3 Iterations Value",
+        "Unable to conform to policy",
+        "Synthesized",
+        "Invalid Query Type",
+    ];
+
+    pub fn purpose(code: u16) -> Option<&'static str> {
+        PURPOSES.get(code as usize).copied()
+    }
+}
+
+/// Codes of the EDNS options read into their fields.
+pub mod edns {
+    /// Name server identifier (RFC 5001).
+    pub const NSID: u16 = 3;
+    /// Client subnet (RFC 7871).
+    pub const SUBNET: u16 = 8;
+    /// Cookie (RFC 7873).
+    pub const COOKIE: u16 = 10;
+    /// Padding (RFC 7830).
+    pub const PADDING: u16 = 12;
+}
+
+pub mod type_groups {
+    use hickory_proto::rr::RecordType;
+
+    /// Mail routing and policy. SPF policies are published as TXT records.
+    /// DMARC policies are also TXT records, but live under `_dmarc.<domain>`,
+    /// so the group also queries TXT records at [`DMARC_LABEL`] below each
+    /// name.
+    pub const MAIL: &[RecordType] = &[RecordType::MX, RecordType::TXT];
+    /// The label that DMARC policies are published under (RFC 7489 section
+    /// 6.1).
+    pub const DMARC_LABEL: &str = "_dmarc";
+    pub const WEB: &[RecordType] = &[
+        RecordType::A,
+        RecordType::AAAA,
+        RecordType::CNAME,
+        RecordType::HTTPS,
+    ];
+    pub const DNSSEC: &[RecordType] = &[
+        RecordType::DNSKEY,
+        RecordType::DS,
+        RecordType::RRSIG,
+        RecordType::NSEC,
+    ];
+    /// The common types, queried one by one, for servers that answer ANY
+    /// queries with a single record only (RFC 8482).
+    pub const ALL: &[RecordType] = &[
+        RecordType::A,
+        RecordType::AAAA,
+        RecordType::CNAME,
+        RecordType::MX,
+        RecordType::NS,
+        RecordType::SOA,
+        RecordType::TXT,
+        RecordType::SRV,
+        RecordType::CAA,
+        RecordType::HTTPS,
+    ];
+
+    pub fn get(name: &str) -> Option<&'static [RecordType]> {
+        match name.to_lowercase().as_str() {
+            "mail" => Some(MAIL),
+            "web" => Some(WEB),
+            "dnssec" => Some(DNSSEC),
+            "all" => Some(ALL),
+            _ => None,
+        }
+    }
 }
 
 pub mod config {
     use hickory_resolver::config::Protocol;
 
     pub mod default {
-        use std::time::Duration;
+        use std::{
+            net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+            time::Duration,
+        };
+
+        use hickory_proto::rr::{dnssec::DigestType, RecordType};
 
         pub const TASKS: usize = 8;
+        /// The largest range `dns reverse` sweeps, a /16 of IPv4 addresses.
+        pub const REVERSE_SWEEP_MAX_ADDRESSES: u128 = 1 << 16;
+        pub const UPDATE_TTL: u32 = 3600;
+        /// The TTL of records served without one.
+        pub const ZONE_TTL: u32 = 3600;
+        /// An unprivileged port on the loopback interface.
+        pub const SERVE_ADDRESS: SocketAddr =
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 1053));
+        /// How far the clocks of the client and server may be apart for a
+        /// TSIG to be accepted (RFC 8945 section 10).
+        pub const TSIG_FUDGE: u16 = 300;
         pub const TIMEOUT: Duration = Duration::from_secs(5);
+        /// How long a failed query waits for the connection to be reported
+        /// closed. The pending queries fail just before the task driving
+        /// the connection exits, which may run on another thread.
+        pub const CLOSE_GRACE: Duration = Duration::from_millis(50);
+        pub const EDNS_PAYLOAD: u16 = 1232;
+        /// The block size clients should pad queries to (RFC 8467 section
+        /// 4.1).
+        pub const PAD_BLOCK_SIZE: u16 = 128;
+        pub const TLSA_PORT: u16 = 443;
+        /// The port of DNSCrypt resolvers whose stamp leaves it out.
+        pub const DNSCRYPT_PORT: u16 = 443;
+        /// How long a DNSCrypt certificate is used before the resolver is
+        /// asked for its certificates again, so that a new one is picked up
+        /// before the old one expires.
+        pub const DNSCRYPT_CERT_REFRESH: Duration = Duration::from_secs(60 * 60);
+        /// The digest type that DS records have to be published with (RFC
+        /// 8624 section 3.3).
+        pub const DS_DIGEST_TYPE: DigestType = DigestType::SHA256;
+        pub const PATH_MTU_MIN_PAYLOAD: usize = 512;
+        /// Jumbo frames of 9000 bytes, less the IPv4 and UDP headers.
+        pub const PATH_MTU_MAX_PAYLOAD: usize = 8972;
+        pub const PATH_MTU_ATTEMPTS: usize = 2;
+        pub const TLSA_TRANSPORT: &str = "tcp";
+        pub const TRACE_MAX_DEPTH: usize = 20;
+        pub const WALK_MAX_NAMES: usize = 10_000;
+        /// The RRsets of a zone that `dns dnssec check-expiry` checks, unless
+        /// types are given.
+        pub const EXPIRY_TYPES: &[RecordType] =
+            &[RecordType::SOA, RecordType::NS, RecordType::DNSKEY];
+        /// Signatures that expire within this long are warned about.
+        pub const EXPIRY_WARNING: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+        /// The IPv4 multicast group of mDNS (RFC 6762 section 3).
+        pub const MDNS_GROUP: SocketAddr =
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(224, 0, 0, 251), 5353));
+        /// How long to wait for mDNS responses, unless a response window is
+        /// given. Responders delay their answers by up to 120ms, and many
+        /// hosts may answer.
+        pub const MDNS_RESPONSE_WINDOW: Duration = Duration::from_secs(1);
+        /// The IPv4 multicast group of LLMNR (RFC 4795 section 2).
+        pub const LLMNR_GROUP: SocketAddr =
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(224, 0, 0, 252), 5355));
+        /// How long to wait for LLMNR responses, unless a response window is
+        /// given (LLMNR_TIMEOUT, RFC 4795 section 7).
+        pub const LLMNR_RESPONSE_WINDOW: Duration = Duration::from_secs(1);
+        /// The path of the JSON API of a resolver, unless the server is given
+        /// as a URL with another. Cloudflare serves it at the same path as
+        /// DNS over HTTPS.
+        pub const DNS_JSON_PATH: &str = "/dns-query";
+        /// Polls are delayed by up to this fraction of the interval, unless
+        /// the jitter is given.
+        pub const JITTER_INTERVAL_DIVISOR: u32 = 10;
+
+        /// IPv4 addresses of the root servers, a through m.
+        pub const ROOT_SERVERS: &[IpAddr] = &[
+            IpAddr::V4(Ipv4Addr::new(198, 41, 0, 4)),
+            IpAddr::V4(Ipv4Addr::new(170, 247, 170, 2)),
+            IpAddr::V4(Ipv4Addr::new(192, 33, 4, 12)),
+            IpAddr::V4(Ipv4Addr::new(199, 7, 91, 13)),
+            IpAddr::V4(Ipv4Addr::new(192, 203, 230, 10)),
+            IpAddr::V4(Ipv4Addr::new(192, 5, 5, 241)),
+            IpAddr::V4(Ipv4Addr::new(192, 112, 36, 4)),
+            IpAddr::V4(Ipv4Addr::new(198, 97, 190, 53)),
+            IpAddr::V4(Ipv4Addr::new(192, 36, 148, 17)),
+            IpAddr::V4(Ipv4Addr::new(192, 58, 128, 30)),
+            IpAddr::V4(Ipv4Addr::new(193, 0, 14, 129)),
+            IpAddr::V4(Ipv4Addr::new(199, 7, 83, 42)),
+            IpAddr::V4(Ipv4Addr::new(202, 12, 27, 33)),
+        ];
     }
 
     pub fn default_port(protocol: Protocol) -> u16 {
@@ -39,15 +349,19 @@ pub mod columns {
     pub const NAME: &str = "name";
     pub const TYPE: &str = "type";
     pub const CLASS: &str = "class";
+    pub const TTL: &str = "ttl";
+    pub const RDATA: &str = "rdata";
 
     pub const MESSAGE_COLS: &[&str] = &[
         "header",
+        "status",
         "question",
         "answer",
         "authority",
         "additional",
         "edns",
         "size",
+        "responder",
     ];
 
     pub const HEADER_COLS: &[&str] = &[
@@ -59,6 +373,7 @@ pub mod columns {
         "recursion_desired",
         "recursion_available",
         "authentic_data",
+        "checking_disabled",
         "response_code",
         "query_count",
         "answer_count",
@@ -66,6 +381,109 @@ pub mod columns {
         "additional_count",
     ];
     pub const QUERY_COLS: &[&str] = &["name", "type", "class"];
+    pub const PLAN_COLS: &[&str] = &["server", "protocol"];
     pub const RECORD_COLS: &[&str] = &["name", "type", "class", "ttl", "rdata"];
     pub const CODE_COLS: &[&str] = &["name", "code"];
+    pub const PATH_MTU_COLS: &[&str] = &["server", "max_payload", "path_mtu", "probes"];
+    pub const PATH_MTU_PROBE_COLS: &[&str] = &["size", "received"];
+    pub const DOA_COLS: &[&str] = &["enterprise", "type", "location", "media_type", "data"];
+    pub const HIP_COLS: &[&str] = &["pk_algorithm", "hit", "public_key", "rendezvous_servers"];
+    pub const PING_COLS: &[&str] = &[
+        "server",
+        "protocol",
+        "reachable",
+        "rtt",
+        "response_code",
+        "error",
+    ];
+    pub const SERVER_ID_COLS: &[&str] = &[
+        "server",
+        "protocol",
+        "version_bind",
+        "hostname_bind",
+        "id_server",
+    ];
+    pub const SRV_TARGET_COLS: &[&str] = &["priority", "weight", "port", "target", "addresses"];
+    pub const DESIGNATED_RESOLVER_COLS: &[&str] = &[
+        "priority",
+        "target",
+        "protocols",
+        "alpn",
+        "port",
+        "dohpath",
+        "addresses",
+    ];
+    pub const BROWSE_COLS: &[&str] = &["instance", "service", "host", "port", "addresses", "txt"];
+    pub const REVERSE_COLS: &[&str] = &["address", "name", "hostnames"];
+    pub const MESSAGES: &str = "messages";
+    pub const STEP: &str = "step";
+    pub const SERVER: &str = "server";
+    pub const SERIAL_DELTA: &str = "serial_delta";
+    pub const VALIDATED: &str = "validated";
+    pub const ADDRESSES: &str = "addresses";
+    pub const EXTENDED_ERRORS: &str = "extended_errors";
+    pub const COOKIE: &str = "cookie";
+    pub const DNSSEC: &str = "dnssec";
+    pub const EDE_COLS: &[&str] = &["code", "purpose", "extra_text"];
+    pub const SPANS: &str = "spans";
+    pub const ANSWER_CHECKSUM: &str = "answer_checksum";
+    pub const TRANSPORT: &str = "transport";
+    pub const TSIG: &str = "tsig";
+    pub const TSIG_COLS: &[&str] = &["key", "algorithm", "verified"];
+    pub const WIRE_SPAN_COLS: &[&str] = &["offset", "length"];
+    pub const RECORD_SPAN_COLS: &[&str] = &[
+        "record", "name", "type", "class", "ttl", "rdlength", "rdata",
+    ];
+    pub const CHAIN_COLS: &[&str] = &["question", "secure", "steps"];
+    pub const CHAIN_STEP_COLS: &[&str] = &["zone", "check", "passed", "detail"];
+    pub const VALIDATION_COLS: &[&str] = &["status", "zones", "steps", "denial"];
+    pub const VALIDATION_ZONE_COLS: &[&str] =
+        &["zone", "status", "dnskey", "ds", "denial", "rrsig", "steps"];
+    pub const DENIAL_COLS: &[&str] = &[
+        "kind",
+        "type",
+        "proven",
+        "closest_encloser",
+        "next_closer",
+        "wildcard",
+        "steps",
+    ];
+    pub const DENIAL_STEP_COLS: &[&str] = &["name", "check", "passed", "detail"];
+    pub const TRACE_COLS: &[&str] = &["question", "outcome", "note", "steps"];
+    pub const WALK_COLS: &[&str] = &["zone", "denial", "complete", "note", "names"];
+    pub const WALK_NAME_COLS: &[&str] = &["name", "types", "next"];
+    pub const EXPIRY_COLS: &[&str] = &[
+        "name",
+        "type",
+        "status",
+        "remaining",
+        "inception",
+        "expiration",
+        "key_tag",
+        "algorithm",
+        "signer",
+    ];
+    pub const CDS_CHECK_COLS: &[&str] = &[
+        "zone", "status", "issues", "add", "remove", "ds", "cds", "cdnskey",
+    ];
+    pub const TRACE_STEP_COLS: &[&str] = &[
+        "depth", "server", "queried", "zone", "status", "rtt", "records",
+    ];
+    pub const TIME: &str = "time";
+    pub const CHANGE: &str = "change";
+    pub const QUESTION: &str = "question";
+    pub const SERIAL: &str = "serial";
+    pub const SOURCE: &str = "source";
+    pub const DESTINATION: &str = "destination";
+    pub const ZONE_CHANGES_COLS: &[&str] =
+        &["zone", "from_serial", "serial", "incremental", "changes"];
+    pub const ZONE_STEPS_COLS: &[&str] = &["zone", "from_serial", "serial", "incremental", "steps"];
+    pub const ZONE_STEP_COLS: &[&str] = &["from_serial", "serial", "removed", "added"];
+    pub const SERVE_COLS: &[&str] = &[
+        "client", "protocol", "question", "code", "answers", "elapsed",
+    ];
+    pub const CHECK_COLS: &[&str] = &["severity", "check", "name", "type", "message"];
+    pub const FLAT_RECORD_COLS: &[&str] = &[
+        "question", "section", "name", "type", "class", "ttl", "rdata",
+    ];
 }
@@ -0,0 +1,81 @@
+use std::{fmt::Display, sync::Mutex};
+
+use hickory_proto::{
+    op::Message,
+    rr::rdata::opt::{EdnsCode, EdnsOption},
+};
+
+use super::constants;
+
+/// The DNS cookies (RFC 7873) of a client: a random client cookie of its own,
+/// and the last server cookie the server returned with it, which is sent along
+/// with the next queries.
+#[derive(Debug)]
+pub struct Cookies {
+    client: [u8; 8],
+    server: Mutex<Option<Vec<u8>>>,
+}
+
+/// Whether a response returned the client cookie of its query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CookieStatus {
+    /// The server does not support cookies.
+    Missing,
+    /// The client cookie is not the one sent, or the server cookie is
+    /// malformed, so the response may not be from the server (RFC 7873 section
+    /// 5.3).
+    Invalid,
+    Valid,
+}
+
+impl Display for CookieStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CookieStatus::Missing => "missing",
+            CookieStatus::Invalid => "invalid",
+            CookieStatus::Valid => "valid",
+        })
+    }
+}
+
+impl Cookies {
+    pub fn new() -> Self {
+        Self {
+            client: rand::random(),
+            server: Mutex::new(None),
+        }
+    }
+
+    /// The option to send, with the client cookie, and the server cookie once
+    /// the server returned one.
+    pub fn option(&self) -> EdnsOption {
+        let mut data = self.client.to_vec();
+        if let Some(server) = &*self.server.lock().unwrap_or_else(|err| err.into_inner()) {
+            data.extend_from_slice(server);
+        }
+
+        EdnsOption::Unknown(constants::edns::COOKIE, data)
+    }
+
+    /// Keeps the server cookie of a response that returned the client cookie,
+    /// for the next queries to send.
+    pub fn receive(&self, response: &Message) -> CookieStatus {
+        let cookie = response
+            .extensions()
+            .as_ref()
+            .and_then(|edns| edns.option(EdnsCode::from(constants::edns::COOKIE)));
+
+        let Some(EdnsOption::Unknown(_, data)) = cookie else {
+            return CookieStatus::Missing;
+        };
+
+        // server cookies are 8 to 32 bytes (RFC 7873 section 4.2)
+        match data.split_at_checked(8) {
+            Some((client, server)) if client == self.client && (8..=32).contains(&server.len()) => {
+                *self.server.lock().unwrap_or_else(|err| err.into_inner()) = Some(server.to_vec());
+                CookieStatus::Valid
+            }
+            _ => CookieStatus::Invalid,
+        }
+    }
+}
@@ -0,0 +1,1036 @@
+//! Proofs of nonexistence: how the NSEC or NSEC3 records of a negative response
+//! prove that there is no such name, or no records of the type asked for (RFC
+//! 4035 section 5.4, RFC 5155 section 8).
+
+use hickory_client::rr::rdata::DNSSECRData;
+use hickory_proto::{
+    op::{Message, Query, ResponseCode},
+    rr::{
+        dnssec::rdata::{NSEC, NSEC3},
+        RData, RecordType,
+    },
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Span, Value};
+
+use super::{constants, names};
+
+/// One fact about a name that the proof rests on.
+struct Step {
+    name: Name,
+    check: &'static str,
+    passed: bool,
+    detail: String,
+}
+
+impl Step {
+    fn into_value(self) -> Value {
+        Value::record(
+            nu_protocol::Record::from_iter(std::iter::zip(
+                constants::columns::DENIAL_STEP_COLS
+                    .iter()
+                    .map(|col| (*col).into()),
+                vec![
+                    Value::string(self.name.to_utf8(), Span::unknown()),
+                    Value::string(self.check, Span::unknown()),
+                    Value::bool(self.passed, Span::unknown()),
+                    Value::string(self.detail, Span::unknown()),
+                ],
+            )),
+            Span::unknown(),
+        )
+    }
+}
+
+/// What the denial records of a negative response prove, and how.
+pub struct Proof {
+    /// Whether there is no such name at all, rather than no records of the
+    /// type asked for.
+    nxdomain: bool,
+    /// NSEC or NSEC3, whichever the zone denies with.
+    rtype: Option<RecordType>,
+    /// The longest existing ancestor of the name (RFC 5155 section 1.3).
+    closest_encloser: Option<Name>,
+    /// The name one label below the closest encloser, on the way to the name,
+    /// which has to be proven not to exist.
+    next_closer: Option<Name>,
+    /// The wildcard below the closest encloser, which could have answered
+    /// instead.
+    wildcard: Option<Name>,
+    steps: Vec<Step>,
+}
+
+impl Proof {
+    /// Whether every fact that the proof rests on holds.
+    pub fn proven(&self) -> bool {
+        !self.steps.is_empty() && self.steps.iter().all(|step| step.passed)
+    }
+
+    /// Why the proof does not hold, if it does not.
+    pub fn failure(&self) -> Option<&str> {
+        match self.steps.iter().find(|step| !step.passed) {
+            Some(step) => Some(&step.detail),
+            None if self.steps.is_empty() => Some("there is nothing to prove it with"),
+            None => None,
+        }
+    }
+
+    pub fn into_value(self) -> Value {
+        let name = |name: Option<Name>| {
+            name.map_or_else(
+                || Value::nothing(Span::unknown()),
+                |name| Value::string(name.to_utf8(), Span::unknown()),
+            )
+        };
+
+        Value::record(
+            nu_protocol::Record::from_iter(std::iter::zip(
+                constants::columns::DENIAL_COLS
+                    .iter()
+                    .map(|col| (*col).into()),
+                vec![
+                    Value::string(
+                        if self.nxdomain { "nxdomain" } else { "nodata" },
+                        Span::unknown(),
+                    ),
+                    self.rtype.map_or_else(
+                        || Value::nothing(Span::unknown()),
+                        |rtype| Value::string(rtype.to_string(), Span::unknown()),
+                    ),
+                    Value::bool(self.proven(), Span::unknown()),
+                    name(self.closest_encloser),
+                    name(self.next_closer),
+                    name(self.wildcard),
+                    Value::list(
+                        self.steps.into_iter().map(Step::into_value).collect(),
+                        Span::unknown(),
+                    ),
+                ],
+            )),
+            Span::unknown(),
+        )
+    }
+}
+
+/// Works out how the NSEC or NSEC3 records in the authority section of a
+/// negative response to `query` prove it, which is nothing for a positive
+/// response. The signatures over the records are not checked here.
+pub fn prove(query: &Query, response: &Message, span: Span) -> Result<Option<Proof>, LabeledError> {
+    let rtype = query.query_type();
+
+    // an alias leaves the name it points to to be proven
+    let mut name = query.name().clone();
+    if rtype != RecordType::CNAME {
+        while let Some(target) = response
+            .answers()
+            .iter()
+            .find_map(|record| match record.data() {
+                Some(RData::CNAME(cname)) if *record.name() == name && cname.0 != name => {
+                    Some(cname.0.clone())
+                }
+                _ => None,
+            })
+        {
+            name = target;
+        }
+    }
+
+    let nxdomain = match response.response_code() {
+        ResponseCode::NXDomain => true,
+        ResponseCode::NoError
+            if !response.answers().iter().any(|record| {
+                *record.name() == name
+                    && (record.record_type() == rtype
+                        || rtype == RecordType::ANY && record.record_type() != RecordType::RRSIG)
+            }) =>
+        {
+            false
+        }
+        _ => return Ok(None),
+    };
+
+    let mut nsec = Vec::new();
+    let mut nsec3 = Vec::new();
+    for record in response.name_servers() {
+        match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::NSEC(data))) => nsec.push((record.name(), data)),
+            Some(RData::DNSSEC(DNSSECRData::NSEC3(data))) => nsec3.push((record.name(), data)),
+            _ => {}
+        }
+    }
+
+    let mut proof = Proof {
+        nxdomain,
+        rtype: None,
+        closest_encloser: None,
+        next_closer: None,
+        wildcard: None,
+        steps: Vec::new(),
+    };
+
+    if !nsec3.is_empty() {
+        proof.rtype = Some(RecordType::NSEC3);
+        prove_nsec3(&mut proof, &name, rtype, &nsec3, span)?;
+    } else if !nsec.is_empty() {
+        proof.rtype = Some(RecordType::NSEC);
+        prove_nsec(&mut proof, &name, rtype, &nsec, span)?;
+    } else {
+        proof.steps.push(Step {
+            name,
+            check: "denial records",
+            passed: false,
+            detail: "there are no NSEC or NSEC3 records in the authority section".into(),
+        });
+    }
+
+    Ok(Some(proof))
+}
+
+/// Proves with NSEC records, each of which covers the names in the zone
+/// between its owner and the next name in canonical order (RFC 4035 section
+/// 5.4).
+fn prove_nsec(
+    proof: &mut Proof,
+    name: &Name,
+    rtype: RecordType,
+    nsec: &[(&Name, &NSEC)],
+    span: Span,
+) -> Result<(), LabeledError> {
+    let matching = |name: &Name| nsec.iter().find(|(owner, _)| *owner == name);
+    let covering = |name: &Name| {
+        nsec.iter()
+            .find(|(owner, data)| covers(*owner, data.next_domain_name(), name))
+    };
+    let span_of =
+        |owner: &Name, data: &NSEC| format!("NSEC {} to {}", owner, data.next_domain_name());
+
+    if !proof.nxdomain {
+        if let Some((owner, data)) = matching(name) {
+            proof.steps.push(check_types(
+                name,
+                format!("NSEC {}", owner),
+                data.type_bit_maps(),
+                rtype,
+            ));
+            return Ok(());
+        }
+
+        // an empty non-terminal has no NSEC record of its own, but the next
+        // name after it is below it
+        if let Some((owner, data)) =
+            covering(name).filter(|(_, data)| name.zone_of(data.next_domain_name()))
+        {
+            proof.steps.push(Step {
+                name: name.clone(),
+                check: "empty non-terminal",
+                passed: true,
+                detail: format!(
+                    "{} covers {}, and {} is below it, so it exists without records of its own",
+                    span_of(owner, data),
+                    name,
+                    data.next_domain_name()
+                ),
+            });
+            return Ok(());
+        }
+    }
+
+    let Some((owner, data)) = covering(name) else {
+        proof.steps.push(Step {
+            name: name.clone(),
+            check: "name",
+            passed: false,
+            detail: match matching(name) {
+                Some((owner, _)) => format!("NSEC {} shows that the name exists", owner),
+                None => "none of the NSEC records covers the name".into(),
+            },
+        });
+        return Ok(());
+    };
+
+    proof.steps.push(Step {
+        name: name.clone(),
+        check: "name",
+        passed: true,
+        detail: format!(
+            "{} covers {}, so there is no such name",
+            span_of(owner, data),
+            name
+        ),
+    });
+
+    // the closest encloser is the longest name that the name has in common
+    // with either end of the span that covers it
+    let closest_encloser = [*owner, data.next_domain_name()]
+        .into_iter()
+        .map(|end| common_ancestor(name, end))
+        .max_by_key(label_count)
+        .expect("a span has two ends");
+    let wildcard = names::wildcard(&closest_encloser, span)?;
+
+    proof.steps.push(Step {
+        name: closest_encloser.clone(),
+        check: "closest encloser",
+        passed: true,
+        detail: format!(
+            "{} is the longest ancestor of {} that {} shares, so it exists",
+            closest_encloser,
+            name,
+            span_of(owner, data)
+        ),
+    });
+
+    proof.steps.push(if proof.nxdomain {
+        no_wildcard(
+            &wildcard,
+            covering(&wildcard).map(|(owner, data)| span_of(owner, data)),
+            matching(&wildcard).map(|(owner, _)| format!("NSEC {}", owner)),
+        )
+    } else {
+        match matching(&wildcard) {
+            Some((owner, data)) => check_types(
+                &wildcard,
+                format!("NSEC {}", owner),
+                data.type_bit_maps(),
+                rtype,
+            ),
+            None => Step {
+                name: wildcard.clone(),
+                check: "wildcard",
+                passed: false,
+                detail: format!(
+                    "none of the NSEC records matches the name or {}, so the records are not proven to be missing",
+                    wildcard
+                ),
+            },
+        }
+    });
+
+    proof.next_closer = Some(next_closer(name, &closest_encloser));
+    proof.closest_encloser = Some(closest_encloser);
+    proof.wildcard = Some(wildcard);
+
+    Ok(())
+}
+
+/// Proves with NSEC3 records, which cover the hashes of names rather than the
+/// names, so that the closest encloser has to be matched outright (RFC 5155
+/// section 8).
+fn prove_nsec3(
+    proof: &mut Proof,
+    name: &Name,
+    rtype: RecordType,
+    nsec3: &[(&Name, &NSEC3)],
+    span: Span,
+) -> Result<(), LabeledError> {
+    // the hashes are of the zone that the NSEC3 records are in, with its
+    // parameters, which all of the records share
+    let (first, params) = nsec3[0];
+    let zone = first.base_name();
+    let hash = |name: &Name| -> Result<String, LabeledError> {
+        names::nsec3(name, &zone, params.salt(), params.iterations(), span)
+            .map(|owner| first_label(&owner))
+    };
+
+    let records: Vec<(String, String, &NSEC3)> = nsec3
+        .iter()
+        .map(|(owner, data)| {
+            (
+                first_label(owner),
+                data_encoding::BASE32_DNSSEC.encode(data.next_hashed_owner_name()),
+                *data,
+            )
+        })
+        .collect();
+    let matching = |hash: &str| records.iter().find(|(owner, ..)| owner == hash);
+    let covering = |hash: &str| {
+        records
+            .iter()
+            .find(|(owner, next, _)| covers(owner.as_str(), next.as_str(), hash))
+    };
+
+    if !proof.nxdomain {
+        if let Some((owner, _, data)) = matching(&hash(name)?) {
+            proof.steps.push(check_types(
+                name,
+                format!("NSEC3 {}", owner),
+                data.type_bit_maps(),
+                rtype,
+            ));
+            return Ok(());
+        }
+    }
+
+    // the closest encloser proof (RFC 5155 section 7.2.1): the longest
+    // ancestor whose hash is matched, and the name below it whose hash is
+    // covered
+    let mut closest_encloser = None;
+    let mut next_closer = None;
+    for labels in (label_count(&zone)..=label_count(name)).rev() {
+        let ancestor = name.trim_to(labels);
+        if let Some((owner, ..)) = matching(&hash(&ancestor)?) {
+            closest_encloser = Some((ancestor, owner));
+            break;
+        }
+        next_closer = Some(ancestor);
+    }
+
+    let Some((closest_encloser, owner)) = closest_encloser else {
+        proof.steps.push(Step {
+            name: name.clone(),
+            check: "closest encloser",
+            passed: false,
+            detail: format!(
+                "none of the NSEC3 records matches the hash of {} or of an ancestor of it in {}",
+                name, zone
+            ),
+        });
+        return Ok(());
+    };
+
+    let Some(next_closer) = next_closer else {
+        proof.steps.push(Step {
+            name: name.clone(),
+            check: "name",
+            passed: false,
+            detail: format!("NSEC3 {} matches the hash of the name, so it exists", owner),
+        });
+        return Ok(());
+    };
+
+    proof.steps.push(Step {
+        name: closest_encloser.clone(),
+        check: "closest encloser",
+        passed: true,
+        detail: format!("NSEC3 {} matches its hash, so it exists", owner),
+    });
+
+    let next_closer_hash = hash(&next_closer)?;
+    let next_closer_span = covering(&next_closer_hash);
+    proof.steps.push(match next_closer_span {
+        Some((owner, next, data)) => Step {
+            name: next_closer.clone(),
+            check: "next closer",
+            passed: true,
+            detail: format!(
+                "NSEC3 {} to {} covers its hash {}, so there is no such name{}",
+                owner,
+                next,
+                next_closer_hash,
+                if data.opt_out() {
+                    ", though with the opt-out flag an unsigned delegation may be in between"
+                } else {
+                    ""
+                }
+            ),
+        },
+        None => Step {
+            name: next_closer.clone(),
+            check: "next closer",
+            passed: false,
+            detail: format!(
+                "none of the NSEC3 records covers its hash {}",
+                next_closer_hash
+            ),
+        },
+    });
+
+    let wildcard = names::wildcard(&closest_encloser, span)?;
+    let wildcard_hash = hash(&wildcard)?;
+
+    if proof.nxdomain {
+        proof.steps.push(no_wildcard(
+            &wildcard,
+            covering(&wildcard_hash).map(|(owner, next, _)| format!("NSEC3 {} to {}", owner, next)),
+            matching(&wildcard_hash).map(|(owner, ..)| format!("NSEC3 {}", owner)),
+        ));
+    } else if let Some((owner, _, data)) = matching(&wildcard_hash) {
+        proof.steps.push(check_types(
+            &wildcard,
+            format!("NSEC3 {}", owner),
+            data.type_bit_maps(),
+            rtype,
+        ));
+    } else {
+        // the only other way there is no DS record is that the name is an
+        // unsigned delegation within the span of an opt-out record (RFC 5155
+        // section 8.6)
+        let opt_out = next_closer_span.filter(|(.., data)| data.opt_out());
+        proof.steps.push(match opt_out {
+            Some((owner, ..)) if rtype == RecordType::DS => Step {
+                name: name.clone(),
+                check: "opt-out",
+                passed: true,
+                detail: format!(
+                    "NSEC3 {} has the opt-out flag, so the name may be an unsigned delegation, which has no DS records",
+                    owner
+                ),
+            },
+            _ => Step {
+                name: wildcard.clone(),
+                check: "wildcard",
+                passed: false,
+                detail: format!(
+                    "none of the NSEC3 records matches the hash of the name or of {}, so the records are not proven to be missing",
+                    wildcard
+                ),
+            },
+        });
+    }
+
+    proof.closest_encloser = Some(closest_encloser);
+    proof.next_closer = Some(next_closer);
+    proof.wildcard = Some(wildcard);
+
+    Ok(())
+}
+
+/// Whether the types of a name that a denial record matches leave out
+/// `rtype`, and the CNAME that would have been followed instead.
+fn check_types(name: &Name, record: String, types: &[RecordType], rtype: RecordType) -> Step {
+    let step = |passed, detail| Step {
+        name: name.clone(),
+        check: "types",
+        passed,
+        detail,
+    };
+
+    if types.contains(&rtype) {
+        return step(
+            false,
+            format!("{} lists {} among the types of {}", record, rtype, name),
+        );
+    }
+
+    // the DS records of a zone are denied by its parent, on the parent side of
+    // the delegation, and not by the apex of the zone itself (RFC 6840 section
+    // 4.4)
+    if rtype == RecordType::DS && types.contains(&RecordType::SOA) {
+        return step(
+            false,
+            format!(
+                "{} is of the apex of {} itself, which can not deny its DS records",
+                record, name
+            ),
+        );
+    }
+
+    if types.contains(&RecordType::CNAME) && rtype != RecordType::DS {
+        return step(
+            false,
+            format!(
+                "{} shows that {} is an alias, which should have been followed",
+                record, name
+            ),
+        );
+    }
+
+    step(
+        true,
+        format!(
+            "{} matches {}, which only has {}, so it has no {} records",
+            record,
+            name,
+            if types.is_empty() {
+                "no types".into()
+            } else {
+                types
+                    .iter()
+                    .map(RecordType::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            },
+            rtype
+        ),
+    )
+}
+
+/// Whether the wildcard that could have answered instead is proven not to
+/// exist, given the span that covers it, or the record that matches it.
+fn no_wildcard(wildcard: &Name, covering: Option<String>, matching: Option<String>) -> Step {
+    let step = |passed, detail| Step {
+        name: wildcard.clone(),
+        check: "wildcard",
+        passed,
+        detail,
+    };
+
+    match (covering, matching) {
+        (Some(span), _) => step(
+            true,
+            format!(
+                "{} covers the wildcard, so none could have answered instead",
+                span
+            ),
+        ),
+        (None, Some(record)) => step(
+            false,
+            format!(
+                "{} shows that the wildcard exists, so it should have answered",
+                record
+            ),
+        ),
+        (None, None) => step(
+            false,
+            "none of the records covers the wildcard, so it may have answered instead".into(),
+        ),
+    }
+}
+
+/// Whether `name` falls between the owner and next name of a denial record,
+/// where the last record wraps around to the first name of the zone.
+fn covers<T: Ord + ?Sized>(owner: &T, next: &T, name: &T) -> bool {
+    if owner < next {
+        owner < name && name < next
+    } else {
+        owner < name || name < next
+    }
+}
+
+/// The longest ancestor of `name`, or `name` itself, that `other` is in.
+fn common_ancestor(name: &Name, other: &Name) -> Name {
+    (0..=label_count(name))
+        .rev()
+        .map(|labels| name.trim_to(labels))
+        .find(|ancestor| ancestor.zone_of(other))
+        .unwrap_or_else(Name::root)
+}
+
+/// The ancestor of `name` one label below `closest_encloser`.
+fn next_closer(name: &Name, closest_encloser: &Name) -> Name {
+    name.trim_to((label_count(closest_encloser) + 1).min(label_count(name)))
+}
+
+/// The number of labels of a name, counting a wildcard, unlike
+/// [`Name::num_labels`].
+fn label_count(name: &Name) -> usize {
+    name.iter().count()
+}
+
+/// The first label of an NSEC3 owner name, which is the hash, in the lowercase
+/// that the hashes are encoded in.
+fn first_label(name: &Name) -> String {
+    name.iter()
+        .next()
+        .map(|label| String::from_utf8_lossy(label).to_lowercase())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::{op::MessageType, rr::dnssec::Nsec3HashAlgorithm, rr::Record};
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_ascii(name).unwrap()
+    }
+
+    /// A negative response to a query, with the denial records in its
+    /// authority section.
+    fn response(
+        owner: &str,
+        rtype: RecordType,
+        code: ResponseCode,
+        denial: Vec<Record>,
+    ) -> (Query, Message) {
+        let query = Query::query(name(owner), rtype);
+        let mut response = Message::new();
+        response
+            .set_message_type(MessageType::Response)
+            .set_response_code(code)
+            .add_query(query.clone())
+            .add_name_servers(denial);
+
+        (query, response)
+    }
+
+    fn prove_response(
+        owner: &str,
+        rtype: RecordType,
+        code: ResponseCode,
+        denial: Vec<Record>,
+    ) -> Proof {
+        let (query, response) = response(owner, rtype, code, denial);
+        prove(&query, &response, Span::test_data())
+            .unwrap()
+            .unwrap()
+    }
+
+    fn nsec(owner: &str, next: &str, types: &[RecordType]) -> Record {
+        Record::from_rdata(
+            name(owner),
+            300,
+            RData::DNSSEC(DNSSECRData::NSEC(NSEC::new(name(next), types.to_vec()))),
+        )
+    }
+
+    /// The NSEC3 records of the example zone of RFC 5155 appendix A whose
+    /// owners start with each of `hashes`. The records all have the opt-out
+    /// flag, 12 iterations and the salt aabbccdd.
+    fn appendix_a(hashes: &[&str]) -> Vec<Record> {
+        use RecordType::{A, AAAA, DNSKEY, DS, HINFO, MX, NS, NSEC3PARAM, RRSIG, SOA};
+
+        let chain: [(&str, &str, &[RecordType]); 12] = [
+            (
+                "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+                &[NS, SOA, MX, RRSIG, DNSKEY, NSEC3PARAM],
+            ),
+            (
+                "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+                "2vptu5timamqttgl4luu9kg21e0aor3s",
+                &[A, RRSIG],
+            ),
+            (
+                "2vptu5timamqttgl4luu9kg21e0aor3s",
+                "35mthgpgcu1qg68fab165klnsnk3dpvl",
+                &[MX, RRSIG],
+            ),
+            (
+                "35mthgpgcu1qg68fab165klnsnk3dpvl",
+                "b4um86eghhds6nea196smvmlo4ors995",
+                &[NS, DS, RRSIG],
+            ),
+            (
+                "b4um86eghhds6nea196smvmlo4ors995",
+                "gjeqe526plbf1g8mklp59enfd789njgi",
+                &[MX, RRSIG],
+            ),
+            (
+                "gjeqe526plbf1g8mklp59enfd789njgi",
+                "ji6neoaepv8b5o6k4ev33abha8ht9fgc",
+                &[A, HINFO, AAAA, RRSIG],
+            ),
+            (
+                "ji6neoaepv8b5o6k4ev33abha8ht9fgc",
+                "k8udemvp1j2f7eg6jebps17vp3n8i58h",
+                &[],
+            ),
+            (
+                "k8udemvp1j2f7eg6jebps17vp3n8i58h",
+                "kohar7mbb8dc2ce8a9qvl8hon4k53uhi",
+                &[],
+            ),
+            (
+                "kohar7mbb8dc2ce8a9qvl8hon4k53uhi",
+                "q04jkcevqvmu85r014c7dkba38o0ji5r",
+                &[A, RRSIG],
+            ),
+            (
+                "q04jkcevqvmu85r014c7dkba38o0ji5r",
+                "r53bq7cc2uvmubfu5ocmm6pers9tk9en",
+                &[A, RRSIG],
+            ),
+            (
+                "r53bq7cc2uvmubfu5ocmm6pers9tk9en",
+                "t644ebqk9bibcna874givr6joj62mlhv",
+                &[MX, RRSIG],
+            ),
+            (
+                "t644ebqk9bibcna874givr6joj62mlhv",
+                "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                &[A, HINFO, AAAA, RRSIG],
+            ),
+        ];
+
+        hashes
+            .iter()
+            .map(|hash| {
+                let (owner, next, types) = chain
+                    .iter()
+                    .find(|(owner, ..)| owner.starts_with(hash))
+                    .unwrap();
+
+                Record::from_rdata(
+                    name(&format!("{}.example.", owner)),
+                    3600,
+                    RData::DNSSEC(DNSSECRData::NSEC3(NSEC3::new(
+                        Nsec3HashAlgorithm::SHA1,
+                        true,
+                        12,
+                        vec![0xaa, 0xbb, 0xcc, 0xdd],
+                        data_encoding::BASE32_DNSSEC
+                            .decode(next.as_bytes())
+                            .unwrap(),
+                        types.to_vec(),
+                    ))),
+                )
+            })
+            .collect()
+    }
+
+    /// The checks of the proof, and whether each passed.
+    fn checks(proof: &Proof) -> Vec<(&'static str, bool)> {
+        proof
+            .steps
+            .iter()
+            .map(|step| (step.check, step.passed))
+            .collect()
+    }
+
+    #[test]
+    fn covers_wraps_around_from_the_last_record() {
+        assert!(covers("b", "d", "c"));
+        assert!(!covers("b", "d", "b"));
+        assert!(!covers("b", "d", "d"));
+        assert!(!covers("b", "d", "e"));
+
+        // the last record covers the names after it, and before the first
+        assert!(covers("x", "b", "y"));
+        assert!(covers("x", "b", "a"));
+        assert!(!covers("x", "b", "c"));
+
+        // a lone record covers every name but its own
+        assert!(covers("a", "a", "b"));
+        assert!(!covers("a", "a", "a"));
+
+        assert!(covers(
+            &name("example."),
+            &name("a.example."),
+            &name("*.example.")
+        ));
+    }
+
+    #[test]
+    fn nsec_proves_nxdomain() {
+        let proof = prove_response(
+            "b.example.",
+            RecordType::A,
+            ResponseCode::NXDomain,
+            vec![
+                nsec("a.example.", "c.example.", &[RecordType::A]),
+                nsec("example.", "a.example.", &[RecordType::SOA, RecordType::NS]),
+            ],
+        );
+
+        assert!(proof.proven());
+        assert_eq!(
+            checks(&proof),
+            [
+                ("name", true),
+                ("closest encloser", true),
+                ("wildcard", true)
+            ]
+        );
+        assert_eq!(proof.closest_encloser, Some(name("example.")));
+        assert_eq!(proof.wildcard, Some(name("*.example.")));
+
+        // without the record that covers the wildcard, it may have answered
+        let proof = prove_response(
+            "b.example.",
+            RecordType::A,
+            ResponseCode::NXDomain,
+            vec![nsec("a.example.", "c.example.", &[RecordType::A])],
+        );
+        assert!(!proof.proven());
+    }
+
+    #[test]
+    fn nsec_proves_nodata() {
+        let denial = || {
+            vec![nsec(
+                "a.example.",
+                "c.example.",
+                &[RecordType::A, RecordType::NSEC],
+            )]
+        };
+
+        let proof = prove_response(
+            "a.example.",
+            RecordType::MX,
+            ResponseCode::NoError,
+            denial(),
+        );
+        assert!(proof.proven());
+        assert_eq!(checks(&proof), [("types", true)]);
+
+        let proof = prove_response("a.example.", RecordType::A, ResponseCode::NoError, denial());
+        assert!(!proof.proven());
+    }
+
+    #[test]
+    fn nsec_proves_an_empty_non_terminal() {
+        let proof = prove_response(
+            "b.example.",
+            RecordType::A,
+            ResponseCode::NoError,
+            vec![nsec("a.example.", "x.b.example.", &[RecordType::A])],
+        );
+
+        assert!(proof.proven());
+        assert_eq!(checks(&proof), [("empty non-terminal", true)]);
+    }
+
+    #[test]
+    fn ds_is_not_denied_by_the_apex_of_the_zone() {
+        let denial = |types: &[RecordType]| vec![nsec("child.example.", "d.example.", types)];
+
+        let proof = prove_response(
+            "child.example.",
+            RecordType::DS,
+            ResponseCode::NoError,
+            denial(&[RecordType::NS, RecordType::RRSIG, RecordType::NSEC]),
+        );
+        assert!(proof.proven());
+
+        let proof = prove_response(
+            "child.example.",
+            RecordType::DS,
+            ResponseCode::NoError,
+            denial(&[
+                RecordType::SOA,
+                RecordType::NS,
+                RecordType::RRSIG,
+                RecordType::NSEC,
+            ]),
+        );
+        assert!(!proof.proven());
+        assert!(proof.failure().unwrap().contains("apex"));
+    }
+
+    #[test]
+    fn nsec3_proves_the_closest_encloser() {
+        // RFC 5155 appendix B.1: x.w.example exists, and neither
+        // c.x.w.example nor *.x.w.example do
+        let proof = prove_response(
+            "a.c.x.w.example.",
+            RecordType::A,
+            ResponseCode::NXDomain,
+            appendix_a(&["0p9mhav", "b4um86", "35mthgp"]),
+        );
+
+        assert!(proof.proven());
+        assert_eq!(
+            checks(&proof),
+            [
+                ("closest encloser", true),
+                ("next closer", true),
+                ("wildcard", true)
+            ]
+        );
+        assert_eq!(proof.closest_encloser, Some(name("x.w.example.")));
+        assert_eq!(proof.next_closer, Some(name("c.x.w.example.")));
+        assert_eq!(proof.wildcard, Some(name("*.x.w.example.")));
+
+        // without the record that covers the wildcard, it may have answered
+        let proof = prove_response(
+            "a.c.x.w.example.",
+            RecordType::A,
+            ResponseCode::NXDomain,
+            appendix_a(&["0p9mhav", "b4um86"]),
+        );
+        assert!(!proof.proven());
+    }
+
+    #[test]
+    fn nsec3_opt_out_proves_no_ds() {
+        // RFC 5155 appendix B.3: c.example is an unsigned delegation in the
+        // span of an opt-out record
+        let proof = prove_response(
+            "c.example.",
+            RecordType::DS,
+            ResponseCode::NoError,
+            appendix_a(&["0p9mhav", "35mthgp"]),
+        );
+        assert!(proof.proven());
+        assert_eq!(
+            checks(&proof),
+            [
+                ("closest encloser", true),
+                ("next closer", true),
+                ("opt-out", true)
+            ]
+        );
+
+        // which only goes for DS records
+        let proof = prove_response(
+            "c.example.",
+            RecordType::A,
+            ResponseCode::NoError,
+            appendix_a(&["0p9mhav", "35mthgp"]),
+        );
+        assert!(!proof.proven());
+    }
+
+    #[test]
+    fn nsec3_proves_nodata() {
+        // RFC 5155 appendix B.2: ns1.example has an A record, but no MX
+        let proof = prove_response(
+            "ns1.example.",
+            RecordType::MX,
+            ResponseCode::NoError,
+            appendix_a(&["2t7b4g4v"]),
+        );
+        assert!(proof.proven());
+        assert_eq!(checks(&proof), [("types", true)]);
+
+        let proof = prove_response(
+            "ns1.example.",
+            RecordType::A,
+            ResponseCode::NoError,
+            appendix_a(&["2t7b4g4v"]),
+        );
+        assert!(!proof.proven());
+    }
+
+    #[test]
+    fn nsec3_proves_an_empty_non_terminal() {
+        // RFC 5155 appendix B.2.1: y.w.example only has names below it, so
+        // its record has no types
+        let proof = prove_response(
+            "y.w.example.",
+            RecordType::A,
+            ResponseCode::NoError,
+            appendix_a(&["ji6neoae"]),
+        );
+
+        assert!(proof.proven());
+        assert_eq!(checks(&proof), [("types", true)]);
+    }
+
+    #[test]
+    fn nsec3_wildcard_expansions_are_not_denials() {
+        // RFC 5155 appendix B.4: the MX record of a.z.w.example is expanded
+        // from *.w.example, which is an answer, not a denial to prove
+        let query = Query::query(name("a.z.w.example."), RecordType::MX);
+        let mut response = Message::new();
+        response
+            .set_message_type(MessageType::Response)
+            .add_query(query.clone())
+            .add_answer(Record::from_rdata(
+                name("a.z.w.example."),
+                3600,
+                RData::MX(hickory_proto::rr::rdata::MX::new(1, name("ai.example."))),
+            ))
+            .add_name_servers(appendix_a(&["q04jkcev"]));
+
+        assert!(prove(&query, &response, Span::test_data())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn nsec3_proves_wildcard_nodata() {
+        // RFC 5155 appendix B.5: a.z.w.example would be expanded from
+        // *.w.example, which has no AAAA record
+        let proof = prove_response(
+            "a.z.w.example.",
+            RecordType::AAAA,
+            ResponseCode::NoError,
+            appendix_a(&["k8udemvp", "q04jkcev", "r53bq7cc"]),
+        );
+
+        assert!(proof.proven());
+        assert_eq!(
+            checks(&proof),
+            [
+                ("closest encloser", true),
+                ("next closer", true),
+                ("types", true)
+            ]
+        );
+        assert_eq!(proof.closest_encloser, Some(name("w.example.")));
+        assert_eq!(proof.next_closer, Some(name("z.w.example.")));
+    }
+}
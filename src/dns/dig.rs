@@ -0,0 +1,374 @@
+//! Text rendering of messages the way dig prints them, for pasting into
+//! tickets and comparing against the output of dig.
+//!
+//! Messages are rendered from the records queries output, so that the same
+//! text comes out of any command that outputs them. Records are laid out on
+//! the tab stops of dig: the TTL at column 24, the class at 32, the type at 40,
+//! and the data at 48.
+
+use hickory_proto::{
+    op::{Message, MessageType},
+    rr::DNSClass,
+};
+use nu_protocol::{LabeledError, Value};
+
+use super::{
+    commands::build,
+    constants,
+    presentation::{self, Names},
+    serde, zone,
+};
+
+const TTL_COLUMN: usize = 24;
+const CLASS_COLUMN: usize = 32;
+const TYPE_COLUMN: usize = 40;
+const RDATA_COLUMN: usize = 48;
+
+/// The messages in `value`, a message or a list of them, rendered one after the
+/// other. Errors kept in the list are rendered as comments.
+pub fn render(value: &Value) -> Result<String, LabeledError> {
+    let mut text = String::new();
+
+    for value in messages(value) {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+
+        match value {
+            Value::Error { error, .. } => text.push_str(&format!(";; {}\n", error)),
+            message => text.push_str(&render_message(message)?),
+        }
+    }
+
+    Ok(text)
+}
+
+/// The record data of the answers of the messages in `value`, in presentation
+/// format, the way `dig +short` prints them. Errors kept in the list are kept
+/// as they are.
+pub fn short(value: &Value) -> Result<Vec<Value>, LabeledError> {
+    let mut answers = Vec::new();
+
+    for value in messages(value) {
+        if let Value::Error { .. } = value {
+            answers.push(value.clone());
+            continue;
+        }
+
+        let Some(Value::List { vals, .. }) = value.get_data_by_key("answer") else {
+            continue;
+        };
+
+        for record in &vals {
+            let (_, rtype, _, _, rdata) = zone::record_columns(record, None, DNSClass::IN)?;
+            let rdata = presentation::rdata(rtype, &rdata, &Names::default())?.line();
+
+            answers.push(Value::string(rdata, value.span()));
+        }
+    }
+
+    Ok(answers)
+}
+
+/// The messages in `value`, a message or a list of them, and in the messages
+/// of each server when they are grouped by server.
+pub(crate) fn messages(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::List { vals, .. } => vals.iter().flat_map(messages).collect(),
+        Value::Record { val, .. } if val.contains(constants::columns::MESSAGES) => val
+            .get(constants::columns::MESSAGES)
+            .map(messages)
+            .unwrap_or_default(),
+        value => vec![value],
+    }
+}
+
+fn render_message(value: &Value) -> Result<String, LabeledError> {
+    let column = |col: &str| value.get_data_by_key(col);
+    let mut text = String::new();
+
+    // the header is read the way dns build reads it, for its codes to be taken
+    // by name or by number
+    let mut message = Message::new();
+    let raw_op_code = match column("header") {
+        Some(header) => build::set_header(&mut message, &header)?,
+        None => None,
+    };
+    let op_code = serde::OpCode(raw_op_code.unwrap_or_else(|| message.op_code().into()));
+
+    let count = |col: &str, len: usize| {
+        column("header")
+            .and_then(|header| header.get_data_by_key(col))
+            .and_then(|count| count.as_int().ok())
+            .unwrap_or(len as i64)
+    };
+
+    let question = column("question")
+        .filter(|question| !question.as_record().is_ok_and(|record| record.is_empty()))
+        .filter(|question| !matches!(question, Value::Nothing { .. }));
+    let section = |col: &str| match column(col) {
+        Some(Value::List { vals, .. }) => vals,
+        _ => Vec::new(),
+    };
+    let (answer, authority, additional) = (
+        section("answer"),
+        section("authority"),
+        section("additional"),
+    );
+    let edns = column("edns").filter(|edns| !matches!(edns, Value::Nothing { .. }));
+
+    let flags = [
+        (message.message_type() == MessageType::Response, "qr"),
+        (message.authoritative(), "aa"),
+        (message.truncated(), "tc"),
+        (message.recursion_desired(), "rd"),
+        (message.recursion_available(), "ra"),
+        (message.authentic_data(), "ad"),
+        (message.checking_disabled(), "cd"),
+    ]
+    .into_iter()
+    .filter_map(|(set, flag)| set.then_some(flag))
+    .collect::<Vec<_>>();
+
+    text.push_str(&format!(
+        ";; ->>HEADER<<- opcode: {}, status: {}, id: {}\n",
+        op_code,
+        serde::ResponseStatus::Other(message.response_code()),
+        message.id(),
+    ));
+    text.push_str(&format!(
+        ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}\n",
+        flags.join(" "),
+        count("query_count", question.iter().count()),
+        count("answer_count", answer.len()),
+        count("name_server_count", authority.len()),
+        count("additional_count", additional.len() + edns.iter().count()),
+    ));
+
+    if let Some(edns) = &edns {
+        text.push_str("\n;; OPT PSEUDOSECTION:\n");
+        text.push_str(&render_edns(edns)?);
+    }
+
+    if let Some(question) = &question {
+        let name = serde::Query::name_from_value(&field(question, constants::columns::NAME)?)?;
+        let serde::RType(rtype) =
+            serde::RType::try_from(&field(question, constants::columns::TYPE)?)?;
+        let class = match question.get_data_by_key(constants::columns::CLASS) {
+            Some(class) => serde::DNSClass::try_from(class)?.0,
+            None => DNSClass::IN,
+        };
+
+        let mut line = format!(";{}", name);
+        tab_to(&mut line, CLASS_COLUMN);
+        line.push_str(&format!("{}\t{}", class, serde::RTypeName(rtype)));
+
+        text.push_str("\n;; QUESTION SECTION:\n");
+        text.push_str(&line);
+        text.push('\n');
+    }
+
+    for (banner, records) in [
+        ("ANSWER", &answer),
+        ("AUTHORITY", &authority),
+        ("ADDITIONAL", &additional),
+    ] {
+        if records.is_empty() {
+            continue;
+        }
+
+        text.push_str(&format!("\n;; {} SECTION:\n", banner));
+
+        for record in records {
+            text.push_str(&render_record(record)?);
+            text.push('\n');
+        }
+    }
+
+    text.push('\n');
+
+    if let Some(Value::String { val: responder, .. }) = column("responder") {
+        let server = responder
+            .parse::<std::net::SocketAddr>()
+            .map_or(responder.clone(), |addr| {
+                format!("{}#{}({})", addr.ip(), addr.port(), addr.ip())
+            });
+
+        match column(constants::columns::TRANSPORT) {
+            Some(Value::String { val: transport, .. }) => text.push_str(&format!(
+                ";; SERVER: {} ({})\n",
+                server,
+                transport.to_uppercase()
+            )),
+            _ => text.push_str(&format!(";; SERVER: {}\n", server)),
+        }
+    }
+
+    if let Some(Value::Filesize { val: size, .. }) = column("size") {
+        text.push_str(&format!(";; MSG SIZE  rcvd: {}\n", size));
+    }
+
+    Ok(text)
+}
+
+fn render_record(record: &Value) -> Result<String, LabeledError> {
+    let (name, rtype, class, ttl, rdata) = zone::record_columns(record, None, DNSClass::IN)?;
+    let rdata = presentation::rdata(rtype, &rdata, &Names::default())?.line();
+
+    let mut line = name.to_string();
+    tab_to(&mut line, TTL_COLUMN);
+    line.push_str(&ttl.to_string());
+    tab_to(&mut line, CLASS_COLUMN);
+    line.push_str(&class.to_string());
+    tab_to(&mut line, TYPE_COLUMN);
+    line.push_str(&serde::RTypeName(rtype).to_string());
+    tab_to(&mut line, RDATA_COLUMN);
+    line.push_str(&rdata);
+
+    Ok(line)
+}
+
+/// The EDNS line of the OPT pseudosection, and a line for each of its options.
+fn render_edns(edns: &Value) -> Result<String, LabeledError> {
+    let version = edns
+        .get_data_by_key("version")
+        .and_then(|version| version.as_int().ok())
+        .unwrap_or_default();
+    let dnssec_ok = edns
+        .get_data_by_key("dnssec_ok")
+        .and_then(|dnssec_ok| dnssec_ok.as_bool().ok())
+        .unwrap_or_default();
+    let udp = match edns.get_data_by_key("max_payload") {
+        Some(Value::Filesize { val, .. }) | Some(Value::Int { val, .. }) => val,
+        _ => constants::config::default::EDNS_PAYLOAD.into(),
+    };
+
+    let mut text = format!(
+        "; EDNS: version: {}, flags:{}; udp: {}\n",
+        version,
+        if dnssec_ok { " do" } else { "" },
+        udp
+    );
+
+    let Some(Value::Record { val: opts, .. }) = edns.get_data_by_key("opts") else {
+        return Ok(text);
+    };
+
+    for (name, option) in opts.iter() {
+        let line = match option {
+            Value::List { vals, .. } => format!(
+                "; {}: {}",
+                name,
+                vals.iter()
+                    .map(|val| val.coerce_str().map(|val| val.into_owned()))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(" ")
+            ),
+            Value::Record { .. } => {
+                let code = field(option, "code")?;
+                let code = u16::try_from(code.as_int()?).map_err(|err| {
+                    LabeledError::new("invalid EDNS option").with_label(
+                        format!("code should be a 16 bit unsigned int: {err}"),
+                        code.span(),
+                    )
+                })?;
+                let data = match field(option, "data")? {
+                    Value::Binary { val, .. } => val,
+                    Value::String { val, .. } => val.into_bytes(),
+                    other => {
+                        return Err(LabeledError::new("invalid EDNS option")
+                            .with_label("data must be binary or a string", other.span()))
+                    }
+                };
+
+                render_option(code, &data)
+            }
+            other => format!(
+                "; {}: {}",
+                name,
+                other.to_abbreviated_string(&Default::default())
+            ),
+        };
+
+        text.push_str(&line);
+        text.push('\n');
+    }
+
+    Ok(text)
+}
+
+/// An option by its code, with its data as hex, and as text too where it is
+/// meant to be read.
+fn render_option(code: u16, data: &[u8]) -> String {
+    let hex = data_encoding::HEXLOWER.encode(data);
+
+    match code {
+        constants::edns::NSID => format!("; NSID: {} (\"{}\")", hex, text(data)),
+        constants::edns::SUBNET => match serde::client_subnet(data) {
+            Some((address, source_prefix, scope_prefix)) => format!(
+                "; CLIENT-SUBNET: {}/{}/{}",
+                address, source_prefix, scope_prefix
+            ),
+            None => format!("; CLIENT-SUBNET: {}", hex),
+        },
+        constants::edns::COOKIE => format!("; COOKIE: {}", hex),
+        constants::edns::PADDING => format!("; PAD: ({} bytes)", data.len()),
+        constants::ede::OPTION_CODE => match data {
+            [high, low, extra_text @ ..] => {
+                let info_code = u16::from_be_bytes([*high, *low]);
+                let mut line = format!("; EDE: {}", info_code);
+
+                if let Some(purpose) = constants::ede::purpose(info_code) {
+                    line.push_str(&format!(" ({})", purpose));
+                }
+                if !extra_text.is_empty() {
+                    line.push_str(&format!(": ({})", text(extra_text)));
+                }
+
+                line
+            }
+            _ => format!("; EDE: {}", hex),
+        },
+        code => format!("; OPT={}: {} (\"{}\")", code, hex, text(data)),
+    }
+}
+
+/// The printable ASCII of `data`, with other bytes as dots, as dig shows text
+/// in options.
+fn text(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| match byte {
+            b' '..=b'~' => *byte as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+/// Pads `line` with tabs to the tab stop at `column`, or a space if it is
+/// already past it.
+fn tab_to(line: &mut String, column: usize) {
+    let mut width = line.chars().fold(0, |width, c| {
+        if c == '\t' {
+            (width / 8 + 1) * 8
+        } else {
+            width + 1
+        }
+    });
+
+    if width >= column {
+        line.push(' ');
+        return;
+    }
+
+    while width < column {
+        line.push('\t');
+        width = (width / 8 + 1) * 8;
+    }
+}
+
+fn field(value: &Value, col: &str) -> Result<Value, LabeledError> {
+    value.get_data_by_key(col).ok_or_else(|| {
+        LabeledError::new("invalid message")
+            .with_label(format!("must have a column named '{}'", col), value.span())
+    })
+}
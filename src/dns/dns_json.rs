@@ -0,0 +1,247 @@
+//! Queries to the JSON APIs of resolvers (application/dns-json), as served by
+//! Cloudflare and Google, whose responses are mapped into messages.
+
+use std::sync::Arc;
+
+use hickory_proto::{
+    error::ProtoError,
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::RecordType,
+};
+use hickory_resolver::Name;
+use nu_protocol::{Record, Span, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_rustls::TlsConnector;
+
+use super::{config::Config, constants, zone};
+
+/// Asks the JSON API at `path` of the server for the records of the query,
+/// over an HTTP/1.1 connection of its own.
+pub async fn query(
+    query: &Query,
+    config: &Config,
+    path: &str,
+    tls_config: Arc<rustls::ClientConfig>,
+) -> Result<Message, ProtoError> {
+    // safe to unwrap because having a DNS name is enforced for HTTPS when
+    // constructing the config
+    let host = config.dns_name.as_ref().unwrap().item.as_str();
+    let server_name = rustls::ServerName::try_from(host)
+        .map_err(|err| ProtoError::from(format!("invalid DNS name {}: {}", host, err)))?;
+
+    let tcp = TcpStream::connect(config.server.item).await?;
+    let mut tls = TlsConnector::from(tls_config)
+        .connect(server_name, tcp)
+        .await?;
+
+    let port = config.server.item.port();
+    let authority = match port {
+        443 => host.to_string(),
+        port => format!("{}:{}", host, port),
+    };
+
+    // the client subnet is a parameter of its own, as there is no EDNS
+    let client_subnet = match &config.client_subnet {
+        Some(subnet) => format!(
+            "&edns_client_subnet={}",
+            percent_encode(&format!("{}/{}", subnet.item.0, subnet.item.1))
+        ),
+        None => String::new(),
+    };
+    let checking_disabled = if config.checking_disabled.item {
+        "&cd=1"
+    } else {
+        ""
+    };
+
+    let request = format!(
+        "GET {}?name={}&type={}{}{} HTTP/1.1\r\nHost: {}\r\nAccept: application/dns-json\r\nConnection: close\r\n\r\n",
+        path,
+        percent_encode(&query.name().to_ascii()),
+        u16::from(query.query_type()),
+        client_subnet,
+        checking_disabled,
+        authority,
+    );
+    tls.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    if let Err(err) = tls.read_to_end(&mut response).await {
+        // some servers close the connection without a TLS close_notify once
+        // the response is sent, which is not an error in HTTP/1.1
+        if err.kind() != std::io::ErrorKind::UnexpectedEof || response.is_empty() {
+            return Err(err.into());
+        }
+    }
+
+    let body = http_body(&response)?;
+    let json: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|err| ProtoError::from(format!("invalid JSON response: {}", err)))?;
+
+    Ok(message_from_json(query, &json))
+}
+
+/// The body of an HTTP/1.1 response, if its status is 200, with the chunked
+/// transfer coding removed (RFC 9112 section 7.1).
+fn http_body(response: &[u8]) -> Result<Vec<u8>, ProtoError> {
+    let end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| ProtoError::from("incomplete HTTP response"))?;
+
+    let head = String::from_utf8_lossy(&response[..end]);
+    let mut body = &response[end + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap_or_default();
+
+    match status.split(' ').nth(1) {
+        Some("200") => {}
+        _ => return Err(ProtoError::from(format!("HTTP error: {}", status))),
+    }
+
+    let mut chunked = false;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        match name.trim().to_ascii_lowercase().as_str() {
+            "transfer-encoding" => chunked = value.to_ascii_lowercase().contains("chunked"),
+            "content-length" => {
+                if let Ok(len) = value.trim().parse::<usize>() {
+                    body = &body[..len.min(body.len())];
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !chunked {
+        return Ok(body.to_vec());
+    }
+
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(|| ProtoError::from("incomplete chunked HTTP response"))?;
+
+        // the size may be followed by chunk extensions, which are ignored
+        let size = String::from_utf8_lossy(&body[..line_end]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|err| ProtoError::from(format!("invalid HTTP chunk size: {}", err)))?;
+
+        if size == 0 {
+            return Ok(decoded);
+        }
+
+        let chunk = body
+            .get(line_end + 2..line_end + 2 + size)
+            .ok_or_else(|| ProtoError::from("incomplete chunked HTTP response"))?;
+        decoded.extend_from_slice(chunk);
+
+        body = body.get(line_end + 2 + size + 2..).unwrap_or_default();
+    }
+}
+
+/// A response of the JSON API as a message. The question is that of the query
+/// if the response leaves it out, and records whose data can not be read are
+/// left out.
+fn message_from_json(query: &Query, json: &serde_json::Value) -> Message {
+    let flag = |name: &str| json[name].as_bool().unwrap_or(false);
+
+    let mut message = Message::new();
+    message
+        .set_message_type(MessageType::Response)
+        .set_op_code(OpCode::Query)
+        .set_truncated(flag("TC"))
+        .set_recursion_desired(flag("RD"))
+        .set_recursion_available(flag("RA"))
+        .set_authentic_data(flag("AD"))
+        .set_checking_disabled(flag("CD"))
+        .set_response_code(<ResponseCode as From<u16>>::from(
+            json["Status"].as_u64().unwrap_or_default() as u16,
+        ));
+
+    let questions = json["Question"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|question| {
+            let name = Name::from_utf8(question["name"].as_str()?).ok()?;
+            let rtype = RecordType::from(question["type"].as_u64()? as u16);
+
+            let mut question = Query::query(name, rtype);
+            question.set_query_class(query.query_class());
+            Some(question)
+        })
+        .collect::<Vec<_>>();
+
+    if questions.is_empty() {
+        message.add_query(query.clone());
+    } else {
+        message.add_queries(questions);
+    }
+
+    let records = |section: &str| {
+        json[section]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|record| {
+                let value = Value::record(
+                    Record::from_iter([
+                        (
+                            constants::columns::NAME.into(),
+                            Value::string(record["name"].as_str()?, Span::unknown()),
+                        ),
+                        (
+                            constants::columns::TYPE.into(),
+                            Value::int(record["type"].as_i64()?, Span::unknown()),
+                        ),
+                        (
+                            constants::columns::TTL.into(),
+                            Value::int(record["TTL"].as_i64().unwrap_or_default(), Span::unknown()),
+                        ),
+                        (
+                            constants::columns::RDATA.into(),
+                            Value::string(record["data"].as_str()?, Span::unknown()),
+                        ),
+                    ]),
+                    Span::unknown(),
+                );
+
+                zone::record_from_value(&value, None, query.query_class())
+                    .inspect_err(|err| {
+                        tracing::debug!(dns_json.record = %record, dns_json.error = ?err, "skipping record whose data can not be read");
+                    })
+                    .ok()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    message.add_answers(records("Answer"));
+    message.add_name_servers(records("Authority"));
+    message.add_additionals(records("Additional"));
+
+    message
+}
+
+/// The name as a query parameter, with all but its unreserved characters
+/// percent-encoded (RFC 3986 section 2).
+fn percent_encode(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,559 @@
+//! DNSCrypt (https://dnscrypt.info/protocol), which encrypts the queries to a
+//! resolver with the key of a short-lived certificate that the resolver
+//! serves in a TXT record, signed with the long-term key of its provider.
+//!
+//! Only the X25519-XChaCha20Poly1305 construction (es-version 2) is spoken.
+
+use std::{
+    borrow::Borrow,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crypto_box::{
+    aead::{Aead, OsRng},
+    ChaChaBox, PublicKey, SecretKey,
+};
+use futures_util::Stream;
+use hickory_proto::{
+    error::ProtoError,
+    op::{Message, MessageFinalizer, MessageType, MessageVerifier, Query},
+    rr::{RData, RecordType},
+    xfer::{DnsRequest, DnsRequestSender, DnsResponse, DnsResponseStream},
+};
+use hickory_resolver::{config::Protocol, Name};
+use ring::signature;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+    sync::Mutex,
+};
+
+use super::{constants, tsig::TsigSigner};
+
+const CERT_MAGIC: &[u8] = b"DNSC";
+/// X25519-XChaCha20Poly1305.
+const ES_VERSION: [u8; 2] = [0x00, 0x02];
+const RESOLVER_MAGIC: &[u8] = b"r6fnvWj8";
+const CERT_LEN: usize = 124;
+const TAG_LEN: usize = 16;
+/// Queries over UDP are padded to at least this length, so that the
+/// resolver has room to answer without truncating (section 10).
+const MIN_UDP_QUERY_LEN: usize = 256;
+const PAD_BLOCK_SIZE: usize = 64;
+
+/// A resolver given by a DNSCrypt stamp: the name its certificates are served
+/// at, and the key of the provider that signs them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsCryptServer {
+    pub provider_name: Name,
+    pub public_key: [u8; 32],
+}
+
+/// The certificate of a resolver, which holds the key to encrypt queries to
+/// it with.
+#[derive(Debug, Clone)]
+struct Certificate {
+    resolver_pk: [u8; 32],
+    client_magic: [u8; 8],
+    serial: u32,
+    ts_start: u32,
+    ts_end: u32,
+    fetched: Instant,
+}
+
+impl Certificate {
+    /// Reads a certificate, if it is one for X25519-XChaCha20Poly1305 signed
+    /// by the provider.
+    fn parse(bytes: &[u8], provider_key: &[u8; 32]) -> Option<Self> {
+        if bytes.len() < CERT_LEN
+            || &bytes[..4] != CERT_MAGIC
+            || bytes[4..6] != ES_VERSION
+            || bytes[6..8] != [0, 0]
+        {
+            return None;
+        }
+
+        // the signature covers everything after it, extensions included
+        let (sig, signed) = bytes[8..].split_at(64);
+        signature::UnparsedPublicKey::new(&signature::ED25519, provider_key)
+            .verify(signed, sig)
+            .ok()?;
+
+        let u32_at = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+
+        Some(Self {
+            resolver_pk: bytes[72..104].try_into().unwrap(),
+            client_magic: bytes[104..112].try_into().unwrap(),
+            serial: u32_at(112),
+            ts_start: u32_at(116),
+            ts_end: u32_at(120),
+            fetched: Instant::now(),
+        })
+    }
+
+    fn is_valid(&self, now: u32) -> bool {
+        (self.ts_start..=self.ts_end).contains(&now)
+    }
+}
+
+/// Sends the queries of a client to a DNSCrypt resolver, over UDP or TCP,
+/// each from a socket or connection of its own. The certificate is fetched
+/// with the first query, and again once it is an hour old or has expired,
+/// which picks up the certificates the resolver rotates to.
+#[derive(Clone)]
+pub struct DnsCryptClientStream {
+    server: SocketAddr,
+    protocol: Protocol,
+    resolver: Arc<DnsCryptServer>,
+    /// Shared between the clones of the stream, so that the clients of a
+    /// connection fetch the certificate once.
+    certificate: Arc<Mutex<Option<Certificate>>>,
+    signer: Option<Arc<TsigSigner>>,
+    is_shutdown: bool,
+}
+
+impl DnsCryptClientStream {
+    pub fn new(
+        server: SocketAddr,
+        protocol: Protocol,
+        resolver: DnsCryptServer,
+        signer: Option<Arc<TsigSigner>>,
+    ) -> Self {
+        Self {
+            server,
+            protocol,
+            resolver: Arc::new(resolver),
+            certificate: Arc::new(Mutex::new(None)),
+            signer,
+            is_shutdown: false,
+        }
+    }
+
+    /// The certificate to encrypt queries with, fetched anew if there is none
+    /// yet or it is due to be.
+    async fn certificate(&self) -> Result<Certificate, ProtoError> {
+        let mut cached = self.certificate.lock().await;
+        let now = unix_time();
+
+        match &*cached {
+            Some(cert)
+                if cert.is_valid(now)
+                    && cert.fetched.elapsed()
+                        < constants::config::default::DNSCRYPT_CERT_REFRESH =>
+            {
+                Ok(cert.clone())
+            }
+            _ => {
+                let cert = self.fetch_certificate(now).await?;
+                tracing::debug!(
+                    dnscrypt.cert.serial = cert.serial,
+                    dnscrypt.cert.ts_end = cert.ts_end,
+                    "fetched DNSCrypt certificate"
+                );
+                Ok(cached.insert(cert).clone())
+            }
+        }
+    }
+
+    /// Asks the resolver for its certificates in the clear, and picks the
+    /// valid one with the highest serial.
+    async fn fetch_certificate(&self, now: u32) -> Result<Certificate, ProtoError> {
+        let mut message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_recursion_desired(true)
+            .add_query(Query::query(
+                self.resolver.provider_name.clone(),
+                RecordType::TXT,
+            ));
+
+        let id = message.id();
+        let response = self
+            .exchange(&message.to_vec()?, |response| {
+                response.get(..2) == Some(&id.to_be_bytes())
+            })
+            .await?;
+
+        Message::from_vec(&response)?
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::TXT(txt)) => Some(txt.txt_data().concat()),
+                _ => None,
+            })
+            .filter_map(|cert| {
+                let parsed = Certificate::parse(&cert, &self.resolver.public_key);
+                if parsed.is_none() {
+                    tracing::debug!(
+                        dnscrypt.cert.len = cert.len(),
+                        "skipping DNSCrypt certificate that is unsupported or badly signed"
+                    );
+                }
+                parsed
+            })
+            .filter(|cert| cert.is_valid(now))
+            .max_by_key(|cert| cert.serial)
+            .ok_or_else(|| {
+                ProtoError::from(format!(
+                    "{} serves no valid DNSCrypt certificate signed by the key of its stamp",
+                    self.resolver.provider_name
+                ))
+            })
+    }
+
+    /// Encrypts a query, sends it, and decrypts the response.
+    async fn query(
+        self,
+        query: Vec<u8>,
+        id: u16,
+        verifier: Option<MessageVerifier>,
+    ) -> Result<DnsResponse, ProtoError> {
+        let cert = self.certificate().await?;
+        let (client_pk, shared_key) = key_exchange(&cert.resolver_pk);
+
+        let mut nonce = [0; 24];
+        nonce[..12].copy_from_slice(&rand::random::<[u8; 12]>());
+
+        let min_len = match self.protocol {
+            Protocol::Udp => MIN_UDP_QUERY_LEN,
+            _ => 0,
+        };
+
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&cert.client_magic);
+        packet.extend_from_slice(&client_pk);
+        packet.extend_from_slice(&nonce[..12]);
+        packet.extend(seal(&shared_key, &nonce, &pad(&query, min_len))?);
+
+        let response = self
+            .exchange(&packet, |response| {
+                response.starts_with(RESOLVER_MAGIC)
+                    && response.get(8..20) == Some(&nonce[..12])
+                    && response.len() >= 32 + TAG_LEN
+            })
+            .await?;
+
+        let nonce: [u8; 24] = response[8..32].try_into().unwrap();
+        let response = open(&shared_key, &nonce, &response[32..])
+            .as_deref()
+            .and_then(unpad)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| ProtoError::from("DNSCrypt response could not be decrypted"))?;
+
+        let message = Message::from_vec(&response)?;
+        if message.id() != id {
+            return Err(ProtoError::from(format!(
+                "expected message id {} in the DNSCrypt response, got {}",
+                id,
+                message.id()
+            )));
+        }
+
+        match verifier {
+            Some(mut verifier) => verifier(&response),
+            None => Ok(DnsResponse::new(message, response)),
+        }
+    }
+
+    /// Sends a packet to the resolver, and returns the first response that
+    /// `is_response` accepts. Over TCP, both are prefixed by their length.
+    async fn exchange(
+        &self,
+        packet: &[u8],
+        is_response: impl Fn(&[u8]) -> bool,
+    ) -> Result<Vec<u8>, ProtoError> {
+        match self.protocol {
+            Protocol::Tcp => {
+                let mut tcp = TcpStream::connect(self.server).await?;
+
+                let len = u16::try_from(packet.len())
+                    .map_err(|_| ProtoError::from("DNSCrypt query is too large for TCP"))?;
+                let mut framed = len.to_be_bytes().to_vec();
+                framed.extend_from_slice(packet);
+                tcp.write_all(&framed).await?;
+
+                let len = tcp.read_u16().await?;
+                let mut response = vec![0; len as usize];
+                tcp.read_exact(&mut response).await?;
+
+                if !is_response(&response) {
+                    return Err(ProtoError::from(
+                        "the DNSCrypt resolver answered with something else than a response",
+                    ));
+                }
+
+                Ok(response)
+            }
+            _ => {
+                let bind_addr: SocketAddr = match self.server {
+                    SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+                    SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+                };
+
+                let socket = UdpSocket::bind(bind_addr).await?;
+                socket.connect(self.server).await?;
+                socket.send(packet).await?;
+
+                let mut buf = vec![0; u16::MAX as usize];
+                loop {
+                    let len = socket.recv(&mut buf).await?;
+
+                    if is_response(&buf[..len]) {
+                        return Ok(buf[..len].to_vec());
+                    }
+                    tracing::debug!(response.len = len, "skipping unexpected DNSCrypt packet");
+                }
+            }
+        }
+    }
+}
+
+impl DnsRequestSender for DnsCryptClientStream {
+    fn send_message(&mut self, mut request: DnsRequest) -> DnsResponseStream {
+        if self.is_shutdown {
+            return ProtoError::from("the DNSCrypt stream is shut down").into();
+        }
+
+        // each query goes out from a socket or connection of its own, like
+        // the UDP stream of hickory
+        request.set_id(rand::random());
+
+        let mut verifier = None;
+        if let Some(signer) = &self.signer {
+            if signer.should_finalize_message(&request) {
+                match request.finalize::<TsigSigner>(signer.borrow(), unix_time()) {
+                    Ok(answer_verifier) => verifier = answer_verifier,
+                    Err(err) => return err.into(),
+                }
+            }
+        }
+
+        let query = match request.to_vec() {
+            Ok(query) => query,
+            Err(err) => return err.into(),
+        };
+
+        Box::pin(self.clone().query(query, request.id(), verifier)).into()
+    }
+
+    fn shutdown(&mut self) {
+        self.is_shutdown = true;
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.is_shutdown
+    }
+}
+
+// there is nothing to drive, as every query is sent on its own
+impl Stream for DnsCryptClientStream {
+    type Item = Result<(), ProtoError>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.is_shutdown {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(Ok(())))
+        }
+    }
+}
+
+fn unix_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as u32)
+}
+
+/// A public key of the query's own, and the key it shares with the resolver,
+/// as with libsodium's crypto_box_curve25519xchacha20poly1305.
+fn key_exchange(resolver_pk: &[u8; 32]) -> ([u8; 32], ChaChaBox) {
+    let secret_key = SecretKey::generate(&mut OsRng);
+    let shared_key = ChaChaBox::new(&PublicKey::from(*resolver_pk), &secret_key);
+
+    (*secret_key.public_key().as_bytes(), shared_key)
+}
+
+/// Pads a query with 0x80 and zeros to a multiple of the block size, and to at
+/// least `min_len`.
+fn pad(query: &[u8], min_len: usize) -> Vec<u8> {
+    let len = (query.len() + 1)
+        .max(min_len)
+        .next_multiple_of(PAD_BLOCK_SIZE);
+
+    let mut padded = query.to_vec();
+    padded.push(0x80);
+    padded.resize(len, 0);
+    padded
+}
+
+fn unpad(padded: &[u8]) -> Option<&[u8]> {
+    let end = padded.iter().rposition(|&byte| byte != 0)?;
+    (padded[end] == 0x80).then_some(&padded[..end])
+}
+
+/// Encrypts a message with the key shared with the resolver, the tag before
+/// the ciphertext as with libsodium's crypto_box.
+fn seal(key: &ChaChaBox, nonce: &[u8; 24], message: &[u8]) -> Result<Vec<u8>, ProtoError> {
+    key.encrypt(nonce.into(), message)
+        .map_err(|_| ProtoError::from("DNSCrypt query could not be encrypted"))
+}
+
+/// Decrypts a message sealed by [`seal`], if its tag is right.
+fn open(key: &ChaChaBox, nonce: &[u8; 24], sealed: &[u8]) -> Option<Vec<u8>> {
+    key.decrypt(nonce.into(), sealed).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::rr::{rdata::A, rdata::TXT, Record};
+    use nu_protocol::Value;
+    use ring::signature::KeyPair;
+
+    use super::*;
+    use crate::dns::{client::DnsClient, config::Config};
+
+    fn hex(hex: &str) -> Vec<u8> {
+        data_encoding::HEXLOWER.decode(hex.as_bytes()).unwrap()
+    }
+
+    /// A box made by libsodium's crypto_box_curve25519xchacha20poly1305_easy,
+    /// which is what DNSCrypt seals queries and responses with.
+    #[test]
+    fn seal_matches_libsodium() {
+        let client_sk = SecretKey::from(std::array::from_fn(|i| i as u8 + 1));
+        let resolver_pk = hex("5714769d116bf76436ae74bc793d2c30ad1903c59ac5273805c7e2698b410c36");
+        let nonce: [u8; 24] = std::array::from_fn(|i| i as u8 + 200);
+        let message = hex("123401000001000000000000076578616d706c65000001000180000000");
+        let sealed = hex(concat!(
+            "2f22320a1a2208edff3892b9325140ae",
+            "901900d89eb0e02cd3399271ea10ce472c0998a6ed7c4bf87595c4e664"
+        ));
+
+        let resolver_pk: [u8; 32] = resolver_pk.try_into().unwrap();
+        let key = ChaChaBox::new(&PublicKey::from(resolver_pk), &client_sk);
+        assert_eq!(seal(&key, &nonce, &message).unwrap(), sealed);
+        assert_eq!(open(&key, &nonce, &sealed), Some(message));
+
+        let mut forged = sealed;
+        forged[20] ^= 1;
+        assert_eq!(open(&key, &nonce, &forged), None);
+    }
+
+    /// A DNSCrypt resolver that serves its certificate, and then answers one
+    /// encrypted query with an A record.
+    async fn resolver(provider: signature::Ed25519KeyPair) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let resolver_sk = SecretKey::generate(&mut OsRng);
+
+        let mut signed = resolver_sk.public_key().as_bytes().to_vec();
+        signed.extend_from_slice(b"clientmg");
+        signed.extend_from_slice(&1u32.to_be_bytes());
+        signed.extend_from_slice(&(unix_time() - 60).to_be_bytes());
+        signed.extend_from_slice(&(unix_time() + 60).to_be_bytes());
+
+        let mut cert = b"DNSC\x00\x02\x00\x00".to_vec();
+        cert.extend_from_slice(provider.sign(&signed).as_ref());
+        cert.extend_from_slice(&signed);
+
+        tokio::spawn(async move {
+            let mut buf = vec![0; u16::MAX as usize];
+
+            let (len, client) = socket.recv_from(&mut buf).await.unwrap();
+            let query = Message::from_vec(&buf[..len]).unwrap();
+            let mut response = query.clone();
+            response
+                .set_message_type(MessageType::Response)
+                .add_answer(Record::from_rdata(
+                    query.queries()[0].name().clone(),
+                    300,
+                    RData::TXT(TXT::from_bytes(vec![&cert])),
+                ));
+            socket
+                .send_to(&response.to_vec().unwrap(), client)
+                .await
+                .unwrap();
+
+            let (len, client) = socket.recv_from(&mut buf).await.unwrap();
+            let packet = &buf[..len];
+            assert_eq!(&packet[..8], b"clientmg");
+            assert!(len >= 52 + MIN_UDP_QUERY_LEN);
+
+            let client_pk: [u8; 32] = packet[8..40].try_into().unwrap();
+            let shared_key = ChaChaBox::new(&PublicKey::from(client_pk), &resolver_sk);
+
+            let mut nonce = [0; 24];
+            nonce[..12].copy_from_slice(&packet[40..52]);
+            let query = open(&shared_key, &nonce, &packet[52..]).unwrap();
+            let query = Message::from_vec(unpad(&query).unwrap()).unwrap();
+
+            let mut response = query.clone();
+            response
+                .set_message_type(MessageType::Response)
+                .add_answer(Record::from_rdata(
+                    query.queries()[0].name().clone(),
+                    300,
+                    RData::A(A::new(192, 0, 2, 1)),
+                ));
+
+            nonce[12..].copy_from_slice(b"resolvernonc");
+            let mut packet = RESOLVER_MAGIC.to_vec();
+            packet.extend_from_slice(&nonce);
+            packet.extend(seal(&shared_key, &nonce, &pad(&response.to_vec().unwrap(), 0)).unwrap());
+            socket.send_to(&packet, client).await.unwrap();
+        });
+
+        addr
+    }
+
+    #[test]
+    fn queries_a_resolver_given_by_a_dnscrypt_stamp() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        runtime.block_on(async {
+            let provider = signature::Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+            let provider_pk = provider.public_key().as_ref().to_vec();
+            let addr = resolver(provider).await;
+
+            let mut stamp = vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0];
+            for field in [
+                addr.to_string().as_bytes(),
+                &provider_pk,
+                b"2.dnscrypt-cert.example",
+            ] {
+                stamp.push(field.len() as u8);
+                stamp.extend_from_slice(field);
+            }
+            let stamp = format!("sdns://{}", data_encoding::BASE64URL_NOPAD.encode(&stamp));
+
+            let config = Config::from_values(|flag| match flag {
+                constants::flags::SERVER => Some(Value::test_string(stamp.clone())),
+                constants::flags::TYPE => Some(Value::test_string("A")),
+                // a single client, which sends a single query
+                constants::flags::DNSSEC => Some(Value::test_string("none")),
+                _ => None,
+            })
+            .unwrap();
+
+            let (client, _bg) = DnsClient::new(&config).await.unwrap();
+            let response = client
+                .send_query(
+                    Query::query(Name::from_ascii("example.").unwrap(), RecordType::A),
+                    &config,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                response.answers()[0].data(),
+                Some(&RData::A(A::new(192, 0, 2, 1)))
+            );
+        });
+    }
+}
@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Counts failed queries, so that a batch can be given up on once too many of
+/// them have failed, e.g. because the nameserver is down.
+#[derive(Debug)]
+pub struct FailureLimit {
+    limit: usize,
+    failures: AtomicUsize,
+    skip_reported: AtomicBool,
+}
+
+impl FailureLimit {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            failures: AtomicUsize::new(0),
+            skip_reported: AtomicBool::new(false),
+        }
+    }
+
+    pub fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reached(&self) -> bool {
+        self.failures.load(Ordering::Relaxed) >= self.limit
+    }
+
+    /// Returns true exactly once, for the first query skipped after the limit
+    /// was reached.
+    pub fn take_skip_report(&self) -> bool {
+        !self.skip_reported.swap(true, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,738 @@
+//! GSS-TSIG (RFC 3645), which signs messages with a security context that is
+//! negotiated with the nameserver through TKEY (RFC 2930) from Kerberos
+//! credentials, as Active Directory requires of dynamic updates.
+//!
+//! The GSS-API library of the system is loaded when a context is negotiated,
+//! so that the plugin does not link against it.
+
+use std::{
+    ffi::c_void,
+    ptr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hickory_proto::{
+    error::{ProtoError, ProtoResult},
+    op::{Message, MessageFinalizer, MessageType, MessageVerifier, Query, ResponseCode},
+    rr::{
+        dnssec::rdata::{
+            tsig::{make_tsig_record, message_tbs, signed_bitmessage_to_buf, TsigAlgorithm, TSIG},
+            DNSSECRData,
+        },
+        rdata::NULL,
+        DNSClass, RData, Record, RecordType,
+    },
+    serialize::binary::{BinDecodable, BinDecoder, BinEncodable},
+    xfer::DnsResponse,
+};
+use hickory_resolver::Name;
+use libloading::Library;
+use nu_protocol::{LabeledError, Spanned};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::{config::Config, constants};
+
+/// The GSS-API libraries of MIT Kerberos and Heimdal.
+const LIBRARIES: &[&str] = &["libgssapi_krb5.so.2", "libgssapi.so.3"];
+
+/// Transaction key (RFC 2930), which hickory does not know.
+const TKEY: u16 = 249;
+/// Mode of a TKEY record that carries GSS-API tokens (RFC 2930 section 2.5).
+const TKEY_MODE_GSS_API: u16 = 3;
+/// How long the server is asked to keep a key (RFC 2930 section 2.3), which
+/// cached clients keep signing with.
+const KEY_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+const GSS_S_COMPLETE: u32 = 0;
+const GSS_S_CONTINUE_NEEDED: u32 = 1;
+const GSS_C_MUTUAL_FLAG: u32 = 2;
+const GSS_C_REPLAY_FLAG: u32 = 4;
+const GSS_C_INTEG_FLAG: u32 = 32;
+const GSS_C_GSS_CODE: i32 = 1;
+const GSS_C_MECH_CODE: i32 = 2;
+
+#[repr(C)]
+struct GssBuffer {
+    length: usize,
+    value: *mut c_void,
+}
+
+impl GssBuffer {
+    fn empty() -> Self {
+        Self {
+            length: 0,
+            value: ptr::null_mut(),
+        }
+    }
+
+    /// A buffer over `bytes`, which the library only reads.
+    fn borrowed(bytes: &[u8]) -> Self {
+        Self {
+            length: bytes.len(),
+            value: bytes.as_ptr() as *mut c_void,
+        }
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        if self.value.is_null() {
+            return Vec::new();
+        }
+        // safe because the library hands out buffers of `length` bytes
+        unsafe { std::slice::from_raw_parts(self.value as *const u8, self.length) }.to_vec()
+    }
+}
+
+#[repr(C)]
+struct GssOid {
+    length: u32,
+    elements: *const u8,
+}
+
+// the OIDs are constants, which the library only reads
+unsafe impl Sync for GssOid {}
+
+/// GSS_C_NT_HOSTBASED_SERVICE, for names like DNS@ns1.example.com (RFC 2743
+/// section 4.1).
+static HOSTBASED_SERVICE: GssOid = GssOid {
+    length: 10,
+    elements: b"\x2a\x86\x48\x86\xf7\x12\x01\x02\x01\x04".as_ptr(),
+};
+
+/// SPNEGO (RFC 4178), which Active Directory negotiates Kerberos with, as
+/// `nsupdate -g` does.
+static SPNEGO: GssOid = GssOid {
+    length: 6,
+    elements: b"\x2b\x06\x01\x05\x05\x02".as_ptr(),
+};
+
+type ImportName =
+    unsafe extern "C" fn(*mut u32, *const GssBuffer, *const GssOid, *mut *mut c_void) -> u32;
+type InitSecContext = unsafe extern "C" fn(
+    *mut u32,
+    *mut c_void,
+    *mut *mut c_void,
+    *mut c_void,
+    *const GssOid,
+    u32,
+    u32,
+    *const c_void,
+    *const GssBuffer,
+    *mut *const GssOid,
+    *mut GssBuffer,
+    *mut u32,
+    *mut u32,
+) -> u32;
+type GetMic =
+    unsafe extern "C" fn(*mut u32, *mut c_void, u32, *const GssBuffer, *mut GssBuffer) -> u32;
+type VerifyMic = unsafe extern "C" fn(
+    *mut u32,
+    *mut c_void,
+    *const GssBuffer,
+    *const GssBuffer,
+    *mut u32,
+) -> u32;
+type ReleaseBuffer = unsafe extern "C" fn(*mut u32, *mut GssBuffer) -> u32;
+type ReleaseName = unsafe extern "C" fn(*mut u32, *mut *mut c_void) -> u32;
+type DeleteSecContext = unsafe extern "C" fn(*mut u32, *mut *mut c_void, *mut GssBuffer) -> u32;
+type DisplayStatus =
+    unsafe extern "C" fn(*mut u32, u32, i32, *const GssOid, *mut u32, *mut GssBuffer) -> u32;
+
+/// The functions of the GSS-API library (RFC 2744) that negotiating a context
+/// and signing with it take.
+struct Gssapi {
+    import_name: ImportName,
+    init_sec_context: InitSecContext,
+    get_mic: GetMic,
+    verify_mic: VerifyMic,
+    release_buffer: ReleaseBuffer,
+    release_name: ReleaseName,
+    delete_sec_context: DeleteSecContext,
+    display_status: DisplayStatus,
+    // the functions point into the library, so it is kept loaded with them
+    _library: Library,
+}
+
+impl Gssapi {
+    fn load() -> Result<Self, String> {
+        // safe because these are the GSS-API libraries, whose initializers
+        // have no requirements
+        let library = LIBRARIES
+            .iter()
+            .find_map(|name| unsafe { Library::new(name) }.ok())
+            .ok_or_else(|| {
+                format!(
+                    "could not load a GSS-API library, tried: {}",
+                    LIBRARIES.join(", ")
+                )
+            })?;
+
+        macro_rules! symbol {
+            ($name:literal) => {
+                // safe because the types are those of RFC 2744
+                *unsafe { library.get(concat!($name, "\0").as_bytes()) }
+                    .map_err(|err| format!("the GSS-API library has no {}: {}", $name, err))?
+            };
+        }
+
+        Ok(Self {
+            import_name: symbol!("gss_import_name"),
+            init_sec_context: symbol!("gss_init_sec_context"),
+            get_mic: symbol!("gss_get_mic"),
+            verify_mic: symbol!("gss_verify_mic"),
+            release_buffer: symbol!("gss_release_buffer"),
+            release_name: symbol!("gss_release_name"),
+            delete_sec_context: symbol!("gss_delete_sec_context"),
+            display_status: symbol!("gss_display_status"),
+            _library: library,
+        })
+    }
+
+    /// The output buffer of a call, released back to the library.
+    fn take(&self, mut buffer: GssBuffer) -> Vec<u8> {
+        let bytes = buffer.to_vec();
+        let mut minor = 0;
+        // safe because the buffer was allocated by the library
+        unsafe { (self.release_buffer)(&mut minor, &mut buffer) };
+        bytes
+    }
+
+    /// The messages of a major status, and of the minor status of the
+    /// mechanism along with it.
+    fn error(&self, call: &str, major: u32, minor: u32) -> String {
+        let mut messages = Vec::new();
+
+        for (status, kind) in [(major, GSS_C_GSS_CODE), (minor, GSS_C_MECH_CODE)] {
+            let mut context = 0;
+            loop {
+                let mut ignored = 0;
+                let mut message = GssBuffer::empty();
+                // safe because the message buffer is released by take
+                let result = unsafe {
+                    (self.display_status)(
+                        &mut ignored,
+                        status,
+                        kind,
+                        ptr::null(),
+                        &mut context,
+                        &mut message,
+                    )
+                };
+                if result != GSS_S_COMPLETE {
+                    break;
+                }
+
+                // MIT counts the terminating NUL in the length of messages
+                let message = self.take(message);
+                let message = String::from_utf8_lossy(&message);
+                let message = message.trim_end_matches('\0');
+                if !message.is_empty() {
+                    messages.push(message.to_string());
+                }
+                if context == 0 {
+                    break;
+                }
+            }
+        }
+
+        format!("{} failed: {}", call, messages.join(": "))
+    }
+}
+
+/// Whether a major status is an error, rather than complete or in need of
+/// another token.
+fn is_error(major: u32) -> bool {
+    major & 0xffff_0000 != 0
+}
+
+/// The name of the service a context is negotiated with.
+struct GssName(*mut c_void);
+
+// the name is only read by the library
+unsafe impl Send for GssName {}
+unsafe impl Sync for GssName {}
+
+/// What signing with a GSS-TSIG key needs of the security context it was
+/// negotiated under.
+trait SecurityContext: Send {
+    /// The message integrity code of a message, which is the MAC of its TSIG.
+    fn get_mic(&self, message: &[u8]) -> ProtoResult<Vec<u8>>;
+
+    /// Checks the message integrity code of a message signed by the peer.
+    fn verify_mic(&self, message: &[u8], mic: &[u8]) -> ProtoResult<()>;
+}
+
+/// A security context established with a nameserver by the GSS-API library.
+struct GssContext {
+    api: Gssapi,
+    handle: *mut c_void,
+}
+
+// the handle is only ever used behind the mutex of a GssKey
+unsafe impl Send for GssContext {}
+
+impl SecurityContext for GssContext {
+    fn get_mic(&self, message: &[u8]) -> ProtoResult<Vec<u8>> {
+        let (mut minor, mut mic) = (0, GssBuffer::empty());
+        // safe because the message outlives the call, and the MIC buffer is
+        // released by take
+        let major = unsafe {
+            (self.api.get_mic)(
+                &mut minor,
+                self.handle,
+                0,
+                &GssBuffer::borrowed(message),
+                &mut mic,
+            )
+        };
+
+        if is_error(major) {
+            return Err(ProtoError::from(self.api.error(
+                "gss_get_mic",
+                major,
+                minor,
+            )));
+        }
+        Ok(self.api.take(mic))
+    }
+
+    fn verify_mic(&self, message: &[u8], mic: &[u8]) -> ProtoResult<()> {
+        let (mut minor, mut qop) = (0, 0);
+        // safe because both buffers outlive the call
+        let major = unsafe {
+            (self.api.verify_mic)(
+                &mut minor,
+                self.handle,
+                &GssBuffer::borrowed(message),
+                &GssBuffer::borrowed(mic),
+                &mut qop,
+            )
+        };
+
+        if major != GSS_S_COMPLETE {
+            return Err(ProtoError::from(self.api.error(
+                "gss_verify_mic",
+                major,
+                minor,
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for GssContext {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            let mut minor = 0;
+            // safe because the handle was made by gss_init_sec_context, and is
+            // not used again
+            unsafe { (self.api.delete_sec_context)(&mut minor, &mut self.handle, ptr::null_mut()) };
+        }
+    }
+}
+
+/// A GSS-TSIG key: the name it was negotiated under, and the context that
+/// signs with it.
+#[derive(Clone)]
+pub struct GssKey {
+    name: Name,
+    context: Arc<Mutex<dyn SecurityContext>>,
+}
+
+impl GssKey {
+    fn get_mic(&self, message: &[u8]) -> ProtoResult<Vec<u8>> {
+        self.context
+            .lock()
+            .map_err(|_| ProtoError::from("GSS-API context poisoned"))?
+            .get_mic(message)
+    }
+
+    /// Checks the TSIG of a message signed with this key. `request_mac` is
+    /// the MAC of the request the message responds to, if any.
+    fn verify(&self, message: &[u8], request_mac: Option<&[u8]>) -> ProtoResult<()> {
+        let (tbv, record) = signed_bitmessage_to_buf(request_mac, message, true)?;
+        let Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) = record.data() else {
+            return Err(ProtoError::from("signature is not TSIG"));
+        };
+
+        if record.name() != &self.name || tsig.algorithm() != &TsigAlgorithm::Gss {
+            return Err(ProtoError::from("response is signed with another key"));
+        }
+
+        self.context
+            .lock()
+            .map_err(|_| ProtoError::from("GSS-API context poisoned"))?
+            .verify_mic(&tbv, tsig.mac())
+    }
+}
+
+impl MessageFinalizer for GssKey {
+    fn finalize_message(
+        &self,
+        message: &Message,
+        current_time: u32,
+    ) -> ProtoResult<(Vec<Record>, Option<MessageVerifier>)> {
+        let pre_tsig = TSIG::new(
+            TsigAlgorithm::Gss,
+            current_time as u64,
+            constants::config::default::TSIG_FUDGE,
+            Vec::new(),
+            message.id(),
+            0,
+            Vec::new(),
+        );
+        let mac = self.get_mic(&message_tbs(None, message, &pre_tsig, &self.name)?)?;
+        let tsig = make_tsig_record(self.name.clone(), pre_tsig.set_mac(mac.clone()));
+
+        let key = self.clone();
+        let verifier = move |response: &[u8]| {
+            key.verify(response, Some(&mac))?;
+            Ok(DnsResponse::new(
+                Message::from_vec(response)?,
+                response.to_vec(),
+            ))
+        };
+
+        Ok((vec![tsig], Some(Box::new(verifier))))
+    }
+}
+
+/// Negotiates a GSS-TSIG key with the nameserver of the config, over TCP,
+/// with the Kerberos credentials of the user for the DNS service of `host`.
+pub async fn negotiate(config: &Config, host: &Spanned<String>) -> Result<GssKey, LabeledError> {
+    let failed =
+        |msg: String| LabeledError::new("GSS-TSIG negotiation failed").with_label(msg, host.span);
+
+    let api = Gssapi::load().map_err(failed)?;
+
+    // the name is unique to this client, as keys are looked up by name on
+    // the server (RFC 3645 section 3.1.1), like nsupdate -g names them
+    let host_name = host.item.trim_end_matches('.');
+    let key_name = Name::from_ascii(format!("{}.sig-{}.", rand::random::<u32>(), host_name))
+        .map_err(|err| failed(format!("invalid key name: {}", err)))?;
+
+    let mut target = GssName(ptr::null_mut());
+    let service = format!("DNS@{}", host_name);
+    let mut minor = 0;
+    // safe because the service name outlives the call, and the name is
+    // released below
+    let major = unsafe {
+        (api.import_name)(
+            &mut minor,
+            &GssBuffer::borrowed(service.as_bytes()),
+            &HOSTBASED_SERVICE,
+            &mut target.0,
+        )
+    };
+    if is_error(major) {
+        return Err(failed(api.error("gss_import_name", major, minor)));
+    }
+
+    let mut context = GssContext {
+        api,
+        handle: ptr::null_mut(),
+    };
+    let result = exchange_tokens(config, &mut context, &target, &key_name).await;
+
+    // safe because the name is not used again
+    unsafe { (context.api.release_name)(&mut minor, &mut target.0) };
+    let last_response = result.map_err(failed)?;
+
+    let key = GssKey {
+        name: key_name,
+        context: Arc::new(Mutex::new(context)),
+    };
+
+    // the server signs the response that completes the context (RFC 3645
+    // section 3.1.3.1)
+    if let Some(response) = last_response {
+        key.verify(&response, None).map_err(|err| {
+            failed(format!(
+                "the response of the server is not signed with the key: {}",
+                err
+            ))
+        })?;
+    }
+
+    Ok(key)
+}
+
+/// Passes tokens between the GSS-API library and the server until the context
+/// is established, and returns the last response, if it is signed.
+async fn exchange_tokens(
+    config: &Config,
+    context: &mut GssContext,
+    target: &GssName,
+    key_name: &Name,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut tcp = TcpStream::connect(config.server.item)
+        .await
+        .map_err(|err| format!("could not connect to {}: {}", config.server.item, err))?;
+
+    let mut input: Option<Vec<u8>> = None;
+    let mut last_response = None;
+
+    loop {
+        let mut minor = 0;
+        let (major, output) = {
+            let input = input.as_deref().map(GssBuffer::borrowed);
+            let mut output = GssBuffer::empty();
+
+            // safe because the input token outlives the call, and the output
+            // token is released by take
+            let major = unsafe {
+                (context.api.init_sec_context)(
+                    &mut minor,
+                    ptr::null_mut(),
+                    &mut context.handle,
+                    target.0,
+                    &SPNEGO,
+                    GSS_C_MUTUAL_FLAG | GSS_C_REPLAY_FLAG | GSS_C_INTEG_FLAG,
+                    0,
+                    ptr::null(),
+                    input
+                        .as_ref()
+                        .map_or(ptr::null(), |input| input as *const GssBuffer),
+                    ptr::null_mut(),
+                    &mut output,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                )
+            };
+            (major, context.api.take(output))
+        };
+
+        if is_error(major) {
+            return Err(context.api.error("gss_init_sec_context", major, minor));
+        }
+
+        if !output.is_empty() {
+            let (token, response) = send_tkey(&mut tcp, key_name, &output).await?;
+            input = Some(token);
+            last_response = Message::from_vec(&response)
+                .is_ok_and(|message| !message.signature().is_empty())
+                .then_some(response);
+        } else if major & GSS_S_CONTINUE_NEEDED != 0 {
+            return Err("GSS-API needs a token from the server, but has none to send".into());
+        }
+
+        if major & GSS_S_CONTINUE_NEEDED == 0 {
+            return Ok(last_response);
+        }
+    }
+}
+
+/// Sends a token to the server in a TKEY query (RFC 3645 section 3.1.1), and
+/// returns the token of its response, along with the response as it is.
+async fn send_tkey(
+    tcp: &mut TcpStream,
+    key_name: &Name,
+    token: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as u32);
+    let tkey_type = RecordType::from(TKEY);
+
+    let mut query = Query::query(key_name.clone(), tkey_type);
+    query.set_query_class(DNSClass::ANY);
+
+    let mut tkey = Record::from_rdata(
+        key_name.clone(),
+        0,
+        RData::Unknown {
+            code: tkey_type,
+            rdata: NULL::with(tkey_rdata(token, now).map_err(|err| err.to_string())?),
+        },
+    );
+    tkey.set_dns_class(DNSClass::ANY);
+
+    let mut message = Message::new();
+    message
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .add_query(query)
+        .add_additional(tkey);
+
+    let request = message.to_vec().map_err(|err| err.to_string())?;
+    let io_err = |err: std::io::Error| format!("TKEY exchange failed: {}", err);
+
+    let len = u16::try_from(request.len()).map_err(|_| "GSS-API token is too large".to_string())?;
+    tcp.write_u16(len).await.map_err(io_err)?;
+    tcp.write_all(&request).await.map_err(io_err)?;
+
+    let len = tcp.read_u16().await.map_err(io_err)?;
+    let mut response = vec![0; len as usize];
+    tcp.read_exact(&mut response).await.map_err(io_err)?;
+
+    let parsed = Message::from_vec(&response).map_err(|err| err.to_string())?;
+    if parsed.id() != message.id() {
+        return Err("the server answered the TKEY query with another message".into());
+    }
+    if parsed.response_code() != ResponseCode::NoError {
+        return Err(format!(
+            "the server answered the TKEY query with {}",
+            parsed.response_code()
+        ));
+    }
+
+    let tkey = parsed
+        .answers()
+        .iter()
+        .find_map(|record| match record.data() {
+            Some(RData::Unknown { code, rdata })
+                if *code == tkey_type && record.name() == key_name =>
+            {
+                Some(rdata.anything())
+            }
+            _ => None,
+        })
+        .ok_or_else(|| "the response to the TKEY query has no TKEY record".to_string())?;
+
+    let (error, token) = parse_tkey(tkey)
+        .ok_or_else(|| "the response to the TKEY query has a malformed TKEY record".to_string())?;
+    if error != 0 {
+        return Err(format!(
+            "the server refused the GSS-API token: {}",
+            ResponseCode::from((error >> 4) as u8, error as u8 & 0x0f)
+        ));
+    }
+
+    Ok((token, response))
+}
+
+/// The RDATA of a TKEY record that carries a GSS-API token (RFC 2930 section
+/// 2), with the names uncompressed.
+fn tkey_rdata(token: &[u8], now: u32) -> ProtoResult<Vec<u8>> {
+    let lifetime = KEY_LIFETIME.as_secs() as u32;
+
+    let mut rdata = TsigAlgorithm::Gss.to_name().to_bytes()?;
+    rdata.extend_from_slice(&now.to_be_bytes());
+    rdata.extend_from_slice(&now.wrapping_add(lifetime).to_be_bytes());
+    rdata.extend_from_slice(&TKEY_MODE_GSS_API.to_be_bytes());
+    rdata.extend_from_slice(&0u16.to_be_bytes());
+    rdata.extend_from_slice(
+        &u16::try_from(token.len())
+            .map_err(|_| ProtoError::from("GSS-API token is too large"))?
+            .to_be_bytes(),
+    );
+    rdata.extend_from_slice(token);
+    rdata.extend_from_slice(&0u16.to_be_bytes());
+    Ok(rdata)
+}
+
+/// The error and key data of the RDATA of a TKEY record.
+fn parse_tkey(rdata: &[u8]) -> Option<(u16, Vec<u8>)> {
+    let mut decoder = BinDecoder::new(rdata);
+    Name::read(&mut decoder).ok()?;
+
+    // inception, expiration, and mode
+    decoder.read_slice(10).ok()?;
+    let error = decoder.read_u16().ok()?.unverified();
+    let len = decoder.read_u16().ok()?.unverified();
+    let key = decoder.read_vec(len as usize).ok()?.unverified();
+
+    Some((error, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::op::OpCode;
+    use ring::hmac;
+
+    use super::*;
+
+    /// A security context that both sides share, with MICs made by
+    /// HMAC-SHA256 under a fixed key instead of by the GSS-API library.
+    struct StubContext(hmac::Key);
+
+    impl SecurityContext for StubContext {
+        fn get_mic(&self, message: &[u8]) -> ProtoResult<Vec<u8>> {
+            Ok(hmac::sign(&self.0, message).as_ref().to_vec())
+        }
+
+        fn verify_mic(&self, message: &[u8], mic: &[u8]) -> ProtoResult<()> {
+            hmac::verify(&self.0, message, mic).map_err(|_| ProtoError::from("bad MIC"))
+        }
+    }
+
+    fn stub_context() -> StubContext {
+        StubContext(hmac::Key::new(hmac::HMAC_SHA256, b"established context"))
+    }
+
+    #[test]
+    fn signs_requests_and_verifies_responses() {
+        let key_name = Name::from_ascii("1234.sig-dc1.ad.example.com.").unwrap();
+        let key = GssKey {
+            name: key_name.clone(),
+            context: Arc::new(Mutex::new(stub_context())),
+        };
+
+        let mut request = Message::new();
+        request
+            .set_id(4321)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Update)
+            .add_query(Query::query(
+                Name::from_ascii("ad.example.com.").unwrap(),
+                RecordType::SOA,
+            ));
+        let mut verifier = request.finalize(&key, 1_700_000_000).unwrap().unwrap();
+        let request = request.to_vec().unwrap();
+
+        // the server checks the request with its side of the context
+        let server = stub_context();
+        let (tbv, record) = signed_bitmessage_to_buf(None, &request, true).unwrap();
+        let Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) = record.data() else {
+            panic!("request is not signed with TSIG");
+        };
+        assert_eq!(record.name(), &key_name);
+        assert_eq!(tsig.algorithm(), &TsigAlgorithm::Gss);
+        server.verify_mic(&tbv, tsig.mac()).unwrap();
+
+        // and signs its response over the MAC of the request
+        let mut response = Message::from_vec(&request).unwrap();
+        response.take_signature();
+        response.set_message_type(MessageType::Response);
+        let pre_tsig = TSIG::new(
+            TsigAlgorithm::Gss,
+            1_700_000_001,
+            constants::config::default::TSIG_FUDGE,
+            Vec::new(),
+            response.id(),
+            0,
+            Vec::new(),
+        );
+        let mac = server
+            .get_mic(&message_tbs(Some(tsig.mac()), &response, &pre_tsig, &key_name).unwrap())
+            .unwrap();
+        response.add_tsig(make_tsig_record(key_name, pre_tsig.set_mac(mac)));
+        let mut response = response.to_vec().unwrap();
+
+        assert!(verifier(&response).is_ok());
+
+        // a response changed on the way does not verify
+        response[3] ^= 0x80;
+        assert!(verifier(&response).is_err());
+    }
+
+    #[test]
+    fn tkey_rdata_carries_the_token() {
+        let token = b"gss-api token".to_vec();
+        let rdata = tkey_rdata(&token, 1_700_000_000).unwrap();
+
+        assert_eq!(parse_tkey(&rdata), Some((0, token)));
+        // the algorithm name, then inception and expiration a day apart
+        let times = &rdata[10..18];
+        assert_eq!(
+            u32::from_be_bytes(times[4..].try_into().unwrap())
+                - u32::from_be_bytes(times[..4].try_into().unwrap()),
+            KEY_LIFETIME.as_secs() as u32
+        );
+        assert_eq!(rdata[18..20], TKEY_MODE_GSS_API.to_be_bytes());
+        assert_eq!(parse_tkey(&rdata[..rdata.len() - 4]), None);
+    }
+}
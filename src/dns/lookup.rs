@@ -0,0 +1,152 @@
+//! Follow-up lookups, for commands that need more than the records of a
+//! single response.
+
+use std::{collections::HashMap, net::IpAddr};
+
+use hickory_proto::{
+    op::{Message, Query, ResponseCode},
+    rr::{RData, Record, RecordType},
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Span};
+
+use super::{client::DnsClient, config::Config};
+
+/// Sends a single query. A name that does not exist is not an error, it just
+/// has no records.
+pub async fn lookup(
+    client: &DnsClient,
+    config: &Config,
+    name: Name,
+    rtype: RecordType,
+    span: Span,
+) -> Result<Message, LabeledError> {
+    client.ready().await;
+
+    let query = Query::query(name, rtype);
+    let response = tokio::time::timeout(config.timeout.item, client.send_query(query, config))
+        .await
+        .map_err(|_| {
+            LabeledError::new("timed out").with_label(
+                format!("request to {} timed out", config.server.item),
+                config.server.span,
+            )
+        })?
+        .map_err(|err| {
+            LabeledError::new("DNS error")
+                .with_label(format!("Error in DNS response: {:?}", err), span)
+        })?
+        .into_message();
+
+    match response.response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => Ok(response),
+        code => Err(LabeledError::new("DNS error").with_label(
+            format!(
+                "looking up {} {} failed: {}",
+                rtype,
+                response_name(&response),
+                code
+            ),
+            span,
+        )),
+    }
+}
+
+fn response_name(message: &Message) -> String {
+    message
+        .queries()
+        .first()
+        .map_or_else(String::new, |query| query.name().to_utf8())
+}
+
+/// The addresses in `records`, which must all belong to the same target.
+pub fn addresses_of<'a>(records: impl Iterator<Item = &'a Record>) -> Vec<IpAddr> {
+    records
+        .filter_map(|record| match record.data() {
+            Some(RData::A(addr)) => Some(IpAddr::V4(addr.0)),
+            Some(RData::AAAA(addr)) => Some(IpAddr::V6(addr.0)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The addresses of `target`, preferring those in `additionals` (glue) over
+/// looking them up.
+pub async fn addresses(
+    client: &DnsClient,
+    config: &Config,
+    target: &Name,
+    additionals: &[Record],
+    span: Span,
+) -> Result<Vec<IpAddr>, LabeledError> {
+    let mut addresses = addresses_of(additionals.iter().filter(|record| record.name() == target));
+
+    // not every server includes the addresses of the targets in the
+    // additional section, so look them up if they are missing
+    if addresses.is_empty() {
+        for rtype in [RecordType::A, RecordType::AAAA] {
+            let resp = lookup(client, config, target.clone(), rtype, span).await?;
+            // the answer may start with the CNAMEs leading to the addresses,
+            // which are skipped
+            addresses.extend(addresses_of(resp.answers().iter()));
+        }
+    }
+
+    Ok(addresses)
+}
+
+/// The addresses of the nameservers in the NS records of the answer.
+pub async fn ns_addresses(
+    client: &DnsClient,
+    config: &Config,
+    message: &Message,
+    span: Span,
+) -> Result<HashMap<Name, Vec<IpAddr>>, LabeledError> {
+    let mut resolved = HashMap::new();
+
+    for record in message.answers() {
+        let Some(RData::NS(ns)) = record.data() else {
+            continue;
+        };
+
+        if resolved.contains_key(&ns.0) {
+            continue;
+        }
+
+        let addresses = addresses(client, config, &ns.0, message.additionals(), span).await?;
+        resolved.insert(ns.0.clone(), addresses);
+    }
+
+    Ok(resolved)
+}
+
+/// Sends a multicast DNS query, and returns the records of every response,
+/// answers and additionals alike. Responders put the records they expect to
+/// be asked for next into the additional section (RFC 6763 section 12), so
+/// those often spare a lookup.
+pub async fn mdns(
+    client: &DnsClient,
+    config: &Config,
+    name: Name,
+    rtype: RecordType,
+    span: Span,
+) -> Result<Vec<Record>, LabeledError> {
+    let responses = client
+        .send_mdns_query(Query::query(name.clone(), rtype), config)
+        .await
+        .map_err(|err| {
+            LabeledError::new("DNS error").with_label(
+                format!("looking up {} {} failed: {}", rtype, name, err.kind()),
+                span,
+            )
+        })?;
+
+    Ok(responses
+        .into_iter()
+        .flat_map(|(mut message, _)| {
+            let mut records = message.take_answers();
+            records.extend(message.take_additionals());
+            records
+        })
+        .collect())
+}
@@ -7,13 +7,38 @@ use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
 use self::{client::DnsClient, commands::query::DnsQueryPluginClient, config::Config};
 
+mod chain;
 mod client;
 mod commands;
 mod config;
 mod constants;
+mod cookie;
+mod denial;
+mod dig;
+mod dns_json;
+mod dnscrypt;
+mod failure_limit;
+#[cfg(feature = "gss-tsig")]
+mod gss_tsig;
+mod lookup;
+mod msgpack;
+mod names;
+mod path_mtu;
+mod pcap;
+mod presentation;
+mod rate_limit;
 mod serde;
+mod stamp;
+mod trace;
+mod trust_anchor;
+mod tsig;
+mod update;
 #[macro_use]
 mod util;
+mod watch;
+mod wire;
+mod xfr;
+mod zone;
 
 pub struct Dns {
     runtime: tokio::runtime::Runtime,
@@ -35,7 +60,9 @@ impl Dns {
     pub async fn dns_client(&self, config: &Config) -> Result<DnsClient, LabeledError> {
         // we could use OnceLock once get_or_try_init is stable
         if let Some((client, _)) = &*self.client.read().await {
-            return Ok(client.clone());
+            if !client.is_closed() && client.is_for(config) {
+                return Ok(client.clone());
+            }
         }
 
         let mut client_guard = self.client.write().await;
@@ -43,10 +70,18 @@ impl Dns {
         // it is cheap to clone and hand back an owned client because underneath
         // it is just a mpsc::Sender
         match &mut *client_guard {
-            Some((client, _)) => Ok(client.clone()),
-            None => {
+            Some((client, _)) if !client.is_closed() && client.is_for(config) => Ok(client.clone()),
+
+            // a client whose connection was closed is useless, and one that was
+            // connected for another config would send its queries to the wrong
+            // place, so replace it with a fresh one
+            _ => {
                 let (client, client_bg) = self.make_dns_client(config).await?;
-                *client_guard = Some((client.clone(), client_bg));
+                if let Some((_, mut old_bg)) = client_guard.replace((client.clone(), client_bg)) {
+                    // commands still running may hold clones of the old client,
+                    // and its connection ends by itself once they are dropped
+                    old_bg.detach_all();
+                }
                 Ok(client)
             }
         }
@@ -0,0 +1,81 @@
+//! MessagePack encoding of command output.
+//!
+//! Values are laid out the way `to json` would lay them out, so consumers see
+//! the same document in either format: records are maps, durations are
+//! nanoseconds, and dates are RFC 3339 strings. Binary data is kept as
+//! MessagePack binary rather than a list of bytes.
+
+use std::fmt::Display;
+
+use nu_protocol::{LabeledError, Span, Value};
+use rmp::encode;
+
+pub fn encode(value: &Value) -> Result<Vec<u8>, LabeledError> {
+    let mut buf = Vec::new();
+    write_value(&mut buf, value)?;
+    Ok(buf)
+}
+
+fn write_value(buf: &mut Vec<u8>, value: &Value) -> Result<(), LabeledError> {
+    let span = value.span();
+
+    match value {
+        Value::Nothing { .. } => encode::write_nil(buf).map_err(|err| encoding_error(err, span))?,
+        Value::Bool { val, .. } => {
+            encode::write_bool(buf, *val).map_err(|err| encoding_error(err, span))?
+        }
+        Value::Int { val, .. } => {
+            encode::write_sint(buf, *val).map_err(|err| encoding_error(err, span))?;
+        }
+        Value::Float { val, .. } => {
+            encode::write_f64(buf, *val).map_err(|err| encoding_error(err, span))?
+        }
+        Value::Filesize { val, .. } => {
+            encode::write_sint(buf, *val).map_err(|err| encoding_error(err, span))?;
+        }
+        Value::Duration { val, .. } => {
+            encode::write_sint(buf, *val).map_err(|err| encoding_error(err, span))?;
+        }
+        Value::Date { val, .. } => {
+            encode::write_str(buf, &val.to_rfc3339()).map_err(|err| encoding_error(err, span))?
+        }
+        Value::String { val, .. } | Value::Glob { val, .. } => {
+            encode::write_str(buf, val).map_err(|err| encoding_error(err, span))?
+        }
+        Value::Binary { val, .. } => {
+            encode::write_bin(buf, val).map_err(|err| encoding_error(err, span))?
+        }
+        Value::List { vals, .. } => {
+            encode::write_array_len(buf, vals.len() as u32)
+                .map_err(|err| encoding_error(err, span))?;
+            for val in vals {
+                write_value(buf, val)?;
+            }
+        }
+        Value::Record { val, .. } => {
+            encode::write_map_len(buf, val.len() as u32)
+                .map_err(|err| encoding_error(err, span))?;
+            for (col, val) in val.iter() {
+                encode::write_str(buf, col).map_err(|err| encoding_error(err, span))?;
+                write_value(buf, val)?;
+            }
+        }
+        Value::Error { error, .. } => return Err(LabeledError::from_diagnostic(error.as_ref())),
+        _ => {
+            return Err(LabeledError::new("encoding error").with_label(
+                format!(
+                    "{} values can not be encoded as MessagePack",
+                    value.get_type()
+                ),
+                span,
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+fn encoding_error(err: impl Display, span: Span) -> LabeledError {
+    LabeledError::new("encoding error")
+        .with_label(format!("Error encoding MessagePack: {}", err), span)
+}
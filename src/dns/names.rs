@@ -0,0 +1,149 @@
+//! Derivation of the owner names that some record types are published under.
+
+use hickory_proto::error::ProtoResult;
+use hickory_proto::rr::dnssec::{DigestType, Nsec3HashAlgorithm};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Span};
+
+fn name_err(err: impl std::fmt::Display, span: Span) -> LabeledError {
+    LabeledError::new("invalid name").with_label(format!("Error deriving name: {}", err), span)
+}
+
+fn prepend_label(label: &str, name: &Name) -> ProtoResult<Name> {
+    Name::from_labels([label])?.append_domain(name)
+}
+
+/// The NSEC3 hash of `name` (RFC 5155 section 5), base32hex encoded.
+pub fn nsec3_hash(
+    name: &Name,
+    salt: &[u8],
+    iterations: u16,
+    span: Span,
+) -> Result<String, LabeledError> {
+    let hash = Nsec3HashAlgorithm::SHA1
+        .hash(salt, &name.to_lowercase(), iterations)
+        .map_err(|err| name_err(err, span))?;
+
+    Ok(data_encoding::BASE32_DNSSEC.encode(hash.as_ref()))
+}
+
+/// The NSEC3 owner name of `name` (RFC 5155 section 5): the base32hex encoded
+/// hash of the name, prepended as a label to the zone.
+pub fn nsec3(
+    name: &Name,
+    zone: &Name,
+    salt: &[u8],
+    iterations: u16,
+    span: Span,
+) -> Result<Name, LabeledError> {
+    let label = nsec3_hash(name, salt, iterations, span)?;
+
+    prepend_label(&label, zone).map_err(|err| name_err(err, span))
+}
+
+/// The wildcard name directly below `name` (RFC 4592), e.g. `*.example.com.`
+pub fn wildcard(name: &Name, span: Span) -> Result<Name, LabeledError> {
+    prepend_label("*", name).map_err(|err| name_err(err, span))
+}
+
+/// The TLSA owner name for a service (RFC 6698 section 3), e.g.
+/// `_443._tcp.example.com.`
+pub fn tlsa(name: &Name, port: u16, transport: &str, span: Span) -> Result<Name, LabeledError> {
+    prepend_label(&format!("_{}", transport.to_lowercase()), name)
+        .and_then(|name| prepend_label(&format!("_{}", port), &name))
+        .map_err(|err| name_err(err, span))
+}
+
+/// The SRV owner name of a service (RFC 2782), e.g. `_ldap._tcp.example.com.`
+/// The underscores may be left out of `service` and `proto`.
+pub fn srv(service: &str, proto: &str, domain: &Name, span: Span) -> Result<Name, LabeledError> {
+    let underscored = |label: &str| format!("_{}", label.trim_start_matches('_').to_lowercase());
+
+    prepend_label(&underscored(proto), domain)
+        .and_then(|name| prepend_label(&underscored(service), &name))
+        .map_err(|err| name_err(err, span))
+}
+
+/// The OPENPGPKEY owner name for an email address (RFC 7929 section 3): the
+/// hex encoded SHA-256 hash of the local part, truncated to 28 octets, under
+/// `_openpgpkey` in the domain of the address.
+pub fn openpgpkey(email: &str, span: Span) -> Result<Name, LabeledError> {
+    let (local_part, domain) = email.rsplit_once('@').ok_or_else(|| {
+        LabeledError::new("invalid email address")
+            .with_label("Email address must be of the form local@domain", span)
+    })?;
+
+    let hash = DigestType::SHA256
+        .hash(local_part.as_bytes())
+        .map_err(|err| name_err(err, span))?;
+
+    let label = data_encoding::HEXLOWER.encode(&hash.as_ref()[..28]);
+
+    Name::from_utf8(domain)
+        .and_then(|domain| prepend_label("_openpgpkey", &domain))
+        .and_then(|name| prepend_label(&label, &name))
+        .map_err(|err| name_err(err, span))
+}
+
+/// Whether `name` is under `local.`, which is resolved over multicast DNS
+/// rather than by nameservers (RFC 6762 section 3).
+pub fn is_mdns(name: &Name) -> bool {
+    Name::from_ascii("local.")
+        .expect("valid name")
+        .zone_of(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nsec3_hashes_match_rfc_5155_appendix_a() {
+        let salt = [0xaa, 0xbb, 0xcc, 0xdd];
+        let hash = |name: &str| {
+            nsec3_hash(
+                &Name::from_ascii(name).unwrap(),
+                &salt,
+                12,
+                Span::test_data(),
+            )
+            .unwrap()
+        };
+
+        for (name, expected) in [
+            ("example.", "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom"),
+            ("a.example.", "35mthgpgcu1qg68fab165klnsnk3dpvl"),
+            ("ai.example.", "gjeqe526plbf1g8mklp59enfd789njgi"),
+            ("ns1.example.", "2t7b4g4vsa5smi47k61mv5bv1a22bojr"),
+            ("ns2.example.", "q04jkcevqvmu85r014c7dkba38o0ji5r"),
+            ("w.example.", "k8udemvp1j2f7eg6jebps17vp3n8i58h"),
+            ("*.w.example.", "r53bq7cc2uvmubfu5ocmm6pers9tk9en"),
+            ("x.w.example.", "b4um86eghhds6nea196smvmlo4ors995"),
+            ("y.w.example.", "ji6neoaepv8b5o6k4ev33abha8ht9fgc"),
+            ("x.y.w.example.", "2vptu5timamqttgl4luu9kg21e0aor3s"),
+            ("xx.example.", "t644ebqk9bibcna874givr6joj62mlhv"),
+        ] {
+            assert_eq!(hash(name), expected, "{}", name);
+        }
+
+        // the hash is of the canonical, lowercase, name
+        assert_eq!(hash("EXAMPLE."), hash("example."));
+    }
+
+    #[test]
+    fn nsec3_owners_are_in_the_zone() {
+        let owner = nsec3(
+            &Name::from_ascii("a.example.").unwrap(),
+            &Name::from_ascii("example.").unwrap(),
+            &[0xaa, 0xbb, 0xcc, 0xdd],
+            12,
+            Span::test_data(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            owner,
+            Name::from_ascii("35mthgpgcu1qg68fab165klnsnk3dpvl.example.").unwrap()
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Estimation of the path MTU towards a nameserver by sending UDP queries of
+//! increasing size with the Don't Fragment bit set.
+
+use std::net::{SocketAddr, UdpSocket};
+
+use hickory_proto::{
+    op::{Edns, Message, MessageType, OpCode, Query},
+    rr::rdata::opt::EdnsOption,
+};
+use hickory_resolver::config::Protocol;
+use nu_protocol::{LabeledError, Record, Span, Value};
+
+use super::{config::Config, constants};
+
+/// Size of the header of an EDNS option.
+const OPTION_HEADER_LEN: usize = 4;
+
+/// EDNS padding option (RFC 7830), which is used to inflate the probes.
+const PADDING: u16 = 12;
+
+/// Binary searches for the largest query that still gets a response, and
+/// reports it along with the path MTU it implies. A server that ignores large
+/// queries is indistinguishable from a small path MTU.
+pub async fn measure(config: &Config, query: Query) -> Result<Value, LabeledError> {
+    if config.protocol.item != Protocol::Udp {
+        return Err(LabeledError::new("unsupported protocol")
+            .with_label("measuring the path MTU needs UDP", config.protocol.span));
+    }
+
+    let server = config.server.item;
+    let socket = probe_socket(server).map_err(|err| {
+        LabeledError::new("socket error").with_label(
+            format!("could not set up a probe socket: {}", err),
+            config.server.span,
+        )
+    })?;
+
+    let mut probes = Vec::new();
+    let mut id = 0u16;
+    let mut probe = |size: usize| {
+        id = id.wrapping_add(1);
+        let message = padded_query(&query, id, size);
+        let socket = &socket;
+
+        async move {
+            let message = message?;
+
+            // a lost probe is retried, so that it is not mistaken for one that
+            // was dropped for being too large
+            for _ in 0..constants::config::default::PATH_MTU_ATTEMPTS {
+                if send_probe(socket, &message, config).await {
+                    return Ok(true);
+                }
+            }
+
+            Ok::<_, LabeledError>(false)
+        }
+    };
+
+    let mut low = constants::config::default::PATH_MTU_MIN_PAYLOAD;
+    let mut high = constants::config::default::PATH_MTU_MAX_PAYLOAD;
+
+    let received = probe(low).await?;
+    probes.push((low, received));
+
+    if !received {
+        return Err(LabeledError::new("no response").with_label(
+            format!(
+                "{} did not respond to a {} byte query",
+                server,
+                constants::config::default::PATH_MTU_MIN_PAYLOAD
+            ),
+            config.server.span,
+        ));
+    }
+
+    // invariant: a query of `low` bytes gets through, one of `high + 1` does not
+    while low < high {
+        let size = low + (high - low).div_ceil(2);
+        let received = probe(size).await?;
+        probes.push((size, received));
+
+        if received {
+            low = size;
+        } else {
+            high = size - 1;
+        }
+    }
+
+    let ip_header_len = match server {
+        SocketAddr::V4(_) => 20,
+        SocketAddr::V6(_) => 40,
+    };
+    let udp_header_len = 8;
+
+    let probes = probes
+        .into_iter()
+        .map(|(size, received)| {
+            Value::record(
+                Record::from_iter(std::iter::zip(
+                    constants::columns::PATH_MTU_PROBE_COLS
+                        .iter()
+                        .map(|col| (*col).into()),
+                    vec![
+                        Value::filesize(size as i64, Span::unknown()),
+                        Value::bool(received, Span::unknown()),
+                    ],
+                )),
+                Span::unknown(),
+            )
+        })
+        .collect();
+
+    Ok(Value::record(
+        Record::from_iter(std::iter::zip(
+            constants::columns::PATH_MTU_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            vec![
+                Value::string(server.to_string(), Span::unknown()),
+                Value::filesize(low as i64, Span::unknown()),
+                Value::filesize(
+                    (low + udp_header_len + ip_header_len) as i64,
+                    Span::unknown(),
+                ),
+                Value::list(probes, Span::unknown()),
+            ],
+        )),
+        Span::unknown(),
+    ))
+}
+
+/// A query padded to exactly `size` bytes.
+fn padded_query(query: &Query, id: u16, size: usize) -> Result<Vec<u8>, LabeledError> {
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .add_query(query.clone())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true);
+
+    message
+        .extensions_mut()
+        .get_or_insert_with(Edns::new)
+        .set_max_payload(constants::config::default::EDNS_PAYLOAD)
+        .set_version(0);
+
+    let encode_err = |err| {
+        LabeledError::new("encoding error")
+            .with_label(format!("could not encode probe: {}", err), Span::unknown())
+    };
+
+    let unpadded = message.to_vec().map_err(encode_err)?.len() + OPTION_HEADER_LEN;
+    let padding = size.saturating_sub(unpadded);
+
+    message
+        .extensions_mut()
+        .as_mut()
+        .expect("EDNS was set above")
+        .options_mut()
+        .insert(EdnsOption::Unknown(PADDING, vec![0; padding]));
+
+    message.to_vec().map_err(encode_err)
+}
+
+/// Whether a response to the probe arrived in time.
+async fn send_probe(socket: &tokio::net::UdpSocket, message: &[u8], config: &Config) -> bool {
+    // a probe larger than the MTU the kernel already knows about fails
+    // immediately
+    if let Err(err) = socket.send(message).await {
+        tracing::debug!(probe.size = message.len(), probe.error = %err);
+        return false;
+    }
+
+    let id = &message[..2];
+    let mut buf = vec![0; u16::MAX as usize];
+
+    let response = tokio::time::timeout(config.timeout.item, async {
+        loop {
+            match socket.recv(&mut buf).await {
+                // responses to earlier probes that arrive late are skipped
+                Ok(len) if len >= 2 && &buf[..2] == id => return true,
+                Ok(_) => continue,
+                Err(err) => {
+                    tracing::debug!(probe.size = message.len(), probe.error = %err);
+                    return false;
+                }
+            }
+        }
+    });
+
+    let received = response.await.unwrap_or(false);
+    tracing::debug!(probe.size = message.len(), probe.received = received);
+
+    received
+}
+
+fn probe_socket(server: SocketAddr) -> std::io::Result<tokio::net::UdpSocket> {
+    let bind_addr: SocketAddr = match server {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+
+    let socket = UdpSocket::bind(bind_addr)?;
+    set_dont_fragment(&socket, server)?;
+    socket.set_nonblocking(true)?;
+    socket.connect(server)?;
+
+    tokio::net::UdpSocket::from_std(socket)
+}
+
+/// Sets the Don't Fragment bit on outgoing packets. The probe mode ignores the
+/// path MTU the kernel has cached, so that every size is actually sent.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_dont_fragment(socket: &UdpSocket, server: SocketAddr) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, name, value) = match server {
+        SocketAddr::V4(_) => (
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_PROBE,
+        ),
+        SocketAddr::V6(_) => (
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_PROBE,
+        ),
+    };
+
+    // SAFETY: the socket is open for the duration of the call, and the option
+    // value is a c_int, whose size is passed along
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_dont_fragment(_socket: &UdpSocket, _server: SocketAddr) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "setting the Don't Fragment bit is only supported on Linux",
+    ))
+}
@@ -0,0 +1,444 @@
+//! DNS messages in packet captures, in the pcap and pcapng formats, over UDP
+//! and TCP on IPv4 and IPv6.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use chrono::{DateTime, Utc};
+use hickory_resolver::config::Protocol;
+use nu_protocol::{LabeledError, Span};
+
+/// A message as it was captured.
+#[derive(Debug)]
+pub struct Captured {
+    pub timestamp: DateTime<Utc>,
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub protocol: Protocol,
+    pub message: Vec<u8>,
+}
+
+/// A packet of a capture, with the type of link it was captured on.
+struct Packet<'a> {
+    timestamp: DateTime<Utc>,
+    link_type: u32,
+    data: &'a [u8],
+}
+
+/// The messages sent to or from `port` in a capture, in the order they were
+/// captured. Messages over TCP are taken out of the stream of each connection
+/// by their length prefix (RFC 1035 section 4.2.2), and end up at the time of
+/// the segment that completes them.
+pub fn messages(capture: &[u8], port: u16, span: Span) -> Result<Vec<Captured>, LabeledError> {
+    let invalid = |msg: &str| LabeledError::new("invalid capture").with_label(msg, span);
+
+    let packets = match capture.get(0..4) {
+        Some([0x0a, 0x0d, 0x0d, 0x0a]) => pcapng_packets(capture)
+            .ok_or_else(|| invalid("the pcapng capture is cut short or malformed"))?,
+        Some(_) => pcap_packets(capture)
+            .ok_or_else(|| invalid("not a capture in the pcap or pcapng format"))?,
+        None => return Err(invalid("the capture is empty")),
+    };
+
+    let mut streams: HashMap<(SocketAddr, SocketAddr), Stream> = HashMap::new();
+    let mut messages = Vec::new();
+
+    for packet in packets {
+        let Some((source, destination, transport)) = ip_payload(packet.link_type, packet.data)
+        else {
+            continue;
+        };
+
+        let (source_port, destination_port) = match &transport {
+            Transport::Udp(datagram) => (datagram.source_port, datagram.destination_port),
+            Transport::Tcp(segment) => (segment.source_port, segment.destination_port),
+        };
+
+        if source_port != port && destination_port != port {
+            continue;
+        }
+
+        let source = SocketAddr::new(source, source_port);
+        let destination = SocketAddr::new(destination, destination_port);
+
+        match transport {
+            Transport::Udp(datagram) => messages.push(Captured {
+                timestamp: packet.timestamp,
+                source,
+                destination,
+                protocol: Protocol::Udp,
+                message: datagram.payload.to_vec(),
+            }),
+            Transport::Tcp(segment) => {
+                let stream = streams.entry((source, destination)).or_default();
+
+                for message in stream.receive(&segment) {
+                    messages.push(Captured {
+                        timestamp: packet.timestamp,
+                        source,
+                        destination,
+                        protocol: Protocol::Tcp,
+                        message,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Packets of a capture in the pcap format, which starts with a header that
+/// tells the byte order, the resolution of timestamps, and the link type.
+fn pcap_packets(capture: &[u8]) -> Option<Vec<Packet<'_>>> {
+    let magic = capture.get(0..4)?;
+    let (big_endian, nanos) = match magic {
+        [0xa1, 0xb2, 0xc3, 0xd4] => (true, false),
+        [0xd4, 0xc3, 0xb2, 0xa1] => (false, false),
+        [0xa1, 0xb2, 0x3c, 0x4d] => (true, true),
+        [0x4d, 0x3c, 0xb2, 0xa1] => (false, true),
+        _ => return None,
+    };
+
+    let read = |offset: usize| u32_at(capture, offset, big_endian);
+    let link_type = read(20)?;
+
+    let mut packets = Vec::new();
+    let mut offset = 24;
+
+    while offset < capture.len() {
+        let secs = read(offset)?;
+        let fraction = read(offset + 4)?;
+        let len = read(offset + 8)? as usize;
+        let data = capture.get(offset + 16..offset + 16 + len)?;
+
+        let nanos = if nanos {
+            fraction
+        } else {
+            fraction.checked_mul(1000)?
+        };
+
+        packets.push(Packet {
+            timestamp: DateTime::from_timestamp(secs.into(), nanos)?,
+            link_type,
+            data,
+        });
+
+        offset += 16 + len;
+    }
+
+    Some(packets)
+}
+
+/// Packets of a capture in the pcapng format, made of blocks. Each section
+/// starts with a header block that tells its byte order, and describes the
+/// interfaces the packets of it were captured on in blocks of their own.
+fn pcapng_packets(capture: &[u8]) -> Option<Vec<Packet<'_>>> {
+    const SECTION_HEADER: u32 = 0x0a0d0d0a;
+    const INTERFACE_DESCRIPTION: u32 = 1;
+    const SIMPLE_PACKET: u32 = 3;
+    const ENHANCED_PACKET: u32 = 6;
+    const IF_TSRESOL: u16 = 9;
+
+    let mut packets = Vec::new();
+    let mut big_endian = false;
+    // the link type and timestamp resolution of each interface, as units per
+    // second
+    let mut interfaces: Vec<(u32, u64)> = Vec::new();
+    let mut offset = 0;
+
+    while offset < capture.len() {
+        if u32_at(capture, offset, true)? == SECTION_HEADER {
+            big_endian = match capture.get(offset + 8..offset + 12)? {
+                [0x1a, 0x2b, 0x3c, 0x4d] => true,
+                [0x4d, 0x3c, 0x2b, 0x1a] => false,
+                _ => return None,
+            };
+            interfaces.clear();
+        }
+
+        let block_type = u32_at(capture, offset, big_endian)?;
+        let len = u32_at(capture, offset + 4, big_endian)? as usize;
+        let body = capture.get(offset + 8..(offset + len).checked_sub(4)?)?;
+
+        match block_type {
+            INTERFACE_DESCRIPTION => {
+                let link_type = u16_at(body, 0, big_endian)?.into();
+                let mut resolution = 1_000_000;
+
+                // options are a code, a length, and a value padded to 32 bits
+                let mut options = body.get(8..)?;
+                while let (Some(code), Some(option_len)) = (
+                    u16_at(options, 0, big_endian),
+                    u16_at(options, 2, big_endian),
+                ) {
+                    let option_len = option_len as usize;
+
+                    if code == IF_TSRESOL {
+                        let tsresol = *options.get(4)?;
+                        let exponent = u32::from(tsresol & 0x7f);
+                        resolution = if tsresol & 0x80 == 0 {
+                            10u64.checked_pow(exponent)?
+                        } else {
+                            2u64.checked_pow(exponent)?
+                        };
+                    }
+
+                    options = options.get(4 + option_len.next_multiple_of(4)..)?;
+                }
+
+                interfaces.push((link_type, resolution));
+            }
+            ENHANCED_PACKET => {
+                let &(link_type, resolution) =
+                    interfaces.get(u32_at(body, 0, big_endian)? as usize)?;
+                let high = u64::from(u32_at(body, 4, big_endian)?);
+                let low = u64::from(u32_at(body, 8, big_endian)?);
+                let captured_len = u32_at(body, 12, big_endian)? as usize;
+
+                let units = (high << 32) | low;
+                let secs = i64::try_from(units / resolution).ok()?;
+                let nanos = (u128::from(units % resolution) * 1_000_000_000
+                    / u128::from(resolution)) as u32;
+
+                packets.push(Packet {
+                    timestamp: DateTime::from_timestamp(secs, nanos)?,
+                    link_type,
+                    data: body.get(20..20 + captured_len)?,
+                });
+            }
+            // simple packets have no timestamp, and are from the first
+            // interface
+            SIMPLE_PACKET => {
+                let &(link_type, _) = interfaces.first()?;
+                let data = body.get(4..)?;
+                let len = (u32_at(body, 0, big_endian)? as usize).min(data.len());
+
+                packets.push(Packet {
+                    timestamp: DateTime::UNIX_EPOCH,
+                    link_type,
+                    data: &data[..len],
+                });
+            }
+            _ => {}
+        }
+
+        offset += len;
+    }
+
+    Some(packets)
+}
+
+enum Transport<'a> {
+    Udp(Datagram<'a>),
+    Tcp(Segment<'a>),
+}
+
+/// The addresses and UDP or TCP payload of a packet, by its link type
+/// (https://www.tcpdump.org/linktypes.html).
+fn ip_payload(link_type: u32, data: &[u8]) -> Option<(IpAddr, IpAddr, Transport<'_>)> {
+    const ETHERTYPE_IPV4: u16 = 0x0800;
+    const ETHERTYPE_IPV6: u16 = 0x86dd;
+    const ETHERTYPE_VLAN: u16 = 0x8100;
+    const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+    let packet = match link_type {
+        // BSD loopback, with the address family in the byte order of the host
+        // that captured it, or in network order
+        0 | 108 => data.get(4..)?,
+        // Ethernet, possibly with VLAN tags
+        1 => {
+            let mut offset = 12;
+            while matches!(u16_at(data, offset, true)?, ETHERTYPE_VLAN | ETHERTYPE_QINQ) {
+                offset += 4;
+            }
+
+            match u16_at(data, offset, true)? {
+                ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => data.get(offset + 2..)?,
+                _ => return None,
+            }
+        }
+        // raw IP
+        101 | 228 | 229 => data,
+        // Linux cooked capture
+        113 => data.get(16..)?,
+        276 => data.get(20..)?,
+        _ => return None,
+    };
+
+    match packet.first()? >> 4 {
+        4 => {
+            let header_len = usize::from(packet.first()? & 0x0f) * 4;
+            let total_len = usize::from(u16_at(packet, 2, true)?);
+            let fragment = u16_at(packet, 6, true)?;
+
+            // only the first fragment has the UDP or TCP header
+            if fragment & 0x1fff != 0 {
+                return None;
+            }
+
+            let source = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(12..16)?).ok()?);
+            let destination = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(16..20)?).ok()?);
+            let payload = packet.get(header_len..total_len.min(packet.len()))?;
+
+            transport(*packet.get(9)?, payload)
+                .map(|payload| (source.into(), destination.into(), payload))
+        }
+        6 => {
+            let payload_len = usize::from(u16_at(packet, 4, true)?);
+            let source = Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(8..24)?).ok()?);
+            let destination = Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(24..40)?).ok()?);
+
+            let mut next_header = *packet.get(6)?;
+            let mut payload = packet.get(40..(40 + payload_len).min(packet.len()))?;
+
+            // extension headers: hop-by-hop, routing, fragment, and
+            // destination options
+            loop {
+                match next_header {
+                    0 | 43 | 60 => {
+                        let len = (usize::from(*payload.get(1)?) + 1) * 8;
+                        next_header = *payload.first()?;
+                        payload = payload.get(len..)?;
+                    }
+                    44 => {
+                        // only the first fragment has the UDP or TCP header
+                        if u16_at(payload, 2, true)? & 0xfff8 != 0 {
+                            return None;
+                        }
+
+                        next_header = *payload.first()?;
+                        payload = payload.get(8..)?;
+                    }
+                    _ => break,
+                }
+            }
+
+            transport(next_header, payload)
+                .map(|payload| (source.into(), destination.into(), payload))
+        }
+        _ => None,
+    }
+}
+
+fn transport(protocol: u8, payload: &[u8]) -> Option<Transport<'_>> {
+    match protocol {
+        6 => Segment::parse(payload).map(Transport::Tcp),
+        17 => Some(Transport::Udp(Datagram {
+            source_port: u16_at(payload, 0, true)?,
+            destination_port: u16_at(payload, 2, true)?,
+            payload: payload.get(8..)?,
+        })),
+        _ => None,
+    }
+}
+
+struct Datagram<'a> {
+    source_port: u16,
+    destination_port: u16,
+    payload: &'a [u8],
+}
+
+struct Segment<'a> {
+    source_port: u16,
+    destination_port: u16,
+    seq: u32,
+    syn: bool,
+    payload: &'a [u8],
+}
+
+impl<'a> Segment<'a> {
+    fn parse(segment: &'a [u8]) -> Option<Self> {
+        let data_offset = usize::from(segment.get(12)? >> 4) * 4;
+
+        Some(Self {
+            source_port: u16_at(segment, 0, true)?,
+            destination_port: u16_at(segment, 2, true)?,
+            seq: u32_at(segment, 4, true)?,
+            syn: segment.get(13)? & 0x02 != 0,
+            payload: segment.get(data_offset..)?,
+        })
+    }
+}
+
+/// The data sent one way over a TCP connection, put back in order by sequence
+/// number, with retransmissions left out.
+#[derive(Default)]
+struct Stream {
+    next_seq: Option<u32>,
+    buf: Vec<u8>,
+}
+
+impl Stream {
+    /// Takes in a segment, and returns the messages it completes.
+    fn receive(&mut self, segment: &Segment) -> Vec<Vec<u8>> {
+        if segment.syn {
+            self.next_seq = Some(segment.seq.wrapping_add(1));
+            self.buf.clear();
+        }
+
+        let mut payload = segment.payload;
+        let mut seq = segment.seq.wrapping_add(u32::from(segment.syn));
+
+        match self.next_seq {
+            Some(next_seq) => {
+                let ahead = seq.wrapping_sub(next_seq) as i32;
+
+                if ahead > 0 {
+                    // a segment was not captured, so what is buffered can not
+                    // be completed
+                    self.buf.clear();
+                } else if ahead < 0 {
+                    // retransmitted, in part or whole
+                    let overlap = ahead.unsigned_abs() as usize;
+                    payload = payload.get(overlap..).unwrap_or_default();
+                    seq = next_seq;
+                }
+            }
+            None => self.next_seq = Some(seq),
+        }
+
+        if payload.is_empty() {
+            return Vec::new();
+        }
+
+        self.buf.extend_from_slice(payload);
+        self.next_seq = Some(seq.wrapping_add(payload.len() as u32));
+
+        let mut messages = Vec::new();
+        while let [high, low, rest @ ..] = self.buf.as_slice() {
+            let len = usize::from(u16::from_be_bytes([*high, *low]));
+
+            if rest.len() < len {
+                break;
+            }
+
+            messages.push(rest[..len].to_vec());
+            self.buf.drain(..2 + len);
+        }
+
+        messages
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = <[u8; 2]>::try_from(data.get(offset..offset + 2)?).ok()?;
+
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn u32_at(data: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = <[u8; 4]>::try_from(data.get(offset..offset + 4)?).ok()?;
+
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
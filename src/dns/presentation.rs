@@ -0,0 +1,611 @@
+//! Record data in presentation format (RFC 1035 section 5.1), from the values
+//! records are output as, to write them back into zone files.
+
+use chrono::Utc;
+use hickory_proto::rr::{
+    dnssec::{Algorithm, DigestType},
+    RecordType,
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Value};
+
+use super::{constants, serde};
+
+/// How names are written: absolute, or relative to the origin of a zone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Names<'a> {
+    origin: Option<&'a Name>,
+}
+
+impl<'a> Names<'a> {
+    pub fn relative_to(origin: &'a Name) -> Self {
+        Self {
+            origin: Some(origin),
+        }
+    }
+
+    /// A name given as a string, in ASCII with special characters escaped.
+    /// A name without a trailing dot is taken to already be relative.
+    pub fn render(&self, value: &Value) -> Result<String, LabeledError> {
+        Ok(self.name(&serde::Query::name_from_value(value)?))
+    }
+
+    /// The owner name of a record, which is `@` for the origin itself.
+    pub fn owner(&self, name: &Name) -> String {
+        match self.origin {
+            Some(origin) if name.is_fqdn() && origin.eq(name) => "@".into(),
+            _ => self.name(name),
+        }
+    }
+
+    /// A name in record data, where the origin is written out in full, since
+    /// not every parser takes `@` there.
+    pub fn name(&self, name: &Name) -> String {
+        let origin = match self.origin {
+            Some(origin) if name.is_fqdn() && origin.zone_of(name) => origin,
+            _ => return name.to_ascii(),
+        };
+
+        // num_labels does not count a leading `*`
+        let labels = name.iter().count() - origin.iter().count();
+
+        if labels == 0 {
+            return name.to_ascii();
+        }
+
+        match Name::from_labels(name.iter().take(labels)) {
+            Ok(mut relative) => {
+                relative.set_fqdn(false);
+                relative.to_ascii()
+            }
+            Err(_) => name.to_ascii(),
+        }
+    }
+}
+
+/// A field of record data, with a comment saying what it is for fields that
+/// are only numbers, as in SOA records.
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub text: String,
+    pub comment: Option<&'static str>,
+}
+
+/// Record data as the parts it may be split into across lines, when it is too
+/// long for one.
+#[derive(Debug, Clone)]
+pub struct Rdata {
+    pub parts: Vec<Part>,
+}
+
+impl Rdata {
+    fn new(parts: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            parts: parts
+                .into_iter()
+                .map(|text| Part {
+                    text,
+                    comment: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Fields written together, and a blob of base64 or hex after them on a
+    /// line of its own when split. The blob is not split itself, since not
+    /// every parser takes whitespace within it.
+    fn with_blob(fields: Vec<String>, blob: String) -> Self {
+        Self::new(
+            [fields.join(" "), blob]
+                .into_iter()
+                .filter(|part| !part.is_empty()),
+        )
+    }
+
+    /// All of the record data on one line.
+    pub fn line(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| part.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn has_comments(&self) -> bool {
+        self.parts.iter().any(|part| part.comment.is_some())
+    }
+}
+
+/// The record data of a record of type `rtype` in presentation format, from
+/// the value it is output as. Strings are taken to already be in presentation
+/// format, except for the types whose data is a name.
+pub fn rdata(rtype: RecordType, value: &Value, names: &Names) -> Result<Rdata, LabeledError> {
+    let span = value.span();
+
+    match (rtype, value) {
+        (
+            RecordType::CNAME | RecordType::NS | RecordType::PTR | RecordType::ANAME,
+            Value::String { .. },
+        ) => Ok(Rdata::new([names.render(value)?])),
+        (RecordType::NULL, Value::String { val, .. }) => Ok(generic(val.as_bytes())),
+        (_, Value::String { val, .. }) => Ok(Rdata::new([val.clone()])),
+
+        // character-strings, as of TXT
+        (_, Value::List { vals, .. }) => Ok(Rdata::new(
+            vals.iter().map(text).collect::<Result<Vec<_>, _>>()?,
+        )),
+
+        (RecordType::OPENPGPKEY, Value::Binary { val, .. }) => Ok(Rdata::with_blob(
+            Vec::new(),
+            data_encoding::BASE64.encode(val),
+        )),
+        (_, Value::Binary { val, .. }) => Ok(generic(val)),
+
+        (_, Value::Record { .. }) => typed(rtype, value, names),
+
+        _ => Err(LabeledError::new("invalid record data").with_label(
+            format!(
+                "can not write {} record data from a {}",
+                rtype,
+                value.get_type()
+            ),
+            span,
+        )),
+    }
+}
+
+fn typed(rtype: RecordType, value: &Value, names: &Names) -> Result<Rdata, LabeledError> {
+    let field = |col: &str| {
+        value.get_data_by_key(col).ok_or_else(|| {
+            LabeledError::new("invalid record data").with_label(
+                format!("{} record data must have a field named '{}'", rtype, col),
+                value.span(),
+            )
+        })
+    };
+    let number_at = |col: &str| field(col).and_then(|val| number(&val));
+    let name_at = |col: &str| field(col).and_then(|val| names.render(&val));
+    let text_at = |col: &str| field(col).and_then(|val| text(&val));
+
+    let rdata = match rtype {
+        RecordType::MX => Rdata::new([number_at("preference")?, name_at("exchange")?]),
+        RecordType::SRV => Rdata::new([
+            number_at("priority")?,
+            number_at("weight")?,
+            number_at("port")?,
+            name_at("target")?,
+        ]),
+        RecordType::SOA => Rdata {
+            parts: vec![
+                Part {
+                    text: format!("{} {}", name_at("mname")?, name_at("rname")?),
+                    comment: None,
+                },
+                Part {
+                    text: number_at("serial")?,
+                    comment: Some("serial"),
+                },
+                Part {
+                    text: number_at("refresh")?,
+                    comment: Some("refresh"),
+                },
+                Part {
+                    text: number_at("retry")?,
+                    comment: Some("retry"),
+                },
+                Part {
+                    text: number_at("expire")?,
+                    comment: Some("expire"),
+                },
+                Part {
+                    text: number_at("minimum")?,
+                    comment: Some("minimum"),
+                },
+            ],
+        },
+        RecordType::NAPTR => Rdata::new([
+            number_at("order")?,
+            number_at("preference")?,
+            text_at("flags")?,
+            text_at("services")?,
+            text_at("regexp")?,
+            name_at("replacement")?,
+        ]),
+        RecordType::HINFO => Rdata::new([text_at("cpu")?, text_at("os")?]),
+        RecordType::CAA => {
+            let critical = matches!(field("issuer_critical")?, Value::Bool { val: true, .. });
+            let caa_value = match field("value")? {
+                issuer @ Value::Record { .. } => {
+                    let mut value = match issuer.get_data_by_key("issuer_name") {
+                        Some(name @ Value::String { .. }) => names.render(&name)?,
+                        _ => String::new(),
+                    };
+
+                    value.push(';');
+
+                    if let Some(Value::Record { val, .. }) = issuer.get_data_by_key("parameters") {
+                        for (key, val) in val.iter() {
+                            value.push_str(&format!(
+                                " {}={};",
+                                key,
+                                val.coerce_str().map_err(|err| {
+                                    LabeledError::new("invalid record data").with_label(
+                                        format!("CAA parameter {}: {}", key, err),
+                                        val.span(),
+                                    )
+                                })?
+                            ));
+                        }
+                    }
+
+                    character_string(value.trim_end_matches(';').as_bytes())
+                }
+                other => text(&other)?,
+            };
+
+            Rdata::new([
+                if critical { "128" } else { "0" }.to_string(),
+                field("tag")?.coerce_string().map_err(|err| {
+                    LabeledError::new("invalid record data")
+                        .with_label(format!("CAA tag: {}", err), value.span())
+                })?,
+                caa_value,
+            ])
+        }
+        RecordType::SSHFP => Rdata::with_blob(
+            vec![
+                code(
+                    &field("algorithm")?,
+                    &[
+                        ("reserved", 0),
+                        ("RSA", 1),
+                        ("DSA", 2),
+                        ("ECDSA", 3),
+                        ("Ed25519", 4),
+                        ("Ed448", 6),
+                    ],
+                )?,
+                code(
+                    &field("fingerprint_type")?,
+                    &[("reserved", 0), ("SHA-1", 1), ("SHA-256", 2)],
+                )?,
+            ],
+            hex(&field("fingerprint")?)?,
+        ),
+        RecordType::TLSA => Rdata::with_blob(
+            vec![
+                code(
+                    &field("cert_usage")?,
+                    &[
+                        ("CA", 0),
+                        ("service", 1),
+                        ("trust anchor", 2),
+                        ("domain issued", 3),
+                        ("private", 255),
+                    ],
+                )?,
+                code(
+                    &field("selector")?,
+                    &[("full", 0), ("spki", 1), ("private", 255)],
+                )?,
+                code(
+                    &field("matching")?,
+                    &[("raw", 0), ("SHA-256", 1), ("SHA-512", 2), ("private", 255)],
+                )?,
+            ],
+            hex(&field("cert_data")?)?,
+        ),
+        RecordType::SVCB | RecordType::HTTPS => {
+            let mut parts = vec![number_at("svc_priority")?, name_at("target_name")?];
+
+            if let Value::Record { val, .. } = field("svc_params")? {
+                for (key, val) in val.iter() {
+                    parts.push(svc_param(key, val)?);
+                }
+            }
+
+            Rdata::new([parts.join(" ")])
+        }
+        RecordType::DNSKEY | RecordType::CDNSKEY => {
+            let flag = |col: &str, bit: u16| {
+                field(col).map(|val| match val {
+                    Value::Bool { val: true, .. } => bit,
+                    _ => 0,
+                })
+            };
+            let flags = flag("zone_key", 0x0100)?
+                | flag("secure_entry_point", 0x0001)?
+                | flag("revoke", 0x0080)?;
+
+            Rdata::with_blob(
+                vec![
+                    flags.to_string(),
+                    "3".into(),
+                    algorithm(&field("algorithm")?)?,
+                ],
+                base64(&field("public_key")?)?,
+            )
+        }
+        RecordType::DS | RecordType::CDS => Rdata::with_blob(
+            vec![
+                number_at("key_tag")?,
+                algorithm(&field("algorithm")?)?,
+                digest_type(&field("digest_type")?)?,
+            ],
+            hex(&field("digest")?)?,
+        ),
+        RecordType::RRSIG | RecordType::SIG => Rdata::with_blob(
+            vec![
+                field("type_covered")?.coerce_string().map_err(|err| {
+                    LabeledError::new("invalid record data")
+                        .with_label(format!("type covered: {}", err), value.span())
+                })?,
+                algorithm(&field("algorithm")?)?,
+                number_at("num_labels")?,
+                number_at("original_ttl")?,
+                timestamp(&field("signature_expiration")?)?,
+                timestamp(&field("signature_inception")?)?,
+                number_at("key_tag")?,
+                name_at("signer_name")?,
+            ],
+            base64(&field("signature")?)?,
+        ),
+        RecordType::NSEC => {
+            let mut parts = vec![name_at("next_domain_name")?];
+            parts.extend(types(&field("types")?)?);
+            Rdata::new([parts.join(" ")])
+        }
+        RecordType::NSEC3 => {
+            let opt_out = matches!(field("opt_out")?, Value::Bool { val: true, .. });
+            let next = match field("next_hashed_owner_name")? {
+                Value::Binary { val, .. } => data_encoding::BASE32HEX_NOPAD.encode(&val),
+                other => other.coerce_string().map_err(|err| {
+                    LabeledError::new("invalid record data")
+                        .with_label(format!("next hashed owner name: {}", err), other.span())
+                })?,
+            };
+
+            let mut parts = vec![
+                "1".into(),
+                if opt_out { "1" } else { "0" }.into(),
+                number_at("iterations")?,
+                salt(&field("salt")?)?,
+                next,
+            ];
+            parts.extend(types(&field("types")?)?);
+            Rdata::new([parts.join(" ")])
+        }
+        RecordType::NSEC3PARAM => Rdata::new([[
+            "1".into(),
+            number_at("flags")?,
+            number_at("iterations")?,
+            salt(&field("salt")?)?,
+        ]
+        .join(" ")]),
+        RecordType::Unknown(code) if code == constants::rtypes::DOA => Rdata::with_blob(
+            vec![
+                number_at("enterprise")?,
+                number_at("type")?,
+                number_at("location")?,
+                text_at("media_type")?,
+            ],
+            base64(&field("data")?)?,
+        ),
+        RecordType::Unknown(code) if code == constants::rtypes::HIP => {
+            let mut parts = vec![
+                number_at("pk_algorithm")?,
+                field("hit")?.coerce_string().map_err(|err| {
+                    LabeledError::new("invalid record data")
+                        .with_label(format!("HIT: {}", err), value.span())
+                })?,
+                base64(&field("public_key")?)?,
+            ];
+
+            if let Value::List { vals, .. } = field("rendezvous_servers")? {
+                for server in &vals {
+                    parts.push(names.render(server)?);
+                }
+            }
+
+            Rdata::new([parts.join(" ")])
+        }
+        // types without a presentation format of their own (RFC 3597 section 5)
+        _ => match (
+            value.get_data_by_key("code"),
+            value.get_data_by_key("rdata"),
+        ) {
+            (Some(_), Some(Value::Binary { val, .. })) => generic(&val),
+            _ => {
+                return Err(LabeledError::new("invalid record data").with_label(
+                    format!("can not write {} record data from a record", rtype),
+                    value.span(),
+                ))
+            }
+        },
+    };
+
+    Ok(rdata)
+}
+
+/// A character-string (RFC 1035 section 5.1), quoted, with quotes and
+/// backslashes escaped, and control characters as `\DDD`. Text that is not
+/// UTF-8 has every byte outside of printable ASCII escaped.
+pub fn character_string(bytes: &[u8]) -> String {
+    fn escape(quoted: &mut String, byte: u8) {
+        match byte {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(byte as char);
+            }
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => quoted.push_str(&format!("\\{:03}", byte)),
+        }
+    }
+
+    let mut quoted = String::from('"');
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            for c in text.chars() {
+                if c.is_ascii() {
+                    escape(&mut quoted, c as u8);
+                } else {
+                    quoted.push(c);
+                }
+            }
+        }
+        Err(_) => {
+            for &byte in bytes {
+                escape(&mut quoted, byte);
+            }
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Record data in the generic format of unknown types: `\# <length> <hex>`.
+fn generic(bytes: &[u8]) -> Rdata {
+    Rdata::with_blob(
+        vec!["\\#".into(), bytes.len().to_string()],
+        data_encoding::HEXUPPER.encode(bytes),
+    )
+}
+
+fn text(value: &Value) -> Result<String, LabeledError> {
+    match value {
+        Value::String { val, .. } => Ok(character_string(val.as_bytes())),
+        Value::Binary { val, .. } => Ok(character_string(val)),
+        _ => Err(invalid(value, "a string")),
+    }
+}
+
+fn number(value: &Value) -> Result<String, LabeledError> {
+    match value {
+        Value::Int { val, .. } => Ok(val.to_string()),
+        Value::Duration { val, .. } => Ok((val / 1_000_000_000).to_string()),
+        _ => Err(invalid(value, "an int or a duration")),
+    }
+}
+
+/// A code given either by its name in `known`, or as a number.
+fn code(value: &Value, known: &[(&str, u8)]) -> Result<String, LabeledError> {
+    match value {
+        Value::Int { val, .. } => Ok(val.to_string()),
+        Value::String { val, .. } => known
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(val))
+            .map(|(_, code)| code.to_string())
+            .ok_or_else(|| invalid(value, "a known name, or a number")),
+        _ => Err(invalid(value, "a name or a number")),
+    }
+}
+
+fn algorithm(value: &Value) -> Result<String, LabeledError> {
+    match value {
+        Value::Int { val, .. } => Ok(val.to_string()),
+        Value::String { val, .. } => (0..=u8::MAX)
+            .find(|&code| {
+                let algorithm = Algorithm::from_u8(code);
+                !matches!(algorithm, Algorithm::Unknown(_))
+                    && algorithm.as_str().eq_ignore_ascii_case(val)
+            })
+            .map(|code| code.to_string())
+            .ok_or_else(|| invalid(value, "a known DNSSEC algorithm, or its number")),
+        _ => Err(invalid(value, "a DNSSEC algorithm")),
+    }
+}
+
+fn digest_type(value: &Value) -> Result<String, LabeledError> {
+    match value {
+        Value::Int { val, .. } => Ok(val.to_string()),
+        Value::String { val, .. } => (0..=u8::MAX)
+            .filter_map(|code| DigestType::from_u8(code).ok().map(|digest| (code, digest)))
+            .find(|(_, digest)| serde::digest_type_name(*digest).eq_ignore_ascii_case(val))
+            .map(|(code, _)| code.to_string())
+            .ok_or_else(|| invalid(value, "a known digest type, or its number")),
+        _ => Err(invalid(value, "a digest type")),
+    }
+}
+
+fn hex(value: &Value) -> Result<String, LabeledError> {
+    match value {
+        Value::Binary { val, .. } => Ok(data_encoding::HEXUPPER.encode(val)),
+        Value::String { val, .. } => Ok(val.clone()),
+        _ => Err(invalid(value, "binary")),
+    }
+}
+
+fn base64(value: &Value) -> Result<String, LabeledError> {
+    match value {
+        Value::Binary { val, .. } => Ok(data_encoding::BASE64.encode(val)),
+        Value::String { val, .. } => Ok(val.clone()),
+        _ => Err(invalid(value, "binary")),
+    }
+}
+
+/// The salt of NSEC3 records in hex, or `-` for none.
+fn salt(value: &Value) -> Result<String, LabeledError> {
+    match value {
+        Value::Binary { val, .. } if val.is_empty() => Ok("-".into()),
+        _ => hex(value),
+    }
+}
+
+/// Times of signatures as `YYYYMMDDHHmmSS` (RFC 4034 section 3.2).
+fn timestamp(value: &Value) -> Result<String, LabeledError> {
+    match value {
+        Value::Date { val, .. } => Ok(val.with_timezone(&Utc).format("%Y%m%d%H%M%S").to_string()),
+        Value::Int { val, .. } => Ok(val.to_string()),
+        _ => Err(invalid(value, "a date")),
+    }
+}
+
+fn types(value: &Value) -> Result<Vec<String>, LabeledError> {
+    match value {
+        Value::List { vals, .. } => vals
+            .iter()
+            .map(|val| {
+                val.coerce_string()
+                    .map_err(|_| invalid(val, "the name of a record type"))
+            })
+            .collect(),
+        _ => Err(invalid(value, "a list of record types")),
+    }
+}
+
+fn svc_param(key: &str, value: &Value) -> Result<String, LabeledError> {
+    // keys hickory does not name are written as their number (RFC 9460
+    // section 2.1)
+    let key = match key {
+        "echconfig" => "ech".to_string(),
+        key => match key.strip_prefix("unknown") {
+            Some(code) => format!("key{}", code),
+            None => key.to_string(),
+        },
+    };
+
+    let value = match value {
+        Value::String { val, .. } if *val == key => return Ok(key),
+        Value::Int { val, .. } => val.to_string(),
+        Value::String { val, .. } => val.clone(),
+        Value::List { vals, .. } => vals
+            .iter()
+            .map(|val| val.coerce_string().map_err(|_| invalid(val, "a string")))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(","),
+        Value::Binary { val, .. } if key == "ech" => data_encoding::BASE64.encode(val),
+        Value::Binary { val, .. } => character_string(val),
+        _ => return Err(invalid(value, "a parameter value")),
+    };
+
+    Ok(format!("{}={}", key, value))
+}
+
+fn invalid(value: &Value, expected: &str) -> LabeledError {
+    LabeledError::new("invalid record data").with_label(
+        format!("expected {}, not a {}", expected, value.get_type()),
+        value.span(),
+    )
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use tokio::{sync::Mutex, time::Instant};
+
+/// Paces requests to a maximum rate. This is a token bucket which holds a
+/// single token, so requests are spread evenly instead of being sent in
+/// bursts.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / per_second),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the next request may be sent.
+    pub async fn acquire(&self) {
+        // holding the lock while sleeping queues up the waiters
+        let mut next = self.next.lock().await;
+        tokio::time::sleep_until(*next).await;
+        *next = Instant::now().max(*next) + self.interval;
+    }
+}
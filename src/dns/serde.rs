@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::net::IpAddr;
+use std::net::SocketAddr;
 use std::ops::Deref;
 use std::str::FromStr;
 
@@ -16,16 +18,23 @@ use hickory_proto::rr::rdata::svcb::SvcParamValue;
 use hickory_proto::rr::rdata::svcb::Unknown;
 use hickory_proto::rr::rdata::tlsa;
 use hickory_proto::rr::RecordType;
+use hickory_proto::serialize::binary::BinDecodable;
+use hickory_proto::serialize::binary::BinDecoder;
 use hickory_proto::serialize::binary::BinEncodable;
+use hickory_proto::serialize::binary::BinEncoder;
 use hickory_resolver::Name;
 use nu_protocol::record;
-use nu_protocol::FromValue;
 use nu_protocol::LabeledError;
 use nu_protocol::Span;
+use nu_protocol::Spanned;
 use nu_protocol::Value;
 
 use super::config::Config;
 use super::constants;
+use super::cookie::CookieStatus;
+use super::tsig;
+use super::wire;
+use super::wire::RecordSpans;
 
 fn code_to_record_u16<C>(code: C, config: &Config) -> Value
 where
@@ -79,15 +88,111 @@ where
     }
 }
 
+/// The type of a question or record, which may be asked for as a bare number
+/// for consumers that key on the numeric code.
+fn rtype_to_value(rtype: RecordType, config: &Config) -> Value {
+    if config.type_as_number.item {
+        Value::int(u16::from(rtype) as i64, Span::unknown())
+    } else {
+        code_to_record_u16(RTypeName(rtype), config)
+    }
+}
+
+/// Names types hickory does not know by their mnemonic, or else in the generic
+/// `TYPE<code>` form (RFC 3597 section 5).
+pub struct RTypeName(pub RecordType);
+
+impl Display for RTypeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            RecordType::Unknown(code) => match constants::rtypes::name(code) {
+                Some(name) => f.write_str(name),
+                None => write!(f, "TYPE{}", code),
+            },
+            rtype => rtype.fmt(f),
+        }
+    }
+}
+
+impl From<RTypeName> for u16 {
+    fn from(rtype: RTypeName) -> Self {
+        rtype.0.into()
+    }
+}
+
 pub struct Message {
     msg: hickory_proto::op::Message,
     bytes: Vec<u8>,
+    responder: Option<SocketAddr>,
+    ns_addresses: HashMap<Name, Vec<IpAddr>>,
+    wire: Option<Vec<u8>>,
+    transport: Option<hickory_resolver::config::Protocol>,
+    question: Option<hickory_proto::op::Query>,
+    cookie: Option<CookieStatus>,
+    dnssec: Option<Value>,
 }
 
 impl Message {
     pub fn new(msg: hickory_proto::op::Message) -> Self {
         let bytes = msg.to_bytes().expect("unencodable message");
-        Self { msg, bytes }
+        Self {
+            msg,
+            bytes,
+            responder: None,
+            ns_addresses: HashMap::new(),
+            wire: None,
+            transport: None,
+            question: None,
+            cookie: None,
+            dnssec: None,
+        }
+    }
+
+    /// Records the address the message was received from.
+    pub fn with_responder(mut self, responder: SocketAddr) -> Self {
+        self.responder = Some(responder);
+        self
+    }
+
+    /// Records the protocol the message was received over, which may differ
+    /// from the one asked for when a truncated response was retried.
+    pub fn with_transport(mut self, transport: hickory_resolver::config::Protocol) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Records the question as it was asked, which is output in place of the
+    /// one of the message, as servers may leave it out or change its case.
+    pub fn with_question(mut self, question: hickory_proto::op::Query) -> Self {
+        self.question = Some(question);
+        self
+    }
+
+    /// Records whether the message returned the client cookie of its query.
+    pub fn with_cookie(mut self, cookie: CookieStatus) -> Self {
+        self.cookie = Some(cookie);
+        self
+    }
+
+    /// Attaches the outcome of validating the message with `--dnssec
+    /// validate`.
+    pub fn with_dnssec(mut self, dnssec: Value) -> Self {
+        self.dnssec = Some(dnssec);
+        self
+    }
+
+    /// Attaches the addresses of the nameservers to the NS records of the
+    /// answer.
+    pub fn with_ns_addresses(mut self, ns_addresses: HashMap<Name, Vec<IpAddr>>) -> Self {
+        self.ns_addresses = ns_addresses;
+        self
+    }
+
+    /// Keeps the message as it was received, to report where each record is
+    /// in it with `--record-spans`, and each of its extended errors.
+    pub fn with_wire(mut self, wire: Vec<u8>) -> Self {
+        self.wire = Some(wire);
+        self
     }
 
     pub fn into_inner(self) -> hickory_proto::op::Message {
@@ -98,44 +203,364 @@ impl Message {
         self.bytes.len()
     }
 
-    pub fn into_value(self, config: &Config) -> Result<Value, LabeledError> {
+    pub fn into_value(mut self, config: &Config) -> Result<Value, LabeledError> {
         let size = Value::filesize(self.size() as i64, Span::unknown());
+        let responder = self
+            .responder
+            .map(|addr| Value::string(addr.to_string(), Span::unknown()))
+            .unwrap_or(Value::nothing(Span::unknown()));
+        let ns_addresses = std::mem::take(&mut self.ns_addresses);
+        let transport = self.transport.take();
+        let asked = self.question.take();
+        let cookie = self.cookie.take();
+        let dnssec = self.dnssec.take();
+        let received = self.wire.take();
+        let edns_options = received.as_deref().and_then(|wire| {
+            wire::edns_options(wire)
+                .inspect_err(
+                    |err| tracing::debug!(wire.error = %err, "could not read EDNS options"),
+                )
+                .ok()
+        });
+        let op_code = received.as_deref().and_then(wire::op_code);
+        let wire = received
+            .filter(|_| config.record_spans.item)
+            .map(|wire| wire::record_spans(&wire))
+            .transpose()
+            .map_err(|err| {
+                LabeledError::new("invalid message").with_label(
+                    format!("could not locate the records in the message: {}", err),
+                    Span::unknown(),
+                )
+            })?;
         let message = self.into_inner();
-        let header = Header(message.header()).into_value(config);
+        let mut header = Header(message.header()).into_value(config);
+        if let Value::Record { val, .. } = &mut header {
+            val.to_mut().push(
+                constants::columns::EXTENDED_ERRORS,
+                extended_errors(message.extensions().as_ref(), edns_options),
+            );
+
+            // hickory reads the opcodes it does not know as QUERY
+            if let Some(op_code) =
+                op_code.filter(|op_code| hickory_proto::op::OpCode::from_u8(*op_code).is_err())
+            {
+                val.to_mut()
+                    .insert("op_code", code_to_record_u8(OpCode(op_code), config));
+            }
+        }
+        let authentic_data = message.authentic_data();
+        let tsig_verified = tsig::verified(&message);
+        let status = Value::string(ResponseStatus::of(&message).to_string(), Span::unknown());
         let mut parts = message.into_parts();
 
-        let question = parts.queries.pop().map_or_else(
+        let question = asked.or_else(|| parts.queries.pop()).map_or_else(
             || Value::record(record!(), Span::unknown()),
             |q| Query(q).into_value(config),
         );
 
-        let parse_records =
-            |records: Vec<hickory_client::rr::Record>| -> Result<Value, LabeledError> {
-                Ok(Value::list(
-                    records
-                        .into_iter()
-                        .map(|record| Record(record).into_value(config))
-                        .collect::<Result<_, _>>()?,
-                    Span::unknown(),
-                ))
-            };
+        let parse_records = |records: Vec<hickory_client::rr::Record>,
+                             mut spans: Option<Vec<(hickory_client::rr::Record, RecordSpans)>>,
+                             ns_addresses: &HashMap<Name, Vec<IpAddr>>|
+         -> Result<Value, LabeledError> {
+            Ok(Value::list(
+                records
+                    .into_iter()
+                    .map(|record| {
+                        let addresses = match record.data() {
+                            Some(hickory_proto::rr::RData::NS(ns)) => ns_addresses.get(&ns.0),
+                            _ => None,
+                        };
 
-        let answer = parse_records(parts.answers)?;
-        let authority = parse_records(parts.name_servers)?;
-        let additional = parse_records(parts.additionals)?;
+                        // validation may have left out records and sorting may
+                        // have reordered them, so they are found by content
+                        let record_spans = spans.as_mut().map(|spans| {
+                            spans
+                                .iter()
+                                .position(|(wire_record, _)| *wire_record == record)
+                                .map(|idx| spans.remove(idx).1)
+                        });
+
+                        let mut value = Record(record).into_value(config)?;
+
+                        if let Value::Record { val, .. } = &mut value {
+                            if let Some(addresses) = addresses {
+                                val.to_mut().push(
+                                    constants::columns::ADDRESSES,
+                                    Value::list(
+                                        addresses
+                                            .iter()
+                                            .map(|addr| {
+                                                Value::string(addr.to_string(), Span::unknown())
+                                            })
+                                            .collect(),
+                                        Span::unknown(),
+                                    ),
+                                );
+                            }
+
+                            if let Some(record_spans) = record_spans {
+                                val.to_mut().push(
+                                    constants::columns::SPANS,
+                                    record_spans.map_or_else(
+                                        || Value::nothing(Span::unknown()),
+                                        RecordSpans::into_value,
+                                    ),
+                                );
+                            }
+                        }
+
+                        Ok(value)
+                    })
+                    .collect::<Result<_, LabeledError>>()?,
+                Span::unknown(),
+            ))
+        };
+
+        let checksum = if config.answer_checksum.item {
+            Some(answer_checksum(&parts.answers)?)
+        } else {
+            None
+        };
+
+        if config.canonical_order.item {
+            sort_canonical(&mut parts.answers);
+            sort_canonical(&mut parts.name_servers);
+            sort_canonical(&mut parts.additionals);
+        }
+
+        let (answer_spans, authority_spans, additional_spans) = match wire {
+            Some(sections) => (
+                Some(sections.answers),
+                Some(sections.authorities),
+                Some(sections.additionals),
+            ),
+            None => (None, None, None),
+        };
+
+        let answer = parse_records(parts.answers, answer_spans, &ns_addresses)?;
+        let authority = parse_records(parts.name_servers, authority_spans, &HashMap::new())?;
+        let additional = parse_records(parts.additionals, additional_spans, &HashMap::new())?;
         let edns = parts
             .edns
             .map(|edns| Edns(edns).into_value(config))
             .unwrap_or(Value::nothing(Span::unknown()));
 
-        Ok(Value::record(
-            nu_protocol::Record::from_iter(std::iter::zip(
-                Vec::from_iter(constants::columns::MESSAGE_COLS.iter().map(|s| (*s).into())),
-                vec![header, question, answer, authority, additional, edns, size],
-            )),
+        let mut record = nu_protocol::Record::from_iter(std::iter::zip(
+            Vec::from_iter(constants::columns::MESSAGE_COLS.iter().map(|s| (*s).into())),
+            vec![
+                header, status, question, answer, authority, additional, edns, size, responder,
+            ],
+        ));
+
+        if let Some(transport) = transport {
+            record.push(
+                constants::columns::TRANSPORT,
+                Value::string(transport.to_string(), Span::unknown()),
+            );
+        }
+
+        if let Some(cookie) = cookie {
+            record.push(
+                constants::columns::COOKIE,
+                Value::string(cookie.to_string(), Span::unknown()),
+            );
+        }
+
+        if let Some(dnssec) = dnssec {
+            record.push(constants::columns::DNSSEC, dnssec);
+        }
+
+        if let Some(checksum) = checksum {
+            record.push(
+                constants::columns::ANSWER_CHECKSUM,
+                Value::string(checksum, Span::unknown()),
+            );
+        }
+
+        // the request was signed, so a response without a verified MAC could
+        // have come from anyone
+        if let Some(key) = &config.tsig {
+            record.push(
+                constants::columns::TSIG,
+                Value::record(
+                    nu_protocol::Record::from_iter(std::iter::zip(
+                        constants::columns::TSIG_COLS
+                            .iter()
+                            .map(|col| (*col).into()),
+                        vec![
+                            Value::string(key.item.name.to_utf8(), Span::unknown()),
+                            Value::string(key.item.algorithm.to_string(), Span::unknown()),
+                            Value::bool(tsig_verified, Span::unknown()),
+                        ],
+                    )),
+                    Span::unknown(),
+                ),
+            );
+        }
+
+        // over an encrypted channel to a trusted resolver, its AD bit is as
+        // good as validating ourselves
+        if config.trust_ad.item {
+            if !authentic_data {
+                tracing::warn!(
+                    message.question = ?record.get("question"),
+                    "resolver did not set the AD bit, so the answer is not validated"
+                );
+            }
+
+            record.push(
+                constants::columns::VALIDATED,
+                Value::bool(authentic_data, Span::unknown()),
+            );
+        }
+
+        Ok(Value::record(record, Span::unknown()))
+    }
+}
+
+/// The outcome of a query, which distinguishes the kinds of "empty" responses
+/// that all share the same response code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseStatus {
+    /// The name exists and has records of the requested type.
+    NoError,
+    /// The name exists, but has no records of the requested type (RFC 2308).
+    NoData,
+    /// The name does not exist.
+    NxDomain,
+    /// The server is not authoritative and delegated to other nameservers.
+    Referral,
+    /// Any other response code.
+    Other(hickory_proto::op::ResponseCode),
+}
+
+impl ResponseStatus {
+    pub fn of(message: &hickory_proto::op::Message) -> Self {
+        use hickory_proto::op::ResponseCode;
+
+        match message.response_code() {
+            ResponseCode::NXDomain => ResponseStatus::NxDomain,
+            ResponseCode::NoError if message.answers().is_empty() => {
+                let has_rtype = |rtype| {
+                    message
+                        .name_servers()
+                        .iter()
+                        .any(|record| record.record_type() == rtype)
+                };
+
+                if !message.authoritative()
+                    && has_rtype(RecordType::NS)
+                    && !has_rtype(RecordType::SOA)
+                {
+                    ResponseStatus::Referral
+                } else {
+                    ResponseStatus::NoData
+                }
+            }
+            ResponseCode::NoError => ResponseStatus::NoError,
+            rcode => ResponseStatus::Other(rcode),
+        }
+    }
+}
+
+impl Display for ResponseStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResponseStatus::NoError => write!(f, "NOERROR"),
+            ResponseStatus::NoData => write!(f, "NODATA"),
+            ResponseStatus::NxDomain => write!(f, "NXDOMAIN"),
+            ResponseStatus::Referral => write!(f, "REFERRAL"),
+            // mnemonics as used by dig
+            ResponseStatus::Other(hickory_proto::op::ResponseCode::Unknown(code)) => {
+                write!(f, "RCODE{}", code)
+            }
+            ResponseStatus::Other(rcode) => write!(f, "{}", format!("{:?}", rcode).to_uppercase()),
+        }
+    }
+}
+
+/// Sorts the records of each RRset into canonical order, as defined in RFC 4034
+/// section 6.3: by their RDATA in canonical wire format, i.e. uncompressed and
+/// with embedded names lowercased, compared as left-justified unsigned octet
+/// sequences. RRsets keep the position of their first record in the section.
+pub fn sort_canonical(records: &mut [hickory_proto::rr::Record]) {
+    let mut rrsets: Vec<(Name, RecordType, hickory_proto::rr::DNSClass)> = Vec::new();
+
+    records.sort_by_cached_key(|record| {
+        let rrset = (
+            record.name().to_lowercase(),
+            record.record_type(),
+            record.dns_class(),
+        );
+
+        let rrset_idx = match rrsets.iter().position(|set| *set == rrset) {
+            Some(idx) => idx,
+            None => {
+                rrsets.push(rrset);
+                rrsets.len() - 1
+            }
+        };
+
+        (rrset_idx, canonical_rdata(record))
+    });
+}
+
+/// Hex encoded SHA-256 hash of the records in canonical form and order (RFC
+/// 4034 section 6), so that the same set of records always hashes the same no
+/// matter how it was sent. TTLs are left out, since they count down between
+/// queries without anything changing.
+pub fn answer_checksum(records: &[hickory_proto::rr::Record]) -> Result<String, LabeledError> {
+    let mut records = records
+        .iter()
+        .map(|record| {
+            (
+                record.name().to_lowercase(),
+                u16::from(record.record_type()),
+                u16::from(record.dns_class()),
+                canonical_rdata(record),
+            )
+        })
+        .collect::<Vec<_>>();
+    records.sort();
+    records.dedup();
+
+    let mut buf = Vec::new();
+    let mut encoder = BinEncoder::new(&mut buf);
+
+    for (name, rtype, class, rdata) in &records {
+        // the records were just decoded, so they can always be encoded again
+        name.emit_as_canonical(&mut encoder, true)
+            .and_then(|_| encoder.emit_u16(*rtype))
+            .and_then(|_| encoder.emit_u16(*class))
+            .and_then(|_| encoder.emit_u16(rdata.len() as u16))
+            .and_then(|_| encoder.emit_vec(rdata))
+            .expect("unencodable record");
+    }
+
+    let digest = dnssec::DigestType::SHA256.hash(&buf).map_err(|err| {
+        LabeledError::new("checksum error").with_label(
+            format!("could not hash the answer: {}", err.kind()),
             Span::unknown(),
-        ))
+        )
+    })?;
+
+    Ok(data_encoding::HEXLOWER.encode(digest.as_ref()))
+}
+
+fn canonical_rdata(record: &hickory_proto::rr::Record) -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    if let Some(rdata) = record.data() {
+        let mut encoder = BinEncoder::new(&mut buf);
+        encoder.set_canonical_names(true);
+
+        // records in a response have already been decoded successfully, so
+        // they can always be encoded again
+        rdata.emit(&mut encoder).expect("unencodable record data");
     }
+
+    buf
 }
 
 pub struct Header<'r>(pub(crate) &'r hickory_proto::op::Header);
@@ -172,6 +597,7 @@ impl<'r> Header<'r> {
         let recursion_desired = Value::bool(header.recursion_desired(), Span::unknown());
         let recursion_available = Value::bool(header.recursion_available(), Span::unknown());
         let authentic_data = Value::bool(header.authentic_data(), Span::unknown());
+        let checking_disabled = Value::bool(header.checking_disabled(), Span::unknown());
         let response_code = code_to_record_u16(header.response_code(), config);
         let query_count = Value::int(header.query_count().into(), Span::unknown());
         let answer_count = Value::int(header.answer_count().into(), Span::unknown());
@@ -190,6 +616,7 @@ impl<'r> Header<'r> {
                     recursion_desired,
                     recursion_available,
                     authentic_data,
+                    checking_disabled,
                     response_code,
                     query_count,
                     answer_count,
@@ -210,7 +637,7 @@ impl Query {
         let Query(query) = self;
 
         let name = Value::string(query.name().to_utf8(), Span::unknown());
-        let qtype = code_to_record_u16(query.query_type(), config);
+        let qtype = rtype_to_value(query.query_type(), config);
         let class = code_to_record_u16(query.query_class(), config);
 
         Value::record(
@@ -238,20 +665,10 @@ impl Query {
                         .with_label(format!("Record must have a column named '{}'", col), span)
                 };
 
-                let name = Name::from_utf8(
-                    String::from_value(
-                        rec.get_data_by_key(constants::columns::NAME)
-                            .ok_or_else(|| must_have_col_err(constants::columns::NAME))?,
-                    )
-                    .map_err(|err| {
-                        LabeledError::new("invalid value")
-                            .with_label(format!("Could not convert value to String: {}", err), span)
-                    })?,
-                )
-                .map_err(|err| {
-                    LabeledError::new("invalid name")
-                        .with_label(format!("Could not convert string to name: {}", err), span)
-                })?;
+                let name = Query::name_from_value(
+                    &rec.get_data_by_key(constants::columns::NAME)
+                        .ok_or_else(|| must_have_col_err(constants::columns::NAME))?,
+                )?;
 
                 let qtype = RType::try_from(
                     &rec.get_data_by_key(constants::columns::TYPE)
@@ -270,8 +687,101 @@ impl Query {
                 Ok(vec![Query(query)])
             }
 
+            // errors from upstream in the pipeline are passed through
+            Value::Error { error, .. } => Err(LabeledError::from(*error.clone())),
+
+            // A list of names, as opposed to a list of labels of one name
+            Value::List { vals, .. } if !Query::is_label_list(vals) => {
+                if !config.zip.item {
+                    return Ok(vals
+                        .iter()
+                        .map(|val| Query::try_from_value(val, config))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .into_iter()
+                        .flatten()
+                        .collect());
+                }
+
+                if vals.len() != config.qtypes.item.len() {
+                    return Err(LabeledError::new("mismatched lengths")
+                        .with_label(format!("{} names given", vals.len()), value.span())
+                        .with_label(
+                            format!(
+                                "{} types given, but --zip needs as many as names",
+                                config.qtypes.item.len()
+                            ),
+                            config.qtypes.span,
+                        ));
+                }
+
+                std::iter::zip(vals, &config.qtypes.item)
+                    .map(|(val, qtype)| {
+                        Ok(Query::queries_for_name(
+                            Query::name_from_value(val)?,
+                            std::slice::from_ref(qtype),
+                            config,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, LabeledError>>()
+                    .map(|queries| queries.into_iter().flatten().collect())
+            }
+
             // If any other input type is given, the CLI flags fill in the type
             // and class.
+            val => Ok(Query::queries_for_name(
+                Query::name_from_value(val)?,
+                &config.qtypes.item,
+                config,
+            )),
+        }
+    }
+
+    /// Whether the list is made up of the raw labels of a single name.
+    fn is_label_list(vals: &[Value]) -> bool {
+        vals.iter().all(|val| {
+            matches!(
+                val,
+                Value::Binary { .. }
+                    | Value::Int { .. }
+                    | Value::Bool { .. }
+                    | Value::Nothing { .. }
+            )
+        })
+    }
+
+    fn queries_for_name(name: Name, qtypes: &[Spanned<RecordType>], config: &Config) -> Vec<Self> {
+        let query = |name: Name, qtype: RecordType| {
+            let mut query = hickory_proto::op::Query::query(name, qtype);
+            query.set_query_class(config.class.item);
+            Query(query)
+        };
+
+        let mut queries: Vec<Self> = qtypes
+            .iter()
+            .map(|qtype| query(name.clone(), qtype.item))
+            .collect();
+
+        // the DMARC policy of a domain is published below it
+        let dmarc_label = constants::type_groups::DMARC_LABEL.as_bytes();
+        let is_dmarc = name
+            .iter()
+            .next()
+            .is_some_and(|label| label.eq_ignore_ascii_case(dmarc_label));
+
+        if config.dmarc.item && !is_dmarc {
+            let dmarc = Name::from_ascii(constants::type_groups::DMARC_LABEL)
+                .and_then(|label| label.append_name(&name));
+            if let Ok(dmarc) = dmarc {
+                queries.push(query(dmarc, RecordType::TXT));
+            }
+        }
+
+        queries
+    }
+
+    /// Parses a name from either a string or a list of raw labels.
+    pub fn name_from_value(value: &Value) -> Result<Name, LabeledError> {
+        match value {
             str_val @ Value::String { val, .. } => {
                 let span = str_val.span();
 
@@ -282,41 +792,12 @@ impl Query {
 
                 tracing::debug!(?name);
 
-                let queries = config
-                    .qtypes
-                    .item
-                    .iter()
-                    .map(|qtype| {
-                        let mut query = hickory_proto::op::Query::query(name.clone(), qtype.item);
-                        query.set_query_class(config.class.item);
-                        Query(query)
-                    })
-                    .collect();
-
-                Ok(queries)
+                Ok(name)
             }
-            list @ Value::List { vals, .. } => {
-                if !vals.iter().all(|val| {
-                    matches!(
-                        val,
-                        Value::Binary { .. }
-                            | Value::Int { .. }
-                            | Value::Bool { .. }
-                            | Value::Nothing { .. }
-                    )
-                }) {
-                    return Ok(vals
-                        .iter()
-                        .map(|val| Query::try_from_value(val, config))
-                        .collect::<Result<Vec<_>, _>>()?
-                        .into_iter()
-                        .flatten()
-                        .collect());
-                }
-
+            list @ Value::List { vals, .. } if Query::is_label_list(vals) => {
                 let span = list.span();
 
-                let name = Name::from_labels(
+                Name::from_labels(
                     vals.iter()
                         .map(|val| match val {
                             Value::Binary { val: bin_val, .. } => Ok(bin_val.clone()),
@@ -340,20 +821,7 @@ impl Query {
                 .map_err(|err| {
                     LabeledError::new("invalid name")
                         .with_label(format!("Error parsing into name: {}", err), span)
-                })?;
-
-                let queries = config
-                    .qtypes
-                    .item
-                    .iter()
-                    .map(|qtype| {
-                        let mut query = hickory_proto::op::Query::query(name.clone(), qtype.item);
-                        query.set_query_class(config.class.item);
-                        Query(query)
-                    })
-                    .collect();
-
-                Ok(queries)
+                })
             }
             val => Err(LabeledError::new("invalid input type").with_label(
                 format!("could not convert input to a DNS record name: {:?}", val),
@@ -371,9 +839,13 @@ impl Record {
         let parts = record.into_parts();
 
         let name = Value::string(parts.name_labels.to_utf8(), Span::unknown());
-        let rtype = code_to_record_u16(parts.rr_type, config);
+        let rtype = rtype_to_value(parts.rr_type, config);
         let class = code_to_record_u16(parts.dns_class, config);
-        let ttl = util::sec_to_duration(parts.ttl);
+        let ttl = if config.ttl_as_int.item {
+            Value::int(parts.ttl.into(), Span::unknown())
+        } else {
+            util::sec_to_duration(parts.ttl)
+        };
         let rdata = match parts.rdata {
             Some(data) => RData(data).into_value(config)?,
             None => Value::nothing(Span::unknown()),
@@ -389,6 +861,18 @@ impl Record {
     }
 }
 
+/// A name embedded in RDATA. Servers may return it in any case, so it can be
+/// lowercased to compare consistently.
+fn rdata_name(name: &Name, config: &Config) -> Value {
+    let name = if config.normalize_rdata_case.item {
+        name.to_lowercase().to_utf8()
+    } else {
+        name.to_utf8()
+    };
+
+    Value::string(name, Span::unknown())
+}
+
 pub struct RData(pub(crate) hickory_proto::rr::RData);
 
 impl RData {
@@ -401,7 +885,7 @@ impl RData {
                     hickory_proto::rr::rdata::caa::Value::Issuer(issuer_name, key_values) => {
                         let issuer_name = issuer_name
                             .as_ref()
-                            .map(|name| Value::string(name.to_string(), Span::unknown()))
+                            .map(|name| rdata_name(name, config))
                             .unwrap_or(Value::nothing(Span::unknown()));
 
                         let parameters: HashMap<String, Value> = key_values
@@ -465,7 +949,7 @@ impl RData {
             hickory_proto::rr::RData::HTTPS(hickory_proto::rr::rdata::HTTPS(svcb))
             | hickory_proto::rr::RData::SVCB(svcb) => {
                 let svc_priority = Value::int(svcb.svc_priority() as i64, Span::unknown());
-                let target_name = Value::string(svcb.target_name().to_string(), Span::unknown());
+                let target_name = rdata_name(svcb.target_name(), config);
                 let svc_params = svcb.svc_params().iter().map(|(key, value)| {
                     let value = match value {
                         SvcParamValue::Mandatory(param_keys) => Value::list(
@@ -527,7 +1011,7 @@ impl RData {
 
             hickory_proto::rr::RData::MX(mx) => {
                 let preference = Value::int(mx.preference() as i64, Span::unknown());
-                let exchange = Value::string(mx.exchange().to_string(), Span::unknown());
+                let exchange = rdata_name(mx.exchange(), config);
 
                 Value::record(
                     record![
@@ -544,7 +1028,7 @@ impl RData {
                 let flags = util::string_or_binary(naptr.flags());
                 let services = util::string_or_binary(naptr.services());
                 let regexp = util::string_or_binary(naptr.regexp());
-                let replacement = Value::string(naptr.replacement().to_string(), Span::unknown());
+                let replacement = rdata_name(naptr.replacement(), config);
 
                 Value::record(
                     record![
@@ -559,17 +1043,19 @@ impl RData {
                 )
             }
 
+            // names are shown in unicode, like owner names
+            hickory_proto::rr::RData::CNAME(cname) => rdata_name(&cname, config),
             hickory_proto::rr::RData::NULL(null) => util::string_or_binary(null.anything()),
-            hickory_proto::rr::RData::NS(ns) => Value::string(ns.to_string(), Span::unknown()),
+            hickory_proto::rr::RData::NS(ns) => rdata_name(&ns, config),
             hickory_proto::rr::RData::OPENPGPKEY(key) => {
                 Value::binary(key.public_key(), Span::unknown())
             }
             hickory_proto::rr::RData::OPT(opt) => Opt(&opt).into_value(config),
-            hickory_proto::rr::RData::PTR(name) => Value::string(name.to_string(), Span::unknown()),
+            hickory_proto::rr::RData::PTR(name) => rdata_name(&name, config),
 
             hickory_proto::rr::RData::SOA(soa) => {
-                let mname = Value::string(soa.mname().to_string(), Span::unknown());
-                let rname = Value::string(soa.rname().to_string(), Span::unknown());
+                let mname = rdata_name(soa.mname(), config);
+                let rname = rdata_name(soa.rname(), config);
                 let serial = Value::int(soa.serial() as i64, Span::unknown());
                 let refresh = util::sec_to_duration(soa.refresh() as u64);
                 let retry = util::sec_to_duration(soa.retry() as u64);
@@ -594,7 +1080,7 @@ impl RData {
                 let priority = Value::int(srv.priority() as i64, Span::unknown());
                 let weight = Value::int(srv.weight() as i64, Span::unknown());
                 let port = Value::int(srv.port() as i64, Span::unknown());
-                let target = Value::string(srv.target().to_string(), Span::unknown());
+                let target = rdata_name(srv.target(), config);
 
                 Value::record(
                     record![
@@ -768,8 +1254,7 @@ impl RData {
                     )
                 }
                 DNSSECRData::NSEC(nsec) => {
-                    let next_domain_name =
-                        Value::string(nsec.next_domain_name().to_string(), Span::unknown());
+                    let next_domain_name = rdata_name(nsec.next_domain_name(), config);
                     let types = Value::list(
                         nsec.type_bit_maps()
                             .iter()
@@ -851,7 +1336,7 @@ impl RData {
                     let sig_expiration = util::sec_to_date(sig.sig_expiration(), Span::unknown())?;
                     let sig_inception = util::sec_to_date(sig.sig_inception(), Span::unknown())?;
                     let key_tag = Value::int(sig.key_tag() as i64, Span::unknown());
-                    let signer_name = Value::string(sig.signer_name().to_string(), Span::unknown());
+                    let signer_name = rdata_name(sig.signer_name(), config);
                     let sig = Value::binary(sig.sig(), Span::unknown());
 
                     Value::record(
@@ -901,6 +1386,33 @@ impl RData {
                 ),
                 rdata => Value::string(rdata.to_string(), Span::unknown()),
             },
+            hickory_proto::rr::RData::Unknown { code: rtype, rdata }
+                if u16::from(rtype) == constants::rtypes::AVC
+                    && parse_character_strings(rdata.anything()).is_some() =>
+            {
+                // same format as TXT
+                Value::list(
+                    parse_character_strings(rdata.anything())
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(util::string_or_binary)
+                        .collect(),
+                    Span::unknown(),
+                )
+            }
+            hickory_proto::rr::RData::Unknown { code: rtype, rdata }
+                if u16::from(rtype) == constants::rtypes::DOA
+                    && parse_doa(rdata.anything()).is_some() =>
+            {
+                parse_doa(rdata.anything()).unwrap_or_else(|| Value::nothing(Span::unknown()))
+            }
+            hickory_proto::rr::RData::Unknown { code: rtype, rdata }
+                if u16::from(rtype) == constants::rtypes::HIP
+                    && parse_hip(rdata.anything(), config).is_some() =>
+            {
+                parse_hip(rdata.anything(), config)
+                    .unwrap_or_else(|| Value::nothing(Span::unknown()))
+            }
             hickory_proto::rr::RData::Unknown { code: rtype, rdata } => Value::record(
                 record![
                     "code"  => Value::int(u16::from(rtype) as i64, Span::unknown()),
@@ -915,21 +1427,135 @@ impl RData {
     }
 }
 
+/// Splits RDATA made up of length prefixed character-strings, as in TXT.
+fn parse_character_strings(mut rdata: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut strings = Vec::new();
+
+    while let Some((&len, rest)) = rdata.split_first() {
+        if rest.len() < len as usize {
+            return None;
+        }
+
+        let (string, rest) = rest.split_at(len as usize);
+        strings.push(string);
+        rdata = rest;
+    }
+
+    Some(strings)
+}
+
+/// DOA RDATA: enterprise, type, location, a character-string with the media
+/// type, and the data itself (draft-durand-doa-over-dns section 3.1).
+fn parse_doa(rdata: &[u8]) -> Option<Value> {
+    let enterprise = u32::from_be_bytes(rdata.get(0..4)?.try_into().ok()?);
+    let doa_type = u32::from_be_bytes(rdata.get(4..8)?.try_into().ok()?);
+    let location = *rdata.get(8)?;
+    let media_type_len = *rdata.get(9)? as usize;
+    let media_type = rdata.get(10..10 + media_type_len)?;
+    let data = &rdata[10 + media_type_len..];
+
+    Some(Value::record(
+        nu_protocol::Record::from_iter(std::iter::zip(
+            constants::columns::DOA_COLS.iter().map(|col| (*col).into()),
+            vec![
+                Value::int(enterprise as i64, Span::unknown()),
+                Value::int(doa_type as i64, Span::unknown()),
+                Value::int(location as i64, Span::unknown()),
+                util::string_or_binary(media_type),
+                Value::binary(data, Span::unknown()),
+            ],
+        )),
+        Span::unknown(),
+    ))
+}
+
+/// HIP RDATA: the lengths of the HIT and public key, the public key
+/// algorithm, the HIT, the public key, and the rendezvous servers as
+/// uncompressed names (RFC 8005 section 5).
+fn parse_hip(rdata: &[u8], config: &Config) -> Option<Value> {
+    let hit_len = *rdata.first()? as usize;
+    let pk_algorithm = *rdata.get(1)?;
+    let pk_len = u16::from_be_bytes(rdata.get(2..4)?.try_into().ok()?) as usize;
+    let hit = rdata.get(4..4 + hit_len)?;
+    let public_key = rdata.get(4 + hit_len..4 + hit_len + pk_len)?;
+
+    let mut decoder = BinDecoder::new(&rdata[4 + hit_len + pk_len..]);
+    let mut rendezvous_servers = Vec::new();
+    while !decoder.is_empty() {
+        rendezvous_servers.push(rdata_name(&Name::read(&mut decoder).ok()?, config));
+    }
+
+    Some(Value::record(
+        nu_protocol::Record::from_iter(std::iter::zip(
+            constants::columns::HIP_COLS.iter().map(|col| (*col).into()),
+            vec![
+                Value::int(pk_algorithm as i64, Span::unknown()),
+                Value::string(data_encoding::HEXUPPER.encode(hit), Span::unknown()),
+                Value::string(data_encoding::BASE64.encode(public_key), Span::unknown()),
+                Value::list(rendezvous_servers, Span::unknown()),
+            ],
+        )),
+        Span::unknown(),
+    ))
+}
+
+pub fn digest_type_name(digest_type: dnssec::DigestType) -> &'static str {
+    match digest_type {
+        dnssec::DigestType::SHA1 => "SHA-1",
+        dnssec::DigestType::SHA256 => "SHA-256",
+        dnssec::DigestType::GOSTR34_11_94 => "GOST R 34.11-94",
+        dnssec::DigestType::SHA384 => "SHA-384",
+        dnssec::DigestType::SHA512 => "SHA-512",
+        dnssec::DigestType::ED25519 => "ED25519",
+        _ => "unknown",
+    }
+}
+
+/// A digest type of DS records (RFC 4034 section 5.1.3), by name or number.
+pub struct DigestType(pub(crate) dnssec::DigestType);
+
+impl TryFrom<Value> for DigestType {
+    type Error = LabeledError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        // the digest types of DS records in use (RFC 3658, RFC 4509, RFC 6605)
+        const SUPPORTED: [dnssec::DigestType; 3] = [
+            dnssec::DigestType::SHA1,
+            dnssec::DigestType::SHA256,
+            dnssec::DigestType::SHA384,
+        ];
+
+        let digest_type = match &value {
+            Value::String { val, .. } => SUPPORTED.into_iter().find(|digest_type| {
+                digest_type_name(*digest_type)
+                    .replace('-', "")
+                    .eq_ignore_ascii_case(&val.replace('-', ""))
+            }),
+            Value::Int { val, .. } => u8::try_from(*val)
+                .ok()
+                .and_then(|code| dnssec::DigestType::from_u8(code).ok())
+                .filter(|digest_type| SUPPORTED.contains(digest_type)),
+            value => {
+                return Err(LabeledError::new("invalid digest type").with_label(
+                    "Invalid type for digest type. Must be either string or int.",
+                    value.span(),
+                ));
+            }
+        };
+
+        digest_type.map(DigestType).ok_or_else(|| {
+            LabeledError::new("invalid digest type").with_label(
+                "should be SHA-1, SHA-256, SHA-384, or their numbers 1, 2, and 4",
+                value.span(),
+            )
+        })
+    }
+}
+
 fn parse_ds<D: Deref<Target = dnssec::rdata::DS>>(ds: D) -> Value {
     let key_tag = Value::int(ds.key_tag() as i64, Span::unknown());
     let algorithm = Value::string(ds.algorithm().to_string(), Span::unknown());
-    let digest_type = Value::string(
-        Into::<String>::into(match ds.digest_type() {
-            dnssec::DigestType::SHA1 => "SHA-1",
-            dnssec::DigestType::SHA256 => "SHA-256",
-            dnssec::DigestType::GOSTR34_11_94 => "GOST R 34.11-94",
-            dnssec::DigestType::SHA384 => "SHA-384",
-            dnssec::DigestType::SHA512 => "SHA-512",
-            dnssec::DigestType::ED25519 => "ED25519",
-            _ => "unknown",
-        }),
-        Span::unknown(),
-    );
+    let digest_type = Value::string(digest_type_name(ds.digest_type()), Span::unknown());
     let digest = Value::binary(ds.digest(), Span::unknown());
     Value::record(
         record![
@@ -943,23 +1569,66 @@ fn parse_ds<D: Deref<Target = dnssec::rdata::DS>>(ds: D) -> Value {
 }
 
 fn parse_dnskey<D: Deref<Target = dnssec::rdata::DNSKEY>>(dnskey: D) -> Value {
+    // the key tag and size are not part of the record, but what it is told by
+    let key_tag = dnskey.calculate_key_tag().map_or_else(
+        |_| Value::nothing(Span::unknown()),
+        |key_tag| Value::int(key_tag as i64, Span::unknown()),
+    );
     let zone_key = Value::bool(dnskey.zone_key(), Span::unknown());
     let secure_entry_point = Value::bool(dnskey.secure_entry_point(), Span::unknown());
     let revoke = Value::bool(dnskey.revoke(), Span::unknown());
     let algorithm = Value::string(dnskey.algorithm().to_string(), Span::unknown());
+    let key_size = key_size(dnskey.algorithm(), dnskey.public_key()).map_or_else(
+        || Value::nothing(Span::unknown()),
+        |bits| Value::int(bits as i64, Span::unknown()),
+    );
     let public_key = Value::binary(dnskey.public_key(), Span::unknown());
     Value::record(
         record![
+            "key_tag"            => key_tag,
             "zone_key"           => zone_key,
             "secure_entry_point" => secure_entry_point,
             "revoke"             => revoke,
             "algorithm"          => algorithm,
+            "key_size"           => key_size,
             "public_key"         => public_key,
         ],
         Span::unknown(),
     )
 }
 
+/// The size in bits of a public key of a DNSKEY record: that of the modulus
+/// of RSA keys (RFC 3110 section 2), of the prime of DSA keys (RFC 2536
+/// section 2), and of the curve of elliptic curve keys.
+fn key_size(algorithm: dnssec::Algorithm, public_key: &[u8]) -> Option<u32> {
+    #[allow(deprecated)]
+    match algorithm {
+        dnssec::Algorithm::RSAMD5
+        | dnssec::Algorithm::RSASHA1
+        | dnssec::Algorithm::RSASHA1NSEC3SHA1
+        | dnssec::Algorithm::RSASHA256
+        | dnssec::Algorithm::RSASHA512 => {
+            // the exponent length takes one octet, or three when it is zero
+            let modulus = match public_key {
+                [0, high, low, rest @ ..] => {
+                    rest.get(usize::from(u16::from_be_bytes([*high, *low]))..)?
+                }
+                [len, rest @ ..] => rest.get(usize::from(*len)..)?,
+                [] => return None,
+            };
+            let first = modulus.iter().position(|byte| *byte != 0)?;
+
+            Some((modulus.len() - first) as u32 * 8 - modulus[first].leading_zeros())
+        }
+        dnssec::Algorithm::DSA => public_key.first().map(|t| 512 + 64 * u32::from(*t)),
+        dnssec::Algorithm::ECDSAP256SHA256 | dnssec::Algorithm::ED25519 => Some(256),
+        dnssec::Algorithm::ECDSAP384SHA384 => Some(384),
+        // ED448 (RFC 8080)
+        dnssec::Algorithm::Unknown(16) => Some(456),
+        _ => None,
+    }
+}
+
 pub struct Edns(pub(crate) hickory_proto::op::Edns);
 
 impl Edns {
@@ -984,6 +1653,66 @@ impl Edns {
     }
 }
 
+/// The extended errors (RFC 8914) in the OPT record of a response. hickory
+/// keeps only the last option of each code, so they are read from the options
+/// of the message as received when it is given, and a response with several
+/// of them reports them all.
+fn extended_errors(
+    edns: Option<&hickory_proto::op::Edns>,
+    received_options: Option<Vec<(u16, Vec<u8>)>>,
+) -> Value {
+    let options = match received_options {
+        Some(options) => options
+            .into_iter()
+            .filter(|(code, _)| *code == constants::ede::OPTION_CODE)
+            .map(|(_, data)| data)
+            .collect(),
+        None => edns
+            .and_then(|edns| edns.option(EdnsCode::Unknown(constants::ede::OPTION_CODE)))
+            .and_then(|option| match option {
+                EdnsOption::Unknown(_, data) => Some(data.clone()),
+                _ => None,
+            })
+            .into_iter()
+            .collect::<Vec<_>>(),
+    };
+
+    let errors = options
+        .iter()
+        .filter_map(|data| match data.as_slice() {
+            [high, low, extra_text @ ..] => Some((u16::from_be_bytes([*high, *low]), extra_text)),
+            _ => {
+                tracing::warn!(ede.data = ?data, "extended error is too short");
+                None
+            }
+        })
+        .map(|(code, extra_text)| {
+            // the text is meant for humans, and may be missing, or not even be
+            // valid UTF-8
+            let extra_text = match String::from_utf8_lossy(extra_text).trim_end_matches('\0') {
+                "" => Value::nothing(Span::unknown()),
+                text => Value::string(text, Span::unknown()),
+            };
+
+            Value::record(
+                nu_protocol::Record::from_iter(std::iter::zip(
+                    constants::columns::EDE_COLS.iter().map(|col| (*col).into()),
+                    vec![
+                        Value::int(code as i64, Span::unknown()),
+                        constants::ede::purpose(code).map_or_else(
+                            || Value::nothing(Span::unknown()),
+                            |purpose| Value::string(purpose, Span::unknown()),
+                        ),
+                        extra_text,
+                    ],
+                )),
+                Span::unknown(),
+            )
+        });
+
+    Value::list(errors.collect(), Span::unknown())
+}
+
 pub struct Opt<'o>(pub(crate) &'o hickory_proto::rr::rdata::OPT);
 
 impl<'o> Opt<'o> {
@@ -1021,14 +1750,12 @@ impl<'o> Opt<'o> {
                             .collect(),
                         Span::unknown(),
                     ),
-                    EdnsOption::Unknown(code, val) => Value::record(
-                        record![
-                            "code" => Value::int(*code as i64, Span::unknown()),
-                            "data" => util::string_or_binary(val.clone()),
-                        ],
-                        Span::unknown(),
+                    EdnsOption::Unknown(code, val) => edns_option(*code, val),
+                    // the client subnet, and options hickory may learn to read
+                    option => edns_option(
+                        EdnsCode::from(option).into(),
+                        &Vec::<u8>::try_from(option).unwrap_or_default(),
                     ),
-                    _ => todo!(),
                 };
 
                 (code, option)
@@ -1039,6 +1766,95 @@ impl<'o> Opt<'o> {
     }
 }
 
+/// An option with its code and data, which `dns build` reads back, and the
+/// fields of the options whose format is known.
+fn edns_option(code: u16, data: &[u8]) -> Value {
+    let mut option = nu_protocol::Record::new();
+    option.push("code", Value::int(code as i64, Span::unknown()));
+
+    match code {
+        // kept as binary, as the text of cookies and padding would be noise
+        constants::edns::COOKIE | constants::edns::PADDING => {
+            option.push("data", Value::binary(data, Span::unknown()))
+        }
+        _ => option.push("data", util::string_or_binary(data.to_vec())),
+    }
+
+    match code {
+        constants::edns::SUBNET => {
+            if let Some((address, source_prefix, scope_prefix)) = client_subnet(data) {
+                option.push(
+                    "address",
+                    Value::string(address.to_string(), Span::unknown()),
+                );
+                option.push(
+                    "source_prefix",
+                    Value::int(source_prefix.into(), Span::unknown()),
+                );
+                option.push(
+                    "scope_prefix",
+                    Value::int(scope_prefix.into(), Span::unknown()),
+                );
+            }
+        }
+        // a client cookie of 8 bytes, and the server cookie of 8 to 32 bytes
+        // when the server sent one
+        constants::edns::COOKIE if data.len() >= 8 => {
+            let (client, server) = data.split_at(8);
+            option.push("client", Value::binary(client, Span::unknown()));
+            option.push(
+                "server",
+                if server.is_empty() {
+                    Value::nothing(Span::unknown())
+                } else {
+                    Value::binary(server, Span::unknown())
+                },
+            );
+        }
+        // identifiers are often text, but are not meant to be, so their hex is
+        // kept too (RFC 5001 section 2.4)
+        constants::edns::NSID => {
+            option.push(
+                "hex",
+                Value::string(data_encoding::HEXLOWER.encode(data), Span::unknown()),
+            );
+        }
+        constants::edns::PADDING => {
+            option.push(
+                "length",
+                Value::filesize(data.len() as i64, Span::unknown()),
+            );
+        }
+        _ => {}
+    }
+
+    Value::record(option, Span::unknown())
+}
+
+/// The address and prefix lengths of a client subnet option (RFC 7871 section
+/// 6), whose address is truncated to its source prefix.
+pub(crate) fn client_subnet(data: &[u8]) -> Option<(IpAddr, u8, u8)> {
+    let [family_high, family_low, source_prefix, scope_prefix, address @ ..] = data else {
+        return None;
+    };
+
+    let address = match u16::from_be_bytes([*family_high, *family_low]) {
+        1 => {
+            let mut octets = [0; 4];
+            octets.get_mut(..address.len())?.copy_from_slice(address);
+            IpAddr::from(octets)
+        }
+        2 => {
+            let mut octets = [0; 16];
+            octets.get_mut(..address.len())?.copy_from_slice(address);
+            IpAddr::from(octets)
+        }
+        _ => return None,
+    };
+
+    Some((address, *source_prefix, *scope_prefix))
+}
+
 pub struct RType(pub(crate) hickory_proto::rr::RecordType);
 
 impl TryFrom<&Value> for RType {
@@ -1051,14 +1867,25 @@ impl TryFrom<&Value> for RType {
         };
 
         match value {
-            Value::String { .. } => Ok(RType(
-                RecordType::from_str(&value.as_str().unwrap().to_uppercase())
-                    .map_err(|err| qtype_err(err, value.span()))?,
-            )),
+            // the generic names of types without one (RFC 3597 section 5) are
+            // taken too, as unknown types are output with them
+            Value::String { val, .. } => {
+                match constants::rtypes::code(val).or_else(|| generic_rtype(val)) {
+                    Some(code) => Ok(RType(RecordType::from(code))),
+                    None => Ok(RType(
+                        RecordType::from_str(&value.as_str().unwrap().to_uppercase())
+                            .map_err(|err| qtype_err(err, value.span()))?,
+                    )),
+                }
+            }
             Value::Int { val, .. } => {
                 let rtype = RecordType::from(*val as u16);
 
                 if let RecordType::Unknown(r) = rtype {
+                    if constants::rtypes::name(r).is_some() {
+                        return Ok(RType(rtype));
+                    }
+
                     return Err(LabeledError::new("invalid record type").with_label(
                         format!("Error parsing record type: unknown code: {}", r),
                         value.span(),
@@ -1075,6 +1902,12 @@ impl TryFrom<&Value> for RType {
     }
 }
 
+fn generic_rtype(name: &str) -> Option<u16> {
+    name.to_uppercase()
+        .strip_prefix("TYPE")
+        .and_then(|code| code.parse().ok())
+}
+
 pub struct DNSClass(pub(crate) hickory_proto::rr::DNSClass);
 
 impl TryFrom<Value> for DNSClass {
@@ -1104,6 +1937,58 @@ impl TryFrom<Value> for DNSClass {
     }
 }
 
+/// An opcode, including those hickory does not know, which it can neither
+/// send nor read.
+#[derive(Debug, Clone, Copy)]
+pub struct OpCode(pub(crate) u8);
+
+impl Display for OpCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            0 => f.write_str("QUERY"),
+            1 => f.write_str("IQUERY"),
+            2 => f.write_str("STATUS"),
+            4 => f.write_str("NOTIFY"),
+            5 => f.write_str("UPDATE"),
+            6 => f.write_str("DSO"),
+            code => write!(f, "RESERVED{code}"),
+        }
+    }
+}
+
+impl From<OpCode> for u8 {
+    fn from(op_code: OpCode) -> Self {
+        op_code.0
+    }
+}
+
+impl TryFrom<Value> for OpCode {
+    type Error = LabeledError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        // the opcode is 4 bits of the header
+        let code = match &value {
+            Value::String { val, .. } => {
+                (0..=15).find(|code| OpCode(*code).to_string().eq_ignore_ascii_case(val))
+            }
+            Value::Int { val, .. } => u8::try_from(*val).ok().filter(|code| *code <= 15),
+            value => {
+                return Err(LabeledError::new("invalid opcode").with_label(
+                    "Invalid type for opcode. Must be either string or int.",
+                    value.span(),
+                ));
+            }
+        };
+
+        code.map(OpCode).ok_or_else(|| {
+            LabeledError::new("invalid opcode").with_label(
+                "should be QUERY, IQUERY, STATUS, NOTIFY, UPDATE, DSO, or a number from 0 to 15",
+                value.span(),
+            )
+        })
+    }
+}
+
 pub struct Protocol(pub(crate) hickory_resolver::config::Protocol);
 
 impl TryFrom<Value> for Protocol {
@@ -1134,13 +2019,52 @@ impl TryFrom<Value> for Protocol {
     }
 }
 
-#[derive(Debug, Default, PartialEq)]
+/// An EDNS option given as `CODE=HEX`, which is sent as is.
+pub struct RawEdnsOption(pub(crate) u16, pub(crate) Vec<u8>);
+
+impl TryFrom<&Value> for RawEdnsOption {
+    type Error = LabeledError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let span = value.span();
+        let opt_err = |msg: String| LabeledError::new("invalid EDNS option").with_label(msg, span);
+
+        let (code, data) = value
+            .as_str()
+            .map_err(|_| opt_err("EDNS option must be a string".into()))?
+            .split_once('=')
+            .ok_or_else(|| opt_err("EDNS option must be given as CODE=HEX".into()))?;
+
+        let code = code
+            .trim()
+            .parse::<u16>()
+            .map_err(|err| opt_err(format!("code must be an int from 0 to 65535: {}", err)))?;
+
+        let data = data_encoding::HEXLOWER_PERMISSIVE
+            .decode(data.trim().as_bytes())
+            .map_err(|err| opt_err(format!("data must be hex encoded: {}", err)))?;
+
+        if data.len() > u16::MAX as usize {
+            return Err(opt_err(format!(
+                "data must be at most {} bytes long",
+                u16::MAX
+            )));
+        }
+
+        Ok(RawEdnsOption(code, data))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub enum DnssecMode {
     None,
     Strict,
 
     #[default]
     Opportunistic,
+    /// The chain of trust of each response is built and checked from the root
+    /// down, rather than by hickory.
+    Validate,
 }
 
 impl TryFrom<Value> for DnssecMode {
@@ -1152,9 +2076,10 @@ impl TryFrom<Value> for DnssecMode {
                 "NONE" => DnssecMode::None,
                 "STRICT" => DnssecMode::Strict,
                 "OPPORTUNISTIC" => DnssecMode::Opportunistic,
+                "VALIDATE" => DnssecMode::Validate,
                 _ => {
                     return Err(LabeledError::new("invalid DNSSEC mode").with_label(
-                        "Invalid DNSSEC mode. Must be one of: none, strict, opportunistic",
+                        "Invalid DNSSEC mode. Must be one of: none, strict, opportunistic, validate",
                         value.span(),
                     ));
                 }
@@ -1165,6 +2090,85 @@ impl TryFrom<Value> for DnssecMode {
     }
 }
 
+/// The oldest TLS version an encrypted connection to a nameserver may use.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TlsVersion {
+    #[default]
+    V1_2,
+    V1_3,
+}
+
+impl TlsVersion {
+    /// The versions that may be negotiated.
+    pub fn allowed(self) -> &'static [&'static rustls::SupportedProtocolVersion] {
+        static TLS13: &[&rustls::SupportedProtocolVersion] = &[&rustls::version::TLS13];
+
+        match self {
+            TlsVersion::V1_2 => rustls::ALL_VERSIONS,
+            TlsVersion::V1_3 => TLS13,
+        }
+    }
+}
+
+impl TryFrom<Value> for TlsVersion {
+    type Error = LabeledError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let version = match &value {
+            Value::String { val, .. } => val.trim_start_matches(['v', 'V']).to_string(),
+            // `1.3` in a config file is a float
+            Value::Float { val, .. } => val.to_string(),
+            _ => {
+                return Err(LabeledError::new("invalid input")
+                    .with_label("TLS version must be a string", value.span()))
+            }
+        };
+
+        match version.as_str() {
+            "1.2" => Ok(TlsVersion::V1_2),
+            "1.3" => Ok(TlsVersion::V1_3),
+            _ => Err(LabeledError::new("invalid TLS version").with_label(
+                "Invalid TLS version. Must be one of: 1.2, 1.3",
+                value.span(),
+            )),
+        }
+    }
+}
+
+/// How a command hands back its results.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Structured nushell values.
+    #[default]
+    Table,
+    /// The same structure, serialized as MessagePack bytes.
+    Msgpack,
+    /// The messages as text, the way dig prints them.
+    Dig,
+}
+
+impl TryFrom<Value> for OutputFormat {
+    type Error = LabeledError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String { .. } => Ok(match value.as_str().unwrap().to_lowercase().as_str() {
+                "table" => OutputFormat::Table,
+                "msgpack" => OutputFormat::Msgpack,
+                "dig" => OutputFormat::Dig,
+                _ => {
+                    return Err(LabeledError::new("invalid output format").with_label(
+                        "Invalid output format. Must be one of: table, msgpack, dig",
+                        value.span(),
+                    ));
+                }
+            }),
+            _ => Err(LabeledError::new("invalid input")
+                .with_label("Output format must be a string", value.span())),
+        }
+    }
+}
+
 pub mod util {
     use std::time::Duration;
 
@@ -1208,3 +2212,300 @@ pub mod util {
         Ok(Value::date(datetime, Span::unknown()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use hickory_proto::rr::{
+        rdata::{A, MX, NS, NULL, SOA, TXT},
+        RData, Record,
+    };
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_ascii(name).unwrap()
+    }
+
+    fn record(owner: &str, ttl: u32, rdata: RData) -> Record {
+        Record::from_rdata(name(owner), ttl, rdata)
+    }
+
+    fn a(owner: &str, ttl: u32, last_octet: u8) -> Record {
+        record(
+            owner,
+            ttl,
+            RData::A(A(Ipv4Addr::new(192, 0, 2, last_octet))),
+        )
+    }
+
+    fn mx(owner: &str, exchange: &str) -> Record {
+        record(owner, 300, RData::MX(MX::new(10, name(exchange))))
+    }
+
+    fn txt(owner: &str, strings: &[&str]) -> Record {
+        record(
+            owner,
+            300,
+            RData::TXT(TXT::new(strings.iter().map(|s| s.to_string()).collect())),
+        )
+    }
+
+    fn rdata(records: &[Record]) -> Vec<&RData> {
+        records.iter().filter_map(|record| record.data()).collect()
+    }
+
+    #[test]
+    fn canonical_rdata_lowercases_embedded_names() {
+        let soa = |mname, rname| {
+            RData::SOA(SOA::new(
+                name(mname),
+                name(rname),
+                1,
+                7200,
+                3600,
+                1209600,
+                300,
+            ))
+        };
+
+        let pairs = [
+            (
+                mx("example.com.", "Mail.Example.COM."),
+                mx("example.com.", "mail.example.com."),
+            ),
+            (
+                record("example.com.", 300, RData::NS(NS(name("NS1.Example.com.")))),
+                record("example.com.", 300, RData::NS(NS(name("ns1.example.com.")))),
+            ),
+            (
+                record(
+                    "example.com.",
+                    300,
+                    soa("NS1.Example.com.", "HostMaster.Example.com."),
+                ),
+                record(
+                    "example.com.",
+                    300,
+                    soa("ns1.example.com.", "hostmaster.example.com."),
+                ),
+            ),
+        ];
+
+        for (mixed, lower) in &pairs {
+            assert_eq!(canonical_rdata(mixed), canonical_rdata(lower));
+        }
+
+        // uncompressed, as a sequence of labels
+        assert_eq!(
+            canonical_rdata(&pairs[0].0),
+            b"\x00\x0a\x04mail\x07example\x03com\x00"
+        );
+    }
+
+    #[test]
+    fn sort_canonical_compares_lowercased_names() {
+        // 'B' sorts before 'a' as is, but not once lowercased
+        let mut records = vec![
+            mx("example.com.", "B.example.com."),
+            mx("example.com.", "a.example.com."),
+        ];
+        sort_canonical(&mut records);
+
+        assert_eq!(
+            rdata(&records),
+            [
+                &RData::MX(MX::new(10, name("a.example.com."))),
+                &RData::MX(MX::new(10, name("B.example.com."))),
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_canonical_puts_a_prefix_first() {
+        let mut records = vec![
+            txt("example.com.", &["ab", "c"]),
+            txt("example.com.", &["ab"]),
+        ];
+        sort_canonical(&mut records);
+
+        assert_eq!(canonical_rdata(&records[0]), b"\x02ab");
+        assert_eq!(canonical_rdata(&records[1]), b"\x02ab\x01c");
+    }
+
+    #[test]
+    fn sort_canonical_keeps_rrsets_in_order_of_appearance() {
+        let mut records = vec![
+            a("b.example.com.", 300, 2),
+            mx("a.example.com.", "mail.example.com."),
+            a("B.example.com.", 300, 1),
+            a("a.example.com.", 300, 3),
+            mx("a.example.com.", "backup.example.com."),
+        ];
+        sort_canonical(&mut records);
+
+        let order: Vec<_> = records
+            .iter()
+            .map(|record| {
+                (
+                    record.name().to_lowercase().to_ascii(),
+                    record.record_type(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("b.example.com.".to_string(), RecordType::A),
+                ("b.example.com.".to_string(), RecordType::A),
+                ("a.example.com.".to_string(), RecordType::MX),
+                ("a.example.com.".to_string(), RecordType::MX),
+                ("a.example.com.".to_string(), RecordType::A),
+            ]
+        );
+
+        assert_eq!(
+            rdata(&records[..2]),
+            [
+                &RData::A(A(Ipv4Addr::new(192, 0, 2, 1))),
+                &RData::A(A(Ipv4Addr::new(192, 0, 2, 2))),
+            ]
+        );
+        // in wire format, the shorter first label comes first
+        assert_eq!(
+            rdata(&records[2..4]),
+            [
+                &RData::MX(MX::new(10, name("mail.example.com."))),
+                &RData::MX(MX::new(10, name("backup.example.com."))),
+            ]
+        );
+    }
+
+    fn unknown(rtype: u16, wire: &[u8]) -> Value {
+        let config = Config::from_values(|_| None).unwrap();
+        super::RData(RData::Unknown {
+            code: RecordType::Unknown(rtype),
+            rdata: NULL::with(wire.to_vec()),
+        })
+        .into_value(&config)
+        .unwrap()
+    }
+
+    #[test]
+    fn avc_rdata_decodes_like_txt() {
+        let value = unknown(
+            constants::rtypes::AVC,
+            b"\x1dapp-name:WOW|app-class:games|\x11business=Blizzard",
+        );
+
+        assert_eq!(
+            value,
+            Value::test_list(vec![
+                Value::test_string("app-name:WOW|app-class:games|"),
+                Value::test_string("business=Blizzard"),
+            ])
+        );
+    }
+
+    #[test]
+    fn doa_rdata_decodes_every_field() {
+        let value = unknown(
+            constants::rtypes::DOA,
+            b"\x00\x00\x00\x00\x00\x00\x00\x01\x02\x0aimage/jpeg\xff\xd8\xff",
+        );
+
+        assert_eq!(
+            value,
+            Value::test_record(record! {
+                "enterprise" => Value::test_int(0),
+                "type" => Value::test_int(1),
+                "location" => Value::test_int(2),
+                "media_type" => Value::test_string("image/jpeg"),
+                "data" => Value::test_binary(vec![0xff, 0xd8, 0xff]),
+            })
+        );
+    }
+
+    #[test]
+    fn idn_targets_decode_to_unicode_but_are_queried_in_punycode() {
+        let config = Config::from_values(|_| None).unwrap();
+
+        let cname = super::RData(RData::CNAME(hickory_proto::rr::rdata::CNAME(name(
+            "xn--bcher-kva.example.",
+        ))))
+        .into_value(&config)
+        .unwrap();
+        assert_eq!(cname, Value::test_string("bücher.example."));
+
+        // following the CNAME asks for the name as shown
+        let queries = Query::try_from_value(&cname, &config).unwrap();
+        assert!(!queries.is_empty());
+
+        for Query(query) in queries {
+            assert_eq!(query.name().to_ascii(), "xn--bcher-kva.example.");
+
+            let wire = query.to_bytes().unwrap();
+            assert!(wire.starts_with(b"\x0dxn--bcher-kva\x07example\x00"));
+        }
+    }
+
+    #[test]
+    fn answer_checksum_ignores_ttl_order_and_case() {
+        let checksum = answer_checksum(&[
+            a("example.com.", 300, 1),
+            a("example.com.", 300, 2),
+            mx("example.com.", "mail.example.com."),
+        ])
+        .unwrap();
+
+        let reordered = answer_checksum(&[
+            mx("Example.com.", "MAIL.example.com."),
+            a("example.com.", 42, 2),
+            a("EXAMPLE.com.", 7, 1),
+        ])
+        .unwrap();
+        assert_eq!(checksum, reordered);
+
+        let changed = answer_checksum(&[
+            a("example.com.", 300, 1),
+            a("example.com.", 300, 3),
+            mx("example.com.", "mail.example.com."),
+        ])
+        .unwrap();
+        assert_ne!(checksum, changed);
+    }
+
+    /// The HIP record of the example in RFC 8005 section 5.
+    const HIP_HIT: &str = "200100107B1A74DF365639CC39F1D578";
+    const HIP_PUBLIC_KEY: &str = "AwEAAbdxyhNuSutc5EMzxTs9LBPCIkOFH8cIvM4p9+LrV4e19WzK00+CI6zBCQTdtWsuxKbWIy87UOoJTwkUs7lBu+Upr1gsNrut79ryra+bSRGQb1slImA8YVJyuIDsj7kwzG7jnERNqnWxZ48AWkskmdHaVDP4BcelrTI3rMXdXF5D";
+
+    #[test]
+    fn hip_rdata_decodes_the_rfc_8005_example() {
+        let hit = data_encoding::HEXUPPER.decode(HIP_HIT.as_bytes()).unwrap();
+        let public_key = data_encoding::BASE64
+            .decode(HIP_PUBLIC_KEY.as_bytes())
+            .unwrap();
+
+        let mut wire = vec![hit.len() as u8, 2];
+        wire.extend((public_key.len() as u16).to_be_bytes());
+        wire.extend(&hit);
+        wire.extend(&public_key);
+        for server in ["rvs1.example.com.", "rvs2.example.com."] {
+            wire.extend(name(server).to_bytes().unwrap());
+        }
+
+        assert_eq!(
+            unknown(constants::rtypes::HIP, &wire),
+            Value::test_record(record! {
+                "pk_algorithm" => Value::test_int(2),
+                "hit" => Value::test_string(HIP_HIT),
+                "public_key" => Value::test_string(HIP_PUBLIC_KEY),
+                "rendezvous_servers" => Value::test_list(vec![
+                    Value::test_string("rvs1.example.com."),
+                    Value::test_string("rvs2.example.com."),
+                ]),
+            })
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! DNS stamps (`sdns://`), which pack the protocol, address, and certificate
+//! name of a resolver into a single string
+//! (https://dnscrypt.info/stamps-specifications).
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    time::SystemTime,
+};
+
+use hickory_proto::rr::dnssec::DigestType;
+use hickory_resolver::{config::Protocol, Name};
+use nu_protocol::{LabeledError, Span};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+    Certificate, ServerName,
+};
+
+use super::{constants, dnscrypt::DnsCryptServer};
+
+const PLAIN: u8 = 0x00;
+const DNSCRYPT: u8 = 0x01;
+const DOH: u8 = 0x02;
+const DOT: u8 = 0x03;
+const DOQ: u8 = 0x04;
+
+/// What a stamp says about how to reach a resolver.
+#[derive(Debug)]
+pub struct Stamp {
+    pub protocol: Protocol,
+    /// IP address, with an optional port. May be left out of encrypted stamps,
+    /// in which case the hostname is to be resolved.
+    pub address: Option<String>,
+    /// Name of the certificate of the resolver, with an optional port.
+    pub hostname: Option<String>,
+    /// Path of the DoH endpoint.
+    pub path: Option<String>,
+    /// SHA-256 digests of the TBS certificates (RFC 5280 section 4.1) in the
+    /// chain of the resolver, one of which the chain must have.
+    pub hashes: Vec<Vec<u8>>,
+    /// The provider of a DNSCrypt resolver, which is spoken to over UDP or
+    /// TCP.
+    pub dnscrypt: Option<DnsCryptServer>,
+}
+
+impl Stamp {
+    pub fn parse(stamp: &str, span: Span) -> Result<Self, LabeledError> {
+        let invalid = |msg: &str| LabeledError::new("invalid stamp").with_label(msg, span);
+
+        let encoded = stamp
+            .strip_prefix("sdns://")
+            .ok_or_else(|| invalid("a stamp starts with sdns://"))?;
+
+        let data = data_encoding::BASE64URL_NOPAD
+            .decode(encoded.trim_end_matches('=').as_bytes())
+            .map_err(|err| invalid(&format!("stamp is not valid base64url: {}", err)))?;
+
+        let (&kind, rest) = data
+            .split_first()
+            .ok_or_else(|| invalid("stamp is empty"))?;
+
+        // the properties are flags about the resolver, such as whether it
+        // keeps logs, which do not matter for querying it
+        let mut reader = Reader(rest.get(8..).ok_or_else(|| invalid("stamp is too short"))?);
+        let too_short = || invalid("stamp is too short");
+
+        let non_empty = |bytes: Vec<u8>| -> Result<Option<String>, LabeledError> {
+            let string = String::from_utf8(bytes)
+                .map_err(|_| invalid("stamp has a field that is not valid UTF-8"))?;
+            Ok((!string.is_empty()).then_some(string))
+        };
+
+        match kind {
+            PLAIN => Ok(Stamp {
+                protocol: Protocol::Udp,
+                address: non_empty(reader.lp().ok_or_else(too_short)?)?,
+                hostname: None,
+                path: None,
+                hashes: Vec::new(),
+                dnscrypt: None,
+            }),
+            DNSCRYPT => {
+                let address = non_empty(reader.lp().ok_or_else(too_short)?)?
+                    .ok_or_else(|| invalid("DNSCrypt stamp has no address"))?;
+
+                // the port is left out when it is the standard one
+                let address = match IpAddr::from_str(address.trim_start_matches('[').trim_end_matches(']')) {
+                    Ok(ip) => {
+                        SocketAddr::new(ip, constants::config::default::DNSCRYPT_PORT).to_string()
+                    }
+                    Err(_) => address,
+                };
+
+                let public_key = reader
+                    .lp()
+                    .ok_or_else(too_short)?
+                    .try_into()
+                    .map_err(|_| invalid("stamp has a provider key that is not Ed25519"))?;
+
+                let provider_name = non_empty(reader.lp().ok_or_else(too_short)?)?
+                    .ok_or_else(|| invalid("DNSCrypt stamp has no provider name"))?;
+                let mut provider_name = Name::from_ascii(&provider_name).map_err(|err| {
+                    invalid(&format!("stamp has an invalid provider name: {}", err))
+                })?;
+                provider_name.set_fqdn(true);
+
+                Ok(Stamp {
+                    protocol: Protocol::Udp,
+                    address: Some(address),
+                    hostname: None,
+                    path: None,
+                    hashes: Vec::new(),
+                    dnscrypt: Some(DnsCryptServer {
+                        provider_name,
+                        public_key,
+                    }),
+                })
+            }
+            DOH | DOT | DOQ => {
+                let address = non_empty(reader.lp().ok_or_else(too_short)?)?;
+
+                // an empty hash stands for none at all
+                let hashes = reader
+                    .vlp()
+                    .ok_or_else(too_short)?
+                    .into_iter()
+                    .filter(|hash| !hash.is_empty())
+                    .collect::<Vec<_>>();
+
+                if hashes.iter().any(|hash| hash.len() != 32) {
+                    return Err(invalid("stamp has a certificate hash that is not SHA-256"));
+                }
+
+                let hostname = non_empty(reader.lp().ok_or_else(too_short)?)?;
+                let path = match kind {
+                    DOH => non_empty(reader.lp().ok_or_else(too_short)?)?,
+                    _ => None,
+                };
+
+                Ok(Stamp {
+                    protocol: match kind {
+                        DOH => Protocol::Https,
+                        DOT => Protocol::Tls,
+                        _ => Protocol::Quic,
+                    },
+                    address,
+                    hostname,
+                    path,
+                    hashes,
+                    dnscrypt: None,
+                })
+            }
+            kind => Err(LabeledError::new("unsupported stamp").with_label(
+                format!(
+                    "stamps of type {:#04x} are not supported. Only plain DNS, DNSCrypt, DoH, DoT, and DoQ stamps are",
+                    kind
+                ),
+                span,
+            )),
+        }
+    }
+}
+
+/// Reads the length prefixed fields of a stamp.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// A field prefixed by its length.
+    fn lp(&mut self) -> Option<Vec<u8>> {
+        let (&len, rest) = self.0.split_first()?;
+        self.field(len as usize, rest)
+    }
+
+    /// A set of fields, where the high bit of the length says that more follow.
+    fn vlp(&mut self) -> Option<Vec<Vec<u8>>> {
+        let mut fields = Vec::new();
+
+        loop {
+            let (&len, rest) = self.0.split_first()?;
+            fields.push(self.field((len & 0x7f) as usize, rest)?);
+
+            if len & 0x80 == 0 {
+                return Some(fields);
+            }
+        }
+    }
+
+    fn field(&mut self, len: usize, rest: &'a [u8]) -> Option<Vec<u8>> {
+        let field = rest.get(..len)?.to_vec();
+        self.0 = &rest[len..];
+        Some(field)
+    }
+}
+
+/// Checks the certificate of a resolver against the usual roots, and then
+/// that its chain has one of the certificates pinned by its stamp.
+pub struct PinnedCertVerifier {
+    roots: WebPkiVerifier,
+    hashes: Vec<Vec<u8>>,
+}
+
+impl PinnedCertVerifier {
+    pub fn new(roots: WebPkiVerifier, hashes: Vec<Vec<u8>>) -> Self {
+        Self { roots, hashes }
+    }
+
+    fn is_pinned(&self, cert: &Certificate) -> bool {
+        tbs_certificate(&cert.0)
+            .and_then(|tbs| DigestType::SHA256.hash(tbs).ok())
+            .is_some_and(|digest| self.hashes.iter().any(|hash| hash == digest.as_ref()))
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.roots.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+
+        if std::iter::once(end_entity)
+            .chain(intermediates)
+            .any(|cert| self.is_pinned(cert))
+        {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::General(
+                "no certificate in the chain of the resolver matches the hashes of its stamp"
+                    .into(),
+            ))
+        }
+    }
+}
+
+/// The TBS certificate of a certificate in DER, which is the first element of
+/// its outer sequence (RFC 5280 section 4.1), header included.
+fn tbs_certificate(der: &[u8]) -> Option<&[u8]> {
+    let (certificate, _) = der_element(der)?;
+    let (_, header_len) = der_header(certificate)?;
+    let (tbs, _) = der_element(&certificate[header_len..])?;
+    Some(tbs)
+}
+
+/// The first DER element of `input`, header included, and what follows it.
+fn der_element(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, header_len) = der_header(input)?;
+    let end = header_len.checked_add(len)?;
+    Some((input.get(..end)?, &input[end..]))
+}
+
+/// The length of the contents of the DER element at the start of `input`, and
+/// the length of its tag and length octets.
+fn der_header(input: &[u8]) -> Option<(usize, usize)> {
+    let &first = input.get(1)?;
+
+    if first < 0x80 {
+        return Some((first as usize, 2));
+    }
+
+    let octets = (first & 0x7f) as usize;
+    if octets == 0 || octets > 4 {
+        return None;
+    }
+
+    let len = input
+        .get(2..2 + octets)?
+        .iter()
+        .fold(0, |len, &octet| (len << 8) | octet as usize);
+    Some((len, 2 + octets))
+}
@@ -0,0 +1,293 @@
+//! Iterative resolution from the root servers down, which shows every
+//! delegation on the way to the answer.
+
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use hickory_proto::{
+    op::{Message, Query},
+    rr::RData,
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Record, Span, Value};
+
+use super::{
+    client::DnsClient,
+    config::Config,
+    constants, lookup,
+    serde::{self, ResponseStatus},
+};
+
+/// Follows the referrals for `query` from the root servers to the servers that
+/// answer it, and reports every step. After `max_depth` referrals, the trace
+/// stops with what it has so far, which guards against delegations that never
+/// end. CNAMEs in the answer are not followed.
+pub async fn trace(
+    config: &mut Config,
+    query: Query,
+    max_depth: usize,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    // nameservers without glue are looked up with the configured one
+    let resolver = config.server.item;
+    let port = constants::config::default_port(config.protocol.item);
+
+    let mut servers: Vec<SocketAddr> = constants::config::default::ROOT_SERVERS
+        .iter()
+        .map(|ip| SocketAddr::new(*ip, port))
+        .collect();
+    let mut zone = Name::root();
+    let mut steps = Vec::new();
+    let mut depth = 0;
+
+    let (outcome, note) = loop {
+        let (queried, response) = ask(config, &servers, &query).await;
+        let Some((server, message, rtt)) = response else {
+            steps.push(step(depth, &zone, &queried, "unreachable", None, config)?);
+            break (
+                "unreachable".to_string(),
+                Some(format!("none of the nameservers of {} responded", zone)),
+            );
+        };
+
+        let status = ResponseStatus::of(&message);
+        steps.push(step(
+            depth,
+            &zone,
+            &queried,
+            &status.to_string(),
+            Some((server, rtt, &message)),
+            config,
+        )?);
+
+        if status != ResponseStatus::Referral {
+            break (status.to_string(), None);
+        }
+
+        let Some(next_zone) = message
+            .name_servers()
+            .iter()
+            .find(|record| matches!(record.data(), Some(RData::NS(_))))
+            .map(|record| record.name().clone())
+        else {
+            break ("lame referral".to_string(), None);
+        };
+
+        // every referral has to lead closer to the name, or the trace could go
+        // around in circles
+        if next_zone == zone || !zone.zone_of(&next_zone) || !next_zone.zone_of(query.name()) {
+            break (
+                "lame referral".to_string(),
+                Some(format!(
+                    "{} referred to {}, which is not closer to {}",
+                    server,
+                    next_zone,
+                    query.name()
+                )),
+            );
+        }
+
+        if depth == max_depth {
+            break (
+                "depth exceeded".to_string(),
+                Some(format!(
+                    "stopped after following {} referrals without reaching an answer",
+                    max_depth
+                )),
+            );
+        }
+
+        depth += 1;
+        zone = next_zone;
+        servers = referred_servers(config, resolver, &message, port, span).await;
+
+        if servers.is_empty() {
+            break (
+                "unreachable".to_string(),
+                Some(format!(
+                    "could not find the addresses of the nameservers of {}",
+                    zone
+                )),
+            );
+        }
+    };
+
+    let values = vec![
+        serde::Query(query).into_value(config),
+        Value::string(outcome, Span::unknown()),
+        note.map_or_else(
+            || Value::nothing(Span::unknown()),
+            |note| Value::string(note, Span::unknown()),
+        ),
+        Value::list(steps, Span::unknown()),
+    ];
+
+    Ok(Value::record(
+        Record::from_iter(std::iter::zip(
+            constants::columns::TRACE_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            values,
+        )),
+        Span::unknown(),
+    ))
+}
+
+/// Sends the query to each of `servers` in turn, until one of them responds.
+/// Returns the servers that were asked, along with the response.
+async fn ask(
+    config: &mut Config,
+    servers: &[SocketAddr],
+    query: &Query,
+) -> (Vec<SocketAddr>, Option<(SocketAddr, Message, Duration)>) {
+    let mut queried = Vec::new();
+
+    for server in servers {
+        queried.push(*server);
+        config.server.item = *server;
+
+        let result = tokio::time::timeout(config.timeout.item, async {
+            let (client, _bg) = DnsClient::new(config).await?;
+
+            // setting up the client does not count towards the round trip
+            let start = Instant::now();
+            let response = client
+                .send_iterative_query(query.clone(), config)
+                .await
+                .map_err(|err| {
+                    LabeledError::new("DNS error")
+                        .with_label(err.kind().to_string(), Span::unknown())
+                })?;
+
+            Ok::<_, LabeledError>((response.into_message(), start.elapsed()))
+        })
+        .await;
+
+        match result {
+            Ok(Ok((message, rtt))) => return (queried, Some((*server, message, rtt))),
+            Ok(Err(err)) => tracing::debug!(trace.server = %server, trace.error = ?err),
+            Err(_) => tracing::debug!(trace.server = %server, trace.error = "timed out"),
+        }
+    }
+
+    (queried, None)
+}
+
+/// The addresses of the nameservers a referral points to, from its glue if
+/// there is any, and looked up with `resolver` otherwise. IPv4 addresses come
+/// first, since they are reachable from more networks.
+async fn referred_servers(
+    config: &mut Config,
+    resolver: SocketAddr,
+    referral: &Message,
+    port: u16,
+    span: Span,
+) -> Vec<SocketAddr> {
+    let nameservers: Vec<&Name> = referral
+        .name_servers()
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::NS(ns)) => Some(&ns.0),
+            _ => None,
+        })
+        .collect();
+
+    let mut addresses = lookup::addresses_of(
+        referral
+            .additionals()
+            .iter()
+            .filter(|record| nameservers.contains(&record.name())),
+    );
+
+    if addresses.is_empty() {
+        config.server.item = resolver;
+
+        match DnsClient::new(config).await {
+            // one nameserver that can be reached is enough to go on with
+            Ok((client, _bg)) => {
+                for ns in nameservers {
+                    match lookup::addresses(&client, config, ns, &[], span).await {
+                        Ok(found) if !found.is_empty() => {
+                            addresses = found;
+                            break;
+                        }
+                        Ok(_) => {}
+                        Err(err) => tracing::debug!(trace.nameserver = %ns, trace.error = ?err),
+                    }
+                }
+            }
+            Err(err) => tracing::debug!(trace.resolver = %resolver, trace.error = ?err),
+        }
+    }
+
+    addresses.sort_by_key(|addr| addr.is_ipv6());
+
+    addresses
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .collect()
+}
+
+/// A step of the trace: every server asked for the zone, and from the one that
+/// responded, the answer if there is one, and the referral or negative
+/// response otherwise.
+fn step(
+    depth: usize,
+    zone: &Name,
+    queried: &[SocketAddr],
+    status: &str,
+    response: Option<(SocketAddr, Duration, &Message)>,
+    config: &Config,
+) -> Result<Value, LabeledError> {
+    let (server, rtt, records) = match response {
+        Some((server, rtt, message)) => {
+            let records = if message.answers().is_empty() {
+                message.name_servers()
+            } else {
+                message.answers()
+            };
+
+            (
+                Value::string(server.to_string(), Span::unknown()),
+                Value::duration(rtt.as_nanos() as i64, Span::unknown()),
+                records
+                    .iter()
+                    .map(|record| serde::Record(record.clone()).into_value(config))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        None => (
+            Value::nothing(Span::unknown()),
+            Value::nothing(Span::unknown()),
+            Vec::new(),
+        ),
+    };
+
+    let values = vec![
+        Value::int(depth as i64, Span::unknown()),
+        server,
+        Value::list(
+            queried
+                .iter()
+                .map(|server| Value::string(server.to_string(), Span::unknown()))
+                .collect(),
+            Span::unknown(),
+        ),
+        Value::string(zone.to_utf8(), Span::unknown()),
+        Value::string(status, Span::unknown()),
+        rtt,
+        Value::list(records, Span::unknown()),
+    ];
+
+    Ok(Value::record(
+        Record::from_iter(std::iter::zip(
+            constants::columns::TRACE_STEP_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            values,
+        )),
+        Span::unknown(),
+    ))
+}
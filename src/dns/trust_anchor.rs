@@ -0,0 +1,188 @@
+//! Trust anchors to root DNSSEC validation at, in place of the root keys built
+//! into hickory.
+
+use hickory_proto::rr::dnssec::{
+    rdata::{DNSKEY, DS},
+    Algorithm, DigestType,
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Spanned};
+
+/// The DS or DNSKEY records that the keys of one zone are trusted by.
+#[derive(Debug, Clone)]
+pub struct TrustAnchor {
+    pub zone: Name,
+    ds: Vec<DS>,
+    keys: Vec<DNSKEY>,
+}
+
+impl TrustAnchor {
+    /// The anchors of a file of DS or DNSKEY records in presentation format,
+    /// the way they are written in zone files and published for the root
+    /// zone, e.g.
+    ///
+    /// ```text
+    /// . IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+    /// ```
+    ///
+    /// Comments start with `;`, and records may span lines within
+    /// parentheses. All of the records have to be of the same zone.
+    pub fn from_file(path: &Spanned<String>) -> Result<Self, LabeledError> {
+        let contents = std::fs::read_to_string(&path.item).map_err(|err| {
+            LabeledError::new("could not read trust anchor file")
+                .with_label(format!("Error reading {}: {}", path.item, err), path.span)
+        })?;
+
+        let invalid = |msg: String| {
+            LabeledError::new("invalid trust anchor file")
+                .with_label(format!("{}: {}", path.item, msg), path.span)
+        };
+
+        let mut zone: Option<Name> = None;
+        let mut ds = Vec::new();
+        let mut keys = Vec::new();
+
+        for tokens in entries(&contents) {
+            let mut tokens = tokens.iter().map(String::as_str);
+
+            let owner = tokens.next().unwrap_or_default();
+            let mut owner = Name::from_utf8(owner)
+                .map_err(|err| invalid(format!("invalid owner name {}: {}", owner, err)))?;
+            owner.set_fqdn(true);
+
+            if let Some(zone) = zone.as_ref().filter(|zone| **zone != owner) {
+                return Err(invalid(format!(
+                    "the anchors are for both {} and {}, while they have to be for a single zone",
+                    zone, owner
+                )));
+            }
+            zone = Some(owner);
+
+            // the TTL and class may come before the type, in either order
+            let rtype = tokens
+                .find(|token| !token.eq_ignore_ascii_case("IN") && token.parse::<u32>().is_err())
+                .unwrap_or_default();
+            let fields: Vec<&str> = tokens.collect();
+
+            match rtype.to_uppercase().as_str() {
+                "DS" => ds.push(parse_ds(&fields).map_err(invalid)?),
+                "DNSKEY" => keys.push(parse_dnskey(&fields).map_err(invalid)?),
+                rtype => {
+                    return Err(invalid(format!(
+                        "{} records are not trust anchors, only DS and DNSKEY records are",
+                        rtype
+                    )))
+                }
+            }
+        }
+
+        let zone = zone.ok_or_else(|| invalid("there are no DS or DNSKEY records".into()))?;
+
+        Ok(Self { zone, ds, keys })
+    }
+
+    /// The `keys` of the zone that an anchor vouches for, either by being the
+    /// key itself, or its digest.
+    pub fn trusted<'k>(&self, keys: &[&'k DNSKEY]) -> Vec<&'k DNSKEY> {
+        keys.iter()
+            .copied()
+            .filter(|key| {
+                self.keys.iter().any(|anchor| {
+                    anchor.algorithm() == key.algorithm() && anchor.public_key() == key.public_key()
+                }) || self
+                    .ds
+                    .iter()
+                    .any(|ds| ds.covers(&self.zone, key).unwrap_or(false))
+            })
+            .collect()
+    }
+}
+
+/// The tokens of each record, without comments or parentheses.
+fn entries(contents: &str) -> Vec<Vec<String>> {
+    let mut entries = Vec::new();
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+
+    for line in contents.lines() {
+        let line = line.split(';').next().unwrap_or_default();
+
+        for token in line
+            .replace('(', " ( ")
+            .replace(')', " ) ")
+            .split_whitespace()
+        {
+            match token {
+                "(" => depth += 1,
+                ")" => depth = depth.saturating_sub(1),
+                token => tokens.push(token.to_string()),
+            }
+        }
+
+        if depth == 0 && !tokens.is_empty() {
+            entries.push(std::mem::take(&mut tokens));
+        }
+    }
+
+    entries
+}
+
+/// The key tag, algorithm, digest type, and hex digest of a DS record.
+fn parse_ds(fields: &[&str]) -> Result<DS, String> {
+    let [key_tag, algorithm, digest_type, digest @ ..] = fields else {
+        return Err("a DS record has a key tag, algorithm, digest type, and digest".into());
+    };
+
+    let key_tag = key_tag
+        .parse()
+        .map_err(|err| format!("invalid key tag {}: {}", key_tag, err))?;
+    let digest_type = digest_type
+        .parse()
+        .map_err(|err| format!("invalid digest type {}: {}", digest_type, err))
+        .and_then(|digest_type| DigestType::from_u8(digest_type).map_err(|err| err.to_string()))?;
+    let digest = data_encoding::HEXUPPER_PERMISSIVE
+        .decode(digest.concat().as_bytes())
+        .map_err(|err| format!("invalid digest: {}", err))?;
+
+    Ok(DS::new(
+        key_tag,
+        parse_algorithm(algorithm)?,
+        digest_type,
+        digest,
+    ))
+}
+
+/// The flags, protocol, algorithm, and base64 public key of a DNSKEY record.
+pub fn parse_dnskey(fields: &[&str]) -> Result<DNSKEY, String> {
+    let [flags, protocol, algorithm, public_key @ ..] = fields else {
+        return Err("a DNSKEY record has flags, a protocol, an algorithm, and a public key".into());
+    };
+
+    let flags: u16 = flags
+        .parse()
+        .map_err(|err| format!("invalid flags {}: {}", flags, err))?;
+
+    // the protocol is always 3 (RFC 4034 section 2.1.2)
+    if *protocol != "3" {
+        return Err(format!("invalid protocol {}, which has to be 3", protocol));
+    }
+
+    let public_key = data_encoding::BASE64
+        .decode(public_key.concat().as_bytes())
+        .map_err(|err| format!("invalid public key: {}", err))?;
+
+    Ok(DNSKEY::new(
+        flags & 0x0100 != 0,
+        flags & 0x0001 != 0,
+        flags & 0x0080 != 0,
+        parse_algorithm(algorithm)?,
+        public_key,
+    ))
+}
+
+fn parse_algorithm(algorithm: &str) -> Result<Algorithm, String> {
+    algorithm
+        .parse()
+        .map(Algorithm::from_u8)
+        .map_err(|err| format!("invalid algorithm {}: {}", algorithm, err))
+}
@@ -0,0 +1,246 @@
+//! Transaction signatures (RFC 8945).
+
+use std::fmt;
+
+use hickory_proto::{
+    error::ProtoResult,
+    op::{Message, MessageFinalizer, MessageVerifier},
+    rr::{
+        dnssec::{rdata::tsig::TsigAlgorithm, tsig::TSigner},
+        Record, RecordType,
+    },
+    xfer::DnsResponse,
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Spanned};
+
+use super::constants;
+#[cfg(feature = "gss-tsig")]
+use super::gss_tsig::GssKey;
+
+/// A key shared with a nameserver to sign the messages exchanged with it.
+#[derive(Clone, PartialEq)]
+pub struct TsigKey {
+    pub name: Name,
+    pub algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+}
+
+// the secret is left out, so that it does not end up in logs
+impl fmt::Debug for TsigKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TsigKey")
+            .field("name", &self.name)
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TsigKey {
+    /// A key given as `name:algorithm:secret`, with the secret in base64, the
+    /// way `dig -y` takes it.
+    pub fn parse(spec: &Spanned<String>) -> Result<Self, LabeledError> {
+        let parts: Vec<&str> = spec.item.splitn(3, ':').collect();
+
+        match parts.as_slice() {
+            [name, algorithm, secret] => Self::new(name, algorithm, secret, spec),
+            _ => Err(LabeledError::new("invalid TSIG key").with_label(
+                "a TSIG key is given as name:algorithm:secret, e.g. mykey:hmac-sha256:c2VjcmV0",
+                spec.span,
+            )),
+        }
+    }
+
+    /// The first key of a key file in the format written by `tsig-keygen` and
+    /// read by `nsupdate -k`:
+    ///
+    /// ```text
+    /// key "mykey" {
+    ///     algorithm hmac-sha256;
+    ///     secret "c2VjcmV0";
+    /// };
+    /// ```
+    pub fn from_file(path: &Spanned<String>) -> Result<Self, LabeledError> {
+        let contents = std::fs::read_to_string(&path.item).map_err(|err| {
+            LabeledError::new("could not read TSIG key file")
+                .with_label(format!("Error reading {}: {}", path.item, err), path.span)
+        })?;
+
+        let invalid = |msg: &str| {
+            LabeledError::new("invalid TSIG key file")
+                .with_label(format!("{}: {}", path.item, msg), path.span)
+        };
+
+        // whole-line comments, in either of the styles of named.conf. Other
+        // comments are not recognized, since `//` can be part of a secret
+        let contents = contents
+            .lines()
+            .filter(|line| {
+                let line = line.trim_start();
+                !line.starts_with('#') && !line.starts_with("//")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let contents = contents.replace(['{', '}', ';'], " ");
+        let mut tokens = contents
+            .split_whitespace()
+            .map(|token| token.trim_matches('"'));
+
+        if !matches!(tokens.next(), Some(token) if token.eq_ignore_ascii_case("key")) {
+            return Err(invalid("expected a key statement"));
+        }
+
+        let name = tokens
+            .next()
+            .ok_or_else(|| invalid("the key has no name"))?;
+        let (mut algorithm, mut secret) = (None, None);
+
+        while let Some(token) = tokens.next() {
+            match token.to_lowercase().as_str() {
+                "algorithm" => algorithm = tokens.next(),
+                "secret" => secret = tokens.next(),
+                // the next key statement
+                "key" => break,
+                _ => {}
+            }
+        }
+
+        Self::new(
+            name,
+            algorithm.ok_or_else(|| invalid("the key has no algorithm"))?,
+            secret.ok_or_else(|| invalid("the key has no secret"))?,
+            path,
+        )
+    }
+
+    fn new(
+        name: &str,
+        algorithm: &str,
+        secret: &str,
+        spec: &Spanned<String>,
+    ) -> Result<Self, LabeledError> {
+        let name = Name::from_utf8(name).map_err(|err| {
+            LabeledError::new("invalid TSIG key")
+                .with_label(format!("Error parsing key name: {}", err.kind()), spec.span)
+        })?;
+
+        let parsed = Name::from_ascii(algorithm.trim_end_matches('.').to_lowercase())
+            .map(TsigAlgorithm::from_name)
+            .ok();
+
+        // GSS-TSIG (RFC 3645) has no shared secret: the key is negotiated with
+        // TKEY from Kerberos credentials
+        if parsed == Some(TsigAlgorithm::Gss) {
+            return Err(LabeledError::new("invalid TSIG key").with_label(
+                "GSS-TSIG keys are not given, but negotiated with the nameserver with --gss-tsig",
+                spec.span,
+            ));
+        }
+
+        let algorithm = parsed
+            .filter(TsigAlgorithm::supported)
+            .ok_or_else(|| {
+                LabeledError::new("invalid TSIG key").with_label(
+                    format!(
+                        "unsupported algorithm {}. Must be one of: hmac-sha256, hmac-sha384, hmac-sha512",
+                        algorithm
+                    ),
+                    spec.span,
+                )
+            })?;
+
+        let secret = data_encoding::BASE64
+            .decode(secret.as_bytes())
+            .map_err(|err| {
+                LabeledError::new("invalid TSIG key")
+                    .with_label(format!("secret is not valid base64: {}", err), spec.span)
+            })?;
+
+        Ok(Self {
+            name,
+            algorithm,
+            secret,
+        })
+    }
+
+    pub fn signer(&self) -> TsigSigner {
+        TsigSigner(Signer::Hmac(
+            TSigner::new(
+                self.secret.clone(),
+                self.algorithm.clone(),
+                self.name.clone(),
+                constants::config::default::TSIG_FUDGE,
+            )
+            // the algorithm is checked to be supported when parsing the key
+            .expect("supported TSIG algorithm"),
+        ))
+    }
+}
+
+/// Signs requests with a TSIG key, and checks the MAC of their responses.
+/// Unlike with [`TSigner`] on its own, a response that does not verify is still
+/// handed back, with its TSIG record moved to the additional section, so that
+/// a server refusing the key can be told apart from one that cannot be
+/// reached.
+#[derive(Clone)]
+pub struct TsigSigner(Signer);
+
+#[derive(Clone)]
+enum Signer {
+    Hmac(TSigner),
+    #[cfg(feature = "gss-tsig")]
+    Gss(GssKey),
+}
+
+#[cfg(feature = "gss-tsig")]
+impl From<GssKey> for TsigSigner {
+    fn from(key: GssKey) -> Self {
+        Self(Signer::Gss(key))
+    }
+}
+
+impl MessageFinalizer for TsigSigner {
+    fn finalize_message(
+        &self,
+        message: &Message,
+        current_time: u32,
+    ) -> ProtoResult<(Vec<Record>, Option<MessageVerifier>)> {
+        let (records, verifier) = match &self.0 {
+            Signer::Hmac(signer) => signer.finalize_message(message, current_time)?,
+            #[cfg(feature = "gss-tsig")]
+            Signer::Gss(key) => key.finalize_message(message, current_time)?,
+        };
+
+        let verifier = verifier.map(|mut verify| -> MessageVerifier {
+            Box::new(move |response: &[u8]| {
+                verify(response).or_else(|err| {
+                    tracing::debug!(tsig.error = %err.kind(), "response MAC did not verify");
+
+                    let mut message = Message::from_vec(response)?;
+                    let signature = message.take_signature();
+                    message.add_additionals(signature);
+
+                    Ok(DnsResponse::new(message, response.to_vec()))
+                })
+            })
+        });
+
+        Ok((records, verifier))
+    }
+
+    // hickory only signs updates, notifies, and zone transfers on its own, but
+    // a key is given to sign every request with
+    fn should_finalize_message(&self, _message: &Message) -> bool {
+        true
+    }
+}
+
+/// Whether the MAC of a response to a signed request verified, which only
+/// leaves a TSIG record in its signature.
+pub fn verified(message: &Message) -> bool {
+    message
+        .signature()
+        .iter()
+        .any(|record| record.record_type() == RecordType::TSIG)
+}
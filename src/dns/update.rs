@@ -0,0 +1,199 @@
+//! Dynamic updates (RFC 2136).
+
+use hickory_proto::{
+    op::{update_message, Message, ResponseCode},
+    rr::{DNSClass, RData, Record, RecordSet, RecordType},
+    serialize::txt::RDataParser,
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Span, Spanned, Value};
+
+use super::{client::DnsClient, config::Config, lookup, serde::RType};
+
+/// A change to the records of a zone.
+#[derive(Debug)]
+pub enum Change {
+    /// Adds a record to its RRset, which is created if needed.
+    Add(Record),
+    /// Deletes the record with the same name, type, and RDATA.
+    Delete(Record),
+    /// Deletes every record of a type at a name.
+    DeleteRrset(Name, RecordType),
+    /// Deletes every record at a name.
+    DeleteName(Name),
+}
+
+impl Change {
+    pub fn name(&self) -> &Name {
+        match self {
+            Change::Add(record) | Change::Delete(record) => record.name(),
+            Change::DeleteRrset(name, _) | Change::DeleteName(name) => name,
+        }
+    }
+
+    /// The UPDATE message that makes the change to `zone`.
+    pub fn into_message(self, zone: Name, class: DNSClass) -> Message {
+        match self {
+            Change::Add(record) => {
+                update_message::append(RecordSet::from(record), zone, false, true)
+            }
+            Change::Delete(record) => {
+                update_message::delete_by_rdata(RecordSet::from(record), zone, true)
+            }
+            Change::DeleteRrset(name, rtype) => {
+                let mut record = Record::with(name, rtype, 0);
+                record.set_dns_class(class);
+                update_message::delete_rrset(record, zone, true)
+            }
+            Change::DeleteName(name) => update_message::delete_all(name, zone, class, true),
+        }
+    }
+}
+
+/// A prerequisite (RFC 2136 section 2.4) given as `NAME`, `NAME TYPE`, or
+/// `NAME TYPE RDATA`, which requires that the name is in use, that it has an
+/// RRset of the type, or that the RRset has a record with the data. Records
+/// with the data of the same RRset are compared with the whole RRset. With
+/// `exists` unset, the name must not be in use, or the RRset must not exist.
+pub fn prerequisite(
+    spec: &Spanned<String>,
+    exists: bool,
+    class: DNSClass,
+) -> Result<Record, LabeledError> {
+    let invalid = |msg: &str| LabeledError::new("invalid prerequisite").with_label(msg, spec.span);
+
+    let mut parts = spec.item.trim().splitn(3, char::is_whitespace);
+    let name = match parts.next() {
+        Some(name) if !name.is_empty() => Name::from_utf8(name).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), spec.span)
+        })?,
+        _ => return Err(invalid("a prerequisite starts with a name")),
+    };
+    let rtype = parts
+        .next()
+        .map(|rtype| RType::try_from(&Value::string(rtype, spec.span)))
+        .transpose()?
+        .map(|RType(rtype)| rtype);
+    let rdata = parts.next().map(str::trim);
+
+    let record = match (rtype, rdata) {
+        (None, _) => {
+            let mut record = Record::with(name, RecordType::ANY, 0);
+            record.set_dns_class(if exists {
+                DNSClass::ANY
+            } else {
+                DNSClass::NONE
+            });
+            record
+        }
+        (Some(rtype), None) => {
+            let mut record = Record::with(name, rtype, 0);
+            record.set_dns_class(if exists {
+                DNSClass::ANY
+            } else {
+                DNSClass::NONE
+            });
+            record
+        }
+        (Some(_), Some(_)) if !exists => {
+            return Err(invalid(
+                "a record can not be required not to exist, only its name or RRset",
+            ))
+        }
+        (Some(rtype), Some(rdata)) => {
+            let rdata = parse_rdata(
+                rtype,
+                &Spanned {
+                    item: rdata.to_string(),
+                    span: spec.span,
+                },
+            )?;
+            let mut record = Record::from_rdata(name, 0, rdata);
+            record.set_dns_class(class);
+            record
+        }
+    };
+
+    Ok(record)
+}
+
+/// RDATA in presentation format, e.g. `10 mail.example.com.` for MX.
+pub fn parse_rdata(rtype: RecordType, rdata: &Spanned<String>) -> Result<RData, LabeledError> {
+    RData::try_from_str(rtype, &rdata.item).map_err(|err| {
+        LabeledError::new("invalid record data").with_label(
+            format!("Error parsing {} record data: {}", rtype, err.kind()),
+            rdata.span,
+        )
+    })
+}
+
+/// The zone `name` belongs to, from the SOA record the server returns for it:
+/// in the answer if `name` is the apex of the zone, and in the authority
+/// section otherwise.
+pub async fn find_zone(
+    client: &DnsClient,
+    config: &Config,
+    name: &Name,
+    span: Span,
+) -> Result<Name, LabeledError> {
+    let response = lookup::lookup(client, config, name.clone(), RecordType::SOA, span).await?;
+
+    response
+        .answers()
+        .iter()
+        .chain(response.name_servers())
+        .find(|record| matches!(record.data(), Some(RData::SOA(_))))
+        .map(|record| record.name().clone())
+        .ok_or_else(|| {
+            LabeledError::new("unknown zone").with_label(
+                format!(
+                    "{} did not say which zone {} belongs to, pass it with --zone",
+                    config.server.item, name
+                ),
+                span,
+            )
+        })
+}
+
+/// Sends an UPDATE message, and fails unless the server made the change.
+pub async fn send(
+    client: &DnsClient,
+    config: &Config,
+    message: Message,
+    span: Span,
+) -> Result<Message, LabeledError> {
+    let zone = message
+        .queries()
+        .first()
+        .map_or_else(String::new, |zone| zone.name().to_string());
+
+    let response = tokio::time::timeout(config.timeout.item, client.send_message(message))
+        .await
+        .map_err(|_| {
+            LabeledError::new("timed out").with_label(
+                format!("update of {} at {} timed out", zone, config.server.item),
+                config.server.span,
+            )
+        })?
+        .map_err(|err| {
+            LabeledError::new("update failed").with_label(
+                format!(
+                    "update of {} at {} failed: {}",
+                    zone,
+                    config.server.item,
+                    err.kind()
+                ),
+                span,
+            )
+        })?
+        .into_message();
+
+    match response.response_code() {
+        ResponseCode::NoError => Ok(response),
+        code => Err(LabeledError::new("update refused").with_label(
+            format!("{} did not update {}: {}", config.server.item, zone, code),
+            span,
+        )),
+    }
+}
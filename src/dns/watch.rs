@@ -0,0 +1,157 @@
+use std::{sync::Arc, time::Duration};
+
+use nu_protocol::{LabeledError, Record, Span, Value};
+use rand::Rng;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    client::DnsClient,
+    commands::query::{AnswerTransform, DnsQuery},
+    config::Config,
+    constants,
+};
+
+/// The answer RRsets of one question, as seen by the last poll.
+type Snapshot = Vec<(Value, Vec<Value>)>;
+
+/// Re-runs the queries every `interval` and sends a row for every record that
+/// was added to or removed from an answer since the previous poll, until
+/// cancelled. The first poll reports every record as added.
+///
+/// Each poll is delayed by a random amount up to the configured jitter, so
+/// that many watches started at once do not all hit the server together.
+pub async fn watch_diff(
+    config: Arc<Config>,
+    input: Value,
+    client: DnsClient,
+    interval: Duration,
+    transform: AnswerTransform,
+    resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
+    cancel: CancellationToken,
+) -> Result<(), LabeledError> {
+    tracing::trace!(task.watch.phase = "start");
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    let mut previous: Snapshot = Vec::new();
+
+    while cancel.run_until_cancelled(ticker.tick()).await.is_some() {
+        if let Some(jitter) = &config.jitter {
+            let delay = rand::thread_rng().gen_range(Duration::ZERO..=jitter.item);
+            tracing::trace!(task.watch.phase = "jitter", ?delay);
+
+            if cancel
+                .run_until_cancelled(tokio::time::sleep(delay))
+                .await
+                .is_none()
+            {
+                break;
+            }
+        }
+
+        let Some(responses) = cancel
+            .run_until_cancelled(DnsQuery::query(
+                config.clone(),
+                input.clone(),
+                client.clone(),
+            ))
+            .await
+        else {
+            break;
+        };
+
+        let time = Value::date(chrono::Utc::now().fixed_offset(), Span::unknown());
+        let mut rows = Vec::new();
+
+        for resp in responses {
+            let message = match transform.apply(resp) {
+                Ok(message) => message,
+                // a failed poll says nothing about the records, so the last
+                // known state is kept to diff the next successful poll against
+                Err(err) => {
+                    rows.push(Err(err));
+                    continue;
+                }
+            };
+
+            let Some((question, answer)) = answer_rrset(&message) else {
+                continue;
+            };
+
+            let known = previous.iter_mut().find(|(q, _)| *q == question);
+            let old = known.as_ref().map_or(&[][..], |(_, old)| old.as_slice());
+
+            for record in old.iter().filter(|old| !contains(&answer, old)) {
+                rows.push(Ok(diff_row(&time, "removed", &question, record)));
+            }
+
+            for record in answer.iter().filter(|new| !contains(old, new)) {
+                rows.push(Ok(diff_row(&time, "added", &question, record)));
+            }
+
+            match known {
+                Some((_, old)) => *old = answer,
+                None => previous.push((question, answer)),
+            }
+        }
+
+        for row in rows {
+            if resp_tx.send(row).await.is_err() {
+                // nobody is listening anymore
+                tracing::trace!(task.watch.phase = "exit", task.watch.reason = "closed");
+                return Ok(());
+            }
+        }
+    }
+
+    tracing::trace!(task.watch.phase = "exit", task.watch.reason = "cancelled");
+
+    Ok(())
+}
+
+fn answer_rrset(message: &Value) -> Option<(Value, Vec<Value>)> {
+    let question = message.get_data_by_key("question")?;
+    let answer = message.get_data_by_key("answer")?.into_list().ok()?;
+
+    Some((question, answer))
+}
+
+/// Records are compared without their TTL, since caching resolvers count it
+/// down between polls.
+fn contains(records: &[Value], record: &Value) -> bool {
+    let without_ttl = |record: &Value| {
+        record
+            .as_record()
+            .ok()
+            .map(|rec| {
+                rec.iter()
+                    .filter(|(col, _)| col.as_str() != "ttl")
+                    .map(|(col, val)| (col.clone(), val.clone()))
+                    .collect::<Record>()
+            })
+            .map(|rec| Value::record(rec, Span::unknown()))
+    };
+
+    let record = without_ttl(record);
+    records.iter().any(|other| without_ttl(other) == record)
+}
+
+fn diff_row(time: &Value, change: &str, question: &Value, record: &Value) -> Value {
+    let mut row = Record::new();
+    row.push(constants::columns::TIME, time.clone());
+    row.push(
+        constants::columns::CHANGE,
+        Value::string(change, Span::unknown()),
+    );
+    row.push(constants::columns::QUESTION, question.clone());
+
+    if let Ok(record) = record.as_record() {
+        for (col, val) in record.iter() {
+            row.push(col.clone(), val.clone());
+        }
+    }
+
+    Value::record(row, Span::unknown())
+}
@@ -0,0 +1,178 @@
+//! Where the fields of the records of a message are in its wire format.
+
+use std::{borrow::Cow, ops::Range};
+
+use hickory_proto::{
+    error::ProtoError,
+    op::{Header, OpCode, Query},
+    rr::{Record, RecordType},
+    serialize::binary::{BinDecodable, BinDecoder},
+};
+use hickory_resolver::Name;
+use nu_protocol::{Span, Value};
+
+use super::constants;
+
+/// Byte ranges of a record and each of its fields.
+#[derive(Debug, Clone)]
+pub struct RecordSpans {
+    pub record: Range<usize>,
+    pub name: Range<usize>,
+    pub rtype: Range<usize>,
+    pub class: Range<usize>,
+    pub ttl: Range<usize>,
+    pub rdlength: Range<usize>,
+    pub rdata: Range<usize>,
+}
+
+impl RecordSpans {
+    pub fn into_value(self) -> Value {
+        let span = |range: Range<usize>| {
+            Value::record(
+                nu_protocol::Record::from_iter(std::iter::zip(
+                    constants::columns::WIRE_SPAN_COLS
+                        .iter()
+                        .map(|col| (*col).into()),
+                    vec![
+                        Value::int(range.start as i64, Span::unknown()),
+                        Value::int(range.len() as i64, Span::unknown()),
+                    ],
+                )),
+                Span::unknown(),
+            )
+        };
+
+        Value::record(
+            nu_protocol::Record::from_iter(std::iter::zip(
+                constants::columns::RECORD_SPAN_COLS
+                    .iter()
+                    .map(|col| (*col).into()),
+                vec![
+                    span(self.record),
+                    span(self.name),
+                    span(self.rtype),
+                    span(self.class),
+                    span(self.ttl),
+                    span(self.rdlength),
+                    span(self.rdata),
+                ],
+            )),
+            Span::unknown(),
+        )
+    }
+}
+
+/// The records of each section of a message as received, in the order they
+/// were sent. The additional section still holds the OPT record, and any
+/// signatures, which hickory moves out of it.
+#[derive(Debug, Default)]
+pub struct Sections {
+    pub answers: Vec<(Record, RecordSpans)>,
+    pub authorities: Vec<(Record, RecordSpans)>,
+    pub additionals: Vec<(Record, RecordSpans)>,
+}
+
+pub fn record_spans(buffer: &[u8]) -> Result<Sections, ProtoError> {
+    let buffer = readable(buffer);
+    let mut decoder = BinDecoder::new(&buffer);
+    let header = Header::read(&mut decoder)?;
+
+    for _ in 0..header.query_count() {
+        Query::read(&mut decoder)?;
+    }
+
+    let mut read_section = |count: u16| -> Result<Vec<(Record, RecordSpans)>, ProtoError> {
+        (0..count)
+            .map(|_| {
+                let start = decoder.index();
+
+                // the name is read a second time to find out where it ends, which
+                // depends on whether it is compressed
+                let mut record_decoder = decoder.clone(start as u16);
+                let record = Record::read(&mut record_decoder)?;
+                Name::read(&mut decoder)?;
+
+                let name_end = decoder.index();
+                let rdata_start = name_end + 10;
+                let end = record_decoder.index();
+                decoder = record_decoder;
+
+                let spans = RecordSpans {
+                    record: start..end,
+                    name: start..name_end,
+                    rtype: name_end..name_end + 2,
+                    class: name_end + 2..name_end + 4,
+                    ttl: name_end + 4..name_end + 8,
+                    rdlength: name_end + 8..rdata_start,
+                    rdata: rdata_start..end,
+                };
+
+                Ok((record, spans))
+            })
+            .collect()
+    };
+
+    Ok(Sections {
+        answers: read_section(header.answer_count())?,
+        authorities: read_section(header.name_server_count())?,
+        additionals: read_section(header.additional_count())?,
+    })
+}
+
+/// The opcode of a message, which may be one that hickory does not know.
+pub fn op_code(buffer: &[u8]) -> Option<u8> {
+    buffer.get(2).map(|flags| (flags >> 3) & 0x0f)
+}
+
+/// The message with its opcode set to QUERY if it is one that hickory does not
+/// know, as hickory refuses to read such a message at all. Nothing else in the
+/// message depends on the opcode.
+pub fn readable(buffer: &[u8]) -> Cow<'_, [u8]> {
+    match op_code(buffer) {
+        Some(op_code) if OpCode::from_u8(op_code).is_err() => {
+            let mut buffer = buffer.to_vec();
+            buffer[2] &= !(0x0f << 3);
+            Cow::Owned(buffer)
+        }
+        _ => Cow::Borrowed(buffer),
+    }
+}
+
+/// The message with its opcode set to one that hickory does not know, which it
+/// can not write itself. The opposite of [`readable`].
+pub fn with_op_code(mut buffer: Vec<u8>, op_code: u8) -> Vec<u8> {
+    if let Some(flags) = buffer.get_mut(2) {
+        *flags = (*flags & !(0x0f << 3)) | ((op_code & 0x0f) << 3);
+    }
+
+    buffer
+}
+
+/// The code and data of each option of the OPT record of a message, in the
+/// order they were sent. hickory keeps only the last option of each code, while
+/// some, like extended errors (RFC 8914 section 2), may be sent more than once.
+pub fn edns_options(buffer: &[u8]) -> Result<Vec<(u16, Vec<u8>)>, ProtoError> {
+    let sections = record_spans(buffer)?;
+    let Some((_, spans)) = sections
+        .additionals
+        .iter()
+        .find(|(record, _)| record.record_type() == RecordType::OPT)
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut options = Vec::new();
+    let mut rdata = &buffer[spans.rdata.clone()];
+
+    while let [code_high, code_low, len_high, len_low, rest @ ..] = rdata {
+        let len = u16::from_be_bytes([*len_high, *len_low]) as usize;
+        let data = rest
+            .get(..len)
+            .ok_or_else(|| ProtoError::from("EDNS option is longer than its record"))?;
+
+        options.push((u16::from_be_bytes([*code_high, *code_low]), data.to_vec()));
+        rdata = &rest[len..];
+    }
+
+    Ok(options)
+}
@@ -0,0 +1,412 @@
+//! Zone transfers (RFC 5936, RFC 1995).
+
+use futures_util::{future, Future, StreamExt};
+use hickory_client::client::ClientHandle;
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{rdata::SOA, RData, Record, RecordType},
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Span, Value};
+
+use super::{client::DnsClient, config::Config, constants, serde, tsig};
+
+/// Fetches the records of a zone with a zone transfer, in the order they were
+/// sent. With `last_serial`, an incremental transfer is requested, which the
+/// server may answer with a full one, and which is retried as a full one if
+/// the server does not know what it is.
+pub async fn transfer(
+    client: &DnsClient,
+    config: &Config,
+    zone: Name,
+    last_serial: Option<u32>,
+    span: Span,
+) -> Result<Vec<Record>, LabeledError> {
+    let mut records = Vec::new();
+
+    transfer_each(client, config, zone, last_serial, span, |answers| {
+        records.extend(answers);
+        future::ready(true)
+    })
+    .await?;
+
+    Ok(records)
+}
+
+/// Like [`transfer`], but hands the records of each message to `sink` as soon
+/// as it arrives, instead of collecting the whole zone. The transfer stops
+/// early once `sink` resolves to false.
+pub async fn transfer_each<F, Fut>(
+    client: &DnsClient,
+    config: &Config,
+    zone: Name,
+    mut last_serial: Option<u32>,
+    span: Span,
+    mut sink: F,
+) -> Result<(), LabeledError>
+where
+    F: FnMut(Vec<Record>) -> Fut,
+    Fut: Future<Output = bool>,
+{
+    'transfer: loop {
+        // only the serial of the SOA matters to the server
+        let last_soa =
+            last_serial.map(|serial| SOA::new(zone.clone(), Name::root(), serial, 0, 0, 0, 0));
+
+        let mut responses = client.clone().zone_transfer(zone.clone(), last_soa);
+        let mut first = true;
+
+        loop {
+            let response = tokio::time::timeout(config.timeout.item, responses.next())
+                .await
+                .map_err(|_| {
+                    LabeledError::new("timed out").with_label(
+                        format!(
+                            "transfer of {} from {} timed out after {:?}",
+                            zone, config.server.item, config.timeout.item
+                        ),
+                        config.server.span,
+                    )
+                })?;
+
+            let Some(response) = response else {
+                break;
+            };
+
+            let response = response.map_err(|err| {
+                LabeledError::new("transfer failed").with_label(
+                    format!(
+                        "transfer of {} from {} over {} failed: {}",
+                        zone,
+                        config.server.item,
+                        config.protocol.item,
+                        err.kind()
+                    ),
+                    span,
+                )
+            })?;
+
+            if response.response_code() != ResponseCode::NoError {
+                // some servers that do not support incremental transfers
+                // reject them, instead of sending the whole zone
+                if first
+                    && last_serial.is_some()
+                    && matches!(
+                        response.response_code(),
+                        ResponseCode::NotImp | ResponseCode::FormErr
+                    )
+                {
+                    tracing::debug!(
+                        transfer.response_code = %response.response_code(),
+                        "retrying as a full transfer"
+                    );
+                    last_serial = None;
+                    continue 'transfer;
+                }
+
+                return Err(LabeledError::new("transfer refused").with_label(
+                    format!(
+                        "{} refused the transfer of {}: {}",
+                        config.server.item,
+                        zone,
+                        response.response_code()
+                    ),
+                    span,
+                ));
+            }
+
+            // the records are returned on their own, so there is nowhere to
+            // report a MAC that did not verify but by failing
+            if let Some(key) = &config.tsig {
+                if !tsig::verified(&response) {
+                    return Err(LabeledError::new("transfer not authenticated").with_label(
+                        format!(
+                            "the transfer of {} from {} was not signed with the key {}",
+                            zone, config.server.item, key.item.name
+                        ),
+                        key.span,
+                    ));
+                }
+            }
+
+            let answers = response.into_message().take_answers();
+
+            // a transfer starts with the SOA of the zone (RFC 5936 section 2.2),
+            // and the transfer stream would wait in vain for one that does not
+            if first {
+                match answers.first() {
+                    Some(soa) if soa.record_type() == RecordType::SOA && soa.name() == &zone => {}
+                    Some(_) => {
+                        return Err(LabeledError::new("invalid transfer").with_label(
+                            format!(
+                                "{} did not start the transfer of {} with its SOA record",
+                                config.server.item, zone
+                            ),
+                            span,
+                        ))
+                    }
+                    None => {
+                        return Err(LabeledError::new("transfer refused").with_label(
+                            format!(
+                            "{} sent no records for {}, so it is probably not authoritative for it",
+                            config.server.item, zone
+                        ),
+                            span,
+                        ))
+                    }
+                }
+            }
+
+            // a server whose zone is no newer than the last serial answers with
+            // just its SOA (RFC 1995 section 2), which the transfer stream would
+            // wait in vain to be followed by more
+            let up_to_date = first && last_serial.is_some() && answers.len() == 1;
+            first = false;
+
+            if !sink(answers).await || up_to_date {
+                break;
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// The versions a zone went through since a serial.
+struct History {
+    /// The serial of the current version.
+    serial: u32,
+    /// Whether the server sent the differences, rather than the whole zone.
+    incremental: bool,
+    steps: Vec<Step>,
+}
+
+/// The differences between two consecutive versions of a zone.
+struct Step {
+    from: u32,
+    to: u32,
+    removed: Vec<Record>,
+    added: Vec<Record>,
+}
+
+impl History {
+    /// Fetches the history of a zone since `serial` with an incremental
+    /// transfer. A server that does not keep it sends the whole zone instead,
+    /// which is a single step that adds every record, since there is nothing
+    /// to compare them to.
+    async fn since(
+        client: &DnsClient,
+        config: &Config,
+        zone: &Name,
+        serial: u32,
+        span: Span,
+    ) -> Result<Self, LabeledError> {
+        let records = transfer(client, config, zone.clone(), Some(serial), span).await?;
+
+        let soa_serial = |record: &Record| record.data().and_then(RData::as_soa).map(SOA::serial);
+        let new_serial = records.first().and_then(soa_serial).ok_or_else(|| {
+            LabeledError::new("invalid transfer").with_label(
+                format!("the transfer of {} did not start with an SOA record", zone),
+                span,
+            )
+        })?;
+
+        // a single SOA means there is nothing newer, and an incremental
+        // transfer continues with the SOA of the oldest version, unlike a full
+        // one
+        let incremental = records.len() == 1
+            || records
+                .get(1)
+                .and_then(soa_serial)
+                .is_some_and(|serial| serial != new_serial);
+
+        let mut steps: Vec<Step> = Vec::new();
+
+        if records.len() > 1 {
+            // leave out the SOA that ends the transfer
+            let body = records[1..records.len() - 1].iter().cloned();
+
+            if incremental {
+                // each difference sequence is the SOA of the old version, the
+                // removed records, the SOA of the new version, and the added
+                // records (RFC 1995 section 4)
+                let mut removing = false;
+
+                for record in body {
+                    if let Some(serial) = soa_serial(&record) {
+                        if removing {
+                            // the removals lead to the version of the SOA
+                            // ending them
+                            if let Some(step) = steps.last_mut() {
+                                step.to = serial;
+                            }
+                        } else {
+                            steps.push(Step {
+                                from: serial,
+                                to: serial,
+                                removed: Vec::new(),
+                                added: Vec::new(),
+                            });
+                        }
+
+                        removing = !removing;
+                        continue;
+                    }
+
+                    if let Some(step) = steps.last_mut() {
+                        if removing {
+                            step.removed.push(record);
+                        } else {
+                            step.added.push(record);
+                        }
+                    }
+                }
+            } else {
+                steps.push(Step {
+                    from: serial,
+                    to: new_serial,
+                    removed: Vec::new(),
+                    added: body.collect(),
+                });
+            }
+        }
+
+        Ok(Self {
+            serial: new_serial,
+            incremental,
+            steps,
+        })
+    }
+}
+
+/// The changes to a zone since `serial`, from an incremental transfer, as one
+/// row per changed record. A server that does not support them sends the
+/// whole zone instead, whose records are all reported as added.
+pub async fn changes_since(
+    client: &DnsClient,
+    config: &Config,
+    zone: Name,
+    serial: u32,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let history = History::since(client, config, &zone, serial, span).await?;
+
+    let mut changes = Vec::new();
+
+    for step in &history.steps {
+        for record in &step.removed {
+            changes.push(change_row(record, "removed", step.to, config)?);
+        }
+
+        for record in &step.added {
+            changes.push(change_row(record, "added", step.to, config)?);
+        }
+    }
+
+    let values = vec![
+        Value::string(zone.to_utf8(), Span::unknown()),
+        Value::int(serial as i64, Span::unknown()),
+        Value::int(history.serial as i64, Span::unknown()),
+        Value::bool(history.incremental, Span::unknown()),
+        Value::list(changes, Span::unknown()),
+    ];
+
+    Ok(Value::record(
+        nu_protocol::Record::from_iter(std::iter::zip(
+            constants::columns::ZONE_CHANGES_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            values,
+        )),
+        Span::unknown(),
+    ))
+}
+
+/// The changes to a zone since `serial`, from an incremental transfer, as the
+/// records each version removed and added.
+pub async fn steps_since(
+    client: &DnsClient,
+    config: &Config,
+    zone: Name,
+    serial: u32,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let history = History::since(client, config, &zone, serial, span).await?;
+
+    let records = |records: Vec<Record>| -> Result<Value, LabeledError> {
+        Ok(Value::list(
+            records
+                .into_iter()
+                .map(|record| serde::Record(record).into_value(config))
+                .collect::<Result<_, _>>()?,
+            Span::unknown(),
+        ))
+    };
+
+    let steps = history
+        .steps
+        .into_iter()
+        .map(|step| {
+            let values = vec![
+                Value::int(step.from as i64, Span::unknown()),
+                Value::int(step.to as i64, Span::unknown()),
+                records(step.removed)?,
+                records(step.added)?,
+            ];
+
+            Ok(Value::record(
+                nu_protocol::Record::from_iter(std::iter::zip(
+                    constants::columns::ZONE_STEP_COLS
+                        .iter()
+                        .map(|col| (*col).into()),
+                    values,
+                )),
+                Span::unknown(),
+            ))
+        })
+        .collect::<Result<_, LabeledError>>()?;
+
+    let values = vec![
+        Value::string(zone.to_utf8(), Span::unknown()),
+        Value::int(serial as i64, Span::unknown()),
+        Value::int(history.serial as i64, Span::unknown()),
+        Value::bool(history.incremental, Span::unknown()),
+        Value::list(steps, Span::unknown()),
+    ];
+
+    Ok(Value::record(
+        nu_protocol::Record::from_iter(std::iter::zip(
+            constants::columns::ZONE_STEPS_COLS
+                .iter()
+                .map(|col| (*col).into()),
+            values,
+        )),
+        Span::unknown(),
+    ))
+}
+
+/// A changed record, with the serial of the version the change leads to.
+fn change_row(
+    record: &Record,
+    change: &str,
+    serial: u32,
+    config: &Config,
+) -> Result<Value, LabeledError> {
+    let mut row = nu_protocol::Record::new();
+    row.push(
+        constants::columns::CHANGE,
+        Value::string(change, Span::unknown()),
+    );
+    row.push(
+        constants::columns::SERIAL,
+        Value::int(serial as i64, Span::unknown()),
+    );
+
+    if let Value::Record { val, .. } = serde::Record(record.clone()).into_value(config)? {
+        for (col, val) in val.into_owned() {
+            row.push(col, val);
+        }
+    }
+
+    Ok(Value::record(row, Span::unknown()))
+}
@@ -0,0 +1,754 @@
+//! Zones loaded from zone files or tables of records, and answering queries
+//! about them as their authoritative server would.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use hickory_proto::{
+    op::{Edns, Message, MessageType, OpCode, ResponseCode},
+    rr::{dnssec::rdata::DNSSECRData, rdata::NULL, DNSClass, LowerName, RData, Record, RecordType},
+    serialize::{binary::BinEncodable, txt::Parser},
+};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Span, Spanned, Value};
+
+use super::{
+    constants,
+    presentation::{self, Names},
+    serde::{self, RType},
+    trust_anchor,
+};
+
+/// The longest chain of CNAMEs followed within the zone, as a guard against
+/// loops.
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// The width past which exported records are split across lines.
+const MAX_LINE_WIDTH: usize = 80;
+
+#[derive(Debug)]
+pub struct Zone {
+    pub origin: Name,
+    class: DNSClass,
+    nodes: BTreeMap<LowerName, Vec<Record>>,
+}
+
+/// The origin of a zone given by name, which is absolute even without a
+/// trailing dot.
+pub fn parse_origin(zone: &Spanned<String>) -> Result<Name, LabeledError> {
+    let mut origin = Name::from_utf8(&zone.item).map_err(|err| {
+        LabeledError::new("invalid name")
+            .with_label(format!("Error parsing name: {}", err), zone.span)
+    })?;
+    origin.set_fqdn(true);
+
+    Ok(origin)
+}
+
+/// The origin and records of a zone file, with its `$ORIGIN`, `$TTL`, and
+/// `$INCLUDE` directives. Without an `$ORIGIN`, `origin` is the zone the
+/// relative names of the file are in, or else the root, in which case no
+/// origin is returned.
+pub fn parse_file(
+    path: &Spanned<String>,
+    origin: Option<Name>,
+) -> Result<(Option<Name>, Vec<Record>), LabeledError> {
+    let contents = std::fs::read_to_string(&path.item).map_err(|err| {
+        LabeledError::new("could not read zone file")
+            .with_label(format!("Error reading {}: {}", path.item, err), path.span)
+    })?;
+
+    parse(contents, PathBuf::from(&path.item), origin, path.span)
+}
+
+/// The origin and records of the text of a zone file, like [`parse_file`].
+/// Relative `$INCLUDE` paths are relative to the directory of `path`.
+pub fn parse(
+    text: String,
+    path: PathBuf,
+    origin: Option<Name>,
+    span: Span,
+) -> Result<(Option<Name>, Vec<Record>), LabeledError> {
+    let given = origin.is_some();
+    let (origin, rrsets) = Parser::new(text, Some(path), Some(origin.unwrap_or_else(Name::root)))
+        .parse()
+        .map_err(|err| {
+            LabeledError::new("invalid zone file")
+                .with_label(format!("Error parsing zone: {}", err.kind()), span)
+        })?;
+
+    let records = rrsets
+        .into_values()
+        .flat_map(|rrset| rrset.records_without_rrsigs().cloned().collect::<Vec<_>>())
+        .map(|mut record| {
+            // hickory gives the SOA record the expire time of the zone as its
+            // TTL, rather than the TTL it is written with, which the negative
+            // caching TTL is closer to
+            if let Some(RData::SOA(soa)) = record.data() {
+                let ttl = soa.minimum();
+                record.set_ttl(ttl);
+            }
+
+            record
+        })
+        .collect();
+
+    Ok(((given || !origin.is_root()).then_some(origin), records))
+}
+
+impl Zone {
+    /// A zone made of `records`. Unless `origin` is given, the zone is the one
+    /// whose SOA record is among them.
+    pub fn from_records(
+        records: Vec<Record>,
+        origin: Option<Name>,
+        span: Span,
+    ) -> Result<Self, LabeledError> {
+        let origin = origin
+            .or_else(|| {
+                records
+                    .iter()
+                    .find(|record| record.record_type() == RecordType::SOA)
+                    .map(|soa| soa.name().clone())
+            })
+            .ok_or_else(|| {
+                LabeledError::new("unknown zone").with_label(
+                    "the records have no SOA record to tell which zone they are, pass it with --zone",
+                    span,
+                )
+            })?;
+
+        let class = records
+            .first()
+            .map_or(DNSClass::IN, |record| record.dns_class());
+        let mut nodes: BTreeMap<LowerName, Vec<Record>> = BTreeMap::new();
+
+        for record in records {
+            if !origin.zone_of(record.name()) {
+                return Err(LabeledError::new("record not in zone").with_label(
+                    format!("{} is not in the zone {}", record.name(), origin),
+                    span,
+                ));
+            }
+
+            let node = nodes.entry(LowerName::new(record.name())).or_default();
+
+            if !node.contains(&record) {
+                node.push(record);
+            }
+        }
+
+        Ok(Self {
+            origin,
+            class,
+            nodes,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.values().map(Vec::len).sum()
+    }
+
+    /// The response to `request`, as the authoritative server of the zone.
+    /// Queries for names outside of it are refused, like a server that does
+    /// not recurse would.
+    pub fn respond(&self, request: &Message) -> Message {
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_checking_disabled(request.checking_disabled())
+            .add_queries(request.queries().iter().cloned());
+
+        if let Some(edns) = request.extensions() {
+            let mut reply = Edns::new();
+            reply.set_max_payload(constants::config::default::EDNS_PAYLOAD);
+            reply.set_dnssec_ok(edns.dnssec_ok());
+            response.set_edns(reply);
+        }
+
+        if request.op_code() != OpCode::Query {
+            response.set_response_code(ResponseCode::NotImp);
+            return response;
+        }
+
+        let query = match request.queries() {
+            [query] => query,
+            _ => {
+                response.set_response_code(ResponseCode::FormErr);
+                return response;
+            }
+        };
+
+        let qname = LowerName::new(query.name());
+        let qtype = query.query_type();
+        let qclass = query.query_class();
+
+        if !LowerName::new(&self.origin).zone_of(&qname)
+            || (qclass != self.class && qclass != DNSClass::ANY)
+        {
+            response.set_response_code(ResponseCode::Refused);
+            return response;
+        }
+
+        // names at and below a zone cut are answered with a referral, except
+        // for the DS records of the child, which the parent holds
+        if let Some(cut) = self.zone_cut(&qname) {
+            if !(cut == qname && qtype == RecordType::DS) {
+                let ns = self.rrset(&cut, RecordType::NS);
+                let glue = self.glue(&ns);
+
+                response.add_name_servers(ns).add_additionals(glue);
+                return response;
+            }
+        }
+
+        response.set_authoritative(true);
+
+        let mut name = qname;
+
+        for _ in 0..MAX_CNAME_CHAIN {
+            let Some(records) = self.node(&name) else {
+                response
+                    .set_response_code(ResponseCode::NXDomain)
+                    .add_name_servers(self.negative_soa());
+                break;
+            };
+
+            let answers: Vec<Record> = records
+                .iter()
+                .filter(|record| qtype == RecordType::ANY || record.record_type() == qtype)
+                .cloned()
+                .collect();
+
+            if !answers.is_empty() {
+                response.add_answers(answers);
+                break;
+            }
+
+            let cname = records.iter().find_map(|record| match record.data() {
+                Some(RData::CNAME(target)) => Some((record.clone(), target.0.clone())),
+                _ => None,
+            });
+
+            match cname {
+                Some((record, target)) => {
+                    response.add_answer(record);
+
+                    // the resolver follows targets outside of the zone itself
+                    if !self.origin.zone_of(&target) {
+                        break;
+                    }
+
+                    name = LowerName::new(&target);
+                }
+                None => {
+                    // NODATA: the name exists, but not with records of the type
+                    response.add_name_servers(self.negative_soa());
+                    break;
+                }
+            }
+        }
+
+        response
+    }
+
+    /// The records at `name`, which are synthesized from a wildcard if there
+    /// are none (RFC 4592). `None` if the name does not exist, while an empty
+    /// list means that it only has names below it.
+    fn node(&self, name: &LowerName) -> Option<Vec<Record>> {
+        if let Some(records) = self.nodes.get(name) {
+            return Some(records.clone());
+        }
+
+        if self.has_descendants(name) {
+            return Some(Vec::new());
+        }
+
+        // the wildcard is only looked for at the closest name that exists,
+        // as the child of it that is an ancestor of the name
+        let mut child = name.clone();
+        let mut encloser = name.base_name();
+
+        while !self.nodes.contains_key(&encloser) && !self.has_descendants(&encloser) {
+            if encloser.is_root() {
+                return None;
+            }
+
+            child = encloser;
+            encloser = child.base_name();
+        }
+
+        let owner = Name::from(name);
+
+        self.nodes.get(&child.into_wildcard()).map(|records| {
+            records
+                .iter()
+                .map(|record| {
+                    let mut record = record.clone();
+                    record.set_name(owner.clone());
+                    record
+                })
+                .collect()
+        })
+    }
+
+    fn has_descendants(&self, name: &LowerName) -> bool {
+        self.nodes
+            .keys()
+            .any(|node| node != name && name.zone_of(node))
+    }
+
+    fn rrset(&self, name: &LowerName, rtype: RecordType) -> Vec<Record> {
+        self.nodes
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|record| record.record_type() == rtype)
+            .cloned()
+            .collect()
+    }
+
+    /// The closest name above or at `name`, other than the apex, with NS
+    /// records, where the zone delegates to a child.
+    fn zone_cut(&self, name: &LowerName) -> Option<LowerName> {
+        let apex = LowerName::new(&self.origin);
+        let mut cut = None;
+        let mut current = name.clone();
+
+        while current != apex && apex.zone_of(&current) {
+            if !self.rrset(&current, RecordType::NS).is_empty() {
+                cut = Some(current.clone());
+            }
+
+            current = current.base_name();
+        }
+
+        cut
+    }
+
+    /// The addresses of the nameservers in `ns` that are in the zone, which
+    /// resolvers could not look up otherwise.
+    fn glue(&self, ns: &[Record]) -> Vec<Record> {
+        ns.iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::NS(target)) => Some(LowerName::new(&target.0)),
+                _ => None,
+            })
+            .flat_map(|target| {
+                let mut addresses = self.rrset(&target, RecordType::A);
+                addresses.extend(self.rrset(&target, RecordType::AAAA));
+                addresses
+            })
+            .collect()
+    }
+
+    /// The SOA record of the zone for negative responses, with the TTL that
+    /// resolvers cache them for (RFC 2308 section 3).
+    fn negative_soa(&self) -> Vec<Record> {
+        self.rrset(&LowerName::new(&self.origin), RecordType::SOA)
+            .into_iter()
+            .map(|mut record| {
+                if let Some(RData::SOA(soa)) = record.data() {
+                    let ttl = record.ttl().min(soa.minimum());
+                    record.set_ttl(ttl);
+                }
+
+                record
+            })
+            .collect()
+    }
+}
+
+/// A record from a row with `name`, `type`, and `rdata` columns, and optionally
+/// `ttl` and `class`, the shape records are output in. The record data is given
+/// either in presentation format, e.g. `10 mail.example.com.` for MX, as a
+/// list of strings for TXT, or as a record of its fields in order.
+///
+/// Names not ending in a dot are relative to `origin`, or else to the root.
+pub fn record_from_value(
+    value: &Value,
+    origin: Option<&Name>,
+    default_class: DNSClass,
+) -> Result<Record, LabeledError> {
+    let (name, rtype, class, ttl, rdata) = record_columns(value, origin, default_class)?;
+
+    let mut origin = origin.cloned().unwrap_or_else(Name::root);
+    origin.set_fqdn(true);
+
+    // hickory can neither read nor write the record data of types it does not
+    // know, so it is put together in wire format here
+    if let RecordType::Unknown(code) = rtype {
+        let rdata = RData::Unknown {
+            code: rtype,
+            rdata: NULL::with(unknown_rdata(code, &rdata)?),
+        };
+
+        let mut record = Record::from_rdata(name, ttl, rdata);
+        record.set_dns_class(class);
+
+        return Ok(record);
+    }
+
+    let rdata_span = rdata.span();
+    let text = presentation::rdata(rtype, &rdata, &Names::default())?.line();
+
+    // hickory only reads DNSKEY records off the wire, not from zone files
+    if rtype == RecordType::DNSKEY {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let key = trust_anchor::parse_dnskey(&fields).map_err(|err| {
+            LabeledError::new("invalid record data").with_label(
+                format!("Error parsing {} record data: {}", rtype, err),
+                rdata_span,
+            )
+        })?;
+
+        let mut record = Record::from_rdata(name, ttl, RData::DNSSEC(DNSSECRData::DNSKEY(key)));
+        record.set_dns_class(class);
+
+        return Ok(record);
+    }
+
+    // the record data is read as a line of a zone file, for its names to be
+    // relative to the origin
+    let line = format!("$ORIGIN {}\n@ 0 {} {}\n", origin, rtype, text);
+
+    let (_, rrsets) = Parser::new(line, None, Some(origin))
+        .parse()
+        .map_err(|err| {
+            LabeledError::new("invalid record data").with_label(
+                format!("Error parsing {} record data: {}", rtype, err.kind()),
+                rdata_span,
+            )
+        })?;
+
+    let rdata = rrsets
+        .into_values()
+        .flat_map(|rrset| rrset.records_without_rrsigs().cloned().collect::<Vec<_>>())
+        .find_map(Record::into_data)
+        .ok_or_else(|| {
+            LabeledError::new("invalid record data")
+                .with_label(format!("no {} record data given", rtype), rdata_span)
+        })?;
+
+    let mut record = Record::from_rdata(name, ttl, rdata);
+    record.set_dns_class(class);
+
+    Ok(record)
+}
+
+/// The record data of a type hickory does not know in wire format, from the
+/// value it is output as: the character-strings of AVC records, the fields of
+/// DOA and HIP records, or the RDATA itself of any type, as binary or in the generic
+/// `\# <length> <hex>` form (RFC 3597 section 5).
+fn unknown_rdata(code: u16, value: &Value) -> Result<Vec<u8>, LabeledError> {
+    let rtype = serde::RTypeName(RecordType::Unknown(code));
+    let field = |col: &str| {
+        value.get_data_by_key(col).ok_or_else(|| {
+            LabeledError::new("invalid record data").with_label(
+                format!("{} record data must have a field named '{}'", rtype, col),
+                value.span(),
+            )
+        })
+    };
+
+    let mut wire = Vec::new();
+
+    match value {
+        Value::Binary { val, .. } => wire.extend(val),
+        Value::String { val, .. } if val.starts_with("\\#") => wire = generic_rdata(value)?,
+        Value::String { .. } if code == constants::rtypes::AVC => {
+            push_character_string(&mut wire, value)?
+        }
+        Value::List { vals, .. } if code == constants::rtypes::AVC => {
+            for val in vals {
+                push_character_string(&mut wire, val)?;
+            }
+        }
+        Value::Record { .. } if code == constants::rtypes::DOA => {
+            wire.extend(rdata_number::<u32>(&field("enterprise")?)?.to_be_bytes());
+            wire.extend(rdata_number::<u32>(&field("type")?)?.to_be_bytes());
+            wire.push(rdata_number::<u8>(&field("location")?)?);
+            push_character_string(&mut wire, &field("media_type")?)?;
+            wire.extend(rdata_bytes(&field("data")?)?);
+        }
+        Value::Record { .. } if code == constants::rtypes::HIP => {
+            let hit = match field("hit")? {
+                Value::Binary { val, .. } => val,
+                hit => data_encoding::HEXUPPER_PERMISSIVE
+                    .decode(hit.as_str()?.as_bytes())
+                    .map_err(|err| {
+                        LabeledError::new("invalid record data")
+                            .with_label(format!("Error decoding hex: {}", err), hit.span())
+                    })?,
+            };
+            let public_key = rdata_bytes(&field("public_key")?)?;
+
+            let too_long = |what: &str| {
+                LabeledError::new("invalid record data")
+                    .with_label(format!("the {} is too long", what), value.span())
+            };
+
+            wire.push(u8::try_from(hit.len()).map_err(|_| too_long("HIT"))?);
+            wire.push(rdata_number::<u8>(&field("pk_algorithm")?)?);
+            wire.extend(
+                u16::try_from(public_key.len())
+                    .map_err(|_| too_long("public key"))?
+                    .to_be_bytes(),
+            );
+            wire.extend(hit);
+            wire.extend(public_key);
+
+            // rendezvous servers are never compressed (RFC 8005 section 5)
+            for server in field("rendezvous_servers")?.into_list()? {
+                let mut server = serde::Query::name_from_value(&server)?;
+                server.set_fqdn(true);
+                wire.extend(server.to_bytes().map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error encoding name: {}", err), value.span())
+                })?);
+            }
+        }
+        Value::Record { .. } => match field(constants::columns::RDATA)? {
+            Value::Binary { val, .. } => wire.extend(val),
+            rdata => return Err(invalid_rdata(&rdata, "binary")),
+        },
+        _ => {
+            return Err(LabeledError::new("invalid record data").with_label(
+                format!(
+                    "can not write {} record data from a {}",
+                    rtype,
+                    value.get_type()
+                ),
+                value.span(),
+            ))
+        }
+    }
+
+    Ok(wire)
+}
+
+/// RDATA in the generic `\# <length> <hex>` form (RFC 3597 section 5).
+fn generic_rdata(value: &Value) -> Result<Vec<u8>, LabeledError> {
+    let text = value.as_str()?;
+    let mut fields = text.split_whitespace().skip(1);
+
+    let len = fields
+        .next()
+        .and_then(|len| len.parse::<usize>().ok())
+        .ok_or_else(|| invalid_rdata(value, "a length after \\#"))?;
+    let hex = fields.collect::<String>();
+
+    match data_encoding::HEXLOWER_PERMISSIVE.decode(hex.as_bytes()) {
+        Ok(wire) if wire.len() == len => Ok(wire),
+        Ok(wire) => Err(LabeledError::new("invalid record data").with_label(
+            format!("{} bytes of record data given, not {}", wire.len(), len),
+            value.span(),
+        )),
+        Err(err) => Err(LabeledError::new("invalid record data")
+            .with_label(format!("Error decoding hex: {}", err), value.span())),
+    }
+}
+
+fn push_character_string(wire: &mut Vec<u8>, value: &Value) -> Result<(), LabeledError> {
+    let string = match value {
+        Value::String { val, .. } => val.as_bytes(),
+        Value::Binary { val, .. } => val,
+        _ => return Err(invalid_rdata(value, "a string")),
+    };
+
+    let len = u8::try_from(string.len()).map_err(|_| {
+        LabeledError::new("invalid record data")
+            .with_label("a character-string is at most 255 bytes long", value.span())
+    })?;
+
+    wire.push(len);
+    wire.extend(string);
+
+    Ok(())
+}
+
+fn rdata_number<N: TryFrom<i64>>(value: &Value) -> Result<N, LabeledError>
+where
+    N::Error: std::fmt::Display,
+{
+    let Value::Int { val, .. } = value else {
+        return Err(invalid_rdata(value, "an int"));
+    };
+
+    N::try_from(*val).map_err(|err| {
+        LabeledError::new("invalid record data")
+            .with_label(format!("out of range: {}", err), value.span())
+    })
+}
+
+/// Binary, or a string of base64.
+fn rdata_bytes(value: &Value) -> Result<Vec<u8>, LabeledError> {
+    match value {
+        Value::Binary { val, .. } => Ok(val.clone()),
+        Value::String { val, .. } => data_encoding::BASE64.decode(val.as_bytes()).map_err(|err| {
+            LabeledError::new("invalid record data")
+                .with_label(format!("Error decoding base64: {}", err), value.span())
+        }),
+        _ => Err(invalid_rdata(value, "binary")),
+    }
+}
+
+fn invalid_rdata(value: &Value, expected: &str) -> LabeledError {
+    LabeledError::new("invalid record data").with_label(
+        format!("expected {}, not a {}", expected, value.get_type()),
+        value.span(),
+    )
+}
+
+/// The owner, type, class, TTL, and record data of a record given as a value,
+/// with its owner made absolute.
+pub fn record_columns(
+    value: &Value,
+    origin: Option<&Name>,
+    default_class: DNSClass,
+) -> Result<(Name, RecordType, DNSClass, u32, Value), LabeledError> {
+    let span = value.span();
+
+    if let Value::Error { error, .. } = value {
+        return Err(LabeledError::from(*error.clone()));
+    }
+
+    let column = |col: &str| {
+        value.get_data_by_key(col).ok_or_else(|| {
+            LabeledError::new("invalid record")
+                .with_label(format!("Record must have a column named '{}'", col), span)
+        })
+    };
+
+    let mut origin = origin.cloned().unwrap_or_else(Name::root);
+    origin.set_fqdn(true);
+
+    let name = match column(constants::columns::NAME)? {
+        Value::String { val, .. } if val == "@" => origin.clone(),
+        name => {
+            let name = serde::Query::name_from_value(&name)?;
+
+            if name.is_fqdn() {
+                name
+            } else {
+                name.append_domain(&origin).map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error parsing name: {}", err.kind()), span)
+                })?
+            }
+        }
+    };
+
+    let RType(rtype) = RType::try_from(&column(constants::columns::TYPE)?)?;
+
+    let class = value
+        .get_data_by_key(constants::columns::CLASS)
+        .map(serde::DNSClass::try_from)
+        .transpose()?
+        .map_or(default_class, |class| class.0);
+
+    let ttl = match value.get_data_by_key(constants::columns::TTL) {
+        None | Some(Value::Nothing { .. }) => constants::config::default::ZONE_TTL,
+        Some(ttl) => ttl_from_value(&ttl)?,
+    };
+
+    let rdata = column(constants::columns::RDATA)?;
+
+    Ok((name, rtype, class, ttl, rdata))
+}
+
+fn ttl_from_value(value: &Value) -> Result<u32, LabeledError> {
+    let secs = match value {
+        Value::Int { val, .. } => *val,
+        Value::Duration { val, .. } => *val / 1_000_000_000,
+        _ => {
+            return Err(LabeledError::new("invalid TTL")
+                .with_label("TTL must be an int or a duration", value.span()))
+        }
+    };
+
+    u32::try_from(secs).map_err(|err| {
+        LabeledError::new("invalid TTL").with_label(
+            format!("should be a 32 bit unsigned int: {err}"),
+            value.span(),
+        )
+    })
+}
+
+/// Renders records given as values as the text of a zone file, sorted with
+/// the SOA first and the rest in canonical order (RFC 4034 section 6.1), and
+/// with names relative to the origin when one is given.
+pub fn export(
+    values: impl IntoIterator<Item = Value>,
+    origin: Option<&Name>,
+    default_class: DNSClass,
+) -> Result<String, LabeledError> {
+    let names = origin.map(Names::relative_to).unwrap_or_default();
+
+    let mut lines = Vec::new();
+    for value in values {
+        if matches!(value, Value::Nothing { .. }) {
+            continue;
+        }
+
+        let (name, rtype, class, ttl, rdata) = record_columns(&value, origin, default_class)?;
+        let rdata = presentation::rdata(rtype, &rdata, &names)?;
+        lines.push((name, rtype, class, ttl, rdata));
+    }
+
+    lines.sort_by(|(a_name, a_type, ..), (b_name, b_type, ..)| {
+        (*a_type != RecordType::SOA, a_name, u16::from(*a_type)).cmp(&(
+            *b_type != RecordType::SOA,
+            b_name,
+            u16::from(*b_type),
+        ))
+    });
+
+    let mut text = String::new();
+
+    if let Some(origin) = origin {
+        text.push_str(&format!("$ORIGIN {}\n", origin.to_ascii()));
+    }
+
+    for (name, rtype, class, ttl, rdata) in lines {
+        let line = format!(
+            "{}\t{}\t{}\t{}\t",
+            names.owner(&name),
+            ttl,
+            class,
+            serde::RTypeName(rtype)
+        );
+        text.push_str(&line);
+
+        let one_line = rdata.line();
+        if rdata.parts.len() < 2
+            || (!rdata.has_comments() && line.len() + one_line.len() <= MAX_LINE_WIDTH)
+        {
+            text.push_str(&one_line);
+            text.push('\n');
+            continue;
+        }
+
+        // too long for one line, so split across lines in parentheses, one
+        // part to a line (RFC 1035 section 5.1)
+        let (first, rest) = rdata.parts.split_at(1);
+        text.push_str(&format!("{} (\n", first[0].text));
+
+        for (i, part) in rest.iter().enumerate() {
+            text.push_str("\t\t\t\t");
+            text.push_str(&part.text);
+
+            let last = i == rest.len() - 1;
+            match part.comment {
+                Some(comment) if last => text.push_str(&format!("\t; {}\n\t\t\t\t)\n", comment)),
+                Some(comment) => text.push_str(&format!("\t; {}\n", comment)),
+                None if last => text.push_str(" )\n"),
+                None => text.push('\n'),
+            }
+        }
+    }
+
+    Ok(text)
+}
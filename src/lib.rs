@@ -1,3 +1,7 @@
+// LabeledError is the error type of the nushell plugin API, so it is returned
+// nearly everywhere regardless of its size
+#![allow(clippy::result_large_err)]
+
 mod dns;
 
 pub use dns::Dns;